@@ -0,0 +1,22 @@
+//! `cargo bench` runs the kernel benchmarks of `mandelbrot bench`, comparing
+//! them with the last run, whose medians are kept in the target directory.
+//! Options after `--` are passed on, e.g.
+//! `cargo bench --bench kernels -- --filter=simd`.
+
+use std::process::Command;
+
+fn main() {
+    // Only `cargo bench` passes `--bench`; as a test there is nothing to check
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.iter().any(|arg| arg == "--bench") {
+        return;
+    }
+    let baseline = concat!(env!("CARGO_TARGET_TMPDIR"), "/kernels-baseline.json");
+    let status = Command::new(env!("CARGO_BIN_EXE_mandelbrot"))
+        .arg("bench")
+        .arg(format!("--baseline={baseline}"))
+        .args(args.iter().filter(|arg| *arg != "--bench"))
+        .status()
+        .expect("failed to run the benchmarks");
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,39 @@
+//! Colors of escape counts.
+
+use crate::math;
+
+/// Color of `m` escape counts in the classic red-yellow gradient, by how
+/// quickly the point escaped.
+pub fn classic_color(m: u32) -> [u8; 4] {
+    [
+        (m.saturating_mul(255) / 40).min(255) as u8,
+        (m.saturating_mul(255) / 100).min(255) as u8,
+        m.saturating_mul(8).min(255) as u8,
+        255,
+    ]
+}
+
+/// Position from 0 to 1 in a colormap of `m` escape counts, going up the
+/// colormap over `period` counts and back down over the next, from the top
+/// if `reversed`.
+pub fn fold(m: f64, period: f64, reversed: bool) -> f64 {
+    let phase = math::rem_euclid(m / period, 2.0);
+    let t = if phase <= 1.0 { phase } else { 2.0 - phase };
+    if reversed { 1.0 - t } else { t }
+}
+
+/// Color at `t` from 0 to 1 of a gradient evenly through `stops`, of which
+/// there are at least two.
+pub fn stops_color(stops: &[[u8; 3]], t: f64) -> [u8; 4] {
+    let position = t * (stops.len() - 1) as f64;
+    let below = (position as usize).min(stops.len() - 2);
+    let (a, b, f) = (stops[below], stops[below + 1], position - below as f64);
+    let [r, g, b] = core::array::from_fn(|i| math::round(a[i] as f64 + (b[i] as f64 - a[i] as f64) * f) as u8);
+    [r, g, b, 255]
+}
+
+/// Color at `t` from 0 to 1 of a colorous `gradient`.
+pub fn gradient_color(gradient: colorous::Gradient, t: f64) -> [u8; 4] {
+    let colorous::Color { r, g, b } = gradient.eval_continuous(t);
+    [r, g, b, 255]
+}
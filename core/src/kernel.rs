@@ -0,0 +1,78 @@
+//! The escape time iteration.
+
+use num_complex::Complex;
+
+use crate::math;
+
+/// Norm of `z` compared against the bailout radius to decide it has escaped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bailout {
+    /// `|z|`
+    Modulus,
+    /// `|Re(z)| + |Im(z)|`
+    Manhattan,
+    /// `max(|Re(z)|, |Im(z)|)`
+    MaxComponent,
+}
+
+impl Bailout {
+    /// Parse the value of the `--bailout-norm` argument.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "modulus" => Some(Bailout::Modulus),
+            "manhattan" => Some(Bailout::Manhattan),
+            "max" => Some(Bailout::MaxComponent),
+            _ => None,
+        }
+    }
+
+    /// Name of the norm for the `--bailout-norm` argument.
+    pub fn name(self) -> &'static str {
+        match self {
+            Bailout::Modulus => "modulus",
+            Bailout::Manhattan => "manhattan",
+            Bailout::MaxComponent => "max",
+        }
+    }
+
+    /// Whether `re + im*i` has escaped, with `norm_sqr = re*re + im*im` already
+    /// computed by the caller.
+    #[inline(always)]
+    pub fn escaped(self, re: f64, im: f64, norm_sqr: f64, radius: f64) -> bool {
+        match self {
+            Bailout::Modulus => norm_sqr > radius * radius,
+            Bailout::Manhattan => re.abs() + im.abs() > radius,
+            Bailout::MaxComponent => re.abs().max(im.abs()) > radius,
+        }
+    }
+}
+
+/// Iterate `z -> z^2 + c` from `z0` until `z` escapes `radius` in the
+/// `bailout` norm or `max_iter` iterations, returning the escape count and
+/// the final value of `z`.
+///
+/// This is the hot loop, so it works on the real and imaginary parts
+/// directly and reuses the squares for the bailout test instead of taking
+/// a square root every iteration.
+#[inline]
+pub fn iterate(z0: Complex<f64>, c: Complex<f64>, bailout: Bailout, radius: f64, max_iter: u32) -> (u32, Complex<f64>) {
+    let (mut re, mut im) = (z0.re, z0.im);
+
+    for n in 0..max_iter {
+        let re_sqr = re * re;
+        let im_sqr = im * im;
+        if bailout.escaped(re, im, re_sqr + im_sqr, radius) {
+            return (n, Complex::new(re, im));
+        }
+        im = 2.0 * re * im + c.im;
+        re = re_sqr - im_sqr + c.re;
+    }
+
+    (max_iter, Complex::new(re, im))
+}
+
+/// Continuous escape count of a point that escaped after `n` iterations at
+/// `z`.
+pub fn smooth_count(n: u32, z: Complex<f64>) -> f64 {
+    (n as f64 + 1.0 - math::ln(math::ln(z.norm_sqr()) / 2.0) / core::f64::consts::LN_2).max(0.0)
+}
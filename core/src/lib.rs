@@ -0,0 +1,23 @@
+//! Iteration and coloring math of the Mandelbrot set, without the windowing,
+//! threads and files of the viewer.
+//!
+//! The crate is `no_std` without its default `std` feature, needing only an
+//! allocator for whole images, so the escape time kernel and the palettes can
+//! run on embedded displays, next to GPU code and in WebAssembly. The viewer
+//! calls into it for every pixel it iterates on the CPU, so the colors match
+//! wherever the math runs, and [`View`] renders whole images for the
+//! bindings to Python and other languages.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod color;
+mod kernel;
+mod math;
+mod render;
+
+pub use color::{classic_color, fold, gradient_color, stops_color};
+pub use kernel::{iterate, smooth_count, Bailout};
+pub use render::{Palette, View, PERIOD};
+pub use num_complex::Complex;
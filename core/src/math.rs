@@ -0,0 +1,92 @@
+//! Floating point functions missing from `core`, from the standard library
+//! with the `std` feature and approximated without it.
+
+#[cfg(feature = "std")]
+pub fn round(x: f64) -> f64 {
+    x.round()
+}
+
+#[cfg(feature = "std")]
+pub fn rem_euclid(x: f64, y: f64) -> f64 {
+    x.rem_euclid(y)
+}
+
+#[cfg(feature = "std")]
+pub fn ln(x: f64) -> f64 {
+    x.ln()
+}
+
+/// `x` rounded down, for `x` within the range of `i64`.
+#[cfg(not(feature = "std"))]
+fn floor(x: f64) -> f64 {
+    let truncated = x as i64 as f64;
+    if truncated > x { truncated - 1.0 } else { truncated }
+}
+
+/// `x` rounded to the nearest integer, halves away from zero.
+#[cfg(not(feature = "std"))]
+pub fn round(x: f64) -> f64 {
+    if x < 0.0 { -floor(0.5 - x) } else { floor(x + 0.5) }
+}
+
+#[cfg(not(feature = "std"))]
+pub fn rem_euclid(x: f64, y: f64) -> f64 {
+    x - y * floor(x / y)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn ln(x: f64) -> f64 {
+    ln_series(x)
+}
+
+/// Natural logarithm of `x` as the exponent of `x` times `ln(2)` plus the
+/// logarithm of the mantissa from a series in `atanh`, to within a few units
+/// in the last place.
+#[cfg_attr(feature = "std", allow(dead_code))]
+fn ln_series(x: f64) -> f64 {
+    use core::f64::consts::{LN_2, SQRT_2};
+
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if x.is_infinite() {
+        return x;
+    }
+    if x < f64::MIN_POSITIVE {
+        // Subnormal, scaled up by 2^54 into the normal range
+        return ln_series(x * 18014398509481984.0) - 54.0 * LN_2;
+    }
+    let bits = x.to_bits();
+    let mut exponent = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mut mantissa = f64::from_bits((bits & 0x000f_ffff_ffff_ffff) | (1023 << 52));
+    if mantissa > SQRT_2 {
+        mantissa /= 2.0;
+        exponent += 1;
+    }
+    // ln(m) = 2 atanh(s) = 2 (s + s^3/3 + s^5/5 + ...), with |s| below 0.18
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s_sqr = s * s;
+    let mut series = 0.0;
+    for k in (0..12).rev() {
+        series = series * s_sqr + 1.0 / (2 * k + 1) as f64;
+    }
+    exponent as f64 * LN_2 + 2.0 * s * series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series_matches_std_ln() {
+        for x in [1e-310, 1e-20, 0.001, 0.5, 1.0, 1.5, 2.0, 3.0, 10.0, 1e100] {
+            let (ln, expected) = (ln_series(x), x.ln());
+            assert!((ln - expected).abs() <= 1e-14 * expected.abs().max(1.0), "ln({x}) = {ln}, not {expected}");
+        }
+        assert!(ln_series(-1.0).is_nan());
+        assert_eq!(ln_series(0.0), f64::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,97 @@
+//! Whole views rendered into buffers, for the bindings to other languages.
+
+use alloc::vec::Vec;
+
+use num_complex::Complex;
+
+use crate::{classic_color, fold, gradient_color, iterate, Bailout};
+
+/// Escape counts from one end of a colormap to the other
+pub const PERIOD: f64 = 100.0;
+/// Width of the plane shown by the height of a view at zoom 1
+const ZOOM_HEIGHT: f64 = 2.5;
+
+/// Colors of escape counts
+#[derive(Clone, Copy, Debug)]
+pub enum Palette {
+    /// The red-yellow gradient of the viewer
+    Classic,
+    /// A colormap, up and back down over `PERIOD` counts
+    Gradient(colorous::Gradient),
+}
+
+impl Palette {
+    /// Palette of the viewer's `--palette` names other than color stops.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let gradient = match name {
+            "classic" => return Some(Palette::Classic),
+            "viridis" => colorous::VIRIDIS,
+            "magma" => colorous::MAGMA,
+            "inferno" => colorous::INFERNO,
+            "turbo" => colorous::TURBO,
+            "cividis" => colorous::CIVIDIS,
+            "high-contrast" => colorous::GREYS,
+            _ => return None,
+        };
+        Some(Palette::Gradient(gradient))
+    }
+
+    /// Color of `m` escape counts, black for points that didn't escape in
+    /// `max_iter` iterations.
+    pub fn color(self, m: u32, max_iter: u32) -> [u8; 4] {
+        match self {
+            _ if m >= max_iter => [0, 0, 0, 255],
+            Palette::Classic => classic_color(m),
+            Palette::Gradient(gradient) => gradient_color(gradient, fold(m as f64, PERIOD, false)),
+        }
+    }
+}
+
+/// A view of the Mandelbrot set, or of a Julia set
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+    pub center: Complex<f64>,
+    /// Magnification, the view is 2.5 high at 1
+    pub zoom: f64,
+    /// Parameter of the Julia set, `None` for the Mandelbrot set
+    pub julia: Option<Complex<f64>>,
+    pub bailout: Bailout,
+    pub bailout_radius: f64,
+    pub max_iter: u32,
+}
+
+impl View {
+    /// The Mandelbrot set around `center` at `zoom`, with the defaults of the
+    /// viewer.
+    pub fn new(center: Complex<f64>, zoom: f64) -> Self {
+        Self { center, zoom, julia: None, bailout: Bailout::Modulus, bailout_radius: 2.0, max_iter: 1000 }
+    }
+
+    /// Point of the plane at pixel `x`, `y` of an image of `width` by
+    /// `height` pixels, mapped as the viewer maps its window.
+    pub fn point(&self, x: f64, y: f64, width: usize, height: usize) -> Complex<f64> {
+        let scale = ZOOM_HEIGHT / height as f64 / self.zoom;
+        self.center + Complex::new((x - width as f64 / 2.0) * scale, (y - height as f64 / 2.0) * scale)
+    }
+
+    /// Escape counts of the pixels of an image of `width` by `height`, row by
+    /// row.
+    pub fn escape_counts(&self, width: usize, height: usize) -> Vec<u32> {
+        (0..width * height)
+            .map(|i| {
+                let point = self.point((i % width) as f64, (i / width) as f64, width, height);
+                let (z0, c) = match self.julia {
+                    Some(c) => (point, c),
+                    None => (Complex::new(0.0, 0.0), point),
+                };
+                iterate(z0, c, self.bailout, self.bailout_radius, self.max_iter).0
+            })
+            .collect()
+    }
+
+    /// RGBA colors of the pixels of an image of `width` by `height` in
+    /// `palette`, row by row.
+    pub fn render(&self, width: usize, height: usize, palette: Palette) -> Vec<u8> {
+        self.escape_counts(width, height).into_iter().flat_map(|m| palette.color(m, self.max_iter)).collect()
+    }
+}
@@ -0,0 +1,57 @@
+/*
+ * C interface of the Mandelbrot render core, see `ffi/src/lib.rs`.
+ *
+ * Link with `libmandelbrot` from `cargo build --release -p mandelbrot-ffi`.
+ * A context is not to be used from two threads at once, but contexts of
+ * their own can render on many threads.
+ */
+
+#ifndef MANDELBROT_H
+#define MANDELBROT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum MandelbrotStatus {
+    MANDELBROT_OK = 0,
+    /* A null pointer, a name that isn't one, a buffer too small or a number
+       out of range */
+    MANDELBROT_INVALID_ARGUMENT = 1,
+} MandelbrotStatus;
+
+/* A view to render and its palette */
+typedef struct MandelbrotContext MandelbrotContext;
+
+/* A new context on the whole Mandelbrot set in the classic palette */
+MandelbrotContext *mandelbrot_context_new(void);
+/* Free a context, doing nothing for NULL */
+void mandelbrot_context_free(MandelbrotContext *context);
+
+MandelbrotStatus mandelbrot_set_center(MandelbrotContext *context, double re, double im);
+/* Positive; at 1 the view is 2.5 high */
+MandelbrotStatus mandelbrot_set_zoom(MandelbrotContext *context, double zoom);
+MandelbrotStatus mandelbrot_set_julia(MandelbrotContext *context, double re, double im);
+MandelbrotStatus mandelbrot_clear_julia(MandelbrotContext *context);
+MandelbrotStatus mandelbrot_set_max_iter(MandelbrotContext *context, uint32_t max_iter);
+/* `norm` is "modulus", "manhattan" or "max" */
+MandelbrotStatus mandelbrot_set_bailout(MandelbrotContext *context, const char *norm, double radius);
+/* "classic", "viridis", "magma", "inferno", "turbo", "cividis" or
+   "high-contrast" */
+MandelbrotStatus mandelbrot_set_palette(MandelbrotContext *context, const char *palette);
+
+/* Render `height` rows of `width` RGBA pixels, each row `stride` bytes after
+   the one before, at least `width * 4`; both dimensions are at least 1 */
+MandelbrotStatus mandelbrot_render(const MandelbrotContext *context, uint8_t *buffer, size_t width, size_t height, size_t stride);
+/* Write `width * height` escape counts, row by row, the maximum iterations
+   for the points that didn't escape; both dimensions are at least 1 */
+MandelbrotStatus mandelbrot_escape_counts(const MandelbrotContext *context, uint32_t *counts, size_t width, size_t height);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
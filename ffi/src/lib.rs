@@ -0,0 +1,248 @@
+//! C ABI of the render core, declared in `include/mandelbrot.h`.
+//!
+//! A `MandelbrotContext` holds a view and its palette: it is made with
+//! `mandelbrot_context_new`, set up with the `mandelbrot_set_*` functions,
+//! rendered as often as needed into buffers of the caller with
+//! `mandelbrot_render` or `mandelbrot_escape_counts`, which only read it, and
+//! freed with `mandelbrot_context_free`. Functions that can fail return a
+//! `MandelbrotStatus` and leave the context and the buffers as they were.
+//!
+//! `cargo build --release -p mandelbrot-ffi` builds `libmandelbrot.so` (or
+//! `.dylib` or `.dll`) and `libmandelbrot.a` into `target/release`.
+
+use std::ffi::{c_char, CStr};
+
+use mandelbrot_core::{Bailout, Complex, Palette, View};
+
+/// Result of the functions that can fail
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MandelbrotStatus {
+    Ok = 0,
+    /// A null pointer, a name that isn't one, a buffer too small or a number
+    /// out of range
+    InvalidArgument = 1,
+}
+
+/// A view to render and its palette
+pub struct MandelbrotContext {
+    view: View,
+    palette: Palette,
+}
+
+/// A new context on the whole Mandelbrot set in the classic palette, to be
+/// freed with `mandelbrot_context_free`.
+#[no_mangle]
+pub extern "C" fn mandelbrot_context_new() -> *mut MandelbrotContext {
+    let context = MandelbrotContext { view: View::new(Complex::new(-0.75, 0.0), 1.0), palette: Palette::Classic };
+    Box::into_raw(Box::new(context))
+}
+
+/// Free a context, doing nothing for null.
+///
+/// # Safety
+///
+/// `context` is null or from `mandelbrot_context_new`, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_context_free(context: *mut MandelbrotContext) {
+    if !context.is_null() {
+        drop(Box::from_raw(context));
+    }
+}
+
+/// The context behind `context`, `None` for null.
+///
+/// # Safety
+///
+/// `context` is null or a live context not used elsewhere meanwhile.
+unsafe fn context_mut<'a>(context: *mut MandelbrotContext) -> Option<&'a mut MandelbrotContext> {
+    context.as_mut()
+}
+
+/// The string behind `name`, `None` for null or text that isn't UTF-8.
+///
+/// # Safety
+///
+/// `name` is null or a NUL-terminated string.
+unsafe fn name<'a>(name: *const c_char) -> Option<&'a str> {
+    if name.is_null() {
+        return None;
+    }
+    CStr::from_ptr(name).to_str().ok()
+}
+
+/// Run `set` on the context, `InvalidArgument` if it is null or `set` fails.
+///
+/// # Safety
+///
+/// As for `context_mut`.
+unsafe fn set(context: *mut MandelbrotContext, set: impl FnOnce(&mut MandelbrotContext) -> Option<()>) -> MandelbrotStatus {
+    match context_mut(context).and_then(set) {
+        Some(()) => MandelbrotStatus::Ok,
+        None => MandelbrotStatus::InvalidArgument,
+    }
+}
+
+/// Center the view on `re + im*i`.
+///
+/// # Safety
+///
+/// `context` is null or a live context.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_set_center(context: *mut MandelbrotContext, re: f64, im: f64) -> MandelbrotStatus {
+    set(context, |context| {
+        (re.is_finite() && im.is_finite()).then(|| context.view.center = Complex::new(re, im))
+    })
+}
+
+/// Magnify the view by `zoom`, which is positive; at 1 it is 2.5 high.
+///
+/// # Safety
+///
+/// `context` is null or a live context.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_set_zoom(context: *mut MandelbrotContext, zoom: f64) -> MandelbrotStatus {
+    set(context, |context| (zoom > 0.0 && zoom.is_finite()).then(|| context.view.zoom = zoom))
+}
+
+/// Show the Julia set of `re + im*i`.
+///
+/// # Safety
+///
+/// `context` is null or a live context.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_set_julia(context: *mut MandelbrotContext, re: f64, im: f64) -> MandelbrotStatus {
+    set(context, |context| {
+        (re.is_finite() && im.is_finite()).then(|| context.view.julia = Some(Complex::new(re, im)))
+    })
+}
+
+/// Show the Mandelbrot set again after `mandelbrot_set_julia`.
+///
+/// # Safety
+///
+/// `context` is null or a live context.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_clear_julia(context: *mut MandelbrotContext) -> MandelbrotStatus {
+    set(context, |context| {
+        context.view.julia = None;
+        Some(())
+    })
+}
+
+/// Iterate points at most `max_iter` times, at least once.
+///
+/// # Safety
+///
+/// `context` is null or a live context.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_set_max_iter(context: *mut MandelbrotContext, max_iter: u32) -> MandelbrotStatus {
+    set(context, |context| (max_iter >= 1).then(|| context.view.max_iter = max_iter))
+}
+
+/// Take points past `radius` in the `norm` named as by `--bailout-norm`,
+/// `"modulus"`, `"manhattan"` or `"max"`, to have escaped.
+///
+/// # Safety
+///
+/// `context` is null or a live context, and `norm` null or a NUL-terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_set_bailout(context: *mut MandelbrotContext, norm: *const c_char, radius: f64) -> MandelbrotStatus {
+    let bailout = name(norm).and_then(Bailout::from_name);
+    set(context, |context| {
+        let bailout = bailout.filter(|_| radius > 0.0 && radius.is_finite())?;
+        (context.view.bailout, context.view.bailout_radius) = (bailout, radius);
+        Some(())
+    })
+}
+
+/// Color escape counts with the palette named as by `--palette`, such as
+/// `"classic"` or `"viridis"`.
+///
+/// # Safety
+///
+/// `context` is null or a live context, and `palette` null or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_set_palette(context: *mut MandelbrotContext, palette: *const c_char) -> MandelbrotStatus {
+    let palette = name(palette).and_then(Palette::from_name);
+    set(context, |context| {
+        context.palette = palette?;
+        Some(())
+    })
+}
+
+/// Render the view into `buffer`, `height` rows of `width` RGBA pixels each
+/// starting `stride` bytes after the one before, at least `width * 4`. Both
+/// dimensions are at least 1.
+///
+/// # Safety
+///
+/// `context` is null or a live context, and `buffer` null or valid for
+/// writes of `stride * height` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_render(context: *const MandelbrotContext, buffer: *mut u8, width: usize, height: usize, stride: usize) -> MandelbrotStatus {
+    let (Some(context), false) = (context.as_ref(), buffer.is_null() || width == 0 || height == 0) else {
+        return MandelbrotStatus::InvalidArgument;
+    };
+    let Some(size) = stride.checked_mul(height).filter(|_| stride >= width.saturating_mul(4)) else {
+        return MandelbrotStatus::InvalidArgument;
+    };
+    let buffer = std::slice::from_raw_parts_mut(buffer, size);
+    let rgba = context.view.render(width, height, context.palette);
+    for (row, pixels) in buffer.chunks_mut(stride.max(1)).zip(rgba.chunks_exact(width * 4)) {
+        row[..width * 4].copy_from_slice(pixels);
+    }
+    MandelbrotStatus::Ok
+}
+
+/// Write the escape counts of the view into `counts`, `height` rows of
+/// `width` each, the maximum iterations for the points that didn't escape.
+/// Both dimensions are at least 1.
+///
+/// # Safety
+///
+/// `context` is null or a live context, and `counts` null or valid for
+/// writes of `width * height` counts.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_escape_counts(context: *const MandelbrotContext, counts: *mut u32, width: usize, height: usize) -> MandelbrotStatus {
+    let (Some(context), Some(size), false) = (context.as_ref(), width.checked_mul(height), counts.is_null() || width == 0 || height == 0) else {
+        return MandelbrotStatus::InvalidArgument;
+    };
+    std::slice::from_raw_parts_mut(counts, size).copy_from_slice(&context.view.escape_counts(width, height));
+    MandelbrotStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_through_the_c_abi() {
+        unsafe {
+            let context = mandelbrot_context_new();
+            assert_eq!(mandelbrot_set_zoom(context, 0.0), MandelbrotStatus::InvalidArgument);
+            assert_eq!(mandelbrot_set_palette(context, c"viridis".as_ptr()), MandelbrotStatus::Ok);
+            assert_eq!(mandelbrot_set_bailout(context, c"taxicab".as_ptr(), 2.0), MandelbrotStatus::InvalidArgument);
+            assert_eq!(mandelbrot_set_julia(context, -0.8, 0.156), MandelbrotStatus::Ok);
+
+            // Rows padded to 20 pixels, the padding left alone
+            let (width, height, stride) = (16, 12, 80);
+            let mut buffer = vec![7; stride * height];
+            assert_eq!(mandelbrot_render(context, buffer.as_mut_ptr(), width, height, width * 4 - 1), MandelbrotStatus::InvalidArgument);
+            assert_eq!(mandelbrot_render(context, buffer.as_mut_ptr(), 0, height, stride), MandelbrotStatus::InvalidArgument);
+            assert_eq!(mandelbrot_render(context, buffer.as_mut_ptr(), width, 0, stride), MandelbrotStatus::InvalidArgument);
+            assert_eq!(mandelbrot_render(context, buffer.as_mut_ptr(), width, height, stride), MandelbrotStatus::Ok);
+            let expected = (*context).view.render(width, height, Palette::from_name("viridis").unwrap());
+            for y in 0..height {
+                assert_eq!(buffer[y * stride..y * stride + width * 4], expected[y * width * 4..(y + 1) * width * 4]);
+                assert!(buffer[y * stride + width * 4..(y + 1) * stride].iter().all(|&byte| byte == 7));
+            }
+            let mut counts = vec![0; width * height];
+            assert_eq!(mandelbrot_escape_counts(context, counts.as_mut_ptr(), 0, height), MandelbrotStatus::InvalidArgument);
+            mandelbrot_context_free(context);
+            mandelbrot_context_free(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Python bindings of the render core.
+//!
+//! `mandelbrot.render((x, y), zoom, (width, height), ...)` renders a view into
+//! a `height` by `width` by 4 NumPy array of RGBA bytes, and
+//! `mandelbrot.escape_counts` with the same arguments into a `height` by
+//! `width` array of the escape counts, for colors of the caller's own. The
+//! views are mapped as in the viewer, so `--center`, `--zoom`, `--julia`,
+//! `--bailout-norm`, `--bailout` and `--palette` of a view carry over as the
+//! keyword arguments of the same names, and invalid ones raise `ValueError`
+//! as the viewer refuses them. The renders release the GIL.
+//!
+//! `maturin build --release` in this directory builds the wheel, and
+//! `maturin develop` installs it into the current virtual environment.
+
+use mandelbrot_core::{Bailout, Complex, Palette, View};
+use numpy::{PyArray1, PyArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// The view of the arguments, shared by the functions.
+fn view(center: (f64, f64), zoom: f64, julia: Option<(f64, f64)>, max_iter: u32, bailout: &str, bailout_radius: f64) -> PyResult<View> {
+    if !(zoom > 0.0 && zoom.is_finite()) {
+        return Err(PyValueError::new_err(format!("invalid zoom: {zoom}")));
+    }
+    if max_iter == 0 {
+        return Err(PyValueError::new_err("invalid max_iter: 0"));
+    }
+    if !(bailout_radius > 0.0 && bailout_radius.is_finite()) {
+        return Err(PyValueError::new_err(format!("invalid bailout: {bailout_radius}")));
+    }
+    let bailout = Bailout::from_name(bailout).ok_or_else(|| PyValueError::new_err(format!("invalid bailout_norm: {bailout}")))?;
+    Ok(View {
+        julia: julia.map(|(re, im)| Complex::new(re, im)),
+        bailout,
+        bailout_radius,
+        max_iter,
+        ..View::new(Complex::new(center.0, center.1), zoom)
+    })
+}
+
+/// Render a view into an array of `height` by `width` RGBA pixels.
+#[pyfunction]
+#[pyo3(signature = (center, zoom, size, *, julia=None, max_iter=1000, bailout_norm="modulus", bailout=2.0, palette="classic"))]
+#[allow(clippy::too_many_arguments)]
+fn render<'py>(
+    py: Python<'py>,
+    center: (f64, f64),
+    zoom: f64,
+    size: (usize, usize),
+    julia: Option<(f64, f64)>,
+    max_iter: u32,
+    bailout_norm: &str,
+    bailout: f64,
+    palette: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let view = view(center, zoom, julia, max_iter, bailout_norm, bailout)?;
+    let palette = Palette::from_name(palette).ok_or_else(|| PyValueError::new_err(format!("invalid palette: {palette}")))?;
+    let (width, height) = size;
+    let rgba = py.allow_threads(|| view.render(width, height, palette));
+    Ok(PyArray1::from_vec_bound(py, rgba).reshape([height, width, 4])?.into_any())
+}
+
+/// Escape counts of a view in an array of `height` by `width`, `max_iter`
+/// for the points that didn't escape.
+#[pyfunction]
+#[pyo3(signature = (center, zoom, size, *, julia=None, max_iter=1000, bailout_norm="modulus", bailout=2.0))]
+fn escape_counts<'py>(
+    py: Python<'py>,
+    center: (f64, f64),
+    zoom: f64,
+    size: (usize, usize),
+    julia: Option<(f64, f64)>,
+    max_iter: u32,
+    bailout_norm: &str,
+    bailout: f64,
+) -> PyResult<Bound<'py, PyAny>> {
+    let view = view(center, zoom, julia, max_iter, bailout_norm, bailout)?;
+    let (width, height) = size;
+    let counts = py.allow_threads(|| view.escape_counts(width, height));
+    Ok(PyArray1::from_vec_bound(py, counts).reshape([height, width])?.into_any())
+}
+
+#[pymodule]
+fn mandelbrot(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(render, module)?)?;
+    module.add_function(wrap_pyfunction!(escape_counts, module)?)?;
+    Ok(())
+}
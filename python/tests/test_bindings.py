@@ -0,0 +1,19 @@
+"""Smoke tests of the bindings, run with `pytest` once `maturin develop` installed them."""
+
+import pytest
+
+import mandelbrot
+
+
+def test_renders_views():
+    pixels = mandelbrot.render((-0.75, 0.0), 1.0, (32, 24), palette="viridis")
+    assert pixels.shape == (24, 32, 4)
+    counts = mandelbrot.escape_counts((-0.75, 0.0), 1.0, (32, 24), max_iter=50)
+    assert counts.shape == (24, 32)
+    assert counts.max() == 50
+
+
+@pytest.mark.parametrize("options", [{"max_iter": 0}, {"bailout": 0.0}, {"bailout": -2.0}, {"bailout": float("inf")}, {"bailout": float("nan")}])
+def test_rejects_invalid_options(options):
+    with pytest.raises(ValueError):
+        mandelbrot.escape_counts((0.0, 0.0), 1.0, (8, 8), **options)
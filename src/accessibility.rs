@@ -0,0 +1,109 @@
+//! Accessibility modes of the viewer.
+//!
+//! `--reduced-motion` opens the windows with the automatic zoom paused, for
+//! Space to start, and moves the colors of `--auto-range` to the range of a
+//! new view at once instead of easing them over several frames. Nothing
+//! else moves on its own unless asked for, as with `--animation` or P.
+//!
+//! `--high-contrast` outlines the overlays in black, so the contours, rays,
+//! morph path, selection, cursor and inset map stand out from any colors
+//! under them, draws the timeline editor in white on black, and starts on
+//! the `high-contrast` palette unless `--palette` is given.
+//!
+//! Everything done with the mouse can also be done from the keyboard:
+//! Shift and the arrow keys move a cursor, drawn as a crosshair until the
+//! mouse moves again, Enter holds the left button down until it is pressed
+//! again, to draw morph paths and drag the Julia seed, Z marks the corners
+//! of a rectangle to zoom into in place of dragging with the right button,
+//! and Ctrl+C copies the point under the cursor like the middle button. The
+//! timeline editor takes Tab, Space and Enter as egui does.
+
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+use crate::{HEIGHT, WIDTH};
+
+/// Pixels the keyboard cursor moves per frame
+const CURSOR_SPEED: i64 = 4;
+/// Color of the outlines in high contrast
+const OUTLINE_COLOR: [u8; 4] = [0, 0, 0, 255];
+
+/// The accessibility modes asked for
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Accessibility {
+    pub reduced_motion: bool,
+    pub high_contrast: bool,
+}
+
+impl Accessibility {
+    pub fn from_args(args: &[String]) -> Self {
+        Self {
+            reduced_motion: args.iter().any(|arg| arg == "--reduced-motion"),
+            high_contrast: args.iter().any(|arg| arg == "--high-contrast"),
+        }
+    }
+}
+
+/// The mouse buttons of a frame, pressed on the mouse or from the keyboard
+pub struct Buttons {
+    pub left_pressed: bool,
+    pub left_held: bool,
+    pub right_pressed: bool,
+    pub right_released: bool,
+    pub middle_pressed: bool,
+}
+
+impl Buttons {
+    /// The buttons of this frame's `input`. `left_latched` is whether Enter
+    /// holds the left button, which this toggles, and `selecting` whether a
+    /// rectangle is being selected, which Z finishes. Shift+Z is left to
+    /// the minibrots.
+    pub fn read(input: &WinitInputHelper, left_latched: &mut bool, selecting: bool) -> Self {
+        let enter = input.key_pressed(KeyCode::Enter);
+        let zoom = input.key_pressed(KeyCode::KeyZ) && !input.held_shift();
+        let left_pressed = input.mouse_pressed(MouseButton::Left) || (enter && !*left_latched);
+        if enter {
+            *left_latched = !*left_latched;
+        }
+        Self {
+            left_pressed,
+            left_held: input.mouse_held(MouseButton::Left) || *left_latched,
+            right_pressed: input.mouse_pressed(MouseButton::Right) || (zoom && !selecting),
+            right_released: input.mouse_released(MouseButton::Right) || (zoom && selecting),
+            middle_pressed: input.mouse_pressed(MouseButton::Middle) || (input.held_control() && input.key_pressed(KeyCode::KeyC)),
+        }
+    }
+}
+
+/// `cursor` moved by Shift and the arrow keys held this frame, starting
+/// from the middle of the frame, `None` if they aren't held.
+pub fn move_cursor(input: &WinitInputHelper, cursor: Option<(usize, usize)>) -> Option<(usize, usize)> {
+    let held = |key: KeyCode| input.held_shift() && input.key_held(key);
+    let dx = (held(KeyCode::ArrowRight) as i64 - held(KeyCode::ArrowLeft) as i64) * CURSOR_SPEED;
+    let dy = (held(KeyCode::ArrowDown) as i64 - held(KeyCode::ArrowUp) as i64) * CURSOR_SPEED;
+    if (dx, dy) == (0, 0) {
+        return None;
+    }
+    let (x, y) = cursor.unwrap_or((WIDTH as usize / 2, HEIGHT as usize / 2));
+    let x = (x as i64 + dx).clamp(0, WIDTH as i64 - 1) as usize;
+    let y = (y as i64 + dy).clamp(0, HEIGHT as i64 - 1) as usize;
+    Some((x, y))
+}
+
+/// Outline in black the pixels of `frame` that the overlays changed from
+/// `under`, the frame before them.
+pub fn outline(frame: &mut [u8], under: &[u8]) {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let drawn: Vec<bool> = frame.chunks_exact(4).zip(under.chunks_exact(4)).map(|(pixel, under)| pixel != under).collect();
+    for y in 0..height {
+        for x in 0..width {
+            let beside_drawn = (y.saturating_sub(1)..=(y + 1).min(height - 1))
+                .any(|ny| (x.saturating_sub(1)..=(x + 1).min(width - 1)).any(|nx| drawn[ny * width + nx]));
+            if beside_drawn && !drawn[y * width + x] {
+                let i = (y * width + x) * 4;
+                frame[i..i + 4].copy_from_slice(&OUTLINE_COLOR);
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Adaptive iteration limits.
+//!
+//! With `--adaptive-iterations=<limit>` the CPU renders blocks of pixels in
+//! passes rather than iterating every pixel up to the same limit: the first
+//! pass iterates all of them 64 times, and each pass after doubles the limit
+//! for the pixels that haven't escaped yet but lie next to one that has,
+//! picking up from where they were, up to `<limit>`. A pixel escaping opens
+//! its neighbors to the same pass, so the iterations spread along the
+//! boundary of the set, and pixels surrounded by others that haven't escaped
+//! stop early as inside. As the outside of the set is connected, the
+//! escaping pixels near the boundary are reachable this way from the ones
+//! escaping early, so boundary pixels get iterations far beyond the fixed
+//! 1000 while the inside costs a fraction of it. The pixels on the edges of
+//! a block, whose neighbors beyond it aren't known, keep going at least to
+//! the fixed limit.
+//!
+//! This takes the plain path of the CPU renderer, the deep views rendered by
+//! perturbation, the mipmapped shallow views, the colorings that follow the
+//! orbit and the coarse and interlaced passes, which render scattered
+//! pixels rather than blocks, keep the fixed limit, and the GPU leaves
+//! adaptive views to the CPU.
+
+use num::Complex;
+
+use crate::{Mandelbrot, MAX_ITER};
+
+/// Iterations of the first pass
+const START: u32 = 64;
+
+impl Mandelbrot {
+    /// Escape counts and colors of `points` of the view plane, a block
+    /// `width` points wide row by row, like `shade_batch` but with adaptive
+    /// iteration limits where asked for.
+    pub fn shade_block(&self, points: &[Complex<f64>], width: usize, counts: &mut [u32], colors: &mut [u8]) {
+        let Some(limit) = self.adaptive_iterations.filter(|_| !self.coloring.needs_orbit()) else {
+            return self.shade_batch(points, counts, colors);
+        };
+        let orbits = self.iterate_adaptive(points, width, limit);
+        for (&(m, z), (count, pixel)) in orbits.iter().zip(counts.iter_mut().zip(colors.chunks_exact_mut(4))) {
+            *count = m;
+            pixel.copy_from_slice(&self.shade(m, z));
+        }
+    }
+
+    /// Escape counts and final values of `z` of `points`, a block `width`
+    /// points wide, iterated in passes up to `limit`, `MAX_ITER` for those
+    /// taken to be inside.
+    fn iterate_adaptive(&self, points: &[Complex<f64>], width: usize, limit: u32) -> Vec<(u32, Complex<f64>)> {
+        let height = points.len() / width.max(1);
+        // Where each orbit starts and what it adds, and how far it got
+        let starts: Vec<(Complex<f64>, Complex<f64>)> = points
+            .iter()
+            .map(|&point| {
+                let point = self.projection.apply(point);
+                match self.julia {
+                    Some(c) => (point, c),
+                    None => (Complex::new(0.0, 0.0), point),
+                }
+            })
+            .collect();
+        let mut orbits: Vec<(u32, Complex<f64>)> = starts.iter().map(|&(z0, _)| (0, z0)).collect();
+        let mut escaped = vec![false; points.len()];
+        let iterate = |i: usize, cap: u32, orbits: &mut [(u32, Complex<f64>)], escaped: &mut [bool]| {
+            let (n, z) = orbits[i];
+            let (steps, z) = self.iterate_to(z, starts[i].1, cap - n);
+            orbits[i] = (n + steps, z);
+            escaped[i] = n + steps < cap;
+        };
+
+        let mut cap = START.min(limit);
+        for i in 0..points.len() {
+            iterate(i, cap, &mut orbits, &mut escaped);
+        }
+        let neighbors = |i: usize| {
+            let (x, y) = (i % width, i / width);
+            let rows = y.saturating_sub(1)..(y + 2).min(height);
+            rows.flat_map(move |ny| (x.saturating_sub(1)..(x + 2).min(width)).map(move |nx| ny * width + nx)).filter(move |&j| j != i)
+        };
+        let on_edge = |i: usize| [0, width - 1].contains(&(i % width)) || [0, height - 1].contains(&(i / width));
+        while cap < limit {
+            let edges = cap < MAX_ITER;
+            cap = cap.saturating_mul(2).min(limit);
+            let mut queued: Vec<bool> = (0..points.len()).map(|i| !escaped[i] && ((edges && on_edge(i)) || neighbors(i).any(|j| escaped[j]))).collect();
+            let mut queue: Vec<usize> = (0..points.len()).filter(|&i| queued[i]).collect();
+            while let Some(i) = queue.pop() {
+                iterate(i, cap, &mut orbits, &mut escaped);
+                if escaped[i] {
+                    for j in neighbors(i) {
+                        if !escaped[j] && !queued[j] {
+                            queued[j] = true;
+                            queue.push(j);
+                        }
+                    }
+                }
+            }
+        }
+
+        // An escape after exactly `MAX_ITER` iterations would read as inside
+        orbits
+            .iter()
+            .zip(&escaped)
+            .map(|(&(n, z), &escaped)| match (escaped, n) {
+                (false, _) => (MAX_ITER, z),
+                (true, MAX_ITER) => (MAX_ITER + 1, z),
+                (true, n) => (n, z),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,476 @@
+//! Scripted animations.
+//!
+//! An animation file is TOML describing timed segments, each animating the
+//! view from where the previous one left it to the values it gives:
+//!
+//! ```toml
+//! # Into seahorse valley, around it, and over to its Julia set
+//! [[segment]]
+//! duration = 10
+//! center = [-0.743, 0.131]
+//! zoom = 200
+//!
+//! [[segment]]
+//! duration = 4
+//! rotation = 90
+//! palette-offset = 50
+//! easing = "linear"
+//!
+//! [[segment]]
+//! duration = 3
+//! julia = [-0.743, 0.131]
+//! center = [0, 0]
+//! zoom = 1
+//! ```
+//!
+//! An optional `[start]` table with the same values, bar the duration and
+//! easing, sets the view the animation starts from, otherwise it starts from
+//! the view given on the command line.
+//!
+//! Segments give a `duration` in seconds, any of `center`, `zoom`,
+//! `rotation` (in degrees) and `palette-offset` to animate to, or `turns` to
+//! turn the view by around its center instead of a `rotation`, and an
+//! `easing` of them all: `linear`, `ease-in`, `ease-out`, `ease-in-out` (the
+//! default, a smoothstep, also called `smoothstep`), `exponential`, which
+//! starts and ends much more gently, or a CSS-like `cubic-bezier(x1, y1, x2,
+//! y2)` of its two control points, with `x1` and `x2` from 0 to 1.
+//! `palette = "<name>"`, `julia = [<re>, <im>]` and `mandelbrot = true` switch
+//! the palette, to the Julia set for a parameter, or back to the Mandelbrot
+//! set at the start of the segment. A Julia parameter given while already
+//! showing a Julia set is animated to instead. `exponent = <d>` animates the
+//! exponent of the Multibrot iteration, see [`crate::multibrot`].
+//!
+//! The viewer plays an animation with `--animation=<file.toml>`, and
+//! `zoom-video --animation=<file.toml>` renders it frame by frame. Tweens are
+//! animations of a single segment from the view to a preset, which Shift and
+//! the digit keys play and `zoom-video --tween=<preset>` renders, eased with
+//! `--tween-easing=<easing>`. Orbits turn the view around its center at a
+//! steady speed, spiraling in or out with a change of zoom on the way, as
+//! `--orbit=<turns> [--orbit-zoom=<factor>] [--orbit-duration=<seconds>]`
+//! sets them up: Y plays one in the viewer, and `zoom-video --orbit` renders
+//! it. Sweeps of the Multibrot exponent there and back, as
+//! `--sweep=<from>,<to> [--sweep-duration=<seconds>]` sets them up, are the
+//! same: Shift+Y plays one, and `zoom-video --sweep` renders it. The
+//! timeline panel of the viewer edits animations as keyframes, the views at
+//! the ends of the segments, and saves them in this format.
+
+use std::path::Path;
+
+use num::Complex;
+use pixels::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::palette::Palette;
+use crate::{arg_value, invalid_arg, parse_positive, Mandelbrot};
+
+/// Seconds a tween to a preset takes unless given
+pub const TWEEN_DURATION: f64 = 4.0;
+/// Seconds an orbit takes unless given
+pub const ORBIT_DURATION: f64 = 8.0;
+/// Exponents a sweep goes between, and the seconds it takes there and
+/// back, unless given
+const SWEEP_EXPONENTS: (f64, f64) = (2.0, 6.0);
+const SWEEP_DURATION: f64 = 12.0;
+/// Where the exponential easing would start unscaled, 2^-10
+const EXPONENTIAL_START: f64 = 1.0 / 1024.0;
+/// Bisections of the time along a Bézier curve, down to below `f64`
+/// resolution
+const BEZIER_STEPS: usize = 60;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Script {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<Segment>,
+    #[serde(rename = "segment", default)]
+    segments: Vec<Segment>,
+}
+
+/// A segment of the script, or its start without a duration
+#[derive(Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Segment {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    #[serde(default)]
+    easing: Easing,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    center: Option<[f64; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zoom: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rotation: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    turns: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette_offset: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    julia: Option<[f64; 2]>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    mandelbrot: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exponent: Option<f64>,
+}
+
+impl Segment {
+    /// The segment reaching `view`, with every value it animates.
+    #[cfg(feature = "editor")]
+    fn to(view: &Mandelbrot, from: Option<&Mandelbrot>) -> Self {
+        Segment {
+            center: Some([view.center_x, view.center_y]),
+            zoom: Some(view.zoom),
+            rotation: Some(view.rotation.to_degrees()),
+            palette_offset: Some(view.palette_offset),
+            palette: Some(view.palette.name()),
+            julia: view.julia.map(|c| [c.re, c.im]),
+            mandelbrot: view.julia.is_none() && from.is_some_and(|from| from.julia.is_some()),
+            // Only where the view or the one before is a Multibrot set
+            exponent: Some(view.exponent).filter(|_| view.multibrot() || from.is_some_and(Mandelbrot::multibrot)),
+            ..Segment::default()
+        }
+    }
+
+    /// Apply the values of the segment to `view`, or describe the first
+    /// invalid one.
+    fn apply(&self, view: &mut Mandelbrot) -> Result<(), String> {
+        if let Some([x, y]) = self.center {
+            (view.center_x, view.center_y) = (x, y);
+        }
+        if let Some(zoom) = self.zoom {
+            view.zoom = Some(zoom).filter(|&zoom| zoom > 0.0 && zoom.is_finite()).ok_or("invalid zoom")?;
+        }
+        match (self.rotation, self.turns) {
+            (Some(_), Some(_)) => return Err("both rotation and turns given".to_owned()),
+            (Some(degrees), None) => view.rotation = degrees.to_radians(),
+            (None, Some(turns)) => view.rotation += Some(turns).filter(|turns| turns.is_finite()).ok_or("invalid turns")? * std::f64::consts::TAU,
+            (None, None) => {}
+        }
+        if let Some(offset) = self.palette_offset {
+            view.palette_offset = Some(offset).filter(|&v| v >= 0.0 && v.is_finite()).ok_or("invalid palette-offset")?;
+        }
+        if let Some(name) = &self.palette {
+            view.palette = Palette::from_name(name).ok_or("invalid palette")?;
+        }
+        match (self.julia, self.mandelbrot) {
+            (Some(_), true) => return Err("both julia and mandelbrot given".to_owned()),
+            (Some([re, im]), false) => view.julia = Some(Complex::new(re, im)),
+            (None, true) => view.julia = None,
+            (None, false) => {}
+        }
+        if let Some(exponent) = self.exponent {
+            view.exponent = Some(exponent).filter(|&d| d > 1.0 && d.is_finite()).ok_or("invalid exponent")?;
+        }
+        Ok(())
+    }
+}
+
+/// How a segment speeds up and slows down
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    /// Smoothstep
+    #[default]
+    EaseInOut,
+    /// Exponential in and out, scaled to start at 0 and end at 1
+    Exponential,
+    /// Cubic Bézier from (0, 0) to (1, 1) with the control points `x1, y1`
+    /// and `x2, y2`, the progress `y` over the time `x`
+    Bezier([f64; 4]),
+}
+
+impl Easing {
+    /// The choices of the timeline, the Bézier curve as CSS's `ease`
+    #[cfg(feature = "editor")]
+    pub const ALL: [Easing; 6] = [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut, Easing::Exponential, Easing::Bezier([0.25, 0.1, 0.25, 1.0])];
+
+    /// Parse the easing named `name` in animation files.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease-in" => Some(Easing::EaseIn),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" | "smoothstep" => Some(Easing::EaseInOut),
+            "exponential" => Some(Easing::Exponential),
+            _ => {
+                let points = name.strip_prefix("cubic-bezier(")?.strip_suffix(')')?;
+                let points: Vec<f64> = points.split(',').map(|value| value.trim().parse().ok().filter(|value: &f64| value.is_finite())).collect::<Option<_>>()?;
+                let points: [f64; 4] = points.try_into().ok()?;
+                // Time only runs forwards between 0 and 1
+                ((0.0..=1.0).contains(&points[0]) && (0.0..=1.0).contains(&points[2])).then_some(Easing::Bezier(points))
+            }
+        }
+    }
+
+    /// Name of the easing in animation files.
+    pub fn name(self) -> String {
+        match self {
+            Easing::Linear => "linear".to_owned(),
+            Easing::EaseIn => "ease-in".to_owned(),
+            Easing::EaseOut => "ease-out".to_owned(),
+            Easing::EaseInOut => "ease-in-out".to_owned(),
+            Easing::Exponential => "exponential".to_owned(),
+            Easing::Bezier([x1, y1, x2, y2]) => format!("cubic-bezier({x1}, {y1}, {x2}, {y2})"),
+        }
+    }
+
+    /// Progress through a segment at the fraction `t` of its duration.
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Exponential => {
+                let half = |t: f64| (2f64.powf(20.0 * t - 10.0) - EXPONENTIAL_START) / (1.0 - EXPONENTIAL_START) / 2.0;
+                if t < 0.5 { half(t) } else { 1.0 - half(1.0 - t) }
+            }
+            Easing::Bezier([x1, y1, x2, y2]) => {
+                let bezier = |a: f64, b: f64, s: f64| 3.0 * a * s * (1.0 - s) * (1.0 - s) + 3.0 * b * s * s * (1.0 - s) + s * s * s;
+                // The time along the curve only grows, bisect for the point
+                // at `t`
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..BEZIER_STEPS {
+                    let middle = (low + high) / 2.0;
+                    match bezier(x1, x2, middle) < t {
+                        true => low = middle,
+                        false => high = middle,
+                    }
+                }
+                bezier(y1, y2, (low + high) / 2.0)
+            }
+        }
+    }
+}
+
+/// An orbit of the view around its center
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orbit {
+    /// Turns around the center, clockwise when negative
+    pub turns: f64,
+    /// Factor the zoom changes by on the way, spiraling in above 1
+    pub zoom: f64,
+    pub duration: f64,
+}
+
+impl Orbit {
+    /// The orbit of `--orbit`, `--orbit-zoom` and `--orbit-duration`, a
+    /// whole turn without a change of zoom by default, and whether
+    /// `--orbit` is given.
+    pub fn from_args(args: &[String]) -> Result<(Self, bool), Error> {
+        let orbit = arg_value(args, "orbit");
+        let turns = match &orbit {
+            Some(value) => value.parse().ok().filter(|turns: &f64| *turns != 0.0 && turns.is_finite()).ok_or_else(|| invalid_arg("orbit", value))?,
+            None => 1.0,
+        };
+        let positive = |name: &str, default: f64| match arg_value(args, name) {
+            Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg(name, &value)),
+            None => Ok(default),
+        };
+        let zoom = positive("orbit-zoom", 1.0)?;
+        let duration = positive("orbit-duration", ORBIT_DURATION)?;
+        Ok((Self { turns, zoom, duration }, orbit.is_some()))
+    }
+
+    /// The animation of the orbit from `from`.
+    pub fn animation(&self, from: &Mandelbrot) -> Animation {
+        let to = Mandelbrot { rotation: from.rotation + self.turns * std::f64::consts::TAU, zoom: from.zoom * self.zoom, ..from.clone() };
+        Animation { keyframes: vec![from.clone(), to], timing: vec![(self.duration, Easing::Linear)] }
+    }
+}
+
+/// A sweep of the Multibrot exponent from one value to another and back
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sweep {
+    pub from: f64,
+    pub to: f64,
+    /// Seconds there and back
+    pub duration: f64,
+}
+
+impl Sweep {
+    /// The sweep of `--sweep` and `--sweep-duration`, from 2 to 6 and back
+    /// over 12 seconds by default, and whether `--sweep` is given.
+    pub fn from_args(args: &[String]) -> Result<(Self, bool), Error> {
+        let sweep = arg_value(args, "sweep");
+        let (from, to) = match &sweep {
+            Some(value) => {
+                let exponents = value.split_once(',').and_then(|(from, to)| Some((from.trim().parse().ok()?, to.trim().parse().ok()?)));
+                let valid = |d: f64| d > 1.0 && d.is_finite();
+                exponents.filter(|&(from, to)| valid(from) && valid(to)).ok_or_else(|| invalid_arg("sweep", value))?
+            }
+            None => SWEEP_EXPONENTS,
+        };
+        let duration = match arg_value(args, "sweep-duration") {
+            Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("sweep-duration", &value))?,
+            None => SWEEP_DURATION,
+        };
+        Ok((Self { from, to, duration }, sweep.is_some()))
+    }
+
+    /// The animation of the sweep from `view`, easing in and out at both
+    /// ends.
+    pub fn animation(&self, view: &Mandelbrot) -> Animation {
+        let at = |exponent| Mandelbrot { exponent, ..view.clone() };
+        let half = (self.duration / 2.0, Easing::EaseInOut);
+        Animation { keyframes: vec![at(self.from), at(self.to), at(self.from)], timing: vec![half, half] }
+    }
+}
+
+/// The easing of tweens, from `--tween-easing`.
+pub fn tween_easing(args: &[String]) -> Result<Easing, Error> {
+    match arg_value(args, "tween-easing") {
+        Some(value) => Easing::from_name(&value).ok_or_else(|| invalid_arg("tween-easing", &value)),
+        None => Ok(Easing::default()),
+    }
+}
+
+impl TryFrom<String> for Easing {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        Easing::from_name(&name).ok_or_else(|| format!("invalid easing {name}"))
+    }
+}
+
+impl From<Easing> for String {
+    fn from(easing: Easing) -> String {
+        easing.name()
+    }
+}
+
+/// A view an animation passes through `time` seconds in, reached from the
+/// previous one with `easing`
+#[derive(Clone, Debug)]
+pub struct Keyframe {
+    pub time: f64,
+    pub view: Mandelbrot,
+    pub easing: Easing,
+}
+
+/// An animation loaded for a starting view, ready to be sampled at any time
+#[derive(Clone, Debug)]
+pub struct Animation {
+    /// The view at the start of every segment and at the end
+    keyframes: Vec<Mandelbrot>,
+    /// Duration and easing of every segment
+    timing: Vec<(f64, Easing)>,
+}
+
+impl Animation {
+    /// Load the animation file at `path`, starting from `start` unless it
+    /// sets its own start.
+    pub fn load(path: &Path, start: &Mandelbrot) -> Result<Self, Error> {
+        let invalid = |message: String| Error::UserDefined(format!("{}: {message}", path.display()).into());
+        let text = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let script: Script = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        if script.segments.is_empty() {
+            return Err(invalid("no segments".to_owned()));
+        }
+
+        let mut view = start.clone();
+        if let Some(start) = &script.start {
+            if start.duration.is_some() {
+                return Err(invalid("start: no duration expected".to_owned()));
+            }
+            start.apply(&mut view).map_err(|e| invalid(format!("start: {e}")))?;
+        }
+        let mut keyframes = vec![view];
+        let mut timing = Vec::new();
+        for (index, segment) in script.segments.iter().enumerate() {
+            let wrong = |message: &str| invalid(format!("segment {}: {message}", index + 1));
+            let duration = segment.duration.filter(|&d| d > 0.0 && d.is_finite()).ok_or_else(|| wrong("invalid duration"))?;
+            let mut view = keyframes[index].clone();
+            segment.apply(&mut view).map_err(|e| wrong(&e))?;
+            keyframes.push(view);
+            timing.push((duration, segment.easing));
+        }
+        Ok(Self { keyframes, timing })
+    }
+
+    /// The animation through `keyframes`, at least two at increasing times
+    /// from 0 on.
+    pub fn from_keyframes(keyframes: &[Keyframe]) -> Self {
+        let timing = keyframes.windows(2).map(|pair| (pair[1].time - pair[0].time, pair[1].easing)).collect();
+        Self { keyframes: keyframes.iter().map(|keyframe| keyframe.view.clone()).collect(), timing }
+    }
+
+    /// The keyframes of the animation, the first at 0 seconds.
+    #[cfg(feature = "editor")]
+    pub fn keyframes(&self) -> Vec<Keyframe> {
+        let mut time = 0.0;
+        let mut keyframes = vec![Keyframe { time, view: self.keyframes[0].clone(), easing: Easing::default() }];
+        for (view, &(duration, easing)) in self.keyframes[1..].iter().zip(&self.timing) {
+            time += duration;
+            keyframes.push(Keyframe { time, view: view.clone(), easing });
+        }
+        keyframes
+    }
+
+    /// The animation in the animation file format, with its start.
+    #[cfg(feature = "editor")]
+    pub fn to_toml(&self) -> String {
+        let segments = self.keyframes.windows(2).zip(&self.timing).map(|(pair, &(duration, easing))| Segment {
+            duration: Some(duration),
+            easing,
+            ..Segment::to(&pair[1], Some(&pair[0]))
+        });
+        let script = Script { start: Some(Segment::to(&self.keyframes[0], None)), segments: segments.collect() };
+        toml::to_string(&script).unwrap_or_default()
+    }
+
+    /// The animation flying from `from` to `to` in `duration` seconds with
+    /// `easing`.
+    pub fn tween(from: &Mandelbrot, to: Mandelbrot, duration: f64, easing: Easing) -> Self {
+        Self { keyframes: vec![from.clone(), to], timing: vec![(duration, easing)] }
+    }
+
+    /// Length of the animation in seconds.
+    pub fn duration(&self) -> f64 {
+        self.timing.iter().map(|&(duration, _)| duration).sum()
+    }
+
+    /// The view `time` seconds into the animation, the last one after it ends.
+    pub fn view_at(&self, time: f64) -> Mandelbrot {
+        let mut start = 0.0;
+        for (index, &(duration, easing)) in self.timing.iter().enumerate() {
+            if time < start + duration {
+                let t = easing.apply(((time - start) / duration).max(0.0));
+                return interpolate(&self.keyframes[index], &self.keyframes[index + 1], t);
+            }
+            start += duration;
+        }
+        self.keyframes[self.keyframes.len() - 1].clone()
+    }
+}
+
+/// The view the fraction `t` of the way from `from` to `to`. Palettes blend
+/// into each other, and what else switches takes `to`'s side the whole way.
+fn interpolate(from: &Mandelbrot, to: &Mandelbrot, t: f64) -> Mandelbrot {
+    let mut view = to.clone();
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+
+    // Zoom at a steady rate, and move the center as the view shrinks, so it
+    // closes in on the target as fast as the view narrows
+    view.zoom = from.zoom * (to.zoom / from.zoom).powf(t);
+    let weight = if (to.zoom / from.zoom - 1.0).abs() > 1e-9 {
+        (1.0 / view.zoom - 1.0 / from.zoom) / (1.0 / to.zoom - 1.0 / from.zoom)
+    } else {
+        t
+    };
+    view.center_x = from.center_x + (to.center_x - from.center_x) * weight;
+    view.center_y = from.center_y + (to.center_y - from.center_y) * weight;
+
+    view.rotation = lerp(from.rotation, to.rotation);
+    view.palette_offset = lerp(from.palette_offset, to.palette_offset);
+    view.exponent = lerp(from.exponent, to.exponent);
+    if let (Some(a), Some(b)) = (from.julia, to.julia) {
+        view.julia = Some(a + (b - a) * t);
+    }
+    view.palette_blend = (from.palette != to.palette && t < 1.0).then_some((from.palette, t));
+    view
+}
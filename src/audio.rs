@@ -0,0 +1,193 @@
+//! Audio-reactive modulation of the viewer parameters.
+//!
+//! Captures the default input device with cpal, runs an FFT over the most recent
+//! window of samples and turns the bass energy into a modulation level that the
+//! main loop applies to the zoom speed, the palette offset or the Julia morph.
+
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, Sample, SizedSample};
+use num::Complex;
+
+const FFT_SIZE: usize = 1024;
+const BASS_LOW_HZ: f32 = 20.0;
+const BASS_HIGH_HZ: f32 = 200.0;
+/// Number of analysis frames making up the running average energy
+const HISTORY_LEN: usize = 60;
+/// Energy this many times above the running average counts as a beat
+const BEAT_THRESHOLD: f32 = 1.4;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Viewer parameter driven by the audio energy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioTarget {
+    ZoomSpeed,
+    PaletteOffset,
+    /// Speed of the Julia morph animation
+    JuliaParameter,
+}
+
+impl AudioTarget {
+    /// Parse the value of the `--audio=<target>` argument.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "zoom" => Some(AudioTarget::ZoomSpeed),
+            "palette" => Some(AudioTarget::PaletteOffset),
+            "julia" => Some(AudioTarget::JuliaParameter),
+            _ => None,
+        }
+    }
+}
+
+/// Result of analysing the latest audio window
+#[derive(Clone, Copy, Debug)]
+pub struct AudioLevels {
+    /// Bass energy relative to the running average (1.0 = average loudness)
+    pub energy: f32,
+    /// Whether the current window stands out enough to count as a beat
+    pub beat: bool,
+}
+
+/// Captures audio and turns it into modulation levels once per frame.
+pub struct AudioReactor {
+    pub target: AudioTarget,
+    // The stream stops capturing when dropped
+    _stream: cpal::Stream,
+    samples: Arc<Mutex<Vec<f32>>>,
+    sample_rate: f32,
+    history: Vec<f32>,
+}
+
+impl AudioReactor {
+    /// Start capturing from the default input device.
+    pub fn new(target: AudioTarget) -> Result<Self, BoxError> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or("no audio input device available")?;
+        let config = device.default_input_config()?;
+        let sample_rate = config.sample_rate().0 as f32;
+        let samples = Arc::new(Mutex::new(Vec::with_capacity(FFT_SIZE * 2)));
+
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config.into(), samples.clone())?,
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config.into(), samples.clone())?,
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config.into(), samples.clone())?,
+            format => return Err(format!("unsupported sample format {format}").into()),
+        };
+        stream.play()?;
+
+        Ok(Self {
+            target,
+            _stream: stream,
+            samples,
+            sample_rate,
+            history: Vec::with_capacity(HISTORY_LEN),
+        })
+    }
+
+    /// Analyse the most recent window of samples.
+    pub fn levels(&mut self) -> AudioLevels {
+        let samples = self.samples.lock().unwrap().clone();
+        analyse(&samples, self.sample_rate, &mut self.history)
+    }
+}
+
+/// Analyse the last `FFT_SIZE` of `samples` against the energy `history` of
+/// the previous windows, which it is added to.
+pub fn analyse(samples: &[f32], sample_rate: f32, history: &mut Vec<f32>) -> AudioLevels {
+    let start = samples.len().saturating_sub(FFT_SIZE);
+    let mut buf: Vec<Complex<f32>> = samples[start..].iter().map(|&s| Complex::new(s, 0.0)).collect();
+    buf.resize(FFT_SIZE, Complex::new(0.0, 0.0));
+
+    // Hann window to reduce spectral leakage
+    for (i, s) in buf.iter_mut().enumerate() {
+        let w = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32).cos();
+        *s *= w;
+    }
+    fft(&mut buf);
+
+    let bin_hz = sample_rate / FFT_SIZE as f32;
+    let low = (BASS_LOW_HZ / bin_hz).ceil() as usize;
+    let high = ((BASS_HIGH_HZ / bin_hz).floor() as usize).clamp(low, FFT_SIZE / 2);
+    let energy: f32 = buf[low..=high].iter().map(|c| c.norm_sqr()).sum();
+
+    let average = if history.is_empty() {
+        energy
+    } else {
+        history.iter().sum::<f32>() / history.len() as f32
+    };
+    if history.len() == HISTORY_LEN {
+        history.remove(0);
+    }
+    history.push(energy);
+
+    let relative = if average > f32::EPSILON { energy / average } else { 0.0 };
+    AudioLevels {
+        energy: relative.min(4.0),
+        beat: relative > BEAT_THRESHOLD,
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Arc<Mutex<Vec<f32>>>,
+) -> Result<cpal::Stream, BoxError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mut samples = samples.lock().unwrap();
+            // Downmix to mono
+            samples.extend(data.chunks(channels).map(|frame| {
+                frame.iter().map(|&s| f32::from_sample(s)).sum::<f32>() / channels as f32
+            }));
+            let excess = samples.len().saturating_sub(FFT_SIZE);
+            samples.drain(..excess);
+        },
+        |err| eprintln!("audio stream error: {err}"),
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// In-place iterative radix-2 FFT. `buf.len()` must be a power of two.
+fn fft(buf: &mut [Complex<f32>]) {
+    let n = buf.len();
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buf.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let w_len = Complex::from_polar(1.0, -2.0 * std::f32::consts::PI / len as f32);
+        for chunk in buf.chunks_mut(len) {
+            let mut w = Complex::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let u = chunk[k];
+                let v = chunk[k + len / 2] * w;
+                chunk[k] = u + v;
+                chunk[k + len / 2] = u - v;
+                w *= w_len;
+            }
+        }
+        len <<= 1;
+    }
+}
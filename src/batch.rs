@@ -0,0 +1,59 @@
+//! Batch rendering of a list of jobs.
+//!
+//! `mandelbrot batch --jobs=<file>` renders every job of a job file in turn,
+//! e.g. a set of posters or wallpapers overnight. Each line of the file holds
+//! the options of one `render` command, view options included:
+//!
+//! ```text
+//! # Seahorse valley as a 4K wallpaper
+//! --out=seahorse.png --size=3840x2160 --center=-0.743,0.131 --zoom=50 --palette-offset=10
+//! ```
+//!
+//! Blank lines and lines starting with `#` are skipped. A failed job is
+//! reported and the remaining jobs are still rendered. `--quiet` and
+//! `--json-progress` apply to the jobs as well as to the batch.
+
+use std::time::Instant;
+
+use pixels::Error;
+
+use crate::progress::{self, Progress};
+use crate::{arg_value, error_message, export, invalid_arg, view_from_args};
+
+/// Run the `batch` command.
+pub fn batch_from_args(args: &[String]) -> Result<(), Error> {
+    let path = arg_value(args, "jobs").ok_or_else(|| invalid_arg("jobs", ""))?;
+    let jobs = std::fs::read_to_string(&path).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    let jobs: Vec<Vec<String>> = jobs
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split_whitespace().map(str::to_owned).collect())
+        .collect();
+
+    let mode = progress::Mode::from_args(args);
+    // The jobs show their own bars
+    let batch_mode = if mode == progress::Mode::Bar { progress::Mode::Quiet } else { mode };
+    let progress = Progress::new(batch_mode, "batch", "jobs", jobs.len() as u64, None);
+    let start = Instant::now();
+    let mut failed = 0;
+    for (index, job) in jobs.iter().enumerate() {
+        if mode == progress::Mode::Bar {
+            eprintln!("job {}/{}: {}", index + 1, jobs.len(), job.join(" "));
+        }
+        let result = view_from_args(job).and_then(|mandelbrot| export::render_from_args(&mandelbrot, job, mode));
+        if let Err(e) = result {
+            eprintln!("job {} failed: {}", index + 1, error_message(&e));
+            failed += 1;
+        }
+        progress.set(index as u64 + 1);
+    }
+
+    if mode == progress::Mode::Bar {
+        eprintln!("{} of {} jobs rendered in {:.0?}", jobs.len() - failed, jobs.len(), start.elapsed());
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(Error::UserDefined(format!("{failed} jobs failed").into())),
+    }
+}
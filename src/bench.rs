@@ -0,0 +1,140 @@
+//! Benchmarks of the iteration kernels.
+//!
+//! `mandelbrot bench` times the kernels on representative views: the scalar
+//! kernel, the SIMD kernel of [`crate::simd`] on one thread and on all of
+//! them, and perturbation from a reference orbit on a deep view, also on one
+//! thread. Each benchmark iterates a grid of points over the window, and
+//! prints the fastest, median and slowest of `--samples=<n>` runs (default
+//! 10) and the iterations per second of the median, after a run to warm up.
+//!
+//! `--filter=<text>` runs only the benchmarks whose names, like
+//! `simd/seahorse`, contain the text. `--baseline=<file.json>` compares the
+//! medians with those saved in the file by the last run and saves the new
+//! ones, so the effect of a change shows run to run. `cargo bench` runs this
+//! command with a baseline in the target directory.
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use num::Complex;
+use pixels::Error;
+use rayon::prelude::*;
+
+use crate::{arg_value, deep, invalid_arg, Mandelbrot, HEIGHT, WIDTH};
+
+const DEFAULT_SAMPLES: usize = 10;
+/// Pixels between the points iterated, along both axes
+const STRIDE: usize = 4;
+/// Points handed to a thread at a time by the threaded kernel
+const CHUNK: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kernel {
+    Scalar,
+    Simd,
+    Threaded,
+    Perturbation,
+}
+
+impl Kernel {
+    fn name(self) -> &'static str {
+        match self {
+            Kernel::Scalar => "scalar",
+            Kernel::Simd => "simd",
+            Kernel::Threaded => "threaded",
+            Kernel::Perturbation => "perturbation",
+        }
+    }
+
+    /// Iterate `points` of `view`, offsets from the center for perturbation
+    /// from `reference`, returning the iterations done.
+    fn run(self, view: &Mandelbrot, reference: Option<&deep::Reference>, points: &[Complex<f64>]) -> u64 {
+        let batch = |points: &[Complex<f64>]| {
+            let mut results = vec![(0, Complex::new(0.0, 0.0)); points.len()];
+            view.iterate_batch(points, &mut results);
+            results.iter().map(|&(n, _)| n as u64).sum::<u64>()
+        };
+        match (self, reference) {
+            (Kernel::Scalar, _) => points.iter().map(|&point| view.iterate_at(point).0 as u64).sum(),
+            (Kernel::Simd, _) => batch(points),
+            (Kernel::Threaded, _) => points.par_chunks(CHUNK).map(batch).sum(),
+            (Kernel::Perturbation, Some(reference)) => points.iter().map(|&offset| reference.iterate(view, offset).0 as u64).sum(),
+            (Kernel::Perturbation, None) => 0,
+        }
+    }
+}
+
+/// The benchmarks, each a kernel on a named view.
+fn benchmarks() -> Vec<(Kernel, &'static str, Mandelbrot)> {
+    let view = |center_x, center_y, zoom, julia| Mandelbrot { center_x, center_y, zoom, julia, ..Mandelbrot::new() };
+    let shallow = [
+        ("whole", view(-0.75, 0.0, 1.0, None)),
+        ("seahorse", view(-0.743643887037151, 0.13182590420533, 1e3, None)),
+        ("julia", view(0.0, 0.0, 1.0, Some(Complex::new(-0.8, 0.156)))),
+    ];
+    let mut benchmarks: Vec<_> = [Kernel::Scalar, Kernel::Simd, Kernel::Threaded]
+        .into_iter()
+        .flat_map(|kernel| shallow.iter().map(move |(name, view)| (kernel, *name, view.clone())))
+        .collect();
+    benchmarks.push((Kernel::Perturbation, "deep", view(-0.743643887037151, 0.13182590420533, 1e20, None)));
+    benchmarks
+}
+
+/// Run the `bench` command.
+pub fn bench_from_args(args: &[String]) -> Result<(), Error> {
+    let samples = match arg_value(args, "samples") {
+        Some(value) => value.parse().ok().filter(|&n| n >= 1).ok_or_else(|| invalid_arg("samples", &value))?,
+        None => DEFAULT_SAMPLES,
+    };
+    let filter = arg_value(args, "filter").unwrap_or_default();
+    let baseline_path = arg_value(args, "baseline");
+    let baseline: BTreeMap<String, f64> = baseline_path
+        .as_ref()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+
+    let mut medians = baseline.clone();
+    for (kernel, view_name, view) in benchmarks() {
+        let name = format!("{}/{view_name}", kernel.name());
+        if !name.contains(&filter) {
+            continue;
+        }
+        let reference = (kernel == Kernel::Perturbation).then(|| deep::Reference::new(&view));
+        let points: Vec<_> = (0..HEIGHT as usize)
+            .step_by(STRIDE)
+            .flat_map(|y| (0..WIDTH as usize).step_by(STRIDE).map(move |x| (x as f64, y as f64)))
+            .map(|(x, y)| match kernel {
+                Kernel::Perturbation => view.view_offset(x, y),
+                _ => view.pixel_to_complex(x, y),
+            })
+            .collect();
+
+        let iterations = black_box(kernel.run(&view, reference.as_ref(), &points));
+        let mut times: Vec<Duration> = (0..samples)
+            .map(|_| {
+                let start = Instant::now();
+                black_box(kernel.run(&view, reference.as_ref(), black_box(&points)));
+                start.elapsed()
+            })
+            .collect();
+        times.sort();
+        let median = times[times.len() / 2];
+
+        let change = match baseline.get(&name) {
+            Some(&before) => format!("  {:+.1}% from the baseline", (median.as_secs_f64() / before - 1.0) * 100.0),
+            None => String::new(),
+        };
+        let rate = iterations as f64 / median.as_secs_f64() / 1e6;
+        println!("{name:<24} [{:.2?} {median:.2?} {:.2?}]  {rate:.0} M iterations/s{change}", times[0], times[times.len() - 1]);
+        medians.insert(name, median.as_secs_f64());
+    }
+
+    if let Some(path) = baseline_path {
+        let json = serde_json::to_string_pretty(&medians).map_err(|e| Error::UserDefined(Box::new(e)))?;
+        std::fs::write(Path::new(&path), json + "\n").map_err(|e| Error::UserDefined(format!("{path}: {e}").into()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Bifurcation strip under the Mandelbrot view.
+//!
+//! Shift+B, or `--bifurcation` from the start, shows the bifurcation diagram
+//! of the real slice of the set in a strip along the bottom of the window:
+//! every column iterates the real parameter `c` shown at that column of the
+//! middle row of the view, lets the orbit of 0 settle, and plots where it
+//! goes on from there, from `z = 2` at the top of the strip to `z = -2` at
+//! the bottom. So the strip lines up with the view: the fixed point of the
+//! main cardioid splits in two where the period 2 bulb sits on the real
+//! axis, in four at the next bulb, and so on into chaos towards -2, with the
+//! windows of the minibrots along the axis where they are in the view.
+//! Multibrot views plot the real part of their orbits.
+
+use crate::multibrot;
+use crate::{Mandelbrot, HEIGHT, WIDTH};
+
+/// Height of the strip in pixels
+const STRIP_HEIGHT: usize = 120;
+/// Iterations the orbit settles for before it is plotted, and is plotted for
+const SETTLE: u32 = 500;
+const SAMPLES: u32 = 400;
+/// Values of `z` at the top and the bottom of the strip
+const EXTENT: f64 = 2.0;
+const EDGE_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+pub struct Bifurcation {
+    /// The parameters and exponent the columns were iterated for, and their
+    /// hits, row by row
+    key: Option<(Vec<f64>, f64)>,
+    hits: Vec<u32>,
+}
+
+impl Bifurcation {
+    pub fn new() -> Self {
+        Self { key: None, hits: vec![0; WIDTH as usize * STRIP_HEIGHT] }
+    }
+
+    /// Draw the strip for `view` along the bottom of `frame`, iterating its
+    /// columns again when the view has moved.
+    pub fn draw(&mut self, frame: &mut [u8], view: &Mandelbrot) {
+        let key = (parameters(view), view.exponent);
+        if self.key.as_ref() != Some(&key) {
+            let width = WIDTH as usize;
+            self.hits.fill(0);
+            for (x, &c) in key.0.iter().enumerate() {
+                for (row, count) in column(c, view.exponent).into_iter().enumerate() {
+                    self.hits[row * width + x] = count;
+                }
+            }
+            self.key = Some(key);
+        }
+
+        // The hits brighten a darkened backdrop on a log scale, which shows
+        // the chaotic columns as well as the periodic ones
+        let top = HEIGHT as usize - STRIP_HEIGHT;
+        let full = (SAMPLES as f64).ln_1p();
+        for (i, &hits) in self.hits.iter().enumerate() {
+            let pixel = &mut frame[(top * WIDTH as usize + i) * 4..][..4];
+            let t = (hits as f64).ln_1p() / full;
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as f64 / 3.0 * (1.0 - t) + 255.0 * t) as u8;
+            }
+        }
+        let edge = top * WIDTH as usize * 4;
+        frame[edge..edge + WIDTH as usize * 4].chunks_exact_mut(4).for_each(|pixel| pixel.copy_from_slice(&EDGE_COLOR));
+    }
+}
+
+/// The real parameters the columns of the strip show, those of the middle
+/// row of `view`.
+pub fn parameters(view: &Mandelbrot) -> Vec<f64> {
+    (0..WIDTH).map(|x| view.projection.apply(view.pixel_to_complex(x as f64 + 0.5, HEIGHT as f64 / 2.0)).re).collect()
+}
+
+/// How often the settled orbit of 0 under `z -> z^d + c` passes through
+/// each row of the strip, from the top, none where it escapes.
+pub fn column(c: f64, d: f64) -> Vec<u32> {
+    let mut hits = vec![0; STRIP_HEIGHT];
+    let c = num::Complex::new(c, 0.0);
+    let mut z = num::Complex::new(0.0, 0.0);
+    for n in 0..SETTLE + SAMPLES {
+        z = multibrot::power(z, d) + c;
+        if z.norm_sqr() > EXTENT * EXTENT {
+            hits.fill(0);
+            break;
+        }
+        if n >= SETTLE {
+            let row = ((EXTENT - z.re) / (2.0 * EXTENT) * STRIP_HEIGHT as f64) as usize;
+            hits[row.min(STRIP_HEIGHT - 1)] += 1;
+        }
+    }
+    hits
+}
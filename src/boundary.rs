@@ -0,0 +1,109 @@
+//! Keeping the view on the boundary of the set.
+//!
+//! The automatic zoom heads for the view center wherever it is, and a center
+//! a little off the boundary ends up in the flat interior or the empty
+//! outside once the view has narrowed past the distance between them. With B
+//! the viewer follows the boundary instead: every frame it moves the center a
+//! part of the way to the nearest boundary point, found by descending the
+//! distance estimate from the center, so the detail stays in view however
+//! deep the zoom goes. Inside the set the search first looks outwards in
+//! growing circles for a point that escapes, and bisects the way to it for
+//! where the set ends.
+//!
+//! This follows the plain view plane, the projections and the exponential
+//! map are left where they are.
+
+use num::Complex;
+
+use crate::projection::Projection;
+use crate::{Mandelbrot, HEIGHT, MAX_ITER};
+
+/// Escape radius of the descent, large enough for the distance estimate to
+/// hold
+const ESCAPE_RADIUS: f64 = 1000.0;
+/// Steps of the descent before it gives up
+const MAX_STEPS: usize = 256;
+/// Directions tried on each circle around an interior point
+const DIRECTIONS: usize = 16;
+/// Part of the way to the boundary the center moves each frame, so it glides
+/// rather than jumps as the target shifts
+const NUDGE: f64 = 0.1;
+
+impl Mandelbrot {
+    /// The boundary point nearest `point` of the view plane to within
+    /// `tolerance`, no further than `reach` from it, if one is found.
+    pub fn nearest_boundary(&self, point: Complex<f64>, tolerance: f64, reach: f64) -> Option<Complex<f64>> {
+        let view = Mandelbrot { bailout_radius: self.bailout_radius.max(ESCAPE_RADIUS), ..self.clone() };
+        let start = match view.walk_derivative(point).0 {
+            MAX_ITER => view.escape_near(point, tolerance, reach)?,
+            _ => point,
+        };
+        let mut point = start;
+        for _ in 0..MAX_STEPS {
+            let (m, z, derivative) = view.walk_derivative(point);
+            if m == MAX_ITER {
+                // Stepped over the boundary, by less than the last step
+                return Some(point);
+            }
+            // A quarter of the distance estimate, which the boundary is no
+            // nearer than, so the descent doesn't step over it
+            let distance = z.norm() * z.norm().ln() / derivative.norm() / 2.0;
+            // The potential falls fastest towards the set, against its
+            // gradient, the conjugate of the logarithmic derivative
+            let direction = -(derivative / z).conj();
+            if !(distance.is_finite() && direction.norm() > 0.0) {
+                return None;
+            }
+            if distance < tolerance {
+                return Some(point);
+            }
+            point += direction / direction.norm() * distance;
+            if (point - start).norm() > 2.0 * reach {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// A point that escapes within `tolerance` of the boundary near the
+    /// interior point `point`, on the way to the first escaping point of the
+    /// smallest circle around it from `tolerance` out to `reach` that has one.
+    fn escape_near(&self, point: Complex<f64>, tolerance: f64, reach: f64) -> Option<Complex<f64>> {
+        let escapes = |point: Complex<f64>| self.walk_derivative(point).0 < MAX_ITER;
+        let mut radius = tolerance;
+        let mut outside = loop {
+            if radius > reach {
+                return None;
+            }
+            let mut circle = (0..DIRECTIONS).map(|i| point + Complex::from_polar(radius, i as f64 * std::f64::consts::TAU / DIRECTIONS as f64));
+            if let Some(outside) = circle.find(|&point| escapes(point)) {
+                break outside;
+            }
+            radius *= 2.0;
+        };
+        // Bisect the way out for where it leaves the set
+        let mut inside = point;
+        while (outside - inside).norm() > tolerance {
+            let middle = (inside + outside) / 2.0;
+            match escapes(middle) {
+                true => outside = middle,
+                false => inside = middle,
+            }
+        }
+        Some(outside)
+    }
+
+    /// Move the view center a part of the way to the nearest boundary point
+    /// within the view, if there is one.
+    pub fn nudge_to_boundary(&mut self) {
+        if self.exponential_map || self.projection != Projection::Plane {
+            return;
+        }
+        let height = 2.5 / self.zoom;
+        let center = Complex::new(self.center_x, self.center_y);
+        if let Some(target) = self.nearest_boundary(center, height / HEIGHT as f64, height) {
+            let center = center + (target - center) * NUDGE;
+            (self.center_x, self.center_y) = (center.re, center.im);
+        }
+    }
+}
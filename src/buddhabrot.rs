@@ -0,0 +1,240 @@
+//! Buddhabrot rendering.
+//!
+//! `mandelbrot buddhabrot --out=<file.png> [--size=<width>x<height>]
+//! [--samples=<n>] [--min-iterations=<n>] [--seed=<n>]` plots how often the orbits of
+//! escaping parameters pass through each pixel of the initial view, for `n`
+//! sampled parameters (a million by default). Short orbits spread evenly over
+//! the view, `--min-iterations` leaves out those escaping sooner to bring out
+//! the structure of zoomed views.
+//!
+//! Sampling parameters uniformly wastes nearly all orbits on zoomed views,
+//! as few of them pass through the view, so the parameters are sampled with
+//! Metropolis–Hastings instead. Several chains each walk from parameter to
+//! parameter, mostly by small mutations and sometimes by jumping to a random
+//! one, and accept a step with the ratio of how many points of the new and the
+//! old orbit land in the view. The chains thus spend their samples on orbits
+//! in proportion to their contribution, and each orbit is plotted with the
+//! inverse weight, which keeps the image the one uniform sampling converges
+//! to.
+//!
+//! The same options render the same image on any number of threads: every
+//! chain draws from its own generator seeded from `--seed` (0 by default) and
+//! its index, and the weights are summed as fixed point numbers, whose sum
+//! doesn't depend on the order the chains are added in.
+
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use num::Complex;
+use pixels::Error;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::export::{self, Encoding, Format};
+use crate::progress::{self, Progress};
+use crate::{arg_value, invalid_arg, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+const DEFAULT_SAMPLES: u64 = 1_000_000;
+/// Chains sampling in parallel, a fixed number so the samples don't depend on
+/// the threads
+const CHAINS: u64 = 64;
+/// Steps of every chain before its samples are plotted, so the chains forget
+/// where they started
+const BURN_IN: u64 = 1000;
+/// Random parameters tried per chain to find one whose orbit meets the view
+const SEED_TRIES: u32 = 100_000;
+/// Chance of a step jumping to a random parameter rather than mutating
+const JUMP_CHANCE: f64 = 0.2;
+/// Random jumps pick a parameter from this square around the origin, which
+/// holds every orbit that doesn't escape right away
+const JUMP_EXTENT: f64 = 2.0;
+/// Mutations move by up to this fraction of the view height, down to that
+/// times `MUTATION_RANGE`, with the distance distributed logarithmically
+const MUTATION_SIZE: f64 = 0.1;
+const MUTATION_RANGE: f64 = 1e-4;
+/// Progress is reported after every round of samples
+const ROUNDS: u64 = 20;
+/// Fixed point unit of the density, a weight of 1 in the plot
+const WEIGHT_UNIT: f64 = (1u64 << 32) as f64;
+
+/// Maps orbit points to pixels of the output image
+struct Canvas<'a> {
+    mandelbrot: &'a Mandelbrot,
+    width: u32,
+    height: u32,
+    /// Orbits escaping in fewer iterations are left out
+    min_iterations: u32,
+}
+
+impl Canvas<'_> {
+    /// Index of the pixel `z` falls on, if it is in the view.
+    fn pixel(&self, z: Complex<f64>) -> Option<usize> {
+        // Window pixels to image pixels, as in `export::render_row`
+        let scale = HEIGHT as f64 / self.height as f64;
+        let (x, y) = self.mandelbrot.complex_to_pixel(z);
+        let x = (x - WIDTH as f64 / 2.0) / scale + self.width as f64 / 2.0;
+        let y = (y - HEIGHT as f64 / 2.0) / scale + self.height as f64 / 2.0;
+        let inside = x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64;
+        inside.then(|| y as usize * self.width as usize + x as usize)
+    }
+
+    /// Iterate `c` and collect the pixels its orbit passes through, leaving
+    /// `hits` empty unless it escapes after at least `min_iterations`.
+    fn orbit(&self, c: Complex<f64>, hits: &mut Vec<usize>) {
+        hits.clear();
+        let mut z = Complex::new(0.0, 0.0);
+        for n in 0..MAX_ITER {
+            z = z * z + c;
+            if z.norm_sqr() > 4.0 {
+                if n < self.min_iterations {
+                    hits.clear();
+                }
+                return;
+            }
+            hits.extend(self.pixel(z));
+        }
+        hits.clear();
+    }
+}
+
+/// A Metropolis–Hastings chain over parameters, at one whose orbit hits the view
+struct Chain {
+    rng: StdRng,
+    c: Complex<f64>,
+    hits: Vec<usize>,
+    /// Scratch space for the orbit of a proposed parameter
+    proposed: Vec<usize>,
+}
+
+impl Chain {
+    /// Start a chain at a random parameter whose orbit hits the view, trying
+    /// parameters shown in the view first as those at least hit it
+    /// themselves.
+    fn new(canvas: &Canvas, mut rng: StdRng) -> Option<Self> {
+        let mut hits = Vec::new();
+        for attempt in 0..SEED_TRIES {
+            let c = if attempt < SEED_TRIES / 2 {
+                let (x, y) = (rng.gen_range(0.0..WIDTH as f64), rng.gen_range(0.0..HEIGHT as f64));
+                canvas.mandelbrot.pixel_to_complex(x, y)
+            } else {
+                random_parameter(&mut rng)
+            };
+            canvas.orbit(c, &mut hits);
+            if !hits.is_empty() {
+                return Some(Self { rng, c, hits, proposed: Vec::new() });
+            }
+        }
+        None
+    }
+
+    /// Take a step, accepting the proposal with the ratio of the orbits'
+    /// contributions to the view. The mutations are symmetric and the jumps
+    /// independent of the current parameter, so that ratio balances the chain.
+    fn step(&mut self, canvas: &Canvas) {
+        let proposal = if self.rng.gen_bool(JUMP_CHANCE) {
+            random_parameter(&mut self.rng)
+        } else {
+            let size = MUTATION_SIZE * 2.5 / canvas.mandelbrot.zoom;
+            let distance = size * MUTATION_RANGE.powf(self.rng.gen::<f64>());
+            self.c + Complex::from_polar(distance, self.rng.gen_range(0.0..TAU))
+        };
+        canvas.orbit(proposal, &mut self.proposed);
+        if self.rng.gen::<f64>() * (self.hits.len() as f64) < self.proposed.len() as f64 {
+            self.c = proposal;
+            std::mem::swap(&mut self.hits, &mut self.proposed);
+        }
+    }
+
+    /// Plot the current orbit, weighted by the inverse of how likely the
+    /// chain is to be at it.
+    fn plot(&self, density: &mut [u64]) {
+        let weight = (WEIGHT_UNIT / self.hits.len() as f64) as u64;
+        for &i in &self.hits {
+            density[i] += weight;
+        }
+    }
+}
+
+fn random_parameter(rng: &mut StdRng) -> Complex<f64> {
+    Complex::new(rng.gen_range(-JUMP_EXTENT..JUMP_EXTENT), rng.gen_range(-JUMP_EXTENT..JUMP_EXTENT))
+}
+
+/// Run the `buddhabrot` command.
+pub fn buddhabrot_from_args(mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    if mandelbrot.julia.is_some() {
+        return Err(Error::UserDefined("the Buddhabrot is plotted for the Mandelbrot set, not a Julia set".into()));
+    }
+    let out = PathBuf::from(arg_value(args, "out").ok_or_else(|| invalid_arg("out", ""))?);
+    let (width, height) = match arg_value(args, "size") {
+        Some(value) => export::parse_size(&value).ok_or_else(|| invalid_arg("size", &value))?,
+        None => (WIDTH, HEIGHT),
+    };
+    let samples = match arg_value(args, "samples") {
+        Some(value) => value.parse().ok().filter(|&samples| samples > 0).ok_or_else(|| invalid_arg("samples", &value))?,
+        None => DEFAULT_SAMPLES,
+    };
+    let min_iterations = match arg_value(args, "min-iterations") {
+        Some(value) => value.parse().ok().filter(|&n| n < MAX_ITER).ok_or_else(|| invalid_arg("min-iterations", &value))?,
+        None => 0,
+    };
+    let seed = match arg_value(args, "seed") {
+        Some(value) => value.parse().map_err(|_| invalid_arg("seed", &value))?,
+        None => 0,
+    };
+    let mode = progress::Mode::from_args(args);
+
+    let canvas = Canvas { mandelbrot, width, height, min_iterations };
+    // Spread the chain indices over the seeds, so neighbouring seeds don't
+    // share chains
+    let mut chains: Vec<Chain> = (0..CHAINS)
+        .into_par_iter()
+        .filter_map(|index| Chain::new(&canvas, StdRng::seed_from_u64(seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15))))
+        .collect();
+    if chains.is_empty() {
+        return Err(Error::UserDefined("no escaping orbits pass through the view".into()));
+    }
+    chains.par_iter_mut().for_each(|chain| (0..BURN_IN).for_each(|_| chain.step(&canvas)));
+
+    let pixels = width as usize * height as usize;
+    let mut density = vec![0u64; pixels];
+    let per_round = samples.div_ceil(ROUNDS * chains.len() as u64);
+    let progress = Progress::new(mode, "buddhabrot", "samples", ROUNDS * per_round * chains.len() as u64, None);
+    for round in 1..=ROUNDS {
+        let plotted = chains
+            .par_iter_mut()
+            .fold(
+                || vec![0; pixels],
+                |mut density, chain| {
+                    for _ in 0..per_round {
+                        chain.step(&canvas);
+                        chain.plot(&mut density);
+                    }
+                    density
+                },
+            )
+            .reduce_with(|mut a, b| {
+                a.iter_mut().zip(&b).for_each(|(a, b)| *a += b);
+                a
+            });
+        if let Some(plotted) = plotted {
+            density.iter_mut().zip(&plotted).for_each(|(a, b)| *a += b);
+        }
+        progress.set(round * per_round * chains.len() as u64);
+    }
+    progress.finish();
+
+    // Colors by density, saturating at the brightest pixels
+    let density: Vec<f64> = density.iter().map(|&d| d as f64 / WEIGHT_UNIT).collect();
+    let mut sorted: Vec<f64> = density.iter().copied().filter(|&d| d > 0.0).collect();
+    sorted.sort_by(f64::total_cmp);
+    let bright = sorted.get(sorted.len().saturating_sub(1) * 999 / 1000).copied().unwrap_or(1.0);
+    let image: Vec<u8> =
+        density.iter().flat_map(|&d| mandelbrot.smooth_color((d / bright).min(1.0) * 100.0)).collect();
+
+    let file = BufWriter::new(File::create(&out).map_err(|e| Error::UserDefined(Box::new(e)))?);
+    let encoding = Encoding { format: Format::Png, bit_depth: 8, quality: 0, dpi: None };
+    export::write_png(file, &mut image.as_slice(), width, height, encoding)
+}
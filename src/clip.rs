@@ -0,0 +1,332 @@
+//! Short animated clips for sharing.
+//!
+//! `mandelbrot clip --out=<file.gif|file.webp>` writes a looping animation of
+//! the initial view as an animated GIF or WebP, which plays wherever images
+//! do, without a video toolchain. `--kind` picks the animation:
+//!
+//! - `palette-cycle` (the default) runs the colors once through the
+//!   colormap, recoloring counts rendered once where the coloring allows
+//! - `zoom-loop` zooms in by `--zoom-factor` (default 4) and back out
+//! - `julia-morph` moves the Julia parameter once around the main cardioid
+//!
+//! `--animation=<file.toml>` plays an animation script instead, see
+//! `animation.rs`. `--frames=<n>` (default 60) sets the frames of the loop,
+//! `--fps=<n>` (default 25) the frame rate, `--size=<width>x<height>`
+//! (default 400x300) the size, and `--loops=<n>` how often the clip plays,
+//! forever with 0 (the default).
+//!
+//! GIF frames share a palette of 256 colors learned from all of them, so
+//! colors don't flicker from frame to frame, and `--dither=<none|ordered|
+//! diffusion>` picks how colors between those of the palette are shown:
+//! rounded to the nearest, with a Bayer pattern, which compresses and loops
+//! better, or with Floyd-Steinberg error diffusion (the default), which
+//! shows smoother gradients. WebP frames are lossless.
+
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use color_quant::NeuQuant;
+use pixels::Error;
+use rayon::prelude::*;
+
+use crate::animation::Animation;
+use crate::deep::Reference;
+use crate::julia::{JuliaMorph, JuliaPath};
+use crate::progress::{self, Progress};
+use crate::{arg_value, coloring, export, invalid_arg, palette, parse_positive, Mandelbrot};
+
+const DEFAULT_SIZE: (u32, u32) = (400, 300);
+const DEFAULT_FRAMES: usize = 60;
+const DEFAULT_FPS: f64 = 25.0;
+const DEFAULT_ZOOM_FACTOR: f64 = 4.0;
+/// Largest width and height of each format
+const GIF_MAX_SIZE: u32 = u16::MAX as u32;
+const WEBP_MAX_SIZE: u32 = 1 << 14;
+/// Pixels the GIF palette is learned from, spread over all frames, and the
+/// sampling of the quantizer, faster and coarser when larger
+const PALETTE_SAMPLES: usize = 1 << 20;
+const QUANTIZER_SAMPLING: i32 = 10;
+/// Amplitude of the ordered dither, about the step between palette colors
+const ORDERED_SPREAD: f64 = 32.0;
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    PaletteCycle,
+    ZoomLoop,
+    JuliaMorph,
+}
+
+#[derive(Clone, Copy)]
+enum Dither {
+    None,
+    Ordered,
+    Diffusion,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Gif,
+    WebP,
+}
+
+/// Run the `clip` command.
+pub fn clip_from_args(mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    let out = PathBuf::from(arg_value(args, "out").ok_or_else(|| invalid_arg("out", ""))?);
+    let format = match out.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("gif") => Format::Gif,
+        Some(extension) if extension.eq_ignore_ascii_case("webp") => Format::WebP,
+        _ => return Err(invalid_arg("out", &out.display().to_string())),
+    };
+    let (width, height) = match arg_value(args, "size") {
+        Some(value) => export::parse_size(&value).ok_or_else(|| invalid_arg("size", &value))?,
+        None => DEFAULT_SIZE,
+    };
+    let max_size = if format == Format::Gif { GIF_MAX_SIZE } else { WEBP_MAX_SIZE };
+    if width > max_size || height > max_size {
+        return Err(Error::UserDefined(format!("clips are at most {max_size} pixels wide and high in this format").into()));
+    }
+    let fps = match arg_value(args, "fps") {
+        Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("fps", &value))?,
+        None => DEFAULT_FPS,
+    };
+    let loops = match arg_value(args, "loops") {
+        Some(value) => value.parse().map_err(|_| invalid_arg("loops", &value))?,
+        None => 0,
+    };
+    let dither = match arg_value(args, "dither").as_deref() {
+        None | Some("diffusion") => Dither::Diffusion,
+        Some("ordered") => Dither::Ordered,
+        Some("none") => Dither::None,
+        Some(value) => return Err(invalid_arg("dither", value)),
+    };
+
+    let (views, kind) = match arg_value(args, "animation") {
+        Some(path) => {
+            let animation = Animation::load(Path::new(&path), mandelbrot)?;
+            let frames = (animation.duration() * fps).ceil() as usize + 1;
+            ((0..frames).map(|index| animation.view_at(index as f64 / fps)).collect(), None)
+        }
+        None => {
+            let kind = match arg_value(args, "kind").as_deref() {
+                None | Some("palette-cycle") => Kind::PaletteCycle,
+                Some("zoom-loop") => Kind::ZoomLoop,
+                Some("julia-morph") => Kind::JuliaMorph,
+                Some(value) => return Err(invalid_arg("kind", value)),
+            };
+            let frames = match arg_value(args, "frames") {
+                Some(value) => value.parse().ok().filter(|&n: &usize| n >= 2).ok_or_else(|| invalid_arg("frames", &value))?,
+                None => DEFAULT_FRAMES,
+            };
+            let zoom_factor = match arg_value(args, "zoom-factor") {
+                Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("zoom-factor", &value))?,
+                None => DEFAULT_ZOOM_FACTOR,
+            };
+            (loop_views(mandelbrot, kind, frames, zoom_factor), Some(kind))
+        }
+    };
+
+    let mode = progress::Mode::from_args(args);
+    let frames = render_frames(&views, kind == Some(Kind::PaletteCycle), width, height, mode);
+    match format {
+        Format::Gif => write_gif(&out, width, height, &frames, fps, loops, dither),
+        Format::WebP => write_webp(&out, width, height, &frames, fps, loops),
+    }
+}
+
+/// The views of a loop of `frames` frames of `kind` from `mandelbrot`, the
+/// one after the last being the first again.
+fn loop_views(mandelbrot: &Mandelbrot, kind: Kind, frames: usize, zoom_factor: f64) -> Vec<Mandelbrot> {
+    let mut start = mandelbrot.clone();
+    let mut morph = JuliaMorph::new(JuliaPath::Cardioid);
+    if kind == Kind::JuliaMorph && start.julia.is_none() {
+        start.toggle_julia(morph.position());
+    }
+    (0..frames)
+        .map(|index| {
+            let t = index as f64 / frames as f64;
+            let mut view = start.clone();
+            match kind {
+                Kind::PaletteCycle => view.palette_offset += t * palette::CYCLE,
+                Kind::ZoomLoop => view.zoom *= zoom_factor.powf((1.0 - (TAU * t).cos()) / 2.0),
+                Kind::JuliaMorph => {
+                    morph.seek(t);
+                    view.julia = Some(morph.position());
+                }
+            }
+            view
+        })
+        .collect()
+}
+
+/// Render `views` as RGBA images, recoloring the counts of the first for
+/// the others if only the colors change and the coloring allows.
+fn render_frames(views: &[Mandelbrot], recolor: bool, width: u32, height: u32, mode: progress::Mode) -> Vec<Vec<u8>> {
+    let render = |view: &Mandelbrot| {
+        let reference = Reference::for_view(view);
+        let rows: Vec<_> = (0..height).into_par_iter().map(|y| export::render_row(view, reference.as_ref(), width, height, y)).collect();
+        let colors: Vec<u8> = rows.iter().flat_map(|(colors, _)| colors.iter().copied()).collect();
+        let counts: Vec<u32> = rows.into_iter().flat_map(|(_, counts)| counts).collect();
+        (colors, counts)
+    };
+    let counts = match views.first() {
+        Some(first) if recolor && first.coloring.counts_only() => Some(render(first).1),
+        _ => None,
+    };
+
+    let progress = Progress::new(mode, "clip", "frames", views.len() as u64, Some(width as u64 * height as u64));
+    let mut frames = Vec::with_capacity(views.len());
+    // Color ranges smoothed around the loop, or that of the counts shared by
+    // every frame
+    let mut views = views.to_vec();
+    let ranges: Vec<_> = match &counts {
+        Some(counts) => views.iter().map(|view| view.auto_range.and_then(|percent| coloring::count_range(counts, percent))).collect(),
+        None => {
+            let ranges: Vec<_> = views.iter().map(export::sample_color_range).collect();
+            coloring::smooth_ranges(&ranges, views.first().map_or(0, |view| view.range_smoothing), true)
+        }
+    };
+    for (view, range) in views.iter_mut().zip(ranges) {
+        view.color_range = range;
+    }
+    for (index, view) in views.iter().enumerate() {
+        let mut frame = match &counts {
+            Some(counts) => counts.par_iter().flat_map_iter(|&m| view.color(m)).collect(),
+            None => render(view).0,
+        };
+        view.post_process(&mut frame, width, height);
+        frames.push(frame);
+        progress.set(index as u64 + 1);
+    }
+    progress.finish();
+    frames
+}
+
+/// Write RGBA `frames` as an animated GIF playing `loops` times, forever
+/// for 0.
+fn write_gif(path: &Path, width: u32, height: u32, frames: &[Vec<u8>], fps: f64, loops: u16, dither: Dither) -> Result<(), Error> {
+    let wrap = |e: gif::EncodingError| Error::UserDefined(Box::new(e));
+    // One palette for every frame, from pixels spread evenly over all of them
+    let pixels = frames.len() * (width * height) as usize;
+    let step = pixels.div_ceil(PALETTE_SAMPLES).max(1);
+    let samples: Vec<u8> = frames.iter().flat_map(|frame| frame.chunks_exact(4)).step_by(step).flatten().copied().collect();
+    let quantizer = NeuQuant::new(QUANTIZER_SAMPLING, 256, &samples);
+    let palette = quantizer.color_map_rgb();
+
+    let indexed: Vec<Vec<u8>> = frames.par_iter().map(|frame| quantize(frame, width as usize, &quantizer, &palette, dither)).collect();
+
+    let file = File::create(path).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    let mut encoder = gif::Encoder::new(BufWriter::new(file), width as u16, height as u16, &palette).map_err(wrap)?;
+    // Without the repetition extension a GIF plays once
+    match loops {
+        0 => encoder.set_repeat(gif::Repeat::Infinite).map_err(wrap)?,
+        1 => {}
+        n => encoder.set_repeat(gif::Repeat::Finite(n - 1)).map_err(wrap)?,
+    }
+    // Delays are in hundredths of a second, and shorter ones than 2 are
+    // slowed down by most viewers
+    let delay = (100.0 / fps).round().max(2.0) as u16;
+    for indices in indexed {
+        let mut frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, indices, None);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(wrap)?;
+    }
+    Ok(())
+}
+
+/// Palette indices of the RGBA `frame`, `width` pixels wide, dithered.
+fn quantize(frame: &[u8], width: usize, quantizer: &NeuQuant, palette: &[u8], dither: Dither) -> Vec<u8> {
+    let index_of = |color: [f64; 3]| {
+        let [r, g, b] = color.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        quantizer.index_of(&[r, g, b, 255]) as u8
+    };
+    match dither {
+        Dither::None | Dither::Ordered => frame
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(i, pixel)| {
+                let offset = match dither {
+                    Dither::Ordered => (BAYER[i / width % 4][i % width % 4] as f64 / 16.0 - 0.5) * ORDERED_SPREAD,
+                    _ => 0.0,
+                };
+                index_of([0, 1, 2].map(|c| pixel[c] as f64 + offset))
+            })
+            .collect(),
+        Dither::Diffusion => {
+            // Floyd-Steinberg: push the error of each pixel on to the
+            // pixels after it
+            let mut colors: Vec<[f64; 3]> = frame.chunks_exact(4).map(|pixel| [0, 1, 2].map(|c| pixel[c] as f64)).collect();
+            let height = colors.len() / width;
+            let mut indices = vec![0; colors.len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let i = y * width + x;
+                    let index = index_of(colors[i]);
+                    indices[i] = index;
+                    let error: [f64; 3] = std::array::from_fn(|c| colors[i][c] - palette[3 * index as usize + c] as f64);
+                    let mut spread = |x: usize, y: usize, weight: f64| {
+                        if x < width && y < height {
+                            (0..3).for_each(|c| colors[y * width + x][c] += error[c] * weight);
+                        }
+                    };
+                    spread(x + 1, y, 7.0 / 16.0);
+                    if x > 0 {
+                        spread(x - 1, y + 1, 3.0 / 16.0);
+                    }
+                    spread(x, y + 1, 5.0 / 16.0);
+                    spread(x + 1, y + 1, 1.0 / 16.0);
+                }
+            }
+            indices
+        }
+    }
+}
+
+/// Write RGBA `frames` as a lossless animated WebP playing `loops` times,
+/// forever for 0.
+fn write_webp(path: &Path, width: u32, height: u32, frames: &[Vec<u8>], fps: f64, loops: u16) -> Result<(), Error> {
+    let io = |e: std::io::Error| Error::UserDefined(Box::new(e));
+    // Every frame is a still image, of which the animation takes the
+    // bitstream, after the 12 bytes of its RIFF header
+    let images: Vec<Vec<u8>> = frames
+        .par_iter()
+        .map(|frame| {
+            let rgb: Vec<u8> = frame.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+            let mut image = Vec::new();
+            image_webp::WebPEncoder::new(&mut image).encode(&rgb, width, height, image_webp::ColorType::Rgb8).map(|()| image)
+        })
+        .collect::<Result<_, _>>()
+        .map_err(|e| Error::UserDefined(Box::new(e)))?;
+
+    let size = |width: u32, height: u32| [&(width - 1).to_le_bytes()[..3], &(height - 1).to_le_bytes()[..3]].concat();
+    let mut body = Vec::new();
+    // Animated, without alpha or metadata
+    write_chunk(&mut body, b"VP8X", &[&[0b10, 0, 0, 0], &size(width, height)[..]].concat());
+    write_chunk(&mut body, b"ANIM", &[&[0, 0, 0, 0][..], &loops.to_le_bytes()].concat());
+    let duration = ((1000.0 / fps).round() as u32).to_le_bytes();
+    for image in &images {
+        // At the origin, covering the canvas without blending into the frame
+        // before
+        let header = [&[0; 6][..], &size(width, height), &duration[..3], &[0b10]].concat();
+        write_chunk(&mut body, b"ANMF", &[&header[..], &image[12..]].concat());
+    }
+
+    let mut file = BufWriter::new(File::create(path).map_err(io)?);
+    file.write_all(b"RIFF").map_err(io)?;
+    file.write_all(&(body.len() as u32 + 4).to_le_bytes()).map_err(io)?;
+    file.write_all(b"WEBP").map_err(io)?;
+    file.write_all(&body).map_err(io)?;
+    file.flush().map_err(io)
+}
+
+/// Append a RIFF chunk, padded to an even length.
+fn write_chunk(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
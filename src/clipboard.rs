@@ -0,0 +1,40 @@
+//! Copying text to the system clipboard.
+//!
+//! The text goes through the clipboard tool of the system: `pbcopy` on
+//! macOS, `clip` on Windows, and elsewhere `wl-copy` under Wayland, or else
+//! `xclip` or `xsel`, whichever is installed.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Clipboard tools to try in order, with their arguments
+fn tools() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbcopy", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("clip", &[])];
+    }
+    let mut tools: Vec<(&str, &[&str])> = vec![("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])];
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.insert(0, ("wl-copy", &[]));
+    }
+    tools
+}
+
+/// Put `text` on the clipboard, or say why it couldn't be.
+pub fn copy(text: &str) -> Result<(), String> {
+    let tools = tools();
+    for &(tool, args) in &tools {
+        let Ok(mut child) = Command::new(tool).args(args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() else {
+            continue;
+        };
+        let written = child.stdin.take().map(|mut stdin| stdin.write_all(text.as_bytes()));
+        return match (written, child.wait()) {
+            (Some(Ok(())), Ok(status)) if status.success() => Ok(()),
+            _ => Err(format!("{tool} failed to copy to the clipboard")),
+        };
+    }
+    let names: Vec<_> = tools.iter().map(|(tool, _)| *tool).collect();
+    Err(format!("no clipboard tool found, install one of {}", names.join(", ")))
+}
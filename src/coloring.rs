@@ -0,0 +1,440 @@
+//! Coloring algorithms.
+//!
+//! The default coloring only depends on the escape count of a pixel, so
+//! counts rendered elsewhere, like on the GPU, can be colored afterwards. The
+//! other algorithms also look at the orbit itself and are rendered on the CPU,
+//! with the SIMD kernel while they only need where the orbit ends up and one
+//! point at a time when they need statistics over the whole orbit.
+
+use num::Complex;
+
+use crate::{multibrot, palette, Mandelbrot, HEIGHT, MAX_ITER};
+
+/// Change of the color range, in escape counts, below which it stays put
+const RANGE_TOLERANCE: f64 = 0.5;
+/// Color the stalks fade to
+const STALK_COLOR: [u8; 4] = [255, 236, 160, 255];
+/// Escape counts of the palette spanned by the curvature average from 0 to π
+const CURVATURE_SPAN: f64 = 50.0;
+/// Color of the boundary line of the distance estimation
+const BOUNDARY_COLOR: [u8; 4] = [0, 0, 0, 255];
+/// Color around the boundary line of the boundary-only coloring
+const BACKGROUND_COLOR: [u8; 4] = [255, 255, 255, 255];
+/// Escape counts of the palette spanned by each doubling of the distance to
+/// the boundary inside the set
+const INTERIOR_SPAN: f64 = 8.0;
+/// Longest cycle looked for inside the set
+const MAX_PERIOD: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coloring {
+    /// By escape count, see `Mandelbrot::color`
+    EscapeTime,
+    /// Binary decomposition, by the half plane the orbit escapes into: black
+    /// and white, or the escape time colors darkened below the real axis
+    /// with `level_sets`
+    Binary { level_sets: bool },
+    /// Pickover stalks, the escape time colors overlaid with the orbit's
+    /// closest approach to the coordinate axes, see `Mandelbrot::stalk_width`
+    Stalks,
+    /// Curvature estimation, the average turning angle of the orbit,
+    /// interpolated between escape counts
+    Curvature,
+    /// By the argument of the orbit at escape, or with `average` its average
+    /// argument interpolated between escape counts like the curvature, over
+    /// the escape time colors by `Mandelbrot::angle_blend`
+    Angle { average: bool },
+    /// The color of the first point of `Mandelbrot::trap_image` the orbit
+    /// lands on, over the escape time colors
+    ImageTrap,
+    /// Distance estimation from both sides of the boundary: the inside of the
+    /// Mandelbrot set shaded by the distance to the boundary, and the
+    /// boundary drawn as a line a pixel wide over the escape time colors
+    DistanceEstimation,
+    /// Only the boundary, the distance estimation drawn as a thin
+    /// anti-aliased line on a plain background
+    Boundary,
+}
+
+impl Coloring {
+    /// Parse the value of the `--coloring` argument.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "escape-time" => Some(Coloring::EscapeTime),
+            "binary" => Some(Coloring::Binary { level_sets: false }),
+            "binary-levels" => Some(Coloring::Binary { level_sets: true }),
+            "stalks" => Some(Coloring::Stalks),
+            "curvature" => Some(Coloring::Curvature),
+            "angle" => Some(Coloring::Angle { average: false }),
+            "angle-average" => Some(Coloring::Angle { average: true }),
+            "image-trap" => Some(Coloring::ImageTrap),
+            "distance" => Some(Coloring::DistanceEstimation),
+            "boundary" => Some(Coloring::Boundary),
+            _ => None,
+        }
+    }
+
+    /// Name of the algorithm for the `--coloring` argument.
+    pub fn name(self) -> &'static str {
+        match self {
+            Coloring::EscapeTime => "escape-time",
+            Coloring::Binary { level_sets: false } => "binary",
+            Coloring::Binary { level_sets: true } => "binary-levels",
+            Coloring::Stalks => "stalks",
+            Coloring::Curvature => "curvature",
+            Coloring::Angle { average: false } => "angle",
+            Coloring::Angle { average: true } => "angle-average",
+            Coloring::ImageTrap => "image-trap",
+            Coloring::DistanceEstimation => "distance",
+            Coloring::Boundary => "boundary",
+        }
+    }
+
+    /// The algorithm after this one, for cycling through them in the viewer.
+    pub fn next(self) -> Self {
+        match self {
+            Coloring::EscapeTime => Coloring::Binary { level_sets: false },
+            Coloring::Binary { level_sets: false } => Coloring::Binary { level_sets: true },
+            Coloring::Binary { level_sets: true } => Coloring::Stalks,
+            Coloring::Stalks => Coloring::Curvature,
+            Coloring::Curvature => Coloring::Angle { average: false },
+            Coloring::Angle { average: false } => Coloring::Angle { average: true },
+            Coloring::Angle { average: true } => Coloring::ImageTrap,
+            Coloring::ImageTrap => Coloring::DistanceEstimation,
+            Coloring::DistanceEstimation => Coloring::Boundary,
+            Coloring::Boundary => Coloring::EscapeTime,
+        }
+    }
+
+    /// Whether colors only depend on escape counts.
+    pub fn counts_only(self) -> bool {
+        self == Coloring::EscapeTime
+    }
+
+    /// Whether colors depend on more of the orbit than its final value.
+    pub fn needs_orbit(self) -> bool {
+        matches!(self, Coloring::Stalks | Coloring::Curvature | Coloring::Angle { average: true } | Coloring::ImageTrap | Coloring::DistanceEstimation | Coloring::Boundary)
+    }
+}
+
+impl Mandelbrot {
+    /// Switch to the next coloring, skipping the image trap without an image.
+    pub fn next_coloring(&mut self) {
+        self.coloring = self.coloring.next();
+        if self.coloring == Coloring::ImageTrap && self.trap_image.is_none() {
+            self.coloring = self.coloring.next();
+        }
+    }
+
+    /// Escape counts and colors of the points of the view plane at `points`,
+    /// the colors in the layout of the frame buffer.
+    pub fn shade_batch(&self, points: &[Complex<f64>], counts: &mut [u32], colors: &mut [u8]) {
+        let pixels = counts.iter_mut().zip(colors.chunks_exact_mut(4));
+        if self.coloring.needs_orbit() {
+            for (&point, (count, pixel)) in points.iter().zip(pixels) {
+                let (m, rgba) = self.shade_at(point);
+                *count = m;
+                pixel.copy_from_slice(&rgba);
+            }
+        } else {
+            let mut results = vec![(0, Complex::new(0.0, 0.0)); points.len()];
+            self.iterate_batch(points, &mut results);
+            for (&(m, z), (count, pixel)) in results.iter().zip(pixels) {
+                *count = m;
+                pixel.copy_from_slice(&self.shade(m, z));
+            }
+        }
+    }
+
+    /// Escape count and color of the point of the view plane at `point`.
+    pub fn shade_at(&self, point: Complex<f64>) -> (u32, [u8; 4]) {
+        match self.coloring {
+            Coloring::Stalks => {
+                // Closest approach of the orbit to either axis
+                let mut distance = f64::INFINITY;
+                let (m, _) = self.walk(point, |z| distance = distance.min(z.re.abs()).min(z.im.abs()));
+                let stalk = (1.0 - distance / self.stalk_width).max(0.0) * self.stalk_blend;
+                let rgba = mix(self.color(m), STALK_COLOR, stalk);
+                (m, rgba)
+            }
+            Coloring::Curvature => {
+                // Running sum of the angles between successive steps of the
+                // orbit, and the sum before the last one
+                let (mut sum, mut previous_sum, mut terms) = (0.0, 0.0, 0);
+                let (mut back, mut back2) = (None, None);
+                let (m, z) = self.walk(point, |z| {
+                    if let (Some(a), Some(b)) = (back, back2) {
+                        let turn: Complex<f64> = (z - a) / (a - b);
+                        previous_sum = sum;
+                        sum += if turn.is_finite() { turn.arg().abs() } else { 0.0 };
+                        terms += 1;
+                    }
+                    (back2, back) = (back, Some(z));
+                });
+                if m == MAX_ITER || terms < 2 {
+                    return (m, self.color(m));
+                }
+                // Blend the averages with and without the last step by how far
+                // the orbit got past the escape radius, which makes the
+                // colors continuous across escape counts
+                let (average, previous) = (sum / terms as f64, previous_sum / (terms - 1) as f64);
+                let fraction = (1.0 - (z.norm().ln() / self.bailout_radius.ln()).log2()).clamp(0.0, 1.0);
+                let curvature = fraction * average + (1.0 - fraction) * previous;
+                (m, self.smooth_color(curvature / std::f64::consts::PI * CURVATURE_SPAN))
+            }
+            Coloring::Angle { average: true } => {
+                // Running sum of the arguments along the orbit, and the sum
+                // before the last one
+                let (mut sum, mut previous_sum, mut terms) = (0.0, 0.0, 0);
+                let (m, z) = self.walk(point, |z| {
+                    previous_sum = sum;
+                    sum += turns(z);
+                    terms += 1;
+                });
+                if m == MAX_ITER || terms < 2 {
+                    return (m, self.color(m));
+                }
+                let (average, previous) = (sum / terms as f64, previous_sum / (terms - 1) as f64);
+                let fraction = (1.0 - (z.norm().ln() / self.bailout_radius.ln()).log2()).clamp(0.0, 1.0);
+                let angle = fraction * average + (1.0 - fraction) * previous;
+                (m, mix(self.color(m), self.smooth_color(angle * palette::PERIOD), self.angle_blend))
+            }
+            Coloring::ImageTrap => {
+                let Some(image) = &self.trap_image else {
+                    let (m, _) = self.iterate_at(point);
+                    return (m, self.color(m));
+                };
+                let mut hit = None;
+                let (m, _) = self.walk(point, |z| {
+                    if hit.is_none() {
+                        hit = image.sample(z).filter(|rgba| rgba[3] > 0);
+                    }
+                });
+                let rgba = match hit {
+                    Some([r, g, b, a]) => mix(self.color(m), [r, g, b, 255], a as f64 / 255.0 * self.trap_blend),
+                    None => self.color(m),
+                };
+                (m, rgba)
+            }
+            Coloring::DistanceEstimation => {
+                let (m, distance) = self.boundary_distance(point);
+                let pixel = 2.5 / self.zoom / HEIGHT as f64;
+                let color = match (m, distance) {
+                    (MAX_ITER, Some(distance)) => self.smooth_color(((distance / pixel).log2() * INTERIOR_SPAN).max(0.0)),
+                    _ => self.color(m),
+                };
+                // Brightest a pixel away from the boundary, on either side
+                let rgba = match distance {
+                    Some(distance) => mix(BOUNDARY_COLOR, color, (distance / pixel).min(1.0)),
+                    None => color,
+                };
+                (m, rgba)
+            }
+            Coloring::Boundary => {
+                let (m, distance) = self.boundary_distance(point);
+                let pixel = 2.5 / self.zoom / HEIGHT as f64;
+                let coverage = distance.map_or(0.0, |distance| (1.0 - distance / pixel).max(0.0));
+                (m, mix(BACKGROUND_COLOR, BOUNDARY_COLOR, coverage))
+            }
+            _ => {
+                let (m, z) = self.iterate_at(point);
+                (m, self.shade(m, z))
+            }
+        }
+    }
+
+    /// Escape count of the point of the view plane at `point` and its
+    /// estimated distance to the boundary, from outside or, in the Mandelbrot
+    /// set, from inside. `None` where there is no estimate.
+    fn boundary_distance(&self, point: Complex<f64>) -> (u32, Option<f64>) {
+        let (m, z, derivative) = self.walk_derivative(point);
+        let distance = match (m, self.julia) {
+            (MAX_ITER, None) if !self.multibrot() => interior_distance(self.projection.apply(point), z),
+            (MAX_ITER, _) => None,
+            _ => Some(z.norm() * z.norm().ln() / derivative.norm()),
+        };
+        (m, distance.filter(|distance| distance.is_finite()))
+    }
+
+    /// Escape count of the point of the view plane at `point`, where its
+    /// orbit ends up and the derivative of that by the parameter of the
+    /// Mandelbrot set, or by the starting value of a Julia set.
+    pub fn walk_derivative(&self, point: Complex<f64>) -> (u32, Complex<f64>, Complex<f64>) {
+        let (mut previous, mut derivative) = match self.julia {
+            Some(_) => (self.projection.apply(point), Complex::new(1.0, 0.0)),
+            None => (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0)),
+        };
+        let step = if self.julia.is_some() { 0.0 } else { 1.0 };
+        let (m, z) = self.walk(point, |z| {
+            derivative = self.exponent * multibrot::power(previous, self.exponent - 1.0) * derivative + step;
+            previous = z;
+        });
+        (m, z, derivative)
+    }
+
+    /// Iterate the point of the view plane at `point` like `iterate_at`,
+    /// handing every iterate after the starting value to `visit` until the
+    /// orbit escapes.
+    fn walk(&self, point: Complex<f64>, mut visit: impl FnMut(Complex<f64>)) -> (u32, Complex<f64>) {
+        let point = self.projection.apply(point);
+        let (mut z, c) = match self.julia {
+            Some(c) => (point, c),
+            None => (Complex::new(0.0, 0.0), point),
+        };
+        for n in 0..MAX_ITER {
+            if self.bailout.escaped(z.re, z.im, z.norm_sqr(), self.bailout_radius) {
+                return (n, z);
+            }
+            z = multibrot::power(z, self.exponent) + c;
+            visit(z);
+        }
+        (MAX_ITER, z)
+    }
+
+    /// Color for a point with escape count `m` and final value `z`, for the
+    /// colorings that only need those.
+    pub fn shade(&self, m: u32, z: Complex<f64>) -> [u8; 4] {
+        match self.coloring {
+            Coloring::EscapeTime | Coloring::Stalks | Coloring::Curvature | Coloring::Angle { average: true } | Coloring::ImageTrap | Coloring::DistanceEstimation | Coloring::Boundary => self.color(m),
+            _ if m == MAX_ITER => self.color(m),
+            Coloring::Binary { level_sets: false } if z.im >= 0.0 => [255, 255, 255, 255],
+            Coloring::Binary { level_sets: false } => [0, 0, 0, 255],
+            Coloring::Binary { level_sets: true } => {
+                let [r, g, b, a] = self.color(m);
+                if z.im >= 0.0 { [r, g, b, a] } else { [r / 3, g / 3, b / 3, a] }
+            }
+            // A whole turn spans the palette once
+            Coloring::Angle { average: false } => mix(self.color(m), self.smooth_color(turns(z) * palette::PERIOD), self.angle_blend),
+        }
+    }
+
+    /// Position in the palette of escape count `m`, the count itself or, with
+    /// a `color_range`, where it falls in the range, which spans the colormaps
+    /// from one end to the other.
+    pub fn palette_position(&self, m: u32) -> f64 {
+        match self.color_range {
+            Some((low, high)) => (m as f64 - low).max(0.0) / (high - low).max(1.0) * palette::PERIOD,
+            None => m as f64,
+        }
+    }
+
+    /// Move the `color_range` towards the escape counts of a finished frame
+    /// with `auto_range`, by a share of the way that gets smaller with
+    /// `smoothing` frames, returning whether it changed.
+    pub fn normalize_colors(&mut self, counts: &[u32], smoothing: u32) -> bool {
+        let Some(percent) = self.auto_range else {
+            return false;
+        };
+        let range = match (self.color_range, count_range(counts, percent)) {
+            (Some((low, high)), Some((to_low, to_high))) => {
+                let t = 1.0 / (smoothing as f64 + 1.0);
+                let (next_low, next_high) = (low + (to_low - low) * t, high + (to_high - high) * t);
+                // Settled once the steps are below a count
+                if (next_low - low).abs().max((next_high - high).abs()) < RANGE_TOLERANCE {
+                    return false;
+                }
+                Some((next_low, next_high))
+            }
+            (_, range) => range,
+        };
+        let changed = range != self.color_range;
+        self.color_range = range;
+        changed
+    }
+
+    /// Escape time color for a fractional escape count, interpolated between
+    /// the neighbouring counts.
+    pub fn smooth_color(&self, m: f64) -> [u8; 4] {
+        let below = m.floor().min((MAX_ITER - 1) as f64) as u32;
+        mix(self.color(below), self.color(below + 1), m - below as f64)
+    }
+}
+
+/// The escape counts between `percent` of the escaped pixels of `counts`
+/// from the lowest and from the highest, `None` if none escaped.
+pub fn count_range(counts: &[u32], percent: f64) -> Option<(f64, f64)> {
+    let mut escaped: Vec<u32> = counts.iter().copied().filter(|&m| m < MAX_ITER).collect();
+    let last = escaped.len().checked_sub(1)?;
+    let clip = (last as f64 * percent / 100.0).round() as usize;
+    let low = *escaped.select_nth_unstable(clip).1;
+    let high = *escaped.select_nth_unstable(last - clip).1;
+    Some((low as f64, high as f64))
+}
+
+/// The color ranges of consecutive frames, each averaged with those up to
+/// `radius` frames before and after it, wrapping around the ends for
+/// `looped` frames, so animations don't flicker with the ranges of single
+/// frames.
+pub fn smooth_ranges(ranges: &[Option<(f64, f64)>], radius: u32, looped: bool) -> Vec<Option<(f64, f64)>> {
+    let radius = radius as isize;
+    (0..ranges.len() as isize)
+        .map(|i| {
+            let window: Vec<(f64, f64)> = (i - radius..=i + radius)
+                .filter_map(|j| match looped {
+                    true => ranges[j.rem_euclid(ranges.len() as isize) as usize],
+                    false => usize::try_from(j).ok().and_then(|j| ranges.get(j).copied().flatten()),
+                })
+                .collect();
+            let n = window.len() as f64;
+            (!window.is_empty()).then(|| window.iter().fold((0.0, 0.0), |(low, high), range| (low + range.0 / n, high + range.1 / n)))
+        })
+        .collect()
+}
+
+/// Argument of `z` in turns, from 0 to 1.
+fn turns(z: Complex<f64>) -> f64 {
+    (z.arg() / std::f64::consts::TAU).rem_euclid(1.0)
+}
+
+/// `a` blended towards `b` by `t` between 0 and 1.
+pub fn mix(a: [u8; 4], b: [u8; 4], t: f64) -> [u8; 4] {
+    std::array::from_fn(|i| (a[i] as f64 + (b[i] as f64 - a[i] as f64) * t).round() as u8)
+}
+
+/// Distance from the parameter `c` inside the Mandelbrot set to its boundary,
+/// estimated from the attracting cycle its orbit has settled near by `z`. The
+/// cycle is found by iterating on until the orbit returns to `z` and refined
+/// by Newton's method, then the derivatives along it give the estimate of
+/// Albert Lobo's interior distance formula. `None` if no attracting cycle
+/// turns up.
+pub fn interior_distance(c: Complex<f64>, z: Complex<f64>) -> Option<f64> {
+    let near = |a: Complex<f64>, b: Complex<f64>| (a - b).norm_sqr() < 1e-20 * (1.0 + b.norm_sqr());
+    let mut w = z;
+    let period = (1..=MAX_PERIOD).find(|_| {
+        w = w * w + c;
+        near(w, z)
+    })?;
+
+    // Newton's method on F(z) - z, F the iterate `period` times
+    let mut z0 = z;
+    for _ in 0..16 {
+        let (mut w, mut dw) = (z0, Complex::new(1.0, 0.0));
+        for _ in 0..period {
+            dw = 2.0 * w * dw;
+            w = w * w + c;
+        }
+        let step = (w - z0) / (dw - 1.0);
+        if !step.is_finite() {
+            break;
+        }
+        z0 -= step;
+        if step.norm_sqr() < 1e-30 {
+            break;
+        }
+    }
+
+    // Derivatives of F by z, by z twice, by c, and by c and z
+    let one = Complex::new(1.0, 0.0);
+    let (mut w, mut dz, mut dzdz, mut dc, mut dcdz) = (z0, one, Complex::new(0.0, 0.0), Complex::new(0.0, 0.0), Complex::new(0.0, 0.0));
+    for _ in 0..period {
+        (dcdz, dzdz) = (2.0 * (w * dcdz + dz * dc), 2.0 * (dz * dz + w * dzdz));
+        (dz, dc) = (2.0 * w * dz, 2.0 * w * dc + one);
+        w = w * w + c;
+    }
+    // Only attracting cycles mark the interior
+    if dz.norm_sqr() >= 1.0 {
+        return None;
+    }
+    let distance = (1.0 - dz.norm_sqr()) / (dcdz + dzdz * dc / (one - dz)).norm();
+    distance.is_finite().then_some(distance)
+}
@@ -0,0 +1,156 @@
+//! Remote control of the viewer over a local socket.
+//!
+//! `--control=<path>` listens on a Unix domain socket at `path` for commands
+//! to the first window, so scripts and other programs can drive the viewer,
+//! e.g. with `socat - UNIX-CONNECT:<path>`. Every line sent is a JSON
+//! command, answered by a line of JSON, `{"ok":true}` or
+//! `{"ok":false,"error":"<why>"}`:
+//!
+//! ```text
+//! {"command":"set-center","re":-0.743,"im":0.131}
+//! {"command":"set-zoom","zoom":200}
+//! {"command":"set-rotation","degrees":45}
+//! {"command":"set-palette","palette":"magma"}
+//! {"command":"set-palette-offset","offset":50}
+//! {"command":"set-julia","re":-0.8,"im":0.156}
+//! {"command":"set-mandelbrot"}
+//! {"command":"screenshot","out":"shot.png","size":"1920x1080"}
+//! {"command":"start-animation","path":"tour.toml"}
+//! {"command":"stop-animation"}
+//! {"command":"get-state"}
+//! ```
+//!
+//! Either part of the center or the Julia parameter can be left out to keep
+//! it. Setting the view stops a playing animation. `screenshot` renders the
+//! current view like the `render` command, at the size given or 800x600,
+//! and answers once the image is written. `get-state` answers with the view
+//! as `{"ok":true,"state":{...}}`, its options as the viewer takes them on
+//! the command line among them.
+
+use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc::{self, Sender};
+
+use pixels::Error;
+use serde::Deserialize;
+use winit::event_loop::EventLoopProxy;
+
+use crate::arg_value;
+
+/// A command sent to the viewer
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Command {
+    SetCenter { re: Option<f64>, im: Option<f64> },
+    SetZoom { zoom: f64 },
+    SetRotation { degrees: f64 },
+    SetPalette { palette: String },
+    SetPaletteOffset { offset: f64 },
+    /// Show the Julia set, for the parameter given or as it is
+    SetJulia { re: Option<f64>, im: Option<f64> },
+    SetMandelbrot,
+    Screenshot { out: String, size: Option<String> },
+    StartAnimation { path: String },
+    StopAnimation,
+    GetState,
+}
+
+/// A command and where its answer goes, nowhere for senders that don't
+/// wait for one
+pub struct Request {
+    pub command: Command,
+    pub reply: Sender<serde_json::Value>,
+}
+
+/// The answer to a command that succeeded, with `fields` added.
+pub fn ok(fields: serde_json::Value) -> serde_json::Value {
+    let mut reply = serde_json::json!({ "ok": true });
+    if let (Some(reply), serde_json::Value::Object(fields)) = (reply.as_object_mut(), fields) {
+        reply.extend(fields);
+    }
+    reply
+}
+
+/// The answer to a command that failed.
+pub fn failed(error: impl std::fmt::Display) -> serde_json::Value {
+    serde_json::json!({ "ok": false, "error": error.to_string() })
+}
+
+/// Send `command` to the viewer and wait for its answer, `None` once the
+/// viewer has quit.
+pub fn submit(command: Command, requests: &Sender<Request>, proxy: &EventLoopProxy<()>) -> Option<serde_json::Value> {
+    let (reply, answer) = mpsc::channel();
+    requests.send(Request { command, reply }).ok()?;
+    proxy.send_event(()).ok()?;
+    answer.recv().ok()
+}
+
+/// The socket address of a `<port>` on `host`, or an `<address:port>`.
+#[cfg_attr(not(any(feature = "osc", feature = "remote")), allow(dead_code))]
+pub fn network_address(value: &str, host: &str) -> String {
+    match value.parse::<u16>() {
+        Ok(port) => format!("{host}:{port}"),
+        Err(_) => value.to_owned(),
+    }
+}
+
+/// Listen on the socket `--control` asks for, if any, sending the requests
+/// to `requests` and waking the event loop through `proxy` for each.
+#[cfg(unix)]
+pub fn listen(args: &[String], requests: Sender<Request>, proxy: EventLoopProxy<()>) -> Result<(), Error> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    let Some(path) = arg_value(args, "control") else {
+        return Ok(());
+    };
+    let io = |e: std::io::Error| Error::UserDefined(format!("{path}: {e}").into());
+    // A socket left behind by an earlier viewer would keep the path taken
+    if std::fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(&path).map_err(io)?;
+    }
+    let listener = UnixListener::bind(&path).map_err(io)?;
+
+    std::thread::Builder::new()
+        .name("control".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (requests, proxy) = (requests.clone(), proxy.clone());
+                let reader = match stream.try_clone() {
+                    Ok(reader) => BufReader::new(reader),
+                    Err(_) => continue,
+                };
+                std::thread::spawn(move || serve(reader, stream, requests, proxy));
+            }
+        })
+        .map_err(|e| Error::UserDefined(Box::new(e)))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn listen(args: &[String], _requests: Sender<Request>, _proxy: EventLoopProxy<()>) -> Result<(), Error> {
+    match arg_value(args, "control") {
+        Some(_) => Err(Error::UserDefined("--control needs Unix domain sockets".into())),
+        None => Ok(()),
+    }
+}
+
+/// Answer the commands of a connection, line by line, until it closes or
+/// the viewer quits.
+fn serve(reader: impl BufRead, mut writer: impl Write, requests: Sender<Request>, proxy: EventLoopProxy<()>) {
+    for line in reader.lines() {
+        let Ok(line) = line else { return };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str(&line) {
+            Ok(command) => match submit(command, &requests, &proxy) {
+                Some(reply) => reply,
+                None => return,
+            },
+            Err(e) => failed(e),
+        };
+        if writeln!(writer, "{reply}").and_then(|()| writer.flush()).is_err() {
+            return;
+        }
+    }
+}
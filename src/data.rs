@@ -0,0 +1,125 @@
+//! Export of the escape data of every pixel, for analysis.
+//!
+//! `mandelbrot data --out=<file.csv|file.npy> [--size=<width>x<height>]`
+//! iterates the pixels of the initial view at the given size (800x600 by
+//! default), mapped as `render` maps them, and writes for each the point
+//! iterated, its escape count and its continuous escape count, which is not
+//! a number for the points that didn't escape. The point is the parameter
+//! `c` of the Mandelbrot set or the starting value of a Julia set, after the
+//! projection of the view.
+//!
+//! CSV files have a header line and a line per pixel, row by row:
+//!
+//! ```text
+//! x,y,re,im,iterations,smooth
+//! 0,0,-1.6666666666666665,-0.25,4,5.011465705056346
+//! ```
+//!
+//! with the continuous count left empty inside the set. NumPy files hold an
+//! array of `height` by `width` records with the fields `re`, `im`,
+//! `iterations` and `smooth`, so `numpy.load("view.npy")["smooth"]` is the
+//! image of the continuous counts. The data is iterated in double precision,
+//! so past a zoom of about 1e13 neighbouring pixels run together.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use pixels::Error;
+use rayon::prelude::*;
+
+use crate::progress::{self, Progress};
+use crate::{arg_value, export, invalid_arg, multibrot, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Rows iterated between writes
+const STRIP_ROWS: u32 = 64;
+/// Record type of the NumPy array
+const NPY_DESCR: &str = "[('re', '<f8'), ('im', '<f8'), ('iterations', '<u4'), ('smooth', '<f8')]";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Npy,
+}
+
+/// The escape data of a pixel
+struct Sample {
+    point: num::Complex<f64>,
+    iterations: u32,
+    smooth: f64,
+}
+
+/// Run the `data` command.
+pub fn data_from_args(mandelbrot: &Mandelbrot, args: &[String], mode: progress::Mode) -> Result<(), Error> {
+    let out = PathBuf::from(arg_value(args, "out").ok_or_else(|| invalid_arg("out", ""))?);
+    let format = match out.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Format::Csv,
+        Some("npy") => Format::Npy,
+        _ => return Err(invalid_arg("out", &out.to_string_lossy())),
+    };
+    let (width, height) = match arg_value(args, "size") {
+        Some(value) => export::parse_size(&value).ok_or_else(|| invalid_arg("size", &value))?,
+        None => (WIDTH, HEIGHT),
+    };
+
+    let io = |e: std::io::Error| Error::UserDefined(format!("{}: {e}", out.display()).into());
+    let mut file = BufWriter::new(File::create(&out).map_err(io)?);
+    match format {
+        Format::Csv => writeln!(file, "x,y,re,im,iterations,smooth").map_err(io)?,
+        Format::Npy => file.write_all(&npy_header(width, height)).map_err(io)?,
+    }
+    let progress = Progress::new(mode, "data", "rows", height as u64, Some(width as u64));
+    for strip in (0..height).step_by(STRIP_ROWS as usize) {
+        let rows: Vec<Vec<Sample>> = (strip..(strip + STRIP_ROWS).min(height)).into_par_iter().map(|y| row(mandelbrot, width, height, y)).collect();
+        for (y, samples) in (strip..).zip(rows) {
+            for (x, sample) in samples.iter().enumerate() {
+                match format {
+                    Format::Csv => {
+                        let smooth = if sample.smooth.is_nan() { String::new() } else { sample.smooth.to_string() };
+                        writeln!(file, "{x},{y},{},{},{},{smooth}", sample.point.re, sample.point.im, sample.iterations).map_err(io)?;
+                    }
+                    Format::Npy => {
+                        file.write_all(&sample.point.re.to_le_bytes()).map_err(io)?;
+                        file.write_all(&sample.point.im.to_le_bytes()).map_err(io)?;
+                        file.write_all(&sample.iterations.to_le_bytes()).map_err(io)?;
+                        file.write_all(&sample.smooth.to_le_bytes()).map_err(io)?;
+                    }
+                }
+            }
+            progress.set(y as u64 + 1);
+        }
+    }
+    file.flush().map_err(io)?;
+    progress.finish();
+    Ok(())
+}
+
+/// The escape data of row `y` of the view at `width` by `height` pixels.
+fn row(mandelbrot: &Mandelbrot, width: u32, height: u32, y: u32) -> Vec<Sample> {
+    // Image pixels to the window's pixel coordinates, as `render` maps them
+    let scale = HEIGHT as f64 / height as f64;
+    let window_y = HEIGHT as f64 / 2.0 + (y as f64 - height as f64 / 2.0) * scale;
+    (0..width)
+        .map(|x| {
+            let window_x = WIDTH as f64 / 2.0 + (x as f64 - width as f64 / 2.0) * scale;
+            let point = mandelbrot.pixel_to_complex(window_x, window_y);
+            let (iterations, z) = mandelbrot.iterate_at(point);
+            let smooth = if iterations == MAX_ITER { f64::NAN } else { multibrot::smooth_count(iterations, z, mandelbrot.exponent) };
+            Sample { point: mandelbrot.projection.apply(point), iterations, smooth }
+        })
+        .collect()
+}
+
+/// Header of a version 1.0 `.npy` file of `height` by `width` records,
+/// padded so the data starts at a multiple of 64 bytes.
+pub fn npy_header(width: u32, height: u32) -> Vec<u8> {
+    let mut dict = format!("{{'descr': {NPY_DESCR}, 'fortran_order': False, 'shape': ({height}, {width}), }}");
+    // Magic, version and length take 10 bytes, and the header ends in a newline
+    let padding = (64 - (10 + dict.len() + 1) % 64) % 64;
+    dict.extend(std::iter::repeat_n(' ', padding));
+    dict.push('\n');
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend((dict.len() as u16).to_le_bytes());
+    header.extend(dict.bytes());
+    header
+}
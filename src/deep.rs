@@ -0,0 +1,359 @@
+//! Perturbation rendering of deep zooms.
+//!
+//! Past a zoom of about 10^12, neighbouring pixels map to points that `f64`
+//! coordinates no longer tell apart, and iterating them directly shows blocks
+//! of noise. Deep views are rendered by perturbation instead: the orbit of
+//! the view center, the reference, is iterated once with the fixed-point
+//! numbers of [`crate::fixed`], as precisely as the zoom needs, and every
+//! pixel iterates only its small offset from the reference orbit in `f64`,
+//! which keeps the precision of the offset however deep the view is.
+//!
+//! Where a pixel's orbit passes much closer to zero than the reference's, its
+//! offset loses its precision and whole groups of pixels come out as a flat
+//! blob. Such glitches are detected with Pauldelbrot's criterion and the
+//! pixels iterated again from a secondary reference at one of them, which is
+//! kept for the rest of the group.
+//!
+//! The reference is put where it serves the view best: at the view center,
+//! unless that escapes and a point elsewhere in the view iterates longer,
+//! such as one in or near a minibrot, whose orbit then reaches the end of
+//! more pixels and leaves fewer of them glitching. A reference placed this
+//! way also tends to stay the longest orbit around as the view pans, so the
+//! viewer reuses it further.
+//!
+//! The viewer keeps the references of a pass for the next one, which zooms
+//! on from them as long as they are precise enough and near the view center,
+//! so continuous zooming doesn't iterate the orbits again every frame.
+//!
+//! Past a zoom of about 10^290 the offsets themselves come close to the
+//! smallest `f64`, so they are iterated as the extended-exponent floats of
+//! [`crate::floatexp`] instead, until they have grown back into the range of
+//! `f64`. The zoom is an `f64` too, which caps views at about 10^308.
+//!
+//! This covers Mandelbrot and Julia sets on the plain plane, the projections
+//! and the exponential map are rendered directly. Colorings that follow the
+//! whole orbit show the escape time colors in deep views. The view center is
+//! an `f64` like everywhere, so views zoom into it exactly but panning only
+//! moves by steps `f64` resolves at the center.
+
+use std::sync::{PoisonError, RwLock};
+
+use num::Complex;
+
+use crate::fixed::Fixed;
+use crate::floatexp::FloatExp;
+use crate::projection::Projection;
+use crate::{Bailout, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Pixel size relative to the coordinates below which views are deep
+const DEEP_RESOLUTION: f64 = 1.0 / (1u64 << 40) as f64;
+/// Bits kept below the pixel size in reference orbits
+const GUARD_BITS: f64 = 64.0;
+/// Binary exponent of the deltas below which they are iterated with extended
+/// exponents, well before `f64` would lose their lower bits to underflow
+const EXTENDED_EXPONENT: i64 = -960;
+/// Squared ratio of a point's distance from zero to the reference's below
+/// which the point glitches
+const GLITCH_TOLERANCE: f64 = 1e-6;
+/// Most secondary references made for a view, points still glitching are
+/// iterated directly
+const MAX_SECONDARY: usize = 64;
+/// Farthest a reference of an earlier frame may lie from the view center to
+/// be reused, in the units of `Mandelbrot::view_offset`, which span 2.5 over
+/// the view height
+const REUSE_RANGE: f64 = 4.0;
+/// Probes along each side of the view for the point to place a reference at
+const PROBES: u32 = 16;
+/// Times the reference moves to a probe that iterates longer
+const PLACEMENTS: usize = 4;
+/// Largest bailout radius, 2^31, which keeps the squares and doubled products
+/// of reference orbits that haven't escaped below the 2^64 of the integer limb
+/// of [`Fixed`]
+pub const MAX_BAILOUT: f64 = 2147483648.0;
+
+/// The orbit of a view center, iterated precisely
+pub struct Reference {
+    orbit: Vec<Complex<f64>>,
+    /// Limbs after the point the orbit was iterated with
+    fraction: usize,
+    /// The plane distance of a unit view offset, the inverse of the zoom
+    scale: FloatExp,
+    /// The view the reference is for
+    zoom: f64,
+    view_center: Complex<f64>,
+    julia: Option<Complex<f64>>,
+    bailout: Bailout,
+    bailout_radius: f64,
+    /// The orbit's starting point, the parameter of the Mandelbrot set or the
+    /// starting value of the Julia set
+    center: Complex<f64>,
+    /// Offset of the starting point from the view center, scaled by the zoom
+    /// like `Mandelbrot::view_offset`, zero but for secondary references and
+    /// references reused after the view moved
+    shift: Complex<f64>,
+    /// Secondary references of the pixels this one glitches on, made as they
+    /// turn up
+    secondary: RwLock<Vec<Reference>>,
+}
+
+impl Reference {
+    /// The reference of `view` if it is deep.
+    pub fn for_view(view: &Mandelbrot) -> Option<Self> {
+        view.deep().then(|| Self::chosen(view, precision(view)))
+    }
+
+    /// The reference of `view` if it is deep, reusing `previous`, the
+    /// reference of an earlier frame, while it lies within reach of the view
+    /// center and is precise enough, so zooming on doesn't iterate the orbit
+    /// again every frame.
+    pub fn reuse(view: &Mandelbrot, previous: Option<Self>) -> Option<Self> {
+        if !view.deep() {
+            return None;
+        }
+        if let Some(reference) = previous.and_then(|previous| previous.moved(view)) {
+            return Some(reference);
+        }
+        // With a limb to spare, which serves another 2^64 of zoom
+        Some(Self::chosen(view, precision(view) + 1))
+    }
+
+    /// Iterate the center of `view` until it escapes or reaches the
+    /// iteration limit.
+    pub fn new(view: &Mandelbrot) -> Self {
+        Self::at(view, Complex::new(0.0, 0.0), precision(view))
+    }
+
+    /// The reference of `view` with `fraction` limbs after the point, at the
+    /// view center unless its orbit escapes and one of a grid of probes across
+    /// the view iterates longer, since the longer the orbit the more pixels it
+    /// serves to the end and the fewer glitch. A probe the reference glitches
+    /// on passed closer to zero than it did, into the atom domain of a longer
+    /// orbit, so the reference moves there and probes again.
+    fn chosen(view: &Mandelbrot, fraction: usize) -> Self {
+        let probe = |i: u32, n: u32, size: u32| (i as f64 + 0.5) * size as f64 / n as f64;
+        let probes: Vec<_> = (0..PROBES).flat_map(|i| (0..PROBES).map(move |j| view.view_offset(probe(i, PROBES, WIDTH), probe(j, PROBES, HEIGHT)))).collect();
+        let mut reference = Self::at(view, Complex::new(0.0, 0.0), fraction);
+        for _ in 0..PLACEMENTS {
+            let escaped = reference.orbit.len() as u32 - 1;
+            let counts = probes.iter().map(|&offset| (reference.iterate_deltas(view, offset).map_or(u32::MAX, |(m, _)| m), offset));
+            match counts.max_by_key(|&(m, _)| m) {
+                Some((m, offset)) if m > escaped && escaped < MAX_ITER => {
+                    let moved = Self::at(view, offset, fraction);
+                    if moved.orbit.len() <= reference.orbit.len() {
+                        break;
+                    }
+                    reference = moved;
+                }
+                _ => break,
+            }
+        }
+        reference
+    }
+
+    /// This reference and its secondary references for `view`, if it still
+    /// serves it.
+    fn moved(self, view: &Mandelbrot) -> Option<Self> {
+        let fits = self.julia == view.julia && self.bailout == view.bailout && self.bailout_radius == view.bailout_radius;
+        if !fits || self.fraction < precision(view) {
+            return None;
+        }
+        let view_center = Complex::new(view.center_x, view.center_y);
+        let shift = self.shift * (view.zoom / self.zoom) + (self.view_center - view_center) * view.zoom;
+        if shift.norm() > REUSE_RANGE {
+            return None;
+        }
+        let secondary = self.secondary.into_inner().unwrap_or_else(PoisonError::into_inner);
+        let secondary = secondary.into_iter().filter_map(|reference| reference.moved(view)).collect();
+        Some(Self {
+            scale: FloatExp::from_f64(view.zoom).recip(),
+            zoom: view.zoom,
+            view_center,
+            shift,
+            secondary: RwLock::new(secondary),
+            ..self
+        })
+    }
+
+    /// Iterate the point at `shift` from the view center, scaled like
+    /// `Mandelbrot::view_offset`, with `fraction` limbs after the point.
+    fn at(view: &Mandelbrot, shift: Complex<f64>, fraction: usize) -> Self {
+        let scale = FloatExp::from_f64(view.zoom).recip();
+        let fixed = |value: f64| Fixed::from_f64(value, fraction);
+        // The center plus a shift that may be below the range of `f64`
+        let shifted = |value: f64, shift: f64| {
+            let (mantissa, exponent) = (FloatExp::from_f64(shift) * scale).parts();
+            &fixed(value) + &Fixed::from_parts(mantissa, exponent, fraction)
+        };
+        let (start_x, start_y) = (shifted(view.center_x, shift.re), shifted(view.center_y, shift.im));
+        let center = Complex::new(start_x.to_f64(), start_y.to_f64());
+        let (mut x, mut y, cx, cy) = match view.julia {
+            Some(c) => (start_x, start_y, fixed(c.re), fixed(c.im)),
+            None => (Fixed::zero(fraction), Fixed::zero(fraction), start_x, start_y),
+        };
+
+        let mut orbit = Vec::with_capacity(MAX_ITER as usize + 1);
+        loop {
+            let z = Complex::new(x.to_f64(), y.to_f64());
+            orbit.push(z);
+            if orbit.len() > MAX_ITER as usize || view.bailout.escaped(z.re, z.im, z.norm_sqr(), view.bailout_radius) {
+                break;
+            }
+            let xy = &x * &y;
+            (x, y) = (&(&(&x * &x) - &(&y * &y)) + &cx, &(&xy + &xy) + &cy);
+        }
+        Self {
+            orbit,
+            fraction,
+            scale,
+            zoom: view.zoom,
+            view_center: Complex::new(view.center_x, view.center_y),
+            julia: view.julia,
+            bailout: view.bailout,
+            bailout_radius: view.bailout_radius,
+            center,
+            shift,
+            secondary: RwLock::default(),
+        }
+    }
+    /// Iterate the point at `offset` from the view center, scaled by the
+    /// zoom like `Mandelbrot::view_offset`, returning the escape count and
+    /// the final value of `z` like `Mandelbrot::iterate_at`. Points the
+    /// reference glitches on are iterated from a secondary reference.
+    pub fn iterate(&self, view: &Mandelbrot, offset: Complex<f64>) -> (u32, Complex<f64>) {
+        if let Some(result) = self.iterate_deltas(view, offset) {
+            return result;
+        }
+        let tried = {
+            let secondary = self.secondary.read().unwrap_or_else(PoisonError::into_inner);
+            if let Some(result) = secondary.iter().find_map(|reference| reference.iterate_deltas(view, offset)) {
+                return result;
+            }
+            secondary.len()
+        };
+
+        let mut secondary = self.secondary.write().unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have added one that serves the point meanwhile
+        if let Some(result) = secondary[tried..].iter().find_map(|reference| reference.iterate_deltas(view, offset)) {
+            return result;
+        }
+        if secondary.len() == MAX_SECONDARY {
+            return view.iterate_at(Complex::new(view.center_x, view.center_y) + offset * self.scale.to_f64());
+        }
+        // A reference at the point itself never glitches on it
+        let reference = Self::at(view, offset, self.fraction);
+        let result = reference.iterate_deltas(view, offset).unwrap_or_default();
+        secondary.push(reference);
+        result
+    }
+
+    /// Iterate the point at `offset` like `iterate` from this reference
+    /// alone, `None` where it glitches.
+    pub fn iterate_deltas(&self, view: &Mandelbrot, offset: Complex<f64>) -> Option<(u32, Complex<f64>)> {
+        let offset = offset - self.shift;
+        if self.scale.exponent() < EXTENDED_EXPONENT {
+            return self.iterate_extended(view, offset);
+        }
+        let offset = offset * self.scale.to_f64();
+        let zero = Complex::new(0.0, 0.0);
+        // The offset of the starting value for Julia sets, of the parameter
+        // for the Mandelbrot set
+        let (dz, dc) = match view.julia {
+            Some(_) => (offset, zero),
+            None => (zero, offset),
+        };
+        self.iterate_from(view, 0, dz, dc)
+    }
+
+    /// Iterate the offset from this reference with extended exponents until
+    /// the delta is large enough for `f64`, then carry on like
+    /// `iterate_deltas`.
+    pub fn iterate_extended(&self, view: &Mandelbrot, offset: Complex<f64>) -> Option<(u32, Complex<f64>)> {
+        let offset = (FloatExp::from_f64(offset.re) * self.scale, FloatExp::from_f64(offset.im) * self.scale);
+        let zero = (FloatExp::ZERO, FloatExp::ZERO);
+        let (mut dz, dc) = match view.julia {
+            Some(_) => (offset, zero),
+            None => (zero, offset),
+        };
+        let to_f64 = |(re, im): (FloatExp, FloatExp)| Complex::new(re.to_f64(), im.to_f64());
+
+        for (n, &reference) in self.orbit.iter().enumerate() {
+            // The parameter offset is negligible next to a delta this large
+            if dz.0.exponent().max(dz.1.exponent()) >= EXTENDED_EXPONENT || n as u32 == MAX_ITER {
+                return self.iterate_from(view, n, to_f64(dz), to_f64(dc));
+            }
+            let z = reference + to_f64(dz);
+            if view.bailout.escaped(z.re, z.im, z.norm_sqr(), view.bailout_radius) {
+                return Some((n as u32, z));
+            }
+            // (2 Z + dz) dz + dc
+            let (re, im) = (FloatExp::from_f64(2.0 * reference.re) + dz.0, FloatExp::from_f64(2.0 * reference.im) + dz.1);
+            dz = (dz.0 * re - dz.1 * im + dc.0, dz.0 * im + dz.1 * re + dc.1);
+        }
+        self.iterate_from(view, self.orbit.len(), to_f64(dz), to_f64(dc))
+    }
+
+    /// Iterate the deltas `dz` and `dc` from the reference orbit from
+    /// iteration `start` on, `None` once the point glitches.
+    fn iterate_from(&self, view: &Mandelbrot, start: usize, mut dz: Complex<f64>, dc: Complex<f64>) -> Option<(u32, Complex<f64>)> {
+        for (n, &reference) in self.orbit.iter().enumerate().skip(start) {
+            let z = reference + dz;
+            if n as u32 == MAX_ITER || view.bailout.escaped(z.re, z.im, z.norm_sqr(), view.bailout_radius) {
+                return Some((n as u32, z));
+            }
+            // Pauldelbrot's criterion: the point passed much closer to zero
+            // than the reference, so the delta dwarfs the orbit it perturbs
+            // and has lost its precision
+            if z.norm_sqr() < GLITCH_TOLERANCE * reference.norm_sqr() {
+                return None;
+            }
+            dz = (2.0 * reference + dz) * dz + dc;
+        }
+
+        // The reference escaped before the point did, which is then far
+        // enough out to carry on directly
+        let c = view.julia.unwrap_or(self.center + dc);
+        let last = self.orbit[self.orbit.len() - 1];
+        let mut z = last * last + view.julia.unwrap_or(self.center) + dz;
+        for n in self.orbit.len() as u32..MAX_ITER {
+            if view.bailout.escaped(z.re, z.im, z.norm_sqr(), view.bailout_radius) {
+                return Some((n, z));
+            }
+            z = z * z + c;
+        }
+        Some((MAX_ITER, z))
+    }
+}
+
+/// Limbs after the point reference orbits of `view` take.
+fn precision(view: &Mandelbrot) -> usize {
+    let pixel = 2.5 / view.zoom / HEIGHT as f64;
+    ((GUARD_BITS - pixel.log2()) / 64.0).ceil().max(1.0) as usize
+}
+
+impl Mandelbrot {
+    /// Whether `f64` coordinates no longer resolve the pixels of the view, so
+    /// it is rendered by perturbation.
+    pub fn deep(&self) -> bool {
+        if self.exponential_map || self.projection != Projection::Plane || self.multibrot() {
+            return false;
+        }
+        self.zoom > self.deep_zoom()
+    }
+
+    /// The zoom past which views of the plane around the view center are
+    /// deep.
+    pub fn deep_zoom(&self) -> f64 {
+        let scale = self.center_x.abs().max(self.center_y.abs()).max(self.bailout_radius);
+        2.5 / HEIGHT as f64 / (scale * DEEP_RESOLUTION)
+    }
+
+    /// Escape counts and colors of the points at `offsets` from the view
+    /// center, like `shade_batch`.
+    pub fn shade_offsets(&self, reference: &Reference, offsets: &[Complex<f64>], counts: &mut [u32], colors: &mut [u8]) {
+        for ((&offset, count), pixel) in offsets.iter().zip(counts).zip(colors.chunks_exact_mut(4)) {
+            let (m, z) = reference.iterate(self, offset);
+            *count = m;
+            pixel.copy_from_slice(&self.shade(m, z));
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Zoom depth indicator of the viewer.
+//!
+//! H, or `--depth` from the start, shows how deep the view is in the corner
+//! of the window: the magnification next to the whole set, as `10^12×` past
+//! a thousand, the width of the view on the plane, and a bar of the depth on
+//! a log scale, with a mark where `f64` runs out of precision and the views
+//! are rendered by perturbation. The bar grows by ten decades at a time as
+//! the zoom goes past its end. Over them is the point under the cursor, to
+//! every digit `f64` holds of it, which the middle mouse button or Ctrl+C
+//! copies to the clipboard as `<re>, <im>`, to go back to with G.
+
+use num::Complex;
+
+use crate::overlay;
+use crate::{Mandelbrot, HEIGHT, WIDTH};
+
+/// Pixels per dot of the font
+const SCALE: usize = 2;
+/// Width and height of the font's glyphs, in dots
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+/// Pixels from one glyph to the next
+const ADVANCE: usize = (GLYPH_WIDTH + 1) * SCALE;
+/// Pixels exponents are raised by
+const RAISE: usize = 2 * SCALE;
+/// Distance of the indicator from the corner of the frame, and of its
+/// contents from its edges
+const MARGIN: usize = 8;
+const BAR_WIDTH: usize = 240;
+const BAR_HEIGHT: usize = 6;
+/// Decades the bar spans at least, and grows by
+const DECADES: f64 = 10.0;
+const TEXT_COLOR: [u8; 4] = [255, 255, 255, 255];
+const LIMIT_COLOR: [u8; 4] = [255, 160, 0, 255];
+
+/// The magnification of a view at `zoom`, next to the whole set at zoom 1.
+pub fn magnification(zoom: f64) -> String {
+    match zoom {
+        zoom if zoom < 10.0 => format!("{zoom:.1}×"),
+        zoom if zoom < 1000.0 => format!("{zoom:.0}×"),
+        zoom => format!("10^{}×", zoom.log10().round()),
+    }
+}
+
+/// The real and imaginary parts of `c` to every digit they hold, in
+/// scientific notation where that is shorter.
+pub fn coordinates(c: Complex<f64>) -> (String, String) {
+    let number = |x: f64| match x.abs() {
+        abs if abs != 0.0 && abs < 1e-4 => format!("{x:e}"),
+        _ => format!("{x}"),
+    };
+    (number(c.re), number(c.im))
+}
+
+/// The width of `view` on the plane.
+pub fn width(view: &Mandelbrot) -> String {
+    format!("width = {:.1e}", 2.5 / view.zoom * WIDTH as f64 / HEIGHT as f64)
+}
+
+/// Draw the depth indicator of `view` in the bottom left corner of `frame`,
+/// over a darkened backdrop, with the point under the cursor if there is
+/// one.
+pub fn draw(frame: &mut [u8], view: &Mandelbrot, cursor: Option<Complex<f64>>) {
+    let (magnification, width) = (magnification(view.zoom), width(view));
+    let cursor: Vec<String> = match cursor.map(coordinates) {
+        Some((re, im)) => vec![format!("re = {re}"), format!("im = {im}")],
+        None => Vec::new(),
+    };
+    let depth = view.zoom.log10().max(0.0);
+    let limit = view.deep_zoom().log10();
+    let decades = ((depth.max(limit) + 1.0) / DECADES).ceil().max(2.0) * DECADES;
+
+    let text_width = |text: &str| text.chars().count() * ADVANCE;
+    let texts = cursor.iter().chain([&magnification, &width]);
+    let panel_width = texts.map(|text| text_width(text)).fold(BAR_WIDTH, usize::max) + 2 * MARGIN;
+    let line = GLYPH_HEIGHT * SCALE + 6;
+    let panel_height = (cursor.len() + 3) * line + RAISE + BAR_HEIGHT + 2 * MARGIN;
+    let (left, top) = (MARGIN, HEIGHT as usize - MARGIN - panel_height);
+    for y in top..top + panel_height {
+        for x in left..left + panel_width {
+            let i = (y * WIDTH as usize + x) * 4;
+            frame[i..i + 3].iter_mut().for_each(|channel| *channel /= 2);
+        }
+    }
+
+    let x = left + MARGIN;
+    let mut y = top + MARGIN;
+    for text in &cursor {
+        draw_text(frame, (x, y), text, TEXT_COLOR);
+        y += line;
+    }
+    y += RAISE;
+    draw_text(frame, (x, y), &magnification, TEXT_COLOR);
+    y += line;
+    draw_text(frame, (x, y), &width, TEXT_COLOR);
+    y += line;
+
+    // The bar, filled up to the depth, and the mark where f64 ends
+    let at = |decade: f64| x + ((decade / decades).clamp(0.0, 1.0) * (BAR_WIDTH - 1) as f64) as usize;
+    overlay::draw_rect(frame, (x as f64, y as f64), ((x + BAR_WIDTH - 1) as f64, (y + BAR_HEIGHT - 1) as f64), TEXT_COLOR);
+    for column in x..=at(depth) {
+        for row in y..y + BAR_HEIGHT {
+            let i = (row * WIDTH as usize + column) * 4;
+            frame[i..i + 4].copy_from_slice(&TEXT_COLOR);
+        }
+    }
+    let mark = at(limit) as f64 + 0.5;
+    overlay::draw_polyline(frame, &[(mark, (y - 2) as f64), (mark, (y + BAR_HEIGHT + 2) as f64)], LIMIT_COLOR);
+    let label = (mark as usize).saturating_sub(text_width("f64") / 2).max(x);
+    draw_text(frame, (label, y + BAR_HEIGHT + 4), "f64", LIMIT_COLOR);
+}
+
+/// Draw `text` with its top left corner at `at`, raising the digits after
+/// `^` as an exponent.
+fn draw_text(frame: &mut [u8], at: (usize, usize), text: &str, rgba: [u8; 4]) {
+    let mut raised = false;
+    let mut x = at.0;
+    for c in text.chars() {
+        if c == '^' {
+            raised = true;
+            continue;
+        }
+        raised &= c.is_ascii_digit() || c == '-';
+        let y = at.1 - if raised { RAISE } else { 0 };
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in (0..GLYPH_WIDTH).filter(|column| bits >> (GLYPH_WIDTH - 1 - column) & 1 == 1) {
+                for (dx, dy) in (0..SCALE * SCALE).map(|i| (i % SCALE, i / SCALE)) {
+                    let (px, py) = (x + column * SCALE + dx, y + row * SCALE + dy);
+                    if px < WIDTH as usize && py < HEIGHT as usize {
+                        let i = (py * WIDTH as usize + px) * 4;
+                        frame[i..i + 4].copy_from_slice(&rgba);
+                    }
+                }
+            }
+        }
+        x += ADVANCE;
+    }
+}
+
+/// Rows of the 3×5 glyph of `c`, the leftmost dot the highest bit, blank for
+/// characters the font lacks.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '×' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        'd' => [0b001, 0b001, 0b111, 0b101, 0b111],
+        'e' => [0b000, 0b111, 0b111, 0b100, 0b111],
+        'f' => [0b011, 0b010, 0b111, 0b010, 0b010],
+        'h' => [0b100, 0b100, 0b111, 0b101, 0b101],
+        'i' => [0b010, 0b000, 0b010, 0b010, 0b010],
+        'm' => [0b000, 0b000, 0b111, 0b111, 0b101],
+        'r' => [0b000, 0b000, 0b111, 0b100, 0b100],
+        't' => [0b010, 0b111, 0b010, 0b010, 0b011],
+        'w' => [0b000, 0b101, 0b101, 0b111, 0b101],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
@@ -0,0 +1,189 @@
+//! Eco mode of the viewer, for laptops left zooming on their own.
+//!
+//! `--eco` keeps the viewer easy on the battery and the fans: it draws at
+//! most `--eco-fps=<n>` frames a second (15 by default), renders on half the
+//! CPU threads, and renders views on the move at half the resolution, every
+//! other pixel of every other row filling its 2×2 block, with the full
+//! resolution following once the view rests. `--eco=auto` turns it on only
+//! while the computer runs on battery, or while the CPU as a whole has been
+//! busier than `--eco-load=<percent>` (85 by default) for half a minute, and
+//! off again a minute after neither holds. Each switch is reported on the
+//! standard error.
+//!
+//! The battery and the load are read from `/sys/class/power_supply` and
+//! `/proc/stat`, so on systems without them `--eco=auto` stays off.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use pixels::Error;
+
+use crate::{arg_value, invalid_arg, parse_positive};
+
+/// Frames a second drawn in eco mode by default
+const FPS: f64 = 15.0;
+/// Percent of the CPU busy for long enough that turns eco mode on by default
+const LOAD: f64 = 85.0;
+/// How often the power supply and the load are read
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How long the load has to stay high to turn eco mode on
+const SUSTAINED: Duration = Duration::from_secs(30);
+/// How long eco mode stays on after its reasons are gone, so that it doesn't
+/// switch off as soon as it has brought the load down
+const HOLD: Duration = Duration::from_secs(60);
+
+/// When eco mode is on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Off,
+    On,
+    /// On battery power or under sustained load
+    Auto,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Self::Off),
+            "on" => Some(Self::On),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Time the CPU spent busy and in total, in clock ticks since boot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuTimes {
+    pub busy: u64,
+    pub total: u64,
+}
+
+impl CpuTimes {
+    /// The times of the `cpu` line of `/proc/stat`, in which idle and iowait
+    /// are the fourth and fifth numbers.
+    pub fn parse(stat: &str) -> Option<Self> {
+        let line = stat.lines().find(|line| line.split_whitespace().next() == Some("cpu"))?;
+        let times: Vec<u64> = line.split_whitespace().skip(1).map(str::parse).collect::<Result<_, _>>().ok()?;
+        let idle = times.get(3)? + times.get(4).unwrap_or(&0);
+        let total = times.iter().sum();
+        Some(Self { busy: total - idle, total })
+    }
+
+    /// Percent of the time between `earlier` and these times the CPU was
+    /// busy.
+    pub fn load_since(&self, earlier: &Self) -> Option<f64> {
+        let total = self.total.checked_sub(earlier.total).filter(|&total| total > 0)?;
+        Some(self.busy.saturating_sub(earlier.busy) as f64 / total as f64 * 100.0)
+    }
+}
+
+/// The eco mode asked for and whether it is on
+pub struct Eco {
+    mode: Mode,
+    /// Time between frames while on
+    pub frame_interval: Duration,
+    /// Percent of the CPU busy that turns `Auto` on
+    load: f64,
+    /// Half the threads of the shared pool, to render on while on
+    pub pool: Option<rayon::ThreadPool>,
+    active: bool,
+    /// When the power supply and the load were last read, and the CPU times
+    /// then
+    polled: Option<Instant>,
+    times: Option<CpuTimes>,
+    /// Since when the load has been high
+    loaded_since: Option<Instant>,
+    /// When a reason to be on last held
+    needed: Option<Instant>,
+}
+
+impl Eco {
+    pub fn from_args(args: &[String]) -> Result<Self, Error> {
+        let mode = match arg_value(args, "eco") {
+            Some(value) => Mode::from_name(&value).ok_or_else(|| invalid_arg("eco", &value))?,
+            None if args.iter().any(|arg| arg == "--eco") => Mode::On,
+            None => Mode::Off,
+        };
+        let fps = match arg_value(args, "eco-fps") {
+            Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("eco-fps", &value))?,
+            None => FPS,
+        };
+        let load = match arg_value(args, "eco-load") {
+            Some(value) => value.parse().ok().filter(|load| (0.0..=100.0).contains(load)).ok_or_else(|| invalid_arg("eco-load", &value))?,
+            None => LOAD,
+        };
+        let pool = match mode {
+            Mode::Off => None,
+            _ => {
+                let threads = (rayon::current_num_threads() / 2).max(1);
+                Some(rayon::ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| Error::UserDefined(e.into()))?)
+            }
+        };
+        Ok(Self {
+            mode,
+            frame_interval: Duration::from_secs_f64(1.0 / fps),
+            load,
+            pool,
+            active: mode == Mode::On,
+            polled: None,
+            times: None,
+            loaded_since: None,
+            needed: None,
+        })
+    }
+
+    /// Whether eco mode is on.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Read the power supply and the load when they are due, switching eco
+    /// mode on or off in the `Auto` mode.
+    pub fn update(&mut self) {
+        if self.mode != Mode::Auto || self.polled.is_some_and(|at| at.elapsed() < POLL_INTERVAL) {
+            return;
+        }
+        let now = Instant::now();
+        let times = std::fs::read_to_string("/proc/stat").ok().and_then(|stat| CpuTimes::parse(&stat));
+        let load = times.zip(self.times).and_then(|(times, earlier)| times.load_since(&earlier));
+        (self.polled, self.times) = (Some(now), times);
+        match load {
+            Some(load) if load > self.load => {
+                self.loaded_since.get_or_insert(now);
+            }
+            _ => self.loaded_since = None,
+        }
+
+        let on_battery = on_battery(Path::new("/sys/class/power_supply"));
+        let loaded = self.loaded_since.is_some_and(|since| now - since >= SUSTAINED);
+        if on_battery || loaded {
+            self.needed = Some(now);
+        }
+        let active = self.needed.is_some_and(|needed| now - needed < HOLD);
+        if active != self.active {
+            match (active, on_battery) {
+                (true, true) => eprintln!("eco mode on, running on battery"),
+                (true, false) => eprintln!("eco mode on, the CPU has been over {}% busy for {}s", self.load, SUSTAINED.as_secs()),
+                (false, _) => eprintln!("eco mode off"),
+            }
+            self.active = active;
+        }
+    }
+}
+
+/// Whether the power supplies under `dir` say the computer runs on battery:
+/// a battery discharging, or a mains supply that is offline.
+pub fn on_battery(dir: &Path) -> bool {
+    let Ok(supplies) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    supplies.flatten().any(|supply| {
+        let read = |name: &str| std::fs::read_to_string(supply.path().join(name)).map(|value| value.trim().to_owned()).unwrap_or_default();
+        match read("type").as_str() {
+            "Battery" => read("status") == "Discharging",
+            "Mains" => read("online") == "0",
+            _ => false,
+        }
+    })
+}
@@ -0,0 +1,214 @@
+//! Post-processing effects on finished frames.
+//!
+//! `--effects=<effect>[:<strength>],...` runs a chain of effects over the
+//! colors of every frame the viewer shows, every image `render` writes and
+//! every frame of videos and clips, in the order given, each as strong as its
+//! strength (the default if left out):
+//!
+//! - `bloom`: bright filaments glow into their surroundings (0.6)
+//! - `vignette`: the corners darken (0.5)
+//! - `aberration`: red and blue split towards the edges like through a cheap
+//!   lens, by this many thousandths of the height at the corners (3)
+//! - `grain`: film grain, noise of this fraction of full brightness (0.06)
+//!
+//! Distances are fractions of the frame height, so a large render looks
+//! like the window. The overlays of the viewer are drawn over the effects,
+//! and the grading of [`crate::lut`] follows them.
+
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::Mandelbrot;
+
+/// Brightness above which pixels bloom, from 0 to 1
+const BLOOM_THRESHOLD: f32 = 0.6;
+/// Reach of the bloom as a fraction of the frame height
+const BLOOM_RADIUS: f64 = 0.012;
+/// Box blurs that add up to the roughly Gaussian bloom
+const BLOOM_PASSES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    Bloom,
+    Vignette,
+    Aberration,
+    Grain,
+}
+
+impl Effect {
+    const ALL: [Effect; 4] = [Effect::Bloom, Effect::Vignette, Effect::Aberration, Effect::Grain];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Effect::Bloom => "bloom",
+            Effect::Vignette => "vignette",
+            Effect::Aberration => "aberration",
+            Effect::Grain => "grain",
+        }
+    }
+
+    fn default_strength(self) -> f64 {
+        match self {
+            Effect::Bloom => 0.6,
+            Effect::Vignette => 0.5,
+            Effect::Aberration => 3.0,
+            Effect::Grain => 0.06,
+        }
+    }
+}
+
+/// An effect of the chain and how strong it is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stage {
+    pub effect: Effect,
+    pub strength: f64,
+}
+
+/// Parse the value of the `--effects` argument.
+pub fn from_arg(value: &str) -> Option<Vec<Stage>> {
+    value
+        .split(',')
+        .map(|stage| {
+            let (name, strength) = match stage.trim().split_once(':') {
+                Some((name, strength)) => (name, Some(strength)),
+                None => (stage.trim(), None),
+            };
+            let effect = Effect::ALL.into_iter().find(|effect| effect.name() == name)?;
+            let strength = match strength {
+                Some(strength) => strength.parse().ok().filter(|s: &f64| *s >= 0.0 && s.is_finite())?,
+                None => effect.default_strength(),
+            };
+            Some(Stage { effect, strength })
+        })
+        .collect()
+}
+
+/// The `--effects` argument for `stages`.
+pub fn to_arg(stages: &[Stage]) -> String {
+    let stages: Vec<_> = stages.iter().map(|stage| format!("{}:{}", stage.effect.name(), stage.strength)).collect();
+    format!("--effects={}", stages.join(","))
+}
+
+/// Run `stages` over an RGBA `frame` of `width` by `height` pixels.
+pub fn apply(stages: &[Stage], frame: &mut [u8], width: u32, height: u32) {
+    let (width, height) = (width as usize, height as usize);
+    for stage in stages {
+        match stage.effect {
+            Effect::Bloom => bloom(frame, width, height, stage.strength as f32),
+            Effect::Vignette => vignette(frame, width, height, stage.strength),
+            Effect::Aberration => aberration(frame, width, height, stage.strength / 1000.0 * height as f64),
+            Effect::Grain => grain(frame, width, stage.strength as f32),
+        }
+    }
+}
+
+impl Mandelbrot {
+    /// Run the effects and then the color grading of the view over an RGBA
+    /// `frame` of `width` by `height` pixels.
+    pub fn post_process(&self, frame: &mut [u8], width: u32, height: u32) {
+        apply(&self.effects, frame, width, height);
+        if let Some(lut) = &self.lut {
+            lut.apply(frame);
+        }
+    }
+}
+
+/// Add the light above `BLOOM_THRESHOLD`, blurred, back to the frame.
+fn bloom(frame: &mut [u8], width: usize, height: usize, strength: f32) {
+    let mut glow: Vec<[f32; 3]> = frame
+        .par_chunks_exact(4)
+        .map(|pixel| {
+            let rgb = [pixel[0], pixel[1], pixel[2]].map(|channel| channel as f32 / 255.0);
+            let luma = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            let excess = ((luma - BLOOM_THRESHOLD) / (1.0 - BLOOM_THRESHOLD)).max(0.0);
+            rgb.map(|channel| channel * excess)
+        })
+        .collect();
+    let radius = ((height as f64 * BLOOM_RADIUS).round() as usize).max(1);
+    for _ in 0..BLOOM_PASSES {
+        blur_rows(&mut glow, width, radius);
+    }
+    let mut glow = transpose(&glow, width, height);
+    for _ in 0..BLOOM_PASSES {
+        blur_rows(&mut glow, height, radius);
+    }
+    let glow = transpose(&glow, height, width);
+
+    frame.par_chunks_exact_mut(4).zip(glow).for_each(|(pixel, glow)| {
+        for (channel, glow) in pixel.iter_mut().zip(glow) {
+            *channel = (*channel as f32 + glow * strength * 255.0).round().min(255.0) as u8;
+        }
+    });
+}
+
+/// Box blur rows of `width` values over `radius` to either side, the edge
+/// values carried on past the edges.
+fn blur_rows(values: &mut [[f32; 3]], width: usize, radius: usize) {
+    let n = (2 * radius + 1) as f32;
+    values.par_chunks_mut(width).for_each(|row| {
+        let source = row.to_vec();
+        let at = |i: isize| source[i.clamp(0, width as isize - 1) as usize];
+        let radius = radius as isize;
+        let mut sum = [0.0; 3];
+        for i in -radius..=radius {
+            sum = std::array::from_fn(|c| sum[c] + at(i)[c]);
+        }
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = sum.map(|s| s / n);
+            let (entering, leaving) = (at(x as isize + radius + 1), at(x as isize - radius));
+            sum = std::array::from_fn(|c| sum[c] + entering[c] - leaving[c]);
+        }
+    });
+}
+
+/// `values` of `width` by `height` as `height` by `width`.
+fn transpose(values: &[[f32; 3]], width: usize, height: usize) -> Vec<[f32; 3]> {
+    (0..width * height).into_par_iter().map(|i| values[(i % height) * width + i / height]).collect()
+}
+
+/// Darken towards the corners, by `strength` at the corners themselves.
+fn vignette(frame: &mut [u8], width: usize, height: usize, strength: f64) {
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let corner = cx.hypot(cy);
+    frame.par_chunks_exact_mut(width * 4).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let r = (x as f64 + 0.5 - cx).hypot(y as f64 + 0.5 - cy) / corner;
+            let factor = (1.0 - strength * r * r).max(0.0);
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as f64 * factor).round() as u8;
+            }
+        }
+    });
+}
+
+/// Take red from farther out and blue from farther in, by `shift` pixels at
+/// the corners.
+fn aberration(frame: &mut [u8], width: usize, height: usize, shift: f64) {
+    let source = frame.to_vec();
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let scale = shift / cx.hypot(cy);
+    let at = |x: f64, y: f64, channel: usize| {
+        let (x, y) = ((x.floor() as isize).clamp(0, width as isize - 1) as usize, (y.floor() as isize).clamp(0, height as isize - 1) as usize);
+        source[(y * width + x) * 4 + channel]
+    };
+    frame.par_chunks_exact_mut(width * 4).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let (dx, dy) = (x as f64 + 0.5 - cx, y as f64 + 0.5 - cy);
+            pixel[0] = at(cx + dx * (1.0 + scale), cy + dy * (1.0 + scale), 0);
+            pixel[2] = at(cx + dx * (1.0 - scale), cy + dy * (1.0 - scale), 2);
+        }
+    });
+}
+
+/// Brighten or darken every pixel at random by up to `strength`.
+fn grain(frame: &mut [u8], width: usize, strength: f32) {
+    frame.par_chunks_exact_mut(width * 4).for_each(|row| {
+        let mut rng = rand::thread_rng();
+        for pixel in row.chunks_exact_mut(4) {
+            let noise = (rng.gen::<f32>() * 2.0 - 1.0) * strength * 255.0;
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as f32 + noise).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    });
+}
@@ -0,0 +1,624 @@
+//! Export of still images at any size.
+//!
+//! `mandelbrot render --out=<file.png> [--size=<width>x<height>]` renders the
+//! initial view at the given size, keeping its vertical extent, in strips of
+//! rows, each split into tiles across the threads. The image is rendered into
+//! a checkpoint file next to the output, `<file.png>.partial`, mapped into
+//! memory, so images far larger than the memory can be rendered, and a render
+//! that gets interrupted resumes after the last finished strip when run again
+//! for the same view and size. PNG and TIFF images are compressed as the
+//! strips complete, on a thread of their own, and JPEG images from the
+//! checkpoint once all rows are done, which keeps memory use independent of
+//! the image size. The output file is complete once the render finishes. The
+//! checkpoint takes 4 bytes per pixel on disk, twice that with contour lines,
+//! which need the escape counts too.
+//!
+//! For prints, `--print-size=<width>x<height><cm|mm|in>` gives the size in
+//! physical units instead, e.g. `60x90cm`, and `--dpi=<dpi>` the resolution
+//! (300 by default). The resolution is stored in the image for print shops.
+//!
+//! The output format follows the file extension, `.png`, `.tif`/`.tiff` or
+//! `.jpg`/`.jpeg`, unless `--format=<png|tiff|jpeg>` names it. PNG and TIFF
+//! can be written with 16 bits per channel with `--bit-depth=16`, which widens
+//! the 8-bit colors for editing without banding on the way to print, and
+//! `--quality=<1-100>` sets the JPEG quality (90 by default).
+//!
+//! `--estimate` renders nothing but short spans of pixels spread over the
+//! image, and prints how long the whole render would take on all threads at
+//! that pace, and how much disk and memory it needs, to weigh up a render of
+//! hours before starting it. The time covers rendering the pixels, encoding
+//! the image and the effects come on top.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use indicatif::{HumanBytes, HumanDuration};
+use memmap2::MmapMut;
+use pixels::Error;
+use rayon::prelude::*;
+
+use crate::deep::Reference;
+use crate::progress::{self, Progress};
+use crate::{arg_value, coloring, invalid_arg, overlay, parse_positive, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Rows rendered between checkpoints
+const STRIP_ROWS: u32 = 64;
+/// Columns of the tiles strips are rendered in
+const TILE_COLUMNS: u32 = 512;
+/// Strips rendered ahead of the encoder at most
+const ENCODE_QUEUE: usize = 2;
+/// Digits of the count of finished rows in the checkpoint
+const DONE_DIGITS: usize = 10;
+/// Pixels across the render `--auto-range` takes the color range from
+const RANGE_SAMPLE_WIDTH: u32 = 160;
+/// Print resolution used with `--print-size` unless `--dpi` is given
+const DEFAULT_DPI: f64 = 300.0;
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+/// Largest width and height of a JPEG image
+const JPEG_MAX_SIZE: u32 = u16::MAX as u32;
+/// Rows of the image `--estimate` samples, spans of pixels across each, and
+/// pixels of each span
+const SAMPLE_ROWS: u32 = 32;
+const SAMPLE_SPANS: u32 = 16;
+const SAMPLE_SPAN: u32 = 16;
+
+/// Image file format of the `render` command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Tiff,
+    Jpeg,
+}
+
+impl Format {
+    /// Parse the value of the `--format` argument, or a file extension.
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            _ => None,
+        }
+    }
+}
+
+/// How the rendered image is written
+#[derive(Clone, Copy, Debug)]
+pub struct Encoding {
+    pub format: Format,
+    /// 8 or 16 bits per channel
+    pub bit_depth: u8,
+    /// JPEG quality from 1 to 100
+    pub quality: u8,
+    /// Print resolution to record
+    pub dpi: Option<f64>,
+}
+
+/// Run the `render` command.
+pub fn render_from_args(mandelbrot: &Mandelbrot, args: &[String], mode: progress::Mode) -> Result<(), Error> {
+    let out = PathBuf::from(arg_value(args, "out").ok_or_else(|| invalid_arg("out", ""))?);
+    let dpi = match arg_value(args, "dpi") {
+        Some(value) => Some(parse_positive(&value).ok_or_else(|| invalid_arg("dpi", &value))?),
+        None => None,
+    };
+    let (width, height, dpi) = match (arg_value(args, "size"), arg_value(args, "print-size")) {
+        (Some(_), Some(value)) => {
+            return Err(Error::UserDefined(format!("--print-size={value} conflicts with --size").into()));
+        }
+        (Some(value), None) => {
+            let (width, height) = parse_size(&value).ok_or_else(|| invalid_arg("size", &value))?;
+            (width, height, dpi)
+        }
+        (None, Some(value)) => {
+            let (width, height) = parse_print_size(&value).ok_or_else(|| invalid_arg("print-size", &value))?;
+            let dpi = dpi.unwrap_or(DEFAULT_DPI);
+            let pixels = |inches: f64| (inches * dpi).round().clamp(1.0, u32::MAX as f64) as u32;
+            (pixels(width), pixels(height), Some(dpi))
+        }
+        (None, None) => (WIDTH, HEIGHT, dpi),
+    };
+
+    let format = match arg_value(args, "format") {
+        Some(value) => Format::from_name(&value).ok_or_else(|| invalid_arg("format", &value))?,
+        None => {
+            let extension = out.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+            Format::from_name(extension).ok_or_else(|| invalid_arg("out", &out.to_string_lossy()))?
+        }
+    };
+    let bit_depth = match arg_value(args, "bit-depth") {
+        Some(value) => {
+            let depth = value.parse().ok().filter(|&depth| depth == 8 || (depth == 16 && format != Format::Jpeg));
+            depth.ok_or_else(|| invalid_arg("bit-depth", &value))?
+        }
+        None => 8,
+    };
+    let quality = match arg_value(args, "quality") {
+        Some(value) => {
+            let quality = value.parse().ok().filter(|quality| (1..=100).contains(quality));
+            quality.ok_or_else(|| invalid_arg("quality", &value))?
+        }
+        None => DEFAULT_JPEG_QUALITY,
+    };
+    if format == Format::Jpeg && (width > JPEG_MAX_SIZE || height > JPEG_MAX_SIZE) {
+        return Err(Error::UserDefined(format!("JPEG images are at most {JPEG_MAX_SIZE} pixels across").into()));
+    }
+
+    let encoding = Encoding { format, bit_depth, quality, dpi };
+    if args.iter().any(|arg| arg == "--estimate") {
+        let estimate = estimate(mandelbrot, width, height, encoding);
+        let threads = match rayon::current_num_threads() {
+            1 => "1 thread".to_owned(),
+            n => format!("{n} threads"),
+        };
+        println!(
+            "{width}x{height} pixels: about {} on {threads}, at {:.0} iterations per pixel with {:.0}% of the pixels inside the set",
+            HumanDuration(estimate.time),
+            estimate.mean_iterations,
+            estimate.inside * 100.0
+        );
+        println!("{} of checkpoint on disk and {} of memory", HumanBytes(estimate.disk_bytes), HumanBytes(estimate.memory_bytes));
+        return Ok(());
+    }
+    render(mandelbrot, &out, width, height, encoding, mode)
+}
+
+/// What a render would take
+#[derive(Clone, Copy, Debug)]
+pub struct Estimate {
+    /// Time rendering the pixels would take on all threads
+    pub time: std::time::Duration,
+    pub mean_iterations: f64,
+    /// Part of the pixels inside the set, which iterate the longest
+    pub inside: f64,
+    /// Size of the checkpoint
+    pub disk_bytes: u64,
+    /// Memory the rendered rows take on the way to the encoder
+    pub memory_bytes: u64,
+}
+
+/// Estimate the render of the view at `width` by `height` pixels with
+/// `encoding` from the pace of a sample of its pixels on a single thread.
+pub fn estimate(mandelbrot: &Mandelbrot, width: u32, height: u32, encoding: Encoding) -> Estimate {
+    // Once for the image, like the reference orbit
+    let start = Instant::now();
+    let view = Mandelbrot { color_range: sample_color_range(mandelbrot).or(mandelbrot.color_range), ..mandelbrot.clone() };
+    let reference = Reference::for_view(&view);
+    let setup = start.elapsed();
+
+    let spread = |i: u32, n: u32, size: u32| ((i as f64 + 0.5) * size as f64 / n as f64) as u32;
+    let spans = (0..SAMPLE_ROWS.min(height)).flat_map(|i| {
+        let y = spread(i, SAMPLE_ROWS.min(height), height);
+        (0..SAMPLE_SPANS).map(move |j| spread(j, SAMPLE_SPANS, width)).map(move |x| (y, x..(x + SAMPLE_SPAN).min(width)))
+    });
+    let start = Instant::now();
+    let counts: Vec<u32> = spans.flat_map(|(y, columns)| render_span(&view, reference.as_ref(), width, height, columns, y).1).collect();
+    let per_pixel = start.elapsed().as_secs_f64() / counts.len() as f64;
+
+    let pixels = width as u64 * height as u64;
+    let threads = rayon::current_num_threads() as f64;
+    let color_bytes = pixels * 4;
+    let strip_bytes = STRIP_ROWS as u64 * width as u64 * 4;
+    Estimate {
+        time: setup + std::time::Duration::from_secs_f64(per_pixel * pixels as f64 / threads),
+        mean_iterations: counts.iter().map(|&m| m as f64).sum::<f64>() / counts.len() as f64,
+        inside: counts.iter().filter(|&&m| m == MAX_ITER).count() as f64 / counts.len() as f64,
+        disk_bytes: if view.contours { 2 * color_bytes } else { color_bytes },
+        // JPEG and the effects take the whole image at once, PNG and TIFF the
+        // strips queued for the encoder and the two it works on
+        memory_bytes: match encoding.format {
+            Format::Png | Format::Tiff if view.effects.is_empty() => (ENCODE_QUEUE as u64 + 2) * strip_bytes,
+            _ => color_bytes,
+        },
+    }
+}
+
+/// Parse a `<width>x<height>` size such as `7680x4320`.
+pub fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let (width, height) = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// Parse a physical `<width>x<height><unit>` size such as `60x90cm` to inches.
+fn parse_print_size(value: &str) -> Option<(f64, f64)> {
+    let (size, inches_per_unit) = [("cm", 1.0 / 2.54), ("mm", 1.0 / 25.4), ("in", 1.0)]
+        .into_iter()
+        .find_map(|(unit, inches)| Some((value.strip_suffix(unit)?, inches)))?;
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (parse_positive(width.trim())?, parse_positive(height.trim())?);
+    Some((width * inches_per_unit, height * inches_per_unit))
+}
+
+/// Render the view at `width` by `height` pixels to the image file `out`,
+/// resuming from its checkpoint when there is one, and report the rows done.
+pub fn render(
+    mandelbrot: &Mandelbrot,
+    out: &Path,
+    width: u32,
+    height: u32,
+    encoding: Encoding,
+    mode: progress::Mode,
+) -> Result<(), Error> {
+    let view = Mandelbrot { color_range: sample_color_range(mandelbrot).or(mandelbrot.color_range), ..mandelbrot.clone() };
+    let mandelbrot = &view;
+    let io = |e: std::io::Error| Error::UserDefined(Box::new(e));
+    let too_large = || Error::UserDefined(format!("{width}x{height} pixels don't fit into the address space").into());
+    let pixels = width as u64 * height as u64;
+    let row_bytes = width as usize * 4;
+
+    // The checkpoint starts with a line identifying the render and the count
+    // of finished rows, followed by the colors of all rows and, for contour
+    // lines, their escape counts
+    let checkpoint_path = checkpoint_path(out);
+    let header = format!("{width}x{height} {mandelbrot:?}\n");
+    let colors_start = header.len() + DONE_DIGITS + 1;
+    let color_bytes = usize::try_from(pixels * 4).map_err(|_| too_large())?;
+    let count_bytes = if mandelbrot.contours { color_bytes } else { 0 };
+    let size = colors_start.checked_add(color_bytes + count_bytes).ok_or_else(too_large)?;
+
+    let checkpoint = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&checkpoint_path)
+        .map_err(io)?;
+    let mut existing = Vec::new();
+    (&checkpoint).take(colors_start as u64).read_to_end(&mut existing).map_err(io)?;
+    let resumable = checkpoint.metadata().map_err(io)?.len() == size as u64;
+    let mut done = match existing.strip_prefix(header.as_bytes()) {
+        Some(count) if resumable => std::str::from_utf8(count).ok().and_then(|count| count.trim_end().parse().ok()).unwrap_or(0).min(height),
+        _ => 0,
+    };
+    if done == 0 {
+        // Zeroed, and sparse where the file system allows
+        checkpoint.set_len(0).map_err(io)?;
+        checkpoint.set_len(size as u64).map_err(io)?;
+    }
+    let mut map = map_checkpoint(&checkpoint).map_err(io)?;
+    if done == 0 {
+        map[..header.len()].copy_from_slice(header.as_bytes());
+        write_done(&mut map[header.len()..colors_start], 0);
+    }
+
+    let mut progress = Progress::new(mode, "render", "rows", height as u64, Some(width as u64));
+    if done > 0 {
+        progress.note(&format!("resuming from row {done} of {height}"));
+        progress.resume(done as u64);
+    }
+    let resumed = done as usize * row_bytes;
+    let reference = Reference::for_view(mandelbrot);
+    // Render the rest of the strips, handing each to `finished` until it
+    // returns false
+    let mut render_rest = |map: &mut MmapMut, finished: &mut dyn FnMut(&[u8]) -> bool| -> Result<(), Error> {
+        while done < height {
+            let rows = done..(done + STRIP_ROWS).min(height);
+            let strip = rows.start as usize * row_bytes..rows.end as usize * row_bytes;
+            let (colors, counts) = map[colors_start..].split_at_mut(color_bytes);
+            render_strip(mandelbrot, reference.as_ref(), width, height, rows.clone(), &mut colors[strip.clone()], counts.get_mut(strip.clone()));
+
+            // Contour lines depend on the escape counts of the row above, which
+            // may belong to the strip before
+            if mandelbrot.contours {
+                let row_counts = |y: u32| -> Vec<u32> {
+                    let row = &counts[y as usize * row_bytes..(y as usize + 1) * row_bytes];
+                    row.chunks_exact(4).map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]])).collect()
+                };
+                let mut above = rows.start.checked_sub(1).map(row_counts);
+                for y in rows.clone() {
+                    let counts = row_counts(y);
+                    let row = &mut colors[y as usize * row_bytes..(y as usize + 1) * row_bytes];
+                    overlay::draw_contour_row(row, &counts, above.as_deref(), mandelbrot.contour_interval, overlay::CONTOUR_COLOR);
+                    above = Some(counts);
+                }
+            }
+            let more = finished(&colors[strip.clone()]);
+
+            // The rows reach the disk before the count that claims them
+            map.flush_range(colors_start + strip.start, strip.len()).map_err(io)?;
+            if mandelbrot.contours {
+                map.flush_range(colors_start + color_bytes + strip.start, strip.len()).map_err(io)?;
+            }
+            done = rows.end;
+            write_done(&mut map[header.len()..colors_start], done);
+            map.flush_range(0, colors_start).map_err(io)?;
+            progress.set(done as u64);
+            if !more {
+                break;
+            }
+        }
+        progress.finish();
+        Ok(())
+    };
+
+    let file = BufWriter::new(File::create(out).map_err(io)?);
+    if !mandelbrot.effects.is_empty() {
+        // Effects reach across strips, so they run over the whole image, a
+        // copy keeping the checkpoint as rendered
+        render_rest(&mut map, &mut |_| true)?;
+        let mut image = map[colors_start..colors_start + color_bytes].to_vec();
+        mandelbrot.post_process(&mut image, width, height);
+        match encoding.format {
+            Format::Png => write_png(file, &mut &image[..], width, height, encoding),
+            Format::Tiff => write_tiff(file, &mut &image[..], width, height, encoding),
+            Format::Jpeg => write_jpeg(file, &image, width, height, encoding),
+        }?;
+    } else {
+        match encoding.format {
+            // Encoded on a thread of their own as the strips complete, starting
+            // over with the rows of an earlier run when resuming
+            Format::Png | Format::Tiff => std::thread::scope(|scope| {
+                let (strips, received) = mpsc::sync_channel(ENCODE_QUEUE);
+                let encoder = scope.spawn(move || {
+                    let mut rows = StripReader { strips: received, strip: Vec::new(), read: 0 };
+                    match encoding.format {
+                        Format::Png => write_png(file, &mut rows, width, height, encoding),
+                        _ => write_tiff(file, &mut rows, width, height, encoding),
+                    }
+                });
+                let earlier = &map[colors_start..colors_start + resumed];
+                let mut send = |strip: &[u8]| strips.send(strip.to_vec()).is_ok();
+                let rendered = match earlier.chunks(STRIP_ROWS as usize * row_bytes).all(&mut send) {
+                    true => render_rest(&mut map, &mut send),
+                    false => Ok(()),
+                };
+                drop(strips);
+                let encoded = encoder.join().unwrap_or_else(|_| Err(Error::UserDefined("the encoder failed".into())));
+                rendered.and(encoded)
+            }),
+            // The whole image at once, paged in from the checkpoint
+            Format::Jpeg => {
+                render_rest(&mut map, &mut |_| true)?;
+                write_jpeg(file, &map[colors_start..colors_start + color_bytes], width, height, encoding)
+            }
+        }?;
+    }
+
+    drop(map);
+    drop(checkpoint);
+    std::fs::remove_file(&checkpoint_path).map_err(io)
+}
+
+/// Render the rows `rows` of the view into `colors` and, where given, their
+/// escape counts into `counts`, in tiles of the strip spread over the threads.
+fn render_strip(mandelbrot: &Mandelbrot, reference: Option<&Reference>, width: u32, height: u32, rows: Range<u32>, colors: &mut [u8], counts: Option<&mut [u8]>) {
+    let row_bytes = width as usize * 4;
+    let strip = Mutex::new((colors, counts));
+    (0..width).into_par_iter().step_by(TILE_COLUMNS as usize).for_each(|x| {
+        let columns = x..(x + TILE_COLUMNS).min(width);
+        let (tile_colors, tile_counts) = render_block(mandelbrot, reference, width, height, columns.clone(), rows.clone());
+
+        let Ok(mut strip) = strip.lock() else { return };
+        let (colors, counts) = &mut *strip;
+        let span = x as usize * 4..columns.end as usize * 4;
+        let tile = tile_colors.chunks_exact(columns.len() * 4).zip(tile_counts.chunks_exact(columns.len()));
+        for (row, (tile_colors, tile_counts)) in tile.enumerate() {
+            let row = row * row_bytes;
+            colors[row + span.start..row + span.end].copy_from_slice(tile_colors);
+            if let Some(counts) = counts {
+                let counts = &mut counts[row + span.start..row + span.end];
+                for (bytes, count) in counts.chunks_exact_mut(4).zip(tile_counts) {
+                    bytes.copy_from_slice(&count.to_le_bytes());
+                }
+            }
+        }
+    });
+}
+
+/// Reads the rows sent over a channel a strip at a time, up to the end of the
+/// image once the sender is dropped.
+struct StripReader {
+    strips: Receiver<Vec<u8>>,
+    strip: Vec<u8>,
+    /// Bytes of `strip` read so far
+    read: usize,
+}
+
+impl Read for StripReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.read == self.strip.len() {
+            match self.strips.recv() {
+                Ok(strip) => (self.strip, self.read) = (strip, 0),
+                Err(_) => return Ok(0),
+            }
+        }
+        let length = buf.len().min(self.strip.len() - self.read);
+        buf[..length].copy_from_slice(&self.strip[self.read..self.read + length]);
+        self.read += length;
+        Ok(length)
+    }
+}
+
+/// Map the checkpoint file into memory, to read and write it in place.
+#[allow(unsafe_code)]
+fn map_checkpoint(checkpoint: &File) -> std::io::Result<MmapMut> {
+    // Safety: the file is only changed through this mapping while the render
+    // runs, as a second render to the same output would clobber it anyway
+    unsafe { MmapMut::map_mut(checkpoint) }
+}
+
+/// Write `done` as the fixed-width count of finished rows in the checkpoint.
+fn write_done(field: &mut [u8], done: u32) {
+    field.copy_from_slice(format!("{done:0width$}\n", width = DONE_DIGITS).as_bytes());
+}
+
+/// Decode a PNG image into its width, height and RGBA pixels.
+pub fn read_png(path: &Path) -> Result<(usize, usize, Vec<[u8; 4]>), Error> {
+    let png = |e: png::DecodingError| Error::UserDefined(format!("{}: {e}", path.display()).into());
+    let file = File::open(path).map_err(|e| Error::UserDefined(format!("{}: {e}", path.display()).into()))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(png)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(png)?;
+    let buffer = &buffer[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Grayscale => buffer.iter().map(|&l| [l, l, l, 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Rgb => buffer.chunks_exact(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::Rgba => buffer.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+        // Expanded to RGB or RGBA by the transformations
+        png::ColorType::Indexed => unreachable!(),
+    };
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+/// Widen 8-bit channels to 16 bits, so 255 becomes 65535.
+fn widen(channel: u8) -> u16 {
+    channel as u16 * 257
+}
+
+pub fn write_png(file: BufWriter<File>, rows: &mut impl Read, width: u32, height: u32, encoding: Encoding) -> Result<(), Error> {
+    let png = |e: png::EncodingError| Error::UserDefined(Box::new(e));
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(match encoding.bit_depth {
+        16 => png::BitDepth::Sixteen,
+        _ => png::BitDepth::Eight,
+    });
+    if let Some(dpi) = encoding.dpi {
+        let per_meter = (dpi / 0.0254).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: per_meter, yppu: per_meter, unit: png::Unit::Meter }));
+    }
+    let mut writer = encoder.write_header().map_err(png)?;
+    let mut stream = writer.stream_writer().map_err(png)?;
+    let mut row = vec![0; width as usize * 4];
+    for _ in 0..height {
+        rows.read_exact(&mut row).map_err(|e| Error::UserDefined(Box::new(e)))?;
+        let written = match encoding.bit_depth {
+            // Big-endian samples
+            16 => stream.write_all(&row.iter().flat_map(|&channel| widen(channel).to_be_bytes()).collect::<Vec<_>>()),
+            _ => stream.write_all(&row),
+        };
+        written.map_err(|e| Error::UserDefined(Box::new(e)))?;
+    }
+    stream.finish().map_err(png)
+}
+
+fn write_tiff(file: BufWriter<File>, rows: &mut impl Read, width: u32, height: u32, encoding: Encoding) -> Result<(), Error> {
+    use tiff::encoder::colortype::{self, ColorType};
+    use tiff::encoder::{Rational, TiffEncoder, TiffValue};
+
+    /// Write the image a strip at a time with samples of type `C`
+    fn write<C: ColorType>(
+        mut encoder: TiffEncoder<BufWriter<File>>,
+        rows: &mut impl Read,
+        width: u32,
+        height: u32,
+        dpi: Option<f64>,
+        sample: impl Fn(u8) -> C::Inner,
+    ) -> tiff::TiffResult<()>
+    where
+        [C::Inner]: TiffValue,
+    {
+        let mut image = encoder.new_image::<C>(width, height)?;
+        image.rows_per_strip(STRIP_ROWS)?;
+        if let Some(dpi) = dpi {
+            image.resolution(tiff::tags::ResolutionUnit::Inch, Rational { n: dpi.round() as u32, d: 1 });
+        }
+        let mut strip = Vec::new();
+        loop {
+            let samples = image.next_strip_sample_count() as usize;
+            if samples == 0 {
+                break;
+            }
+            strip.resize(samples, 0);
+            rows.read_exact(&mut strip)?;
+            image.write_strip(&strip.iter().map(|&channel| sample(channel)).collect::<Vec<_>>())?;
+        }
+        image.finish()
+    }
+
+    let encoder = TiffEncoder::new(file).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    let written = match encoding.bit_depth {
+        16 => write::<colortype::RGBA16>(encoder, rows, width, height, encoding.dpi, widen),
+        _ => write::<colortype::RGBA8>(encoder, rows, width, height, encoding.dpi, |channel| channel),
+    };
+    written.map_err(|e| Error::UserDefined(Box::new(e)))
+}
+
+/// JPEG encoding takes the whole image at once, which the mapped checkpoint
+/// provides without holding it in memory.
+fn write_jpeg(file: BufWriter<File>, image: &[u8], width: u32, height: u32, encoding: Encoding) -> Result<(), Error> {
+    let mut encoder = jpeg_encoder::Encoder::new(file, encoding.quality);
+    if let Some(dpi) = encoding.dpi {
+        let dpi = dpi.round().clamp(1.0, u16::MAX as f64) as u16;
+        encoder.set_density(jpeg_encoder::Density::Inch { x: dpi, y: dpi });
+    }
+    encoder
+        .encode(image, width as u16, height as u16, jpeg_encoder::ColorType::Rgba)
+        .map_err(|e| Error::UserDefined(Box::new(e)))
+}
+
+/// Render row `y` of the view at `width` by `height` pixels, returning its
+/// colors and escape counts. Deep views take their `reference` orbit.
+pub fn render_row(mandelbrot: &Mandelbrot, reference: Option<&Reference>, width: u32, height: u32, y: u32) -> (Vec<u8>, Vec<u32>) {
+    render_span(mandelbrot, reference, width, height, 0..width, y)
+}
+
+/// The color range of a view with `auto_range`, from a coarse render of it,
+/// for renders that color their pixels as they go.
+pub fn sample_color_range(mandelbrot: &Mandelbrot) -> Option<(f64, f64)> {
+    let percent = mandelbrot.auto_range?;
+    let (width, height) = (RANGE_SAMPLE_WIDTH, RANGE_SAMPLE_WIDTH * HEIGHT / WIDTH);
+    let reference = Reference::for_view(mandelbrot);
+    let counts: Vec<u32> = (0..height).into_par_iter().flat_map_iter(|y| render_row(mandelbrot, reference.as_ref(), width, height, y).1).collect();
+    coloring::count_range(&counts, percent)
+}
+
+/// Render the `columns` of row `y` of the view at `width` by `height` pixels.
+fn render_span(mandelbrot: &Mandelbrot, reference: Option<&Reference>, width: u32, height: u32, columns: Range<u32>, y: u32) -> (Vec<u8>, Vec<u32>) {
+    let to_window = to_window(width, height);
+    let mut colors = vec![0; columns.len() * 4];
+    let mut counts = vec![0; columns.len()];
+    match reference {
+        Some(reference) => {
+            let offsets: Vec<_> = columns.map(|x| to_window(x as f64, y as f64)).map(|(x, y)| mandelbrot.view_offset(x, y)).collect();
+            mandelbrot.shade_offsets(reference, &offsets, &mut counts, &mut colors);
+        }
+        None => {
+            let points: Vec<_> = columns.map(|x| to_window(x as f64, y as f64)).map(|(x, y)| mandelbrot.pixel_to_complex(x, y)).collect();
+            mandelbrot.shade_block(&points, points.len(), &mut counts, &mut colors);
+        }
+    }
+    (colors, counts)
+}
+
+/// The colors and escape counts of the block of `columns` and `rows` of the
+/// image, row by row, rendered as one for adaptive iteration limits.
+fn render_block(mandelbrot: &Mandelbrot, reference: Option<&Reference>, width: u32, height: u32, columns: Range<u32>, rows: Range<u32>) -> (Vec<u8>, Vec<u32>) {
+    if reference.is_some() || mandelbrot.adaptive_iterations.is_none() {
+        let spans = rows.map(|y| render_span(mandelbrot, reference, width, height, columns.clone(), y));
+        return spans.fold((Vec::new(), Vec::new()), |(mut colors, mut counts), (span_colors, span_counts)| {
+            colors.extend(span_colors);
+            counts.extend(span_counts);
+            (colors, counts)
+        });
+    }
+    let to_window = to_window(width, height);
+    let pixels = rows.flat_map(|y| columns.clone().map(move |x| (x, y)));
+    let points: Vec<_> = pixels.map(|(x, y)| to_window(x as f64, y as f64)).map(|(x, y)| mandelbrot.pixel_to_complex(x, y)).collect();
+    let mut colors = vec![0; points.len() * 4];
+    let mut counts = vec![0; points.len()];
+    mandelbrot.shade_block(&points, columns.len(), &mut counts, &mut colors);
+    (colors, counts)
+}
+
+/// The window's pixel coordinates of the pixels of an image `width` by
+/// `height` pixels, centered and scaled to the window height.
+fn to_window(width: u32, height: u32) -> impl Fn(f64, f64) -> (f64, f64) {
+    let scale = HEIGHT as f64 / height as f64;
+    move |x, y| (WIDTH as f64 / 2.0 + (x - width as f64 / 2.0) * scale, HEIGHT as f64 / 2.0 + (y - height as f64 / 2.0) * scale)
+}
+
+fn checkpoint_path(out: &Path) -> PathBuf {
+    let mut path = out.as_os_str().to_owned();
+    path.push(".partial");
+    PathBuf::from(path)
+}
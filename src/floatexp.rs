@@ -0,0 +1,107 @@
+//! Floating-point numbers with an extended exponent.
+//!
+//! Perturbation deltas of the deepest views are smaller than the smallest
+//! normal `f64`, about 10^-308, and would lose their lower bits to underflow
+//! or vanish. A `FloatExp` keeps an `f64` mantissa with a separate 64-bit
+//! exponent, so it holds the deltas with full precision at any depth, at the
+//! cost of normalizing after every operation.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// `mantissa * 2^exponent`, the mantissa between 0.5 and 1 in magnitude or
+/// zero
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloatExp {
+    mantissa: f64,
+    exponent: i64,
+}
+
+impl FloatExp {
+    pub const ZERO: Self = Self { mantissa: 0.0, exponent: 0 };
+
+    pub fn new(mantissa: f64, exponent: i64) -> Self {
+        if mantissa == 0.0 || !mantissa.is_finite() {
+            return Self { mantissa, exponent: 0 };
+        }
+        let bits = mantissa.to_bits();
+        let biased = (bits >> 52 & 0x7ff) as i64;
+        if biased == 0 {
+            // Subnormal, scale it into the normal range first
+            return Self::new(mantissa * 2f64.powi(64), exponent - 64);
+        }
+        let mantissa = f64::from_bits(bits & !(0x7ff << 52) | 1022 << 52);
+        Self { mantissa, exponent: exponent + biased - 1022 }
+    }
+
+    pub fn from_f64(value: f64) -> Self {
+        Self::new(value, 0)
+    }
+
+    /// The nearest `f64`, zero or infinite outside its range.
+    pub fn to_f64(self) -> f64 {
+        // In two steps, as either power alone may be out of range
+        let exponent = self.exponent.clamp(-2200, 2200) as i32;
+        self.mantissa * 2f64.powi(exponent / 2) * 2f64.powi(exponent - exponent / 2)
+    }
+
+    pub fn recip(self) -> Self {
+        Self::new(1.0 / self.mantissa, -self.exponent)
+    }
+
+    /// The mantissa and the exponent.
+    pub fn parts(self) -> (f64, i64) {
+        (self.mantissa, self.exponent)
+    }
+
+    /// The binary exponent, which is `i64::MIN` for zero.
+    pub fn exponent(self) -> i64 {
+        if self.mantissa == 0.0 { i64::MIN } else { self.exponent }
+    }
+}
+
+impl Add for FloatExp {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (large, small) = if self.exponent() >= other.exponent() { (self, other) } else { (other, self) };
+        let shift = large.exponent().saturating_sub(small.exponent());
+        if small.mantissa == 0.0 || shift > 64 {
+            return large;
+        }
+        // 2^-shift, exactly
+        let scale = f64::from_bits(((1023 - shift) as u64) << 52);
+        Self::new(large.mantissa + small.mantissa * scale, large.exponent)
+    }
+}
+
+impl Sub for FloatExp {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Neg for FloatExp {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { mantissa: -self.mantissa, exponent: self.exponent }
+    }
+}
+
+impl Mul for FloatExp {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(self.mantissa * other.mantissa, self.exponent + other.exponent)
+    }
+}
+
+impl Mul<f64> for FloatExp {
+    type Output = Self;
+
+    fn mul(self, other: f64) -> Self {
+        Self::new(self.mantissa * other, self.exponent)
+    }
+}
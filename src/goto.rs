@@ -0,0 +1,64 @@
+//! Go-to dialog of the viewer.
+//!
+//! G opens a dialog to type or paste a location into, in any of the formats
+//! of [`crate::location`]. "Go" (or Ctrl+Enter) moves the view there, keeping
+//! the zoom unless the location gives one, and closes the dialog, which stays
+//! open instead to say what is wrong with the location or what of it was left
+//! out. "Cancel" or Escape closes it.
+
+use egui::{Color32, Context, Key, Modifiers};
+
+use crate::location::{self, Location};
+use crate::Mandelbrot;
+
+/// Lines of the text box, enough for a pasted Kalles Fraktaler location
+const ROWS: usize = 4;
+const ERROR_COLOR: Color32 = Color32::from_rgb(255, 96, 96);
+
+/// What the dialog asks of the viewer
+pub enum Request {
+    Go(Location),
+    Close,
+}
+
+#[derive(Default)]
+pub struct GoTo {
+    text: String,
+    /// What is wrong with the text, or was left out of the last location gone
+    /// to, and whether it is an error
+    message: Option<(String, bool)>,
+}
+
+impl GoTo {
+    /// Lay out the dialog over the viewer of `view`.
+    pub fn ui(&mut self, context: &Context, view: &Mandelbrot) -> Option<Request> {
+        let mut request = None;
+        egui::Window::new("Go to").collapsible(false).resizable(false).show(context, |ui| {
+            ui.label("Center and zoom, a Kalles Fraktaler location or a Fractint center-mag");
+            let text = egui::TextEdit::multiline(&mut self.text).desired_rows(ROWS).hint_text("-0.743643887, 0.131825904, 2.5e6");
+            ui.add(text).request_focus();
+            if let Some((message, error)) = &self.message {
+                match error {
+                    true => ui.colored_label(ERROR_COLOR, message),
+                    false => ui.label(message),
+                };
+            }
+            ui.horizontal(|ui| {
+                let submitted = ui.input_mut(|input| input.consume_key(Modifiers::COMMAND, Key::Enter));
+                if ui.button("Go").clicked() || submitted {
+                    match location::parse(&self.text, view.zoom) {
+                        Ok(location) => {
+                            self.message = location.note.clone().map(|note| (note, false));
+                            request = Some(Request::Go(location));
+                        }
+                        Err(e) => self.message = Some((e, true)),
+                    }
+                }
+                if ui.button("Cancel").clicked() || ui.input(|input| input.key_pressed(Key::Escape)) {
+                    request = Some(Request::Close);
+                }
+            });
+        });
+        request
+    }
+}
@@ -0,0 +1,428 @@
+//! Escape counts computed on the GPU.
+//!
+//! A compute shader iterates every pixel in single precision on the same wgpu
+//! device `pixels` presents with, and the counts are read back and colored by
+//! the CPU like the counts of the CPU renderer. Single precision only resolves
+//! shallow zooms, so deeper views go to a second shader that emulates double
+//! precision with double-single numbers: the unevaluated sum of two `f32`s,
+//! added and multiplied with the error-free transformations of Dekker and
+//! Knuth, which keep the rounding error of every operation in the low part.
+//! That holds about 44 bits of mantissa, for zooms some six orders of
+//! magnitude deeper, at a few times the cost, which GPUs without native
+//! `f64` still run faster than the CPU. The rounding errors the transforms
+//! recover are zero to an optimizing compiler, so every step is multiplied by
+//! a `one` the shader only knows at run time, which keeps it from folding
+//! them away. `precision` tells which precision resolves a view, and
+//! `suitable` whether the device renders it at all, otherwise the viewer
+//! renders on the CPU in double precision. The viewer also falls back to the
+//! CPU for good when the device turns out not to support compute shaders or
+//! gets lost.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+use pixels::wgpu;
+use pixels::Error;
+
+use crate::projection::Projection;
+use crate::{Bailout, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// A pixel has to span at least this many steps of the precision of the GPU
+/// of the coordinates around it for the GPU to render the view
+const MIN_STEPS_PER_PIXEL: f64 = 16.0;
+/// Relative precision of double-single numbers, a few bits short of their
+/// 48 bits of mantissa for the rounding of the operations
+const DOUBLE_SINGLE_EPSILON: f64 = 1.0 / (1u64 << 44) as f64;
+const WORKGROUP_SIZE: u32 = 8;
+/// Size of the uniform parameter block, see `Params` in the shaders, that of
+/// the double-single one, which is the larger
+const PARAMS_SIZE: u64 = 80;
+const COUNTS_SIZE: u64 = WIDTH as u64 * HEIGHT as u64 * 4;
+
+const SHADER: &str = r#"
+struct Params {
+    center: vec2<f32>,
+    // Offset of neighbouring pixel columns and rows in the complex plane
+    column: vec2<f32>,
+    row: vec2<f32>,
+    julia: vec2<f32>,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    is_julia: u32,
+    bailout: u32,
+    radius: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> counts: array<u32>;
+
+fn escaped(z: vec2<f32>, norm_sqr: f32) -> bool {
+    switch params.bailout {
+        case 1u: { return abs(z.x) + abs(z.y) > params.radius; }
+        case 2u: { return max(abs(z.x), abs(z.y)) > params.radius; }
+        default: { return norm_sqr > params.radius * params.radius; }
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let dx = f32(id.x) - f32(params.width) / 2.0;
+    let dy = f32(id.y) - f32(params.height) / 2.0;
+    let point = params.center + dx * params.column + dy * params.row;
+
+    var z = vec2<f32>(0.0, 0.0);
+    var c = point;
+    if params.is_julia != 0u {
+        z = point;
+        c = params.julia;
+    }
+
+    var n = 0u;
+    loop {
+        if n >= params.max_iter {
+            break;
+        }
+        let sqr = z * z;
+        if escaped(z, sqr.x + sqr.y) {
+            break;
+        }
+        z = vec2<f32>(sqr.x - sqr.y + c.x, 2.0 * z.x * z.y + c.y);
+        n += 1u;
+    }
+    counts[id.y * params.width + id.x] = n;
+}
+"#;
+
+const DOUBLE_SINGLE_SHADER: &str = r#"
+// Double-single numbers are vec2<f32>, the high part x and the low part y
+struct Params {
+    center_re: vec2<f32>,
+    center_im: vec2<f32>,
+    // Offset of neighbouring pixel columns and rows, which single precision
+    // holds as well as the offsets of the pixels from the center
+    column: vec2<f32>,
+    row: vec2<f32>,
+    julia_re: vec2<f32>,
+    julia_im: vec2<f32>,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    is_julia: u32,
+    bailout: u32,
+    radius: f32,
+    // Always 1, see the module documentation
+    one: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> counts: array<u32>;
+
+// a + b where |a| >= |b|, with the rounding error in the low part
+fn quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = (a + b) * params.one;
+    return vec2<f32>(s, b - (s - a));
+}
+
+// a + b with the rounding error in the low part
+fn two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = (a + b) * params.one;
+    let v = (s - a) * params.one;
+    return vec2<f32>(s, (a - (s - v)) + (b - v));
+}
+
+// a as the sum of two halves of 12 bits of mantissa, which multiply exactly
+fn split(a: f32) -> vec2<f32> {
+    let t = (4097.0 * a) * params.one;
+    let high = (t - (t - a)) * params.one;
+    return vec2<f32>(high, a - high);
+}
+
+// a * b with the rounding error in the low part
+fn two_prod(a: f32, b: f32) -> vec2<f32> {
+    let p = (a * b) * params.one;
+    let x = split(a);
+    let y = split(b);
+    return vec2<f32>(p, ((x.x * y.x - p) + x.x * y.y + x.y * y.x) + x.y * y.y);
+}
+
+fn ds_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let s = two_sum(a.x, b.x);
+    let t = two_sum(a.y, b.y);
+    let u = quick_two_sum(s.x, s.y + t.x);
+    return quick_two_sum(u.x, u.y + t.y);
+}
+
+fn ds_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let p = two_prod(a.x, b.x);
+    return quick_two_sum(p.x, p.y + (a.x * b.y + a.y * b.x));
+}
+
+fn escaped(z: vec2<f32>, norm_sqr: f32) -> bool {
+    switch params.bailout {
+        case 1u: { return abs(z.x) + abs(z.y) > params.radius; }
+        case 2u: { return max(abs(z.x), abs(z.y)) > params.radius; }
+        default: { return norm_sqr > params.radius * params.radius; }
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let dx = f32(id.x) - f32(params.width) / 2.0;
+    let dy = f32(id.y) - f32(params.height) / 2.0;
+    let offset = dx * params.column + dy * params.row;
+    let point_re = ds_add(params.center_re, vec2<f32>(offset.x, 0.0));
+    let point_im = ds_add(params.center_im, vec2<f32>(offset.y, 0.0));
+
+    var z_re = vec2<f32>(0.0, 0.0);
+    var z_im = vec2<f32>(0.0, 0.0);
+    var c_re = point_re;
+    var c_im = point_im;
+    if params.is_julia != 0u {
+        z_re = point_re;
+        z_im = point_im;
+        c_re = params.julia_re;
+        c_im = params.julia_im;
+    }
+
+    // The escape test only needs the high parts
+    var n = 0u;
+    loop {
+        if n >= params.max_iter {
+            break;
+        }
+        let re_sqr = ds_mul(z_re, z_re);
+        let im_sqr = ds_mul(z_im, z_im);
+        if escaped(vec2<f32>(z_re.x, z_im.x), re_sqr.x + im_sqr.x) {
+            break;
+        }
+        z_im = ds_add(ds_mul(2.0 * z_re, z_im), c_im);
+        z_re = ds_add(ds_add(re_sqr, -im_sqr), c_re);
+        n += 1u;
+    }
+    counts[id.y * params.width + id.x] = n;
+}
+"#;
+
+/// Precision the GPU renders a view in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    Single,
+    /// Emulated with pairs of `f32`
+    DoubleSingle,
+}
+
+/// The precision of the GPU that resolves `mandelbrot`, `None` where the
+/// view is too deep for either, or its projection or coloring too involved
+/// for the shaders.
+pub fn precision(mandelbrot: &Mandelbrot) -> Option<Precision> {
+    if mandelbrot.exponential_map || mandelbrot.projection != Projection::Plane || !mandelbrot.coloring.counts_only() || mandelbrot.mipmapped() || mandelbrot.adaptive_iterations.is_some() || mandelbrot.multibrot() {
+        return None;
+    }
+    let (center, column, row) = Gpu::axes(mandelbrot);
+    // Coordinates reach out to the view corners and to the escape radius
+    let extent = center.norm() + (column.norm() * WIDTH as f64 + row.norm() * HEIGHT as f64) / 2.0;
+    let scale = extent.max(mandelbrot.bailout_radius);
+    let resolves = |epsilon: f64| column.norm().min(row.norm()) > scale * MIN_STEPS_PER_PIXEL * epsilon;
+    [(Precision::Single, f32::EPSILON as f64), (Precision::DoubleSingle, DOUBLE_SINGLE_EPSILON)]
+        .into_iter()
+        .find_map(|(precision, epsilon)| resolves(epsilon).then_some(precision))
+}
+
+/// `x` as a double-single number, its nearest `f32` and the rest.
+pub fn split(x: f64) -> [f32; 2] {
+    let high = x as f32;
+    [high, (x - high as f64) as f32]
+}
+
+/// A compute pipeline and the bind group of the buffers for it
+struct Pipeline {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Compute pipelines and buffers for rendering escape counts on a device
+pub struct Gpu {
+    single: Pipeline,
+    /// The double-single pipeline, where the device builds it
+    double_single: Option<Pipeline>,
+    params: wgpu::Buffer,
+    counts: wgpu::Buffer,
+    /// Mappable copy of `counts` for reading them back
+    staging: wgpu::Buffer,
+    lost: Arc<AtomicBool>,
+}
+
+impl Gpu {
+    /// Set up rendering on `device`, `None` when it can't run the shader.
+    pub fn new(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Option<Self> {
+        let compute = adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+        let limits = device.limits();
+        if !compute
+            || limits.max_storage_buffers_per_shader_stage < 1
+            || limits.max_compute_invocations_per_workgroup < WORKGROUP_SIZE * WORKGROUP_SIZE
+        {
+            return None;
+        }
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("mandelbrot params"),
+            size: PARAMS_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let counts = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("mandelbrot counts"),
+            size: COUNTS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("mandelbrot staging"),
+            size: COUNTS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let single = Self::pipeline(device, SHADER, &params, &counts);
+        if pollster::block_on(device.pop_error_scope()).is_some() {
+            return None;
+        }
+        // Without the double-single pipeline the single one still renders,
+        // and `suitable` leaves the deeper views to the CPU
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let double_single = Self::pipeline(device, DOUBLE_SINGLE_SHADER, &params, &counts);
+        let double_single = pollster::block_on(device.pop_error_scope()).is_none().then_some(double_single);
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let flag = lost.clone();
+        device.set_device_lost_callback(move |_, _| flag.store(true, Ordering::Relaxed));
+
+        Some(Self { single, double_single, params, counts, staging, lost })
+    }
+
+    /// The pipeline of the compute shader `source` on the buffers.
+    fn pipeline(device: &wgpu::Device, source: &str, params: &wgpu::Buffer, counts: &wgpu::Buffer) -> Pipeline {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("mandelbrot"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("mandelbrot"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mandelbrot"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: counts.as_entire_binding() },
+            ],
+        });
+        Pipeline { pipeline, bind_group }
+    }
+
+    /// Whether the device renders the view, in a precision that resolves it
+    /// with a shader that supports its projection and coloring.
+    pub fn suitable(&self, mandelbrot: &Mandelbrot) -> bool {
+        self.pipeline_for(mandelbrot).is_some()
+    }
+
+    fn pipeline_for(&self, mandelbrot: &Mandelbrot) -> Option<(&Pipeline, Precision)> {
+        match precision(mandelbrot)? {
+            Precision::Single => Some((&self.single, Precision::Single)),
+            Precision::DoubleSingle => Some((self.double_single.as_ref()?, Precision::DoubleSingle)),
+        }
+    }
+
+    /// Escape counts of every pixel of the view, row by row, in the
+    /// precision that resolves it, or in single precision where none does.
+    pub fn render(&self, device: &wgpu::Device, queue: &wgpu::Queue, mandelbrot: &Mandelbrot) -> Result<Vec<u32>, Error> {
+        if self.lost.load(Ordering::Relaxed) {
+            return Err(Error::UserDefined("GPU device lost".into()));
+        }
+
+        let (pipeline, precision) = self.pipeline_for(mandelbrot).unwrap_or((&self.single, Precision::Single));
+        queue.write_buffer(&self.params, 0, &Self::params(mandelbrot, precision));
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("mandelbrot") });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("mandelbrot"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline.pipeline);
+            pass.set_bind_group(0, &pipeline.bind_group, &[]);
+            pass.dispatch_workgroups(WIDTH.div_ceil(WORKGROUP_SIZE), HEIGHT.div_ceil(WORKGROUP_SIZE), 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.counts, 0, &self.staging, 0, COUNTS_SIZE);
+        queue.submit(Some(encoder.finish()));
+
+        // Wait for the counts, which fails when the device is lost meanwhile
+        let slice = self.staging.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        match receiver.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(Error::UserDefined(Box::new(e))),
+            Err(_) => return Err(Error::UserDefined("GPU readback did not complete".into())),
+        }
+
+        let counts = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        self.staging.unmap();
+        Ok(counts)
+    }
+
+    /// The view center and the offsets of neighbouring pixel columns and rows.
+    fn axes(mandelbrot: &Mandelbrot) -> (num::Complex<f64>, num::Complex<f64>, num::Complex<f64>) {
+        let (x, y) = (WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0);
+        let center = mandelbrot.pixel_to_complex(x, y);
+        let column = mandelbrot.pixel_to_complex(x + 1.0, y) - center;
+        let row = mandelbrot.pixel_to_complex(x, y + 1.0) - center;
+        (center, column, row)
+    }
+
+    /// Contents of the uniform parameter block for the view, for the shader
+    /// of `precision`.
+    fn params(mandelbrot: &Mandelbrot, precision: Precision) -> Vec<u8> {
+        let (center, column, row) = Self::axes(mandelbrot);
+        let julia = mandelbrot.julia.unwrap_or_default();
+        let bailout = match mandelbrot.bailout {
+            Bailout::Modulus => 0,
+            Bailout::Manhattan => 1,
+            Bailout::MaxComponent => 2,
+        };
+
+        let floats: Vec<f32> = match precision {
+            Precision::Single => [center, column, row, julia].into_iter().flat_map(|c| [c.re as f32, c.im as f32]).collect(),
+            Precision::DoubleSingle => {
+                let [center_re, center_im, julia_re, julia_im] = [center.re, center.im, julia.re, julia.im].map(split);
+                [center_re, center_im, [column.re as f32, column.im as f32], [row.re as f32, row.im as f32], julia_re, julia_im].concat()
+            }
+        };
+        let mut bytes: Vec<u8> = floats.into_iter().flat_map(f32::to_le_bytes).collect();
+        for value in [WIDTH, HEIGHT, MAX_ITER, mandelbrot.julia.is_some() as u32, bailout] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend((mandelbrot.bailout_radius as f32).to_le_bytes());
+        if precision == Precision::DoubleSingle {
+            bytes.extend(1f32.to_le_bytes());
+        }
+        bytes.resize(PARAMS_SIZE as usize, 0);
+        bytes
+    }
+}
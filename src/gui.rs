@@ -0,0 +1,114 @@
+//! egui drawn over the view of a window.
+//!
+//! The user interface is laid out while a frame is drawn and painted over
+//! the scaled pixel buffer in the same render pass of the window surface.
+
+use egui::{Color32, ClippedPrimitive, Context, Stroke, TexturesDelta, ViewportId, Visuals};
+use egui_wgpu::{Renderer, ScreenDescriptor};
+use egui_winit::EventResponse;
+use pixels::{wgpu, Pixels, PixelsContext};
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+/// The egui state of a window
+pub struct Gui {
+    context: Context,
+    state: egui_winit::State,
+    renderer: Renderer,
+    screen: ScreenDescriptor,
+    /// What was laid out for the frame, ready to paint
+    paint_jobs: Vec<ClippedPrimitive>,
+    textures: TexturesDelta,
+}
+
+impl Gui {
+    /// The egui state of `window`, in white on black for `high_contrast`.
+    pub fn new(window: &Window, pixels: &Pixels, high_contrast: bool) -> Self {
+        let size = window.inner_size();
+        let scale = window.scale_factor() as f32;
+        let context = Context::default();
+        if high_contrast {
+            context.set_visuals(high_contrast_visuals());
+        }
+        let max_texture_side = pixels.device().limits().max_texture_dimension_2d as usize;
+        let state = egui_winit::State::new(context.clone(), ViewportId::ROOT, window, Some(scale), Some(max_texture_side));
+        let renderer = Renderer::new(pixels.device(), pixels.render_texture_format(), None, 1);
+        Self {
+            context,
+            state,
+            renderer,
+            screen: ScreenDescriptor { size_in_pixels: [size.width, size.height], pixels_per_point: scale },
+            paint_jobs: Vec::new(),
+            textures: TexturesDelta::default(),
+        }
+    }
+
+    /// Pass a window event to egui, which tells whether it took the event
+    /// and needs to repaint.
+    pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) -> EventResponse {
+        self.state.on_window_event(window, event)
+    }
+
+    /// Follow the window surface to its new size in physical pixels.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.screen.size_in_pixels = [width, height];
+        }
+    }
+
+    /// Lay out the user interface with `ui` for the next `render`, returning
+    /// whether egui wants another frame right away, as while animating.
+    pub fn prepare(&mut self, window: &Window, ui: impl FnMut(&Context)) -> bool {
+        let input = self.state.take_egui_input(window);
+        let output = self.context.run(input, ui);
+        self.state.handle_platform_output(window, output.platform_output);
+        self.textures.append(output.textures_delta);
+        self.screen.pixels_per_point = output.pixels_per_point;
+        self.paint_jobs = self.context.tessellate(output.shapes, output.pixels_per_point);
+        output.viewport_output.get(&ViewportId::ROOT).is_some_and(|viewport| viewport.repaint_delay.is_zero())
+    }
+
+    /// Paint what was laid out over `target`.
+    pub fn render(&mut self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, context: &PixelsContext) {
+        let textures = std::mem::take(&mut self.textures);
+        for (id, delta) in &textures.set {
+            self.renderer.update_texture(&context.device, &context.queue, *id, delta);
+        }
+        self.renderer.update_buffers(&context.device, &context.queue, encoder, &self.paint_jobs, &self.screen);
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("gui"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.renderer.render(&mut pass, &self.paint_jobs, &self.screen);
+        }
+        for id in &textures.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+/// Dark visuals with white text and outlines on black, selections in deep
+/// blue.
+fn high_contrast_visuals() -> Visuals {
+    let mut visuals = Visuals::dark();
+    visuals.override_text_color = Some(Color32::WHITE);
+    (visuals.panel_fill, visuals.window_fill, visuals.extreme_bg_color) = (Color32::BLACK, Color32::BLACK, Color32::BLACK);
+    visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.selection.bg_fill = Color32::from_rgb(0, 50, 200);
+    visuals.selection.stroke = Stroke::new(2.0, Color32::WHITE);
+    let widgets = &mut visuals.widgets;
+    for widget in [&mut widgets.noninteractive, &mut widgets.inactive, &mut widgets.hovered, &mut widgets.active, &mut widgets.open] {
+        widget.bg_stroke = Stroke::new(1.5, Color32::WHITE);
+        widget.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+    }
+    (widgets.inactive.bg_fill, widgets.inactive.weak_bg_fill) = (Color32::BLACK, Color32::BLACK);
+    visuals
+}
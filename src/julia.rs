@@ -0,0 +1,175 @@
+//! Julia set morphing.
+//!
+//! Moves the Julia parameter `c` along a closed path over time, either around
+//! the boundary of the main cardioid or along a path drawn with the mouse on the
+//! Mandelbrot view. In Julia mode an inset map of the Mandelbrot set marks the
+//! current parameter and lets it be dragged around.
+
+use num::Complex;
+
+use crate::accessibility::Buttons;
+use crate::{Mandelbrot, HEIGHT, WIDTH};
+
+/// Fraction of the path covered per frame at normal speed
+const MORPH_SPEED: f64 = 0.002;
+
+/// Closed path followed by the Julia parameter
+#[derive(Clone, Debug)]
+pub enum JuliaPath {
+    /// The boundary of the main cardioid
+    Cardioid,
+    /// Points drawn by the user, joined into a loop
+    Drawn(Vec<Complex<f64>>),
+}
+
+/// An in-progress Julia morph animation
+#[derive(Clone, Debug)]
+pub struct JuliaMorph {
+    path: JuliaPath,
+    /// Position along the path in `0.0..1.0`
+    phase: f64,
+}
+
+impl JuliaMorph {
+    pub fn new(path: JuliaPath) -> Self {
+        Self { path, phase: 0.0 }
+    }
+
+    /// Move along the path, `amount` is relative to the normal per-frame speed.
+    pub fn advance(&mut self, amount: f64) {
+        self.phase = (self.phase + amount * MORPH_SPEED).rem_euclid(1.0);
+    }
+
+    /// Jump to `phase`, the fraction of the path from its start.
+    pub fn seek(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// The Julia parameter at the current position.
+    pub fn position(&self) -> Complex<f64> {
+        match &self.path {
+            JuliaPath::Cardioid => {
+                let w = Complex::from_polar(1.0, 2.0 * std::f64::consts::PI * self.phase);
+                w / 2.0 - w * w / 4.0
+            }
+            JuliaPath::Drawn(points) => {
+                // Interpolate by arc length so the speed is even along the path
+                let segment = |i: usize| (points[i], points[(i + 1) % points.len()]);
+                let total: f64 = points
+                    .iter()
+                    .zip(points.iter().cycle().skip(1))
+                    .map(|(a, b)| (b - a).norm())
+                    .sum();
+                let mut remaining = self.phase * total;
+                for i in 0..points.len() {
+                    let (a, b) = segment(i);
+                    let length = (b - a).norm();
+                    if remaining <= length && length > 0.0 {
+                        return a + (b - a) * (remaining / length);
+                    }
+                    remaining -= length;
+                }
+                points[0]
+            }
+        }
+    }
+}
+
+/// Size of the inset Mandelbrot map shown in Julia mode
+const INSET_WIDTH: usize = 200;
+const INSET_HEIGHT: usize = 150;
+/// Distance of the inset from the bottom right corner of the window
+const INSET_MARGIN: usize = 8;
+
+/// Inset Mandelbrot map marking the current Julia parameter, which can be
+/// dragged with the mouse to change it.
+pub struct SeedMap {
+    view: Mandelbrot,
+    image: Vec<[u8; 4]>,
+    dragging: bool,
+}
+
+impl SeedMap {
+    pub fn new() -> Self {
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y, view.zoom) = (-0.75, 0.0, 1.0);
+
+        // The map never changes, so render it once at inset resolution
+        let image = (0..INSET_WIDTH * INSET_HEIGHT)
+            .map(|i| {
+                let (x, y) = view.inset_to_pixel((i % INSET_WIDTH) as f64, (i / INSET_WIDTH) as f64);
+                view.color(view.iterate(x, y).0)
+            })
+            .collect();
+
+        Self { view, image, dragging: false }
+    }
+
+    /// Track dragging of the marker, returning the new Julia parameter while
+    /// the marker is being dragged with the left button. `cursor` is in
+    /// frame buffer pixels.
+    pub fn update(&mut self, buttons: &Buttons, cursor: Option<(usize, usize)>) -> Option<Complex<f64>> {
+        let inset = cursor.and_then(|(x, y)| Self::to_inset(x, y));
+        if buttons.left_pressed {
+            self.dragging = inset.is_some();
+        }
+        if !buttons.left_held {
+            self.dragging = false;
+        }
+        if !self.dragging {
+            return None;
+        }
+
+        // Keep following the cursor when it leaves the inset mid-drag
+        let (x, y) = cursor?;
+        let ix = (x as f64 - Self::left() as f64).clamp(0.0, INSET_WIDTH as f64 - 1.0);
+        let iy = (y as f64 - Self::top() as f64).clamp(0.0, INSET_HEIGHT as f64 - 1.0);
+        let (px, py) = self.view.inset_to_pixel(ix, iy);
+        Some(self.view.pixel_to_complex(px, py))
+    }
+
+    /// Draw the inset with a cross marking `seed` to the frame buffer.
+    pub fn draw(&self, frame: &mut [u8], seed: Complex<f64>) {
+        let width = WIDTH as usize;
+        for (row, line) in self.image.chunks_exact(INSET_WIDTH).enumerate() {
+            let start = ((Self::top() + row) * width + Self::left()) * 4;
+            for (pixel, rgba) in frame[start..start + INSET_WIDTH * 4].chunks_exact_mut(4).zip(line) {
+                pixel.copy_from_slice(rgba);
+            }
+        }
+
+        let (px, py) = self.view.complex_to_pixel(seed);
+        let mx = (px * INSET_WIDTH as f64 / WIDTH as f64).round() as isize;
+        let my = (py * INSET_HEIGHT as f64 / HEIGHT as f64).round() as isize;
+        for d in -4..=4isize {
+            for (x, y) in [(mx + d, my), (mx, my + d)] {
+                if (0..INSET_WIDTH as isize).contains(&x) && (0..INSET_HEIGHT as isize).contains(&y) {
+                    let i = ((Self::top() + y as usize) * width + Self::left() + x as usize) * 4;
+                    frame[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+
+    fn left() -> usize {
+        WIDTH as usize - INSET_WIDTH - INSET_MARGIN
+    }
+
+    fn top() -> usize {
+        HEIGHT as usize - INSET_HEIGHT - INSET_MARGIN
+    }
+
+    /// Frame buffer pixel to inset pixel, `None` outside the inset.
+    fn to_inset(x: usize, y: usize) -> Option<(usize, usize)> {
+        let ix = x.checked_sub(Self::left()).filter(|&ix| ix < INSET_WIDTH)?;
+        let iy = y.checked_sub(Self::top()).filter(|&iy| iy < INSET_HEIGHT)?;
+        Some((ix, iy))
+    }
+}
+
+impl Mandelbrot {
+    /// Scale inset pixel coordinates up to the full window this view maps.
+    fn inset_to_pixel(&self, x: f64, y: f64) -> (f64, f64) {
+        (x * WIDTH as f64 / INSET_WIDTH as f64, y * HEIGHT as f64 / INSET_HEIGHT as f64)
+    }
+}
@@ -0,0 +1,146 @@
+//! Locations typed or pasted in to go to.
+//!
+//! The go-to dialog of the viewer (G) reads a center and optionally a zoom
+//! from text in any of these formats:
+//!
+//! - two or three numbers, the real and imaginary parts of the center and
+//!   then the zoom, separated by commas or spaces, in decimal or scientific
+//!   notation with as many digits as wanted: `-0.743643887, 0.131825904,
+//!   2.5e6`
+//! - a complex number with the zoom after it: `-0.75+0.1i 40`
+//! - the options of this program: `--center=-0.75,0.1 --zoom=40`
+//! - a Kalles Fraktaler location, lines `Re: <re>`, `Im: <im>` and
+//!   `Zoom: <zoom>` as in its `.kfr` files
+//! - a Fractint `center-mag=<re>/<im>/<magnification>` parameter, as in its
+//!   `.par` files
+//!
+//! The zooms of Kalles Fraktaler and Fractint are converted: at their zoom 1
+//! the view is 4 and 2 high, where it is 2.5 here. The center is kept as an
+//! `f64` like everywhere, so digits beyond its precision are rounded off,
+//! which the dialog points out when that moves the view by a pixel or more.
+
+use num::Complex;
+
+use crate::{arg_value, HEIGHT};
+
+/// Height of the view at zoom 1 in Kalles Fraktaler and in Fractint, which
+/// is 2.5 here
+const KALLES_FRAKTALER_HEIGHT: f64 = 4.0;
+const FRACTINT_HEIGHT: f64 = 2.0;
+const VIEW_HEIGHT: f64 = 2.5;
+/// Significant digits `f64` holds
+const F64_DIGITS: usize = 17;
+
+/// A place to go to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub center: Complex<f64>,
+    /// The zoom, if the location gives one
+    pub zoom: Option<f64>,
+    /// What was left out or rounded
+    pub note: Option<String>,
+}
+
+/// The location in `text`, at `current_zoom` unless it gives a zoom, or what
+/// is wrong with it.
+pub fn parse(text: &str, current_zoom: f64) -> Result<Location, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("enter a center, like -0.75, 0.1, and optionally a zoom after it".to_owned());
+    }
+    let (re, im, zoom, mut note) = if let Some(value) = text.split_whitespace().find_map(|word| word.strip_prefix("center-mag=")) {
+        fractint(value)?
+    } else if text.lines().any(|line| key_value(line, "re").is_some()) {
+        kalles_fraktaler(text)?
+    } else if text.starts_with("--") {
+        options(text)?
+    } else {
+        numbers(text)?
+    };
+
+    let center = Complex::new(number(&re, "the real part")?, number(&im, "the imaginary part")?);
+    let zoom = match zoom {
+        Some((value, scale)) => match number(&value, "the zoom")? * scale {
+            zoom if zoom > 0.0 && zoom.is_finite() => Some(zoom),
+            _ => return Err(format!("the zoom must be a positive number, not {value}")),
+        },
+        None => None,
+    };
+
+    // Digits past the precision of f64 move the center by up to an ulp
+    let digits = significant_digits(&re).max(significant_digits(&im));
+    let pixel = VIEW_HEIGHT / HEIGHT as f64 / zoom.unwrap_or(current_zoom);
+    let rounding = center.re.abs().max(center.im.abs()) * f64::EPSILON / pixel;
+    if digits > F64_DIGITS && rounding >= 1.0 {
+        let rounded = format!("the center has more digits than double precision holds, rounding it moves the view by up to {rounding:.0} pixels");
+        note = Some(note.map_or(rounded.clone(), |note| format!("{note}; {rounded}")));
+    }
+    Ok(Location { center, zoom, note })
+}
+
+/// The parts of a location: the real and the imaginary part of the center,
+/// the zoom and what scales it to a zoom here, and a note
+type Parts = (String, String, Option<(String, f64)>, Option<String>);
+
+fn fractint(value: &str) -> Result<Parts, String> {
+    let values: Vec<&str> = value.split('/').collect();
+    if values.len() < 2 {
+        return Err(format!("center-mag needs the real and imaginary parts of the center, separated by /, not {value}"));
+    }
+    let magnification = values.get(2).filter(|value| !value.is_empty());
+    let note = (values.len() > 3).then(|| "the stretch, rotation and skew of the center-mag are left out".to_owned());
+    Ok((values[0].to_owned(), values[1].to_owned(), magnification.map(|value| (value.to_string(), VIEW_HEIGHT / FRACTINT_HEIGHT)), note))
+}
+
+fn kalles_fraktaler(text: &str) -> Result<Parts, String> {
+    let find = |key: &str| text.lines().find_map(|line| key_value(line, key));
+    let re = find("re").ok_or("the Kalles Fraktaler location has no Re: line")?;
+    let im = find("im").ok_or("the Kalles Fraktaler location has no Im: line")?;
+    Ok((re, im, find("zoom").map(|zoom| (zoom, VIEW_HEIGHT / KALLES_FRAKTALER_HEIGHT)), None))
+}
+
+fn options(text: &str) -> Result<Parts, String> {
+    let args: Vec<String> = text.split_whitespace().map(str::to_owned).collect();
+    let center = arg_value(&args, "center").ok_or("the options have no --center")?;
+    let (re, im) = center.split_once(',').ok_or_else(|| format!("--center needs the real and imaginary parts separated by a comma, not {center}"))?;
+    Ok((re.to_owned(), im.to_owned(), arg_value(&args, "zoom").map(|zoom| (zoom, 1.0)), None))
+}
+
+fn numbers(text: &str) -> Result<Parts, String> {
+    let words: Vec<&str> = text.split(|c: char| c == ',' || c.is_whitespace()).filter(|word| !word.is_empty()).collect();
+    let zoom = |word: Option<&&str>| word.map(|zoom| (zoom.to_string(), 1.0));
+    match words.as_slice() {
+        // A complex number, with the imaginary unit after the imaginary part
+        [complex, rest @ ..] if complex.ends_with('i') && rest.len() <= 1 => {
+            // At the last sign that neither leads nor starts an exponent
+            let split = complex.char_indices().skip(1).filter(|&(i, c)| matches!(c, '+' | '-') && !complex[..i].ends_with(['e', 'E'])).last();
+            let (re, im) = split.map(|(i, _)| complex.split_at(i)).ok_or_else(|| format!("{complex} is not a complex number like -0.75+0.1i"))?;
+            Ok((re.to_owned(), im.trim_end_matches('i').trim_start_matches('+').to_owned(), zoom(rest.first()), None))
+        }
+        [re, im] | [re, im, _] => Ok((re.to_string(), im.to_string(), zoom(words.get(2)), None)),
+        _ => Err(format!("expected the real and imaginary parts of the center and optionally the zoom, not {} numbers", words.len())),
+    }
+}
+
+/// The value of a `Key: value` line, `key` in any case.
+fn key_value(line: &str, key: &str) -> Option<String> {
+    let (name, value) = line.split_once(':')?;
+    name.trim().eq_ignore_ascii_case(key).then(|| value.trim().to_owned())
+}
+
+fn number(text: &str, what: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(format!("{text} is not a number, for {what}")),
+    }
+}
+
+/// The digits of the decimal number `text` from its first nonzero one, up to
+/// the exponent.
+fn significant_digits(text: &str) -> usize {
+    let mantissa = text.split(['e', 'E']).next().unwrap_or_default();
+    // Zeros ending a fraction are exact
+    let mantissa = if mantissa.contains('.') { mantissa.trim_end_matches('0') } else { mantissa };
+    let digits = mantissa.chars().filter(char::is_ascii_digit);
+    digits.skip_while(|&digit| digit == '0').count()
+}
@@ -0,0 +1,112 @@
+//! Color grading with 3D lookup tables.
+//!
+//! `--lut=<file.cube>` loads a lookup table in the `.cube` format of Adobe
+//! and DaVinci Resolve and grades every finished frame with it, after the
+//! effects of [`crate::effects`], so fractal footage can take on the look of
+//! the project it goes into. Each color is looked up by trilinear
+//! interpolation between the entries of the table around it.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use pixels::Error;
+use rayon::prelude::*;
+
+/// Largest table the format allows along each axis
+const MAX_SIZE: usize = 256;
+
+/// A 3D lookup table from a `.cube` file
+#[derive(Clone)]
+pub struct Lut {
+    path: PathBuf,
+    size: usize,
+    /// Input colors mapped to the first and the last entries along each axis
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// Output colors, red changing fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+}
+
+// Like `TrapImage`, the table would swamp the debug output of the view
+impl fmt::Debug for Lut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Lut({} {}^3)", self.path.display(), self.size)
+    }
+}
+
+impl Lut {
+    /// Load a `.cube` file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let error = |e: String| Error::UserDefined(format!("{}: {e}", path.display()).into());
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        Self::parse(path, &text).map_err(error)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The table in the text of the `.cube` file at `path`.
+    fn parse(path: &Path, text: &str) -> Result<Self, String> {
+        let triple = |values: &[&str]| -> Option<[f32; 3]> {
+            match values {
+                [r, g, b] => Some([r.parse().ok()?, g.parse().ok()?, b.parse().ok()?]),
+                _ => None,
+            }
+        };
+        let (mut size, mut domain_min, mut domain_max, mut table) = (None, [0.0; 3], [1.0; 3], Vec::new());
+        for (number, line) in text.lines().enumerate() {
+            let invalid = || format!("invalid line {}: {line}", number + 1);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                [comment, ..] if comment.starts_with('#') => {}
+                ["TITLE", ..] => {}
+                ["LUT_1D_SIZE", ..] => return Err("1D lookup tables are not supported, only 3D ones".to_owned()),
+                ["LUT_3D_SIZE", n] => size = Some(n.parse().ok().filter(|n| (2..=MAX_SIZE).contains(n)).ok_or_else(invalid)?),
+                ["DOMAIN_MIN", values @ ..] => domain_min = triple(values).ok_or_else(invalid)?,
+                ["DOMAIN_MAX", values @ ..] => domain_max = triple(values).ok_or_else(invalid)?,
+                values => table.push(triple(values).ok_or_else(invalid)?),
+            }
+        }
+        let size = size.ok_or("no LUT_3D_SIZE")?;
+        if table.len() != size * size * size {
+            return Err(format!("{} entries for a size of {size}, not {}", table.len(), size * size * size));
+        }
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            return Err("empty domain".to_owned());
+        }
+        Ok(Self { path: path.to_owned(), size, domain_min, domain_max, table })
+    }
+
+    /// Grade the colors of an RGBA `frame`.
+    pub fn apply(&self, frame: &mut [u8]) {
+        frame.par_chunks_exact_mut(4).for_each(|pixel| {
+            let rgb = self.lookup([pixel[0], pixel[1], pixel[2]].map(|channel| channel as f32 / 255.0));
+            for (channel, value) in pixel.iter_mut().zip(rgb) {
+                *channel = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        });
+    }
+
+    /// The graded color of `rgb`, interpolated between the eight entries
+    /// around it.
+    fn lookup(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        let position: [f32; 3] = std::array::from_fn(|i| ((rgb[i] - self.domain_min[i]) / (self.domain_max[i] - self.domain_min[i]) * last).clamp(0.0, last));
+        let low = position.map(|p| (p as usize).min(self.size - 2));
+        let t: [f32; 3] = std::array::from_fn(|i| position[i] - low[i] as f32);
+        let entry = |r: usize, g: usize, b: usize| self.table[(b * self.size + g) * self.size + r];
+
+        let mut graded = [0.0; 3];
+        for corner in 0..8 {
+            let offset = [corner & 1, corner >> 1 & 1, corner >> 2 & 1];
+            let weight: f32 = (0..3).map(|i| if offset[i] == 1 { t[i] } else { 1.0 - t[i] }).product();
+            let color = entry(low[0] + offset[0], low[1] + offset[1], low[2] + offset[2]);
+            for (graded, value) in graded.iter_mut().zip(color) {
+                *graded += weight * value;
+            }
+        }
+        graded
+    }
+}
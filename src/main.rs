@@ -1,86 +1,546 @@
 #![deny(clippy::all)]
-#![forbid(unsafe_code)]
+// Allowed only for the kernels of `simd`, the calls of `opencl` into the
+// OpenCL library and the file mapping of `export::map_checkpoint`
+#![deny(unsafe_code)]
 
-use pixels::{Error, Pixels, SurfaceTexture};
-use winit::dpi::LogicalSize;
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::EventLoop;
-use winit::keyboard::KeyCode;
-use winit::window::WindowBuilder;
-use winit_input_helper::WinitInputHelper;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use mandelbrot_core::Bailout;
+use pixels::Error;
+use rayon::prelude::*;
+use winit::event_loop::{ControlFlow, EventLoop};
+
+mod accessibility;
+mod adaptive;
+mod animation;
+#[cfg(feature = "audio")]
+mod audio;
+mod batch;
+mod bench;
+mod bifurcation;
+mod boundary;
+mod buddhabrot;
+mod clip;
+mod clipboard;
+mod coloring;
+mod control;
+mod data;
+mod deep;
+mod depth;
+mod eco;
+mod effects;
+mod export;
+mod fixed;
+mod floatexp;
+#[cfg(feature = "editor")]
+mod goto;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "editor")]
+mod gui;
+mod julia;
+#[cfg(feature = "editor")]
+mod location;
+mod lut;
+#[cfg(feature = "midi")]
+mod midi;
+mod minibrot;
+mod minimap;
+mod mipmap;
+mod multibrot;
+// Calls into the OpenCL library, loaded at run time
+#[cfg(feature = "opencl")]
+#[allow(unsafe_code)]
+mod opencl;
+#[cfg(feature = "osc")]
+mod osc;
+mod overlay;
+mod palette;
+mod presets;
+mod progress;
+mod progressive;
+mod projection;
+mod queue;
+mod rays;
+mod recording;
+#[cfg(feature = "remote")]
+mod remote;
+mod session;
+// Dispatches to `#[target_feature]` kernels
+#[allow(unsafe_code)]
+mod simd;
+mod skew;
+mod stream;
+mod surface;
+mod svg;
+mod telemetry;
+mod terrain;
+#[cfg(feature = "editor")]
+mod timeline;
+mod trap;
+mod verify;
+mod video;
+mod viewer;
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 600;
 const MAX_ITER: u32 = 1000;  // More iterations = smoother color gradients and more detail
 const ZOOM_SPEED: f64 = 1.01;
+/// Fraction of the view height panned per frame while a pan key is held
+const PAN_SPEED: f64 = 0.02;
+/// Zoom factor of a single PageUp/PageDown step
+const ZOOM_STEP: f64 = 2.0;
+/// Radians rotated per frame while Q or E is held
+const ROTATE_SPEED: f64 = 0.02;
+/// Change of angle and of log radius per pixel of the exponential map, so the
+/// rows cover one full turn and pixels are square
+const EXPONENTIAL_MAP_STEP: f64 = 2.0 * std::f64::consts::PI / HEIGHT as f64;
+
+/// Per-column and per-row coordinates of a view, see
+/// `Mandelbrot::coordinate_tables`
+struct CoordinateTables {
+    xs: Vec<num::Complex<f64>>,
+    ys: Vec<num::Complex<f64>>,
+    /// Reference orbit of deep views, whose entries are offsets from the
+    /// view center scaled by the zoom instead
+    reference: Option<deep::Reference>,
+    /// Mipmap of views zoomed out past the whole set
+    mipmap: Option<mipmap::Mipmap>,
+}
 
 /// Representation of the application state
+#[derive(Clone, Debug)]
 struct Mandelbrot {
     center_x: f64,
     center_y: f64,
-    zoom: f64
+    zoom: f64,
+    /// Rotation of the view in radians
+    rotation: f64,
+    /// Skew and stretch applied to pixel offsets before the rotation
+    skew: skew::Matrix,
+    /// Maps the view plane to the points that are iterated
+    projection: projection::Projection,
+    /// Show the view in log-polar coordinates around the zoom target, with
+    /// the distance growing exponentially to the right and the angle downwards
+    exponential_map: bool,
+    /// Exponent `d` of the iteration `z -> z^d + c`, 2 for the Mandelbrot
+    /// set, see `multibrot`
+    exponent: f64,
+    /// Escape radius, larger radii give more accurate smooth coloring
+    bailout_radius: f64,
+    bailout: Bailout,
+    palette_offset: f64,
+    palette: palette::Palette,
+    /// Run the colormap palettes from their last color
+    palette_reversed: bool,
+    /// Palette the colors are blended from while tweening to `palette`, and
+    /// how far towards `palette`, from 0 to 1
+    palette_blend: Option<(palette::Palette, f64)>,
+    /// Percent of the escaped pixels at either end of their escape counts
+    /// left out of the range the palette spans, with `--auto-range`
+    auto_range: Option<f64>,
+    /// Escape counts the palette spans once, from the last frame with
+    /// `auto_range`, instead of running on over every count
+    color_range: Option<(f64, f64)>,
+    /// Frames over which videos and the viewer average the color range of
+    /// `auto_range`, so it doesn't flicker from frame to frame
+    range_smoothing: u32,
+    coloring: coloring::Coloring,
+    /// Distance from the axes over which Pickover stalks fade out, and how
+    /// strongly they cover the escape time colors, from 0 to 1
+    stalk_width: f64,
+    stalk_blend: f64,
+    /// Image for the image trap coloring, and how strongly it covers the
+    /// escape time colors, from 0 to 1
+    trap_image: Option<Arc<trap::TrapImage>>,
+    trap_blend: f64,
+    /// How strongly the angle coloring covers the escape time colors, from 0
+    /// to 1
+    angle_blend: f64,
+    /// Draw iso-iteration contour lines every `contour_interval` escape counts
+    contours: bool,
+    contour_interval: u32,
+    /// Most iterations the CPU gives the pixels near the boundary, rendering
+    /// in passes, see `adaptive`
+    adaptive_iterations: Option<u32>,
+    /// Post-processing of the finished frames, in order
+    effects: Vec<effects::Stage>,
+    /// Color grading after the effects
+    lut: Option<Arc<lut::Lut>>,
+    /// Julia parameter, `None` renders the Mandelbrot set
+    julia: Option<num::Complex<f64>>,
+    /// Mandelbrot view to return to when leaving Julia mode
+    parameter_view: (f64, f64, f64),
+    morph: Option<julia::JuliaMorph>,
+    /// Path drawn with the mouse for the Julia morph to follow
+    julia_path: Vec<num::Complex<f64>>
 }
 
 fn main() -> Result<(), Error> {
+    let args = presets::expand(std::env::args().skip(1).collect())?;
+    let mandelbrot = view_from_args(&args)?;
+
+    // Commands run headless instead of opening the viewer
+    if let Some(command) = args.first().filter(|arg| !arg.starts_with("--")) {
+        return match command.as_str() {
+            "queue" => queue::queue_from_args(&args),
+            command => run_command(command, &mandelbrot, &args),
+        };
+    }
+
+    // The viewer continues the last session unless started afresh, in high
+    // contrast on its palette unless one is given
+    let accessibility = accessibility::Accessibility::from_args(&args);
+    let palette_given = args.iter().any(|arg| arg.starts_with("--palette=") || arg.starts_with("--palette-image="));
+    let args = match args.iter().any(|arg| arg == "--fresh") {
+        true => args,
+        false => session::resume(args),
+    };
+    let mut mandelbrot = view_from_args(&args)?;
+    if accessibility.high_contrast && !palette_given {
+        mandelbrot.palette = palette::Palette::HighContrast;
+    }
+    let geometry = viewer::Geometry {
+        size: match arg_value(&args, "window-size") {
+            Some(value) => Some(export::parse_size(&value).ok_or_else(|| invalid_arg("window-size", &value))?),
+            None => None,
+        },
+        position: match arg_value(&args, "window-position") {
+            Some(value) => {
+                let position = value.split_once(',').and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)));
+                Some(position.ok_or_else(|| invalid_arg("window-position", &value))?)
+            }
+            None => None,
+        },
+        fullscreen: args.iter().any(|arg| arg == "--fullscreen"),
+        monitor: match arg_value(&args, "monitor") {
+            Some(value) => Some(value.parse().ok().filter(|&n| n >= 1).ok_or_else(|| invalid_arg("monitor", &value))?),
+            None => None,
+        },
+    };
+
     let event_loop = EventLoop::new().unwrap();
-    let mut input = WinitInputHelper::new();
-    let window = {
-        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-        WindowBuilder::new()
-            .with_title("Mandelbrot")
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .build(&event_loop)
-            .unwrap()
+
+    // The Möbius transform joins the projections cycled through with I
+    #[cfg_attr(not(feature = "audio"), allow(unused_mut))]
+    let mut context = viewer::FrameContext {
+        mobius: match arg_value(&args, "mobius") {
+            Some(value) => Some(projection::Projection::from_mobius_arg(&value).ok_or_else(|| invalid_arg("mobius", &value))?),
+            None => None,
+        },
+        rays: match arg_value(&args, "rays") {
+            Some(value) => {
+                let angles: Option<Vec<_>> = value.split(',').map(rays::Angle::from_arg).collect();
+                angles.ok_or_else(|| invalid_arg("rays", &value))?.into_iter().map(rays::trace).collect()
+            }
+            None => Vec::new(),
+        },
+        #[cfg(feature = "audio")]
+        audio: None,
+        #[cfg(feature = "midi")]
+        midi: midi::MidiLevels::default(),
+        surface: surface::Surface::from_args(&args)?,
+        accessibility,
+        tween_easing: animation::tween_easing(&args)?,
+        orbit: animation::Orbit::from_args(&args)?.0,
+        sweep: animation::Sweep::from_args(&args)?.0,
+        eco: eco::Eco::from_args(&args)?,
+        scan: match arg_value(&args, "scan") {
+            Some(value) => progressive::Scan::from_name(&value).ok_or_else(|| invalid_arg("scan", &value))?,
+            None => progressive::Scan::Tiles,
+        },
+        depth: args.iter().any(|arg| arg == "--depth"),
+        minimap: args.iter().any(|arg| arg == "--minimap"),
+        bifurcation: args.iter().any(|arg| arg == "--bifurcation"),
+        #[cfg(feature = "opencl")]
+        opencl: args.iter().any(|arg| arg == "--opencl"),
     };
 
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width*3, window_size.height*4, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture)?
+    // `--audio` or `--audio=<zoom|palette|julia>` enables the audio-reactive mode
+    #[cfg(feature = "audio")]
+    let mut audio = match args.iter().find(|arg| arg.starts_with("--audio")) {
+        Some(arg) => {
+            let name = arg.strip_prefix("--audio=").unwrap_or("zoom");
+            let target = audio::AudioTarget::from_name(name).ok_or_else(|| invalid_arg("audio", name))?;
+            Some(audio::AudioReactor::new(target).map_err(Error::UserDefined)?)
+        }
+        None => None,
     };
-    let mut mandelbrot = Mandelbrot::new();
+
+    // `--midi=<mapping.toml>` sets parameters from the knobs and faders of a
+    // MIDI controller
+    #[cfg(feature = "midi")]
+    let midi = match arg_value(&args, "midi") {
+        Some(path) => {
+            let port = arg_value(&args, "midi-port");
+            Some(midi::MidiControls::connect(Path::new(&path), port.as_deref(), event_loop.create_proxy()).map_err(Error::UserDefined)?)
+        }
+        None => None,
+    };
+
+    // Every window views the fractal independently, N opens another one
+    let mut viewers = HashMap::new();
+    let animation = match (arg_value(&args, "animation"), arg_value(&args, "replay")) {
+        (Some(path), None) => Some(animation::Animation::load(Path::new(&path), &mandelbrot)?),
+        (None, Some(path)) => Some(recording::load(Path::new(&path))?),
+        (Some(_), Some(path)) => return Err(invalid_arg("replay", &path)),
+        (None, None) => None,
+    };
+    let recorder = match arg_value(&args, "record") {
+        Some(path) => Some(recording::Recorder::create(Path::new(&path))?),
+        None => None,
+    };
+    let stream = stream::Stream::from_args(&args)?;
+    let telemetry = telemetry::Telemetry::from_args(&args)?;
+    let mut first = viewer::Viewer::open(&event_loop, mandelbrot, geometry, &context)?;
+    if let Some(animation) = animation {
+        first.play(animation);
+    }
+    if let Some(stream) = stream {
+        first.stream_to(stream);
+    }
+    if let Some(telemetry) = telemetry {
+        first.log_to(telemetry);
+    }
+    if let Some(recorder) = recorder {
+        first.record_to(recorder);
+    }
+    // Commands of the control socket, the remote control API and OSC messages
+    // go to the first window, or to another one once it is closed
+    let (requests, control) = std::sync::mpsc::channel();
+    control::listen(&args, requests.clone(), event_loop.create_proxy())?;
+    #[cfg(feature = "osc")]
+    osc::listen(&args, requests.clone(), event_loop.create_proxy())?;
+    #[cfg(feature = "remote")]
+    remote::listen(&args, requests.clone(), event_loop.create_proxy())?;
+    let mut controlled = first.window.id();
+    viewers.insert(first.window.id(), first);
 
     let res = event_loop.run(|event, elwt| {
-        // Draw the current frame
-        if let Event::WindowEvent {
-            event: WindowEvent::RedrawRequested,
-            ..
-        } = event
-        {
-            mandelbrot.draw(pixels.frame_mut());
-            if let Err(_) = pixels.render() {
-                elwt.exit();
-                return;
+        // Analyse the audio once per frame for all windows
+        #[cfg(feature = "audio")]
+        if let winit::event::Event::AboutToWait = event {
+            context.audio = audio.as_mut().map(|audio| (audio.target, audio.levels()));
+        }
+        #[cfg(feature = "midi")]
+        if let winit::event::Event::AboutToWait = event {
+            context.midi = midi.as_ref().map(midi::MidiControls::levels).unwrap_or_default();
+        }
+        if let winit::event::Event::AboutToWait = event {
+            context.eco.update();
+        }
+
+        if let winit::event::Event::UserEvent(()) = event {
+            if !viewers.contains_key(&controlled) {
+                if let Some(&id) = viewers.keys().next() {
+                    controlled = id;
+                }
+            }
+            for request in control.try_iter() {
+                match viewers.get_mut(&controlled) {
+                    Some(viewer) => viewer.control(request),
+                    None => {
+                        let _ = request.reply.send(control::failed("no window open"));
+                    }
+                }
             }
         }
 
-        // Handle input events
-        if input.update(&event) {
-            // Close events
-            if input.key_pressed(KeyCode::Escape) || input.close_requested() {
-                elwt.exit();
-                return;
+        let mut opened = Vec::new();
+        let mut closed = None;
+        viewers.retain(|_, viewer| match viewer.handle(&event, &context) {
+            viewer::Action::Continue => true,
+            viewer::Action::Close => {
+                closed = Some((viewer.mandelbrot().clone(), viewer.geometry()));
+                false
+            }
+            viewer::Action::Open(mandelbrot) => {
+                opened.push(*mandelbrot);
+                true
             }
+        });
+        for mandelbrot in opened {
+            match viewer::Viewer::open(elwt, mandelbrot, viewer::Geometry::default(), &context) {
+                Ok(viewer) => {
+                    viewers.insert(viewer.window.id(), viewer);
+                }
+                Err(err) => eprintln!("failed to open window: {}", error_message(&err)),
+            }
+        }
 
-            // Automatically zoom in by 10% each frame
-            mandelbrot.zoom *= ZOOM_SPEED;
+        // Wake up for the frames eco mode held back
+        if let winit::event::Event::AboutToWait = event {
+            match viewers.values().filter_map(|viewer| viewer.redraw_due(&context.eco)).min() {
+                Some(due) => elwt.set_control_flow(ControlFlow::WaitUntil(due)),
+                None => elwt.set_control_flow(ControlFlow::Wait),
+            }
+        }
 
-            // Resize the window
-            if let Some(size) = input.window_resized() {
-                if let Err(_) = pixels.resize_surface(size.width, size.height) {
-                    elwt.exit();
-                    return;
+        // Quitting with the last window, whose view is the session to resume
+        if viewers.is_empty() {
+            if let Some((mandelbrot, geometry)) = closed {
+                if let Err(e) = session::save(&mandelbrot, geometry) {
+                    eprintln!("failed to save the session: {}", error_message(&e));
                 }
             }
-
-            // Request a redraw
-            window.request_redraw();
+            elwt.exit();
         }
     });
     res.map_err(|e| Error::UserDefined(Box::new(e)))
 }
 
+/// Set up the initial view from command line arguments.
+fn view_from_args(args: &[String]) -> Result<Mandelbrot, Error> {
+    let mut mandelbrot = Mandelbrot::new();
+    if let Some(value) = arg_value(args, "center") {
+        let c = parse_complex_pair(&value).ok_or_else(|| invalid_arg("center", &value))?;
+        (mandelbrot.center_x, mandelbrot.center_y) = (c.re, c.im);
+    }
+    if let Some(value) = arg_value(args, "zoom") {
+        mandelbrot.zoom = parse_positive(&value).ok_or_else(|| invalid_arg("zoom", &value))?.max(mipmap::MIN_ZOOM);
+    }
+    if let Some(value) = arg_value(args, "julia") {
+        mandelbrot.julia = Some(parse_complex_pair(&value).ok_or_else(|| invalid_arg("julia", &value))?);
+    }
+    if let Some(value) = arg_value(args, "exponent") {
+        let exponent = value.parse().ok().filter(|&d: &f64| d > 1.0 && d.is_finite());
+        mandelbrot.exponent = exponent.ok_or_else(|| invalid_arg("exponent", &value))?;
+    }
+    if let Some(value) = arg_value(args, "bailout") {
+        let radius = parse_positive(&value).filter(|&r| r <= deep::MAX_BAILOUT);
+        mandelbrot.bailout_radius = radius.ok_or_else(|| invalid_arg("bailout", &value))?;
+    }
+    if let Some(value) = arg_value(args, "rotation") {
+        let degrees: f64 = value.parse().map_err(|_| invalid_arg("rotation", &value))?;
+        mandelbrot.rotation = degrees.to_radians();
+    }
+    if let Some(value) = arg_value(args, "skew") {
+        mandelbrot.skew = skew::from_arg(&value).ok_or_else(|| invalid_arg("skew", &value))?;
+    }
+    if let Some(value) = arg_value(args, "bailout-norm") {
+        mandelbrot.bailout = Bailout::from_name(&value).ok_or_else(|| invalid_arg("bailout-norm", &value))?;
+    }
+    if let Some(value) = arg_value(args, "palette-offset") {
+        let offset = value.parse().ok().filter(|&v: &f64| v >= 0.0 && v.is_finite());
+        mandelbrot.palette_offset = offset.ok_or_else(|| invalid_arg("palette-offset", &value))?;
+    }
+    if let Some(value) = arg_value(args, "palette") {
+        mandelbrot.palette = palette::Palette::from_name(&value).ok_or_else(|| invalid_arg("palette", &value))?;
+    }
+    if let Some(value) = arg_value(args, "palette-image") {
+        mandelbrot.palette = palette::Palette::from_image(Path::new(&value))?;
+    }
+    mandelbrot.palette_reversed = args.iter().any(|a| a == "--palette-reversed");
+    if let Some(arg) = args.iter().find(|arg| *arg == "--auto-range" || arg.starts_with("--auto-range=")) {
+        let percent = match arg.strip_prefix("--auto-range=") {
+            Some(value) => value.parse().ok().filter(|percent: &f64| (0.0..50.0).contains(percent)).ok_or_else(|| invalid_arg("auto-range", value))?,
+            None => 0.0,
+        };
+        mandelbrot.auto_range = Some(percent);
+    }
+    if let Some(value) = arg_value(args, "range-smoothing") {
+        mandelbrot.range_smoothing = value.parse().map_err(|_| invalid_arg("range-smoothing", &value))?;
+    }
+    if let Some(value) = arg_value(args, "coloring") {
+        mandelbrot.coloring = coloring::Coloring::from_name(&value).ok_or_else(|| invalid_arg("coloring", &value))?;
+    }
+    if let Some(value) = arg_value(args, "stalk-width") {
+        mandelbrot.stalk_width = parse_positive(&value).ok_or_else(|| invalid_arg("stalk-width", &value))?;
+    }
+    if let Some(value) = arg_value(args, "stalk-blend") {
+        let blend = value.parse().ok().filter(|blend: &f64| (0.0..=1.0).contains(blend));
+        mandelbrot.stalk_blend = blend.ok_or_else(|| invalid_arg("stalk-blend", &value))?;
+    }
+    // An image trap is the coloring unless another one is asked for
+    if let Some(value) = arg_value(args, "trap-image") {
+        let mut image = trap::TrapImage::load(Path::new(&value))?;
+        if let Some(value) = arg_value(args, "trap-center") {
+            image.center = parse_complex_pair(&value).ok_or_else(|| invalid_arg("trap-center", &value))?;
+        }
+        if let Some(value) = arg_value(args, "trap-size") {
+            image.size = parse_positive(&value).ok_or_else(|| invalid_arg("trap-size", &value))?;
+        }
+        mandelbrot.trap_image = Some(Arc::new(image));
+        if arg_value(args, "coloring").is_none() {
+            mandelbrot.coloring = coloring::Coloring::ImageTrap;
+        }
+    }
+    if let Some(value) = arg_value(args, "trap-blend") {
+        let blend = value.parse().ok().filter(|blend: &f64| (0.0..=1.0).contains(blend));
+        mandelbrot.trap_blend = blend.ok_or_else(|| invalid_arg("trap-blend", &value))?;
+    }
+    if let Some(value) = arg_value(args, "angle-blend") {
+        let blend = value.parse().ok().filter(|blend: &f64| (0.0..=1.0).contains(blend));
+        mandelbrot.angle_blend = blend.ok_or_else(|| invalid_arg("angle-blend", &value))?;
+    }
+    if let Some(value) = arg_value(args, "adaptive-iterations") {
+        let limit = value.parse().ok().filter(|&limit| limit > 0);
+        mandelbrot.adaptive_iterations = Some(limit.ok_or_else(|| invalid_arg("adaptive-iterations", &value))?);
+    }
+    if let Some(value) = arg_value(args, "contours") {
+        let interval = value.parse().ok().filter(|&interval| interval > 0);
+        mandelbrot.contour_interval = interval.ok_or_else(|| invalid_arg("contours", &value))?;
+        mandelbrot.contours = true;
+    }
+    if let Some(value) = arg_value(args, "effects") {
+        mandelbrot.effects = effects::from_arg(&value).ok_or_else(|| invalid_arg("effects", &value))?;
+    }
+    if let Some(value) = arg_value(args, "lut") {
+        mandelbrot.lut = Some(Arc::new(lut::Lut::load(Path::new(&value))?));
+    }
+    Ok(mandelbrot)
+}
+
+/// Parse a `re,im` pair such as `-0.75,0.1`.
+fn parse_complex_pair(value: &str) -> Option<num::Complex<f64>> {
+    let (re, im) = value.split_once(',')?;
+    Some(num::Complex::new(re.trim().parse().ok()?, im.trim().parse().ok()?))
+}
+
+fn parse_positive(value: &str) -> Option<f64> {
+    value.parse().ok().filter(|&v: &f64| v > 0.0 && v.is_finite())
+}
+
+/// Value of a `--name=value` argument.
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("--{name}=");
+    args.iter().find_map(|arg| arg.strip_prefix(&prefix).map(str::to_owned))
+}
+
+fn invalid_arg(name: &str, value: &str) -> Error {
+    Error::UserDefined(format!("invalid value `{value}` for --{name}").into())
+}
+
+/// Run the headless command `command` on the view `mandelbrot` with the
+/// command line `args`, which start with it.
+fn run_command(command: &str, mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    match command {
+        "batch" => batch::batch_from_args(args),
+        "bench" => bench::bench_from_args(args),
+        "buddhabrot" => buddhabrot::buddhabrot_from_args(mandelbrot, args),
+        "clip" => clip::clip_from_args(mandelbrot, args),
+        "data" => data::data_from_args(mandelbrot, args, progress::Mode::from_args(args)),
+        "minibrot" => minibrot::minibrot_from_args(mandelbrot, args),
+        "presets" => presets::presets_from_args(mandelbrot, args),
+        "render" => export::render_from_args(mandelbrot, args, progress::Mode::from_args(args)),
+        "svg" => svg::svg_from_args(mandelbrot, args),
+        "verify" => verify::verify_from_args(mandelbrot, args),
+        "zoom-video" => video::zoom_video_from_args(mandelbrot, args),
+        _ => Err(Error::UserDefined(format!("unknown command `{command}`").into())),
+    }
+}
+
+/// Message of an error for printing, as `pixels::Error` only names the
+/// variant for errors of our own.
+fn error_message(error: &Error) -> String {
+    match error {
+        Error::UserDefined(inner) => inner.to_string(),
+        error => error.to_string(),
+    }
+}
+
 impl Mandelbrot {
     /// Create a new mandelbrot instance.
     fn new() -> Self {
@@ -93,55 +553,1659 @@ impl Mandelbrot {
             // Mini Mandelbrot: (-1.77, 0.0)
             center_x: 0.0,
             center_y: 1.0,
-            zoom: 1.0
+            zoom: 1.0,
+            rotation: 0.0,
+            skew: skew::IDENTITY,
+            projection: projection::Projection::Plane,
+            exponential_map: false,
+            exponent: multibrot::QUADRATIC,
+            bailout_radius: 2.0,
+            bailout: Bailout::Modulus,
+            palette_offset: 0.0,
+            palette: palette::Palette::Classic,
+            palette_reversed: false,
+            palette_blend: None,
+            auto_range: None,
+            color_range: None,
+            range_smoothing: 12,
+            coloring: coloring::Coloring::EscapeTime,
+            stalk_width: 0.02,
+            stalk_blend: 1.0,
+            trap_image: None,
+            trap_blend: 1.0,
+            angle_blend: 1.0,
+            contours: false,
+            contour_interval: 10,
+            adaptive_iterations: None,
+            effects: Vec::new(),
+            lut: None,
+            julia: None,
+            parameter_view: (0.0, 0.0, 1.0),
+            morph: None,
+            julia_path: Vec::new()
         }
     }
 
-    fn mandelbrot(&self, x: u32, y: u32) -> u32 {
-        let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
+    /// Advance the animations by one frame.
+    fn step(&mut self, zoom_speed: f64) {
+        self.zoom *= zoom_speed;
+        if let Some(morph) = &mut self.morph {
+            morph.advance(1.0);
+            self.julia = Some(morph.position());
+        }
+    }
+
+    /// Move the view center by `dx` and `dy` view heights, so the on-screen
+    /// step is the same at every zoom level.
+    fn pan(&mut self, dx: f64, dy: f64) {
         let zoom_width = 2.5 / self.zoom;
-        
+        let offset = self.transform(dx * zoom_width, dy * zoom_width);
+        self.center_x += offset.re;
+        self.center_y += offset.im;
+    }
+
+    /// Zoom so the rectangle spanned by the pixel corners `a` and `b` fills the
+    /// window, growing it to the window aspect ratio around its center.
+    fn zoom_to_rect(&mut self, a: (f64, f64), b: (f64, f64)) {
+        let width = (a.0 - b.0).abs();
+        let height = (a.1 - b.1).abs();
+        // Ignore clicks without a meaningful drag
+        if width < 4.0 && height < 4.0 {
+            return;
+        }
+
+        let center = self.pixel_to_complex((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        let scale = (width / WIDTH as f64).max(height / HEIGHT as f64);
+        self.center_x = center.re;
+        self.center_y = center.im;
+        self.zoom /= scale;
+    }
+
+    /// Switch between the Mandelbrot set and the Julia set for `c`.
+    fn toggle_julia(&mut self, c: num::Complex<f64>) {
+        match self.julia {
+            Some(_) => {
+                self.julia = None;
+                (self.center_x, self.center_y, self.zoom) = self.parameter_view;
+            }
+            None => {
+                self.julia = Some(c);
+                self.parameter_view = (self.center_x, self.center_y, self.zoom);
+                (self.center_x, self.center_y, self.zoom) = (0.0, 0.0, 1.0);
+            }
+        }
+    }
+
+    /// The view of a window of its own on the Julia set for the point under
+    /// pixel `x`, `y` of this Mandelbrot view, with everything else as here.
+    fn julia_window(&self, x: f64, y: f64) -> Mandelbrot {
+        let mut view = Mandelbrot { morph: None, julia_path: Vec::new(), ..self.clone() };
+        view.toggle_julia(self.projection.apply(self.pixel_to_complex(x, y)));
+        view
+    }
+
+    /// Start morphing the Julia set along the drawn path, or around the main
+    /// cardioid if no path was drawn. Stops a running morph.
+    fn toggle_morph(&mut self) {
+        if self.morph.take().is_some() {
+            return;
+        }
+        let path = if self.julia_path.len() >= 2 {
+            julia::JuliaPath::Drawn(self.julia_path.clone())
+        } else {
+            julia::JuliaPath::Cardioid
+        };
+        let morph = julia::JuliaMorph::new(path);
+        if self.julia.is_none() {
+            self.toggle_julia(morph.position());
+        }
+        self.morph = Some(morph);
+    }
+
+    /// Apply the audio levels of this frame to the modulated parameter,
+    /// returning the zoom speed to use.
+    #[cfg(feature = "audio")]
+    fn apply_audio(&mut self, target: audio::AudioTarget, levels: audio::AudioLevels) -> f64 {
+        match target {
+            audio::AudioTarget::ZoomSpeed => ZOOM_SPEED.powf(levels.energy as f64 * 2.0),
+            audio::AudioTarget::PaletteOffset => {
+                // Jump on beats and decay back towards the base gradient
+                let kick = if levels.beat { levels.energy as f64 * 20.0 } else { 0.0 };
+                self.palette_offset = (self.palette_offset * 0.9).max(kick);
+                ZOOM_SPEED
+            }
+            audio::AudioTarget::JuliaParameter => {
+                // Louder than average speeds the morph up, quieter slows it down
+                if let Some(morph) = &mut self.morph {
+                    morph.advance(levels.energy as f64 - 1.0);
+                }
+                ZOOM_SPEED
+            }
+        }
+    }
+
+    /// Set the parameters the MIDI controllers moved, returning the zoom speed.
+    #[cfg(feature = "midi")]
+    fn apply_midi(&mut self, levels: midi::MidiLevels, zoom_speed: f64) -> f64 {
+        if let Some(offset) = levels.palette_offset {
+            self.palette_offset = offset;
+        }
+        if let Some(julia) = &mut self.julia {
+            if levels.julia_re.is_some() || levels.julia_im.is_some() {
+                self.morph = None;
+            }
+            julia.re = levels.julia_re.unwrap_or(julia.re);
+            julia.im = levels.julia_im.unwrap_or(julia.im);
+        }
+        levels.zoom_speed.unwrap_or(zoom_speed)
+    }
+
+    /// Map (possibly fractional) pixel coordinates to the complex plane.
+    fn pixel_to_complex(&self, x: f64, y: f64) -> num::Complex<f64> {
+        if self.exponential_map {
+            let center = num::Complex::new(self.center_x, self.center_y);
+            return center + num::Complex::from_polar(self.exponential_radius(x), self.exponential_angle(y));
+        }
+
+        num::Complex::new(self.center_x, self.center_y) + self.pixel_offset(x, y)
+    }
+
+    /// Offset of the point at pixel coordinates from the view center on the
+    /// plane, which keeps its precision in deep views.
+    fn pixel_offset(&self, x: f64, y: f64) -> num::Complex<f64> {
+        self.view_offset(x, y) / self.zoom
+    }
+
+    /// Offset of the point at pixel coordinates from the view center, scaled
+    /// by the zoom, which stays in the range of `f64` however deep the view.
+    fn view_offset(&self, x: f64, y: f64) -> num::Complex<f64> {
+        let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
+        let zoom_width = 2.5;
+
         // Map pixel coordinates to complex plane, centered on target point
-        let x_coord = self.center_x + (x as f64 - WIDTH as f64 / 2.0) * zoom_width / WIDTH as f64 * aspect_ratio;
-        let y_coord = self.center_y + (y as f64 - HEIGHT as f64 / 2.0) * zoom_width / HEIGHT as f64;
+        let dx = (x - WIDTH as f64 / 2.0) * zoom_width / WIDTH as f64 * aspect_ratio;
+        let dy = (y - HEIGHT as f64 / 2.0) * zoom_width / HEIGHT as f64;
+        self.transform(dx, dy)
+    }
+
+    /// Map a point of the complex plane to pixel coordinates, the inverse of
+    /// `pixel_to_complex`.
+    fn complex_to_pixel(&self, c: num::Complex<f64>) -> (f64, f64) {
+        let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
+        let zoom_width = 2.5 / self.zoom;
+
+        let offset = c - num::Complex::new(self.center_x, self.center_y);
+        if self.exponential_map {
+            let step = EXPONENTIAL_MAP_STEP;
+            let (radius, angle) = offset.to_polar();
+            let x = WIDTH as f64 + (radius / self.exponential_radius(WIDTH as f64)).ln() / step;
+            let y = (angle - self.rotation).rem_euclid(2.0 * std::f64::consts::PI) / step;
+            return (x, y);
+        }
+
+        // Undo the view rotation and skew
+        let (sin, cos) = self.rotation.sin_cos();
+        let (dx, dy) = skew::apply(
+            &skew::inverse(&self.skew),
+            offset.re * cos + offset.im * sin,
+            offset.im * cos - offset.re * sin,
+        );
+
+        let x = dx * WIDTH as f64 / (zoom_width * aspect_ratio) + WIDTH as f64 / 2.0;
+        let y = dy * HEIGHT as f64 / zoom_width + HEIGHT as f64 / 2.0;
+        (x, y)
+    }
+
+    /// Distance from the zoom target shown by pixel column `x` of the
+    /// exponential map. The right edge is the corner of the normal view and
+    /// every column to the left is a constant factor deeper.
+    fn exponential_radius(&self, x: f64) -> f64 {
+        let zoom_width = 2.5 / self.zoom;
+        let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
+        let corner = zoom_width / 2.0 * (aspect_ratio * aspect_ratio + 1.0).sqrt();
+        corner * ((x - WIDTH as f64) * EXPONENTIAL_MAP_STEP).exp()
+    }
+
+    /// Angle around the zoom target shown by pixel row `y` of the exponential
+    /// map, the rows covering one full turn.
+    fn exponential_angle(&self, y: f64) -> f64 {
+        self.rotation + y * EXPONENTIAL_MAP_STEP
+    }
+
+    /// Skew and rotate an offset from the view center.
+    fn transform(&self, dx: f64, dy: f64) -> num::Complex<f64> {
+        let (dx, dy) = skew::apply(&self.skew, dx, dy);
+        let (sin, cos) = self.rotation.sin_cos();
+        num::Complex::new(dx * cos - dy * sin, dx * sin + dy * cos)
+    }
+
+    /// Offsets along the horizontal axis for every pixel column, including the
+    /// view center, and along the vertical axis for every pixel row. A pixel
+    /// maps to the sum of its column and row entries, so the renderer maps each
+    /// coordinate once per frame rather than once per pixel.
+    ///
+    /// For the exponential map the column entries are the radii and the row
+    /// entries the unit rotations instead, and a pixel maps to the view center
+    /// plus their product. Deep views keep to offsets from the view center
+    /// with their reference orbit, and views zoomed out past the whole set
+    /// take a mipmap, both reused from the `previous` tables where they serve.
+    fn coordinate_tables(&self, previous: Option<CoordinateTables>) -> CoordinateTables {
+        if self.exponential_map {
+            let xs = (0..WIDTH).map(|x| num::Complex::new(self.exponential_radius(x as f64), 0.0)).collect();
+            let ys = (0..HEIGHT).map(|y| num::Complex::from_polar(1.0, self.exponential_angle(y as f64))).collect();
+            return CoordinateTables { xs, ys, reference: None, mipmap: None };
+        }
+
+        let (reference, mipmap) = previous.map_or((None, None), |previous| (previous.reference, previous.mipmap));
+        let reference = deep::Reference::reuse(self, reference);
+        if reference.is_some() {
+            let xs = (0..WIDTH).map(|x| self.view_offset(x as f64, HEIGHT as f64 / 2.0)).collect();
+            let ys = (0..HEIGHT).map(|y| self.view_offset(WIDTH as f64 / 2.0, y as f64)).collect();
+            return CoordinateTables { xs, ys, reference, mipmap: None };
+        }
+        let center = num::Complex::new(self.center_x, self.center_y);
+        let xs = (0..WIDTH).map(|x| center + self.pixel_offset(x as f64, HEIGHT as f64 / 2.0)).collect();
+        let ys = (0..HEIGHT).map(|y| self.pixel_offset(WIDTH as f64 / 2.0, y as f64)).collect();
+        CoordinateTables { xs, ys, reference, mipmap: mipmap::Mipmap::reuse(self, mipmap) }
+    }
+
+    /// Iterate the point at (possibly fractional) pixel coordinates,
+    /// returning the escape count and the final value of `z`.
+    fn iterate(&self, x: f64, y: f64) -> (u32, num::Complex<f64>) {
+        self.iterate_at(self.pixel_to_complex(x, y))
+    }
+
+    /// Iterate the point of the view plane at `point`, projected to the
+    /// parameter of the Mandelbrot set or the starting value of the Julia set.
+    fn iterate_at(&self, point: num::Complex<f64>) -> (u32, num::Complex<f64>) {
+        let point = self.projection.apply(point);
+        match self.julia {
+            Some(c) => self.iterate_point(point, c),
+            None => self.iterate_point(num::Complex::new(0.0, 0.0), point),
+        }
+    }
+
+    /// Iterate `z -> z^2 + c` from `z0`, or the view's other exponent,
+    /// returning the escape count and the final value of `z`.
+    fn iterate_point(&self, z0: num::Complex<f64>, c: num::Complex<f64>) -> (u32, num::Complex<f64>) {
+        self.iterate_to(z0, c, MAX_ITER)
+    }
+
+    /// Iterate like `iterate_point`, for at most `max_iter` iterations.
+    fn iterate_to(&self, z0: num::Complex<f64>, c: num::Complex<f64>, max_iter: u32) -> (u32, num::Complex<f64>) {
+        match self.multibrot() {
+            true => multibrot::iterate(z0, c, self.exponent, self.bailout, self.bailout_radius, max_iter),
+            false => mandelbrot_core::iterate(z0, c, self.bailout, self.bailout_radius, max_iter),
+        }
+    }
 
-        let c = num::Complex::new(x_coord, y_coord);
-        let mut z = num::Complex::new(0.0, 0.0);
+    /// Iterate the point shown at `point` while tracking the derivative of `z`
+    /// with respect to it, returning the escape count, the final `z` and its
+    /// derivative.
+    fn iterate_derivative(&self, point: num::Complex<f64>) -> (u32, num::Complex<f64>, num::Complex<f64>) {
+        let zero = num::Complex::new(0.0, 0.0);
+        let projected = self.projection.apply(point);
+        let derivative = self.projection.derivative(point);
+        // The parameter for the Mandelbrot set, the starting value for Julia sets
+        let (mut z, mut dz, c, dc) = match self.julia {
+            Some(c) => (projected, derivative, c, zero),
+            None => (zero, zero, projected, derivative),
+        };
 
         for n in 0..MAX_ITER {
-            if z.norm() > 2.0 {
-                return n;
+            if self.bailout.escaped(z.re, z.im, z.norm_sqr(), self.bailout_radius) {
+                return (n, z, dz);
             }
-            z = z * z + c;
+            dz = self.exponent * multibrot::power(z, self.exponent - 1.0) * dz + dc;
+            z = multibrot::power(z, self.exponent) + c;
         }
 
-        return MAX_ITER;
+        (MAX_ITER, z, dz)
+    }
+
+    /// Continuous escape count at pixel coordinates, `None` inside the set.
+    fn smooth(&self, x: f64, y: f64) -> Option<f64> {
+        let (n, z) = self.iterate(x, y);
+        if n == MAX_ITER {
+            return None;
+        }
+        Some(multibrot::smooth_count(n, z, self.exponent))
     }
 
-    /// Draw the Mandelbrot state to the frame buffer.
+    /// Color for an escape count.
+    fn color(&self, m: u32) -> [u8; 4] {
+        if m == MAX_ITER {
+            // In the Mandelbrot set
+            return [0, 0, 0, 255];
+        }
+        let m = self.palette_position(m);
+        let color = |palette: palette::Palette| match palette.color(m + self.palette_offset, self.palette_reversed) {
+            Some(rgba) => rgba,
+            None => mandelbrot_core::classic_color(m as u32 + self.palette_offset as u32),
+        };
+        match self.palette_blend {
+            Some((from, t)) => coloring::mix(color(from), color(self.palette), t),
+            None => color(self.palette),
+        }
+    }
+
+    /// Draw the pixels of row `y` from column `x` on, as many as `span` holds,
+    /// using the view's `coordinate_tables`, and write their escape counts to
+    /// `counts`.
     ///
     /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
-    fn draw(&self, frame: &mut [u8]) {
-        for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let x = (i % WIDTH as usize) as u32;
-            let y = (i / WIDTH as usize) as u32;
-
-            let m = self.mandelbrot(x, y);
-
-            let rgba: [u8; 4] = if m == MAX_ITER {
-                // In the Mandelbrot set
-                [0, 0, 0, 255]
-            } else {
-                // Not in the Mandelbrot set
-                // point escaped, color based on how quickly
-                // using a simple red-yellow gradient
-                [std::cmp::min(255, m*255 / 40) as u8,
-                 std::cmp::min(255, m*255 / 100) as u8, 
-                 std::cmp::min(255, m*8) as u8, 
-                 255]
-            };
-
-            pixel.copy_from_slice(&rgba);
-        }
-    }
-}
\ No newline at end of file
+    fn draw_span(&self, tables: &CoordinateTables, x: usize, y: usize, span: &mut [u8], counts: &mut [u32]) {
+        let at: Vec<_> = (x..x + span.len() / 4).map(|x| (x, y)).collect();
+        self.draw_pixels(tables, &at, span, counts);
+    }
+
+    /// Draw the pixels at `at`, wherever they are in the view, like
+    /// `draw_span`.
+    fn draw_pixels(&self, tables: &CoordinateTables, at: &[(usize, usize)], span: &mut [u8], counts: &mut [u32]) {
+        let CoordinateTables { xs, ys, reference, mipmap } = tables;
+        let center = num::Complex::new(self.center_x, self.center_y);
+        let points: Vec<_> = at
+            .iter()
+            .map(|&(x, y)| if self.exponential_map { center + xs[x] * ys[y] } else { xs[x] + ys[y] })
+            .collect();
+        match (reference, mipmap) {
+            (Some(reference), _) => self.shade_offsets(reference, &points, counts, span),
+            (None, Some(mipmap)) => self.shade_mipmapped(mipmap, &points, counts, span),
+            (None, None) => self.shade_batch(&points, counts, span),
+        }
+    }
+
+    /// Draw the block of `width` pixels from column `x` on, of the rows from
+    /// `y` on that `counts` holds, like `draw_span` row by row, with
+    /// adaptive iteration limits where asked for.
+    fn draw_block(&self, tables: &CoordinateTables, x: usize, y: usize, width: usize, pixels: &mut [u8], counts: &mut [u32]) {
+        let CoordinateTables { xs, ys, reference, mipmap } = tables;
+        if self.adaptive_iterations.is_none() || reference.is_some() || mipmap.is_some() || self.exponential_map {
+            let rows = pixels.chunks_exact_mut(width * 4).zip(counts.chunks_exact_mut(width));
+            for (row, (span, counts)) in rows.enumerate() {
+                self.draw_span(tables, x, y + row, span, counts);
+            }
+            return;
+        }
+        let rows = counts.len() / width;
+        let points: Vec<_> = ys[y..y + rows].iter().flat_map(|&row_offset| xs[x..x + width].iter().map(move |&column| column + row_offset)).collect();
+        self.shade_block(&points, width, counts, pixels);
+    }
+
+    /// Color the view from escape counts computed elsewhere, one per pixel row
+    /// by row, which needs a coloring that only depends on the counts.
+    fn draw_counts(&self, frame: &mut [u8], counts: &[u32]) {
+        frame
+            .par_chunks_exact_mut(4)
+            .zip(counts)
+            .for_each(|(pixel, &m)| pixel.copy_from_slice(&self.color(m)));
+    }
+
+    /// Mark the drawn morph path on the Mandelbrot view.
+    fn draw_path(&self, frame: &mut [u8]) {
+        if self.julia.is_none() {
+            for &c in &self.julia_path {
+                let (x, y) = self.complex_to_pixel(self.projection.invert(c));
+                if x >= 0.0 && y >= 0.0 && x < WIDTH as f64 && y < HEIGHT as f64 {
+                    let i = (y as usize * WIDTH as usize + x as usize) * 4;
+                    frame[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::Complex;
+    use rand::Rng;
+
+    /// Straightforward `num::Complex` version of `Mandelbrot::iterate_point`.
+    fn iterate_reference(bailout: Bailout, radius: f64, z0: Complex<f64>, c: Complex<f64>) -> (u32, Complex<f64>) {
+        let escaped = |z: Complex<f64>| match bailout {
+            Bailout::Modulus => z.norm() > radius,
+            Bailout::Manhattan => z.l1_norm() > radius,
+            Bailout::MaxComponent => z.re.abs().max(z.im.abs()) > radius,
+        };
+        let mut z = z0;
+        for n in 0..MAX_ITER {
+            if escaped(z) {
+                return (n, z);
+            }
+            z = z * z + c;
+        }
+        (MAX_ITER, z)
+    }
+
+    /// Sample points, spaced irregularly so none lands exactly on the bailout
+    /// circle where `norm()` and `norm_sqr()` may round differently.
+    fn grid() -> impl Iterator<Item = Complex<f64>> {
+        (0..64).flat_map(|j| (0..64).map(move |i| Complex::new(-2.2031 + i as f64 * 0.0497, -1.6017 + j as f64 * 0.0503)))
+    }
+
+    /// A view of the seahorse valley at `zoom`, for the backends whose
+    /// precision runs out on the way in.
+    #[cfg(any(feature = "gpu", feature = "opencl"))]
+    fn seahorse_valley(zoom: f64) -> Mandelbrot {
+        Mandelbrot { center_x: -0.743643887, center_y: 0.131825904, zoom, ..Mandelbrot::new() }
+    }
+
+    #[test]
+    fn kernel_matches_reference() {
+        let mut mandelbrot = Mandelbrot::new();
+        for bailout in [Bailout::Modulus, Bailout::Manhattan, Bailout::MaxComponent] {
+            for radius in [2.0, 256.0] {
+                mandelbrot.bailout = bailout;
+                mandelbrot.bailout_radius = radius;
+                for c in grid() {
+                    let zero = Complex::new(0.0, 0.0);
+                    assert_eq!(
+                        mandelbrot.iterate_point(zero, c),
+                        iterate_reference(bailout, radius, zero, c),
+                        "{bailout:?} radius {radius} at {c}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn batch_kernel_matches_scalar() {
+        let mut mandelbrot = Mandelbrot::new();
+        // Not a multiple of the lane count, so the last batch is partial
+        let points: Vec<_> = grid().take(4093).collect();
+        let mut results = vec![(0, Complex::new(0.0, 0.0)); points.len()];
+        for julia in [None, Some(Complex::new(-0.8, 0.156))] {
+            for bailout in [Bailout::Modulus, Bailout::Manhattan, Bailout::MaxComponent] {
+                mandelbrot.julia = julia;
+                mandelbrot.bailout = bailout;
+                mandelbrot.iterate_batch(&points, &mut results);
+                for (&point, &result) in points.iter().zip(&results) {
+                    assert_eq!(result, mandelbrot.iterate_at(point), "{bailout:?} at {point}");
+                }
+            }
+        }
+    }
+
+    /// Check `property` on `CASES` random cases, the same on every run so a
+    /// failure can be reproduced, reporting the case that failed.
+    fn for_random_cases(mut property: impl FnMut(&mut rand::rngs::StdRng)) {
+        use rand::SeedableRng;
+        const CASES: u64 = 256;
+        for case in 0..CASES {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(case);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| property(&mut rng)));
+            if let Err(e) = result {
+                eprintln!("failed on random case {case}");
+                std::panic::resume_unwind(e);
+            }
+        }
+    }
+
+    /// A random view around the set, zoomed out to a billion times in, turned
+    /// and skewed any way.
+    fn random_view(rng: &mut impl Rng) -> Mandelbrot {
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y) = (rng.gen_range(-2.0..1.0), rng.gen_range(-1.5..1.5));
+        view.zoom = 10f64.powf(rng.gen_range(-1.0..9.0));
+        view.rotation = rng.gen_range(-10.0..10.0);
+        let skew = [rng.gen_range(0.5..2.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(0.5..2.0)];
+        view.skew = skew::normalize(&skew).unwrap_or(skew::IDENTITY);
+        view
+    }
+
+    /// Width on the plane of the pixel of `view` at `x`, `y`, and how many
+    /// pixels the rounding of points there is worth, which the mappings can't
+    /// be expected to beat.
+    fn pixel_and_rounding(view: &Mandelbrot, x: f64, y: f64) -> (f64, f64) {
+        let c = view.pixel_to_complex(x, y);
+        let pixel = (view.pixel_to_complex(x + 1.0, y) - c).norm();
+        (pixel, f64::EPSILON * (c.norm() + 1.0) / pixel)
+    }
+
+    #[test]
+    fn pixels_round_trip_through_the_plane() {
+        for_random_cases(|rng| {
+            let mut view = random_view(rng);
+            view.exponential_map = rng.gen_bool(0.25);
+            let (x, y) = (rng.gen_range(0.0..WIDTH as f64), rng.gen_range(0.0..HEIGHT as f64 - 1.0));
+            let (px, py) = view.complex_to_pixel(view.pixel_to_complex(x, y));
+            let tolerance = 1e-6 + 4.0 * pixel_and_rounding(&view, x, y).1;
+            assert!((px - x).abs() < tolerance && (py - y).abs() < tolerance, "{view:?}: ({x}, {y}) came back as ({px}, {py})");
+        });
+    }
+
+    #[test]
+    fn zooming_keeps_the_center() {
+        for_random_cases(|rng| {
+            let view = random_view(rng);
+            let zoomed = Mandelbrot { zoom: view.zoom * rng.gen_range(0.1..10.0), ..view.clone() };
+            let center = Complex::new(view.center_x, view.center_y);
+            assert_eq!(zoomed.pixel_to_complex(WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0), center);
+
+            // Every other point moves straight towards or away from it
+            let (x, y) = (rng.gen_range(0.0..WIDTH as f64), rng.gen_range(0.0..HEIGHT as f64));
+            let scaled = (zoomed.pixel_to_complex(x, y) - center) * (zoomed.zoom / view.zoom);
+            let offset = view.pixel_to_complex(x, y) - center;
+            let (pixel, rounding) = pixel_and_rounding(&view, x, y);
+            let tolerance = 1e-6 + 4.0 * (rounding + pixel_and_rounding(&zoomed, x, y).1);
+            assert!((scaled - offset).norm() / pixel <= tolerance, "{view:?} at ({x}, {y})");
+        });
+    }
+
+    #[test]
+    fn pixels_are_square() {
+        for_random_cases(|rng| {
+            let view = Mandelbrot { skew: skew::IDENTITY, ..random_view(rng) };
+            let (x, y) = (rng.gen_range(0.0..WIDTH as f64 - 1.0), rng.gen_range(0.0..HEIGHT as f64 - 1.0));
+            let origin = view.pixel_to_complex(x, y);
+            let (right, down) = (view.pixel_to_complex(x + 1.0, y) - origin, view.pixel_to_complex(x, y + 1.0) - origin);
+            let pixel = 2.5 / view.zoom / HEIGHT as f64;
+            let tolerance = 1e-6 + 4.0 * pixel_and_rounding(&view, x, y).1;
+            assert!((right.norm() / pixel - 1.0).abs() < tolerance && (down.norm() / pixel - 1.0).abs() < tolerance, "{view:?}");
+            // At right angles, a quarter turn apart as the image rows go down
+            assert!(((right * Complex::i()) / down - 1.0).norm() < tolerance, "{view:?}");
+        });
+    }
+
+    #[test]
+    fn core_views_map_pixels_as_the_viewer() {
+        for_random_cases(|rng| {
+            let view = Mandelbrot { skew: skew::IDENTITY, rotation: 0.0, ..random_view(rng) };
+            let core_view = mandelbrot_core::View::new(Complex::new(view.center_x, view.center_y), view.zoom);
+            let (x, y) = (rng.gen_range(0.0..WIDTH as f64), rng.gen_range(0.0..HEIGHT as f64));
+            let (pixel, rounding) = pixel_and_rounding(&view, x, y);
+            let point = core_view.point(x, y, WIDTH as usize, HEIGHT as usize);
+            assert!((point - view.pixel_to_complex(x, y)).norm() / pixel < 1e-6 + 4.0 * rounding, "{view:?}");
+        });
+    }
+
+    #[test]
+    fn kernel_is_symmetric_under_conjugation() {
+        for_random_cases(|rng| {
+            let mut view = Mandelbrot::new();
+            view.bailout = [Bailout::Modulus, Bailout::Manhattan, Bailout::MaxComponent][rng.gen_range(0..3)];
+            view.bailout_radius = rng.gen_range(2.0..100.0);
+            let z0 = Complex::new(rng.gen_range(-2.0..2.0), rng.gen_range(-2.0..2.0));
+            let c = Complex::new(rng.gen_range(-2.0..1.0), rng.gen_range(-1.5..1.5));
+            let (n, z) = view.iterate_point(z0, c);
+            assert_eq!(view.iterate_point(z0.conj(), c.conj()), (n, z.conj()), "{z0} {c}");
+            // Julia sets are symmetric about the origin too
+            assert_eq!(view.iterate_point(-z0, c).0, n, "{z0} {c}");
+        });
+    }
+
+    #[test]
+    fn julia_kernel_matches_reference() {
+        let mandelbrot = Mandelbrot::new();
+        let c = Complex::new(-0.8, 0.156);
+        for z0 in grid() {
+            assert_eq!(mandelbrot.iterate_point(z0, c), iterate_reference(Bailout::Modulus, 2.0, z0, c));
+        }
+    }
+
+    #[test]
+    fn fixed_point_matches_f64() {
+        let (a, b, c) = (-1.7548776662466927, 0.30179, 2.5e-7);
+        let fixed = |value: f64| fixed::Fixed::from_f64(value, 3);
+        assert_eq!(fixed(a).to_f64(), a);
+        assert_eq!(fixed(c).to_f64(), c);
+        let result = &(&(&fixed(a) * &fixed(a)) - &(&fixed(b) * &fixed(c))) + &fixed(b);
+        assert!((result.to_f64() - (a * a - b * c + b)).abs() < 1e-15);
+        assert!((&fixed(b) - &fixed(a)).to_f64() == b - a);
+
+        // Larger radii would let orbits square past the integer limb
+        let big = fixed(deep::MAX_BAILOUT);
+        assert_eq!((&(&big * &big) + &(&big * &big)).to_f64(), 2.0 * deep::MAX_BAILOUT * deep::MAX_BAILOUT);
+        assert!(view_from_args(&[format!("--bailout={}", deep::MAX_BAILOUT)]).is_ok());
+        assert!(view_from_args(&["--bailout=1e10".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn perturbation_matches_direct_iteration() {
+        // Shallow enough for `f64` to render directly
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y, view.zoom) = (-0.743643887037151, 0.13182590420533, 1e5);
+        let reference = deep::Reference::new(&view);
+        let pixels: Vec<_> = (0..HEIGHT).step_by(20).flat_map(|y| (0..WIDTH).step_by(20).map(move |x| (x as f64, y as f64))).collect();
+        let agree = pixels
+            .iter()
+            .filter(|&&(x, y)| reference.iterate(&view, view.view_offset(x, y)).0 == view.iterate(x, y).0)
+            .count();
+        assert!(agree * 100 >= pixels.len() * 98, "{agree} of {} agree", pixels.len());
+
+        assert!(!view.deep());
+        view.zoom = 1e20;
+        assert!(view.deep());
+    }
+
+    #[test]
+    fn glitches_are_corrected() {
+        // The nucleus of a minibrot passes through zero, the reference beside
+        // it doesn't
+        let nucleus = num::Complex::new(-1.7548776662466927, 0.0);
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y, view.zoom) = (nucleus.re + 1e-7, 1e-7, 1e7);
+        let reference = deep::Reference::new(&view);
+        let (x, y) = view.complex_to_pixel(nucleus);
+        let offset = view.view_offset(x, y);
+        assert!(reference.iterate_deltas(&view, offset).is_none());
+        assert_eq!(reference.iterate(&view, offset).0, view.iterate_at(nucleus).0);
+    }
+
+    #[test]
+    fn references_are_placed_in_minibrots() {
+        // A period 15 minibrot a tenth of the view high, below the center
+        let nucleus = num::Complex::new(-1.9934999836047422, 0.0);
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y, view.zoom) = (nucleus.re, 7.5e-11, 1e10);
+        let centered = deep::Reference::new(&view);
+        let placed = deep::Reference::for_view(&view).unwrap();
+        // Most of the minibrot glitches but on a reference inside it
+        let pixels: Vec<_> = (0..HEIGHT).step_by(10).flat_map(|y| (0..WIDTH).step_by(10).map(move |x| (x as f64, y as f64))).map(|(x, y)| view.view_offset(x, y)).collect();
+        let glitches = |reference: &deep::Reference| pixels.iter().filter(|&&offset| reference.iterate_deltas(&view, offset).is_none()).count();
+        assert!(glitches(&placed) * 10 < glitches(&centered), "{} and {} glitch", glitches(&placed), glitches(&centered));
+        for (x, y) in [(0.0, 0.0), (123.0, 456.0), (700.0, 20.0), (400.0, 300.0)] {
+            let offset = view.view_offset(x, y);
+            assert_eq!(placed.iterate(&view, offset).0, centered.iterate(&view, offset).0);
+        }
+    }
+
+    #[test]
+    fn reused_references_match_fresh_ones() {
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y, view.zoom) = (0.0, 1.0, 1e20);
+        let mut reference = deep::Reference::reuse(&view, None);
+        // Zooming and panning on, then zooming past the orbit's precision
+        for (shift, zoom) in [(0.0, 2.0), (0.3, 1.5), (-0.4, 1e3), (0.0, 1e25)] {
+            view.center_x += shift / view.zoom;
+            view.zoom *= zoom;
+            reference = deep::Reference::reuse(&view, reference);
+            let fresh = deep::Reference::new(&view);
+            for (x, y) in [(0.0, 0.0), (123.0, 456.0), (700.0, 20.0), (400.0, 300.0)] {
+                let offset = view.view_offset(x, y);
+                assert_eq!(reference.as_ref().unwrap().iterate(&view, offset).0, fresh.iterate(&view, offset).0);
+            }
+        }
+    }
+
+    #[test]
+    fn extended_deltas_match_f64() {
+        use floatexp::FloatExp;
+        // Products and sums far below the smallest f64
+        let (a, b) = (FloatExp::new(0.75, -2000), FloatExp::new(0.5, -1990));
+        assert_eq!((a * b).to_f64(), 0.0);
+        assert_eq!((a * b * FloatExp::new(1.0, 3990)).to_f64(), 0.375);
+        assert_eq!(((a + b) * FloatExp::new(1.0, 1990)).to_f64(), 0.5 + 0.75 / 1024.0);
+        assert_eq!(FloatExp::from_f64(-3.0e-310).recip().recip().to_f64(), -3.0e-310);
+
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y, view.zoom) = (0.0, 1.0, 1e20);
+        let reference = deep::Reference::new(&view);
+        for (x, y) in [(0.0, 0.0), (123.0, 456.0), (700.0, 20.0), (400.0, 300.0)] {
+            let offset = view.view_offset(x, y);
+            assert_eq!(reference.iterate_extended(&view, offset).map(|(m, _)| m), reference.iterate_deltas(&view, offset).map(|(m, _)| m));
+        }
+    }
+
+    #[test]
+    fn outward_ray_reads_traced_angle() {
+        for (angle, turns) in [("1/3", 1.0 / 3.0), ("1/7", 1.0 / 7.0), ("1/4", 0.25), ("1/2", 0.5)] {
+            let ray = rays::trace(rays::Angle::from_arg(angle).unwrap());
+            assert!(ray.len() > 16, "ray {angle} stops early");
+            // Far out the orbits escape too early for all digits to be exact
+            for &c in ray.iter().step_by(64).filter(|c| c.norm() < 4.0) {
+                let outward = rays::trace_out(c).unwrap();
+                assert!((outward.turns - turns).abs() < 1e-6, "ray {angle} at {c} reads {outward}");
+            }
+        }
+        let landing = *rays::trace(rays::Angle::from_arg("1/2").unwrap()).last().unwrap();
+        assert!((landing - Complex::new(-2.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn boundary_is_found_from_either_side() {
+        let view = Mandelbrot::new();
+        let near = |re: f64, im: f64| view.nearest_boundary(Complex::new(re, im), 1e-4, 1.0).unwrap();
+        // Outside, to the cusp and to the tip of the antenna
+        assert!((near(0.5, 0.0) - Complex::new(0.25, 0.0)).norm() < 0.01);
+        assert!((near(-2.5, 0.0) - Complex::new(-2.0, 0.0)).norm() < 1e-3);
+        // Inside the period 2 bulb, out to its circle
+        let distance = (near(-1.0, 0.02) - Complex::new(-1.0, 0.0)).norm();
+        assert!((0.2..0.3).contains(&distance), "{distance}");
+        assert_eq!(view.nearest_boundary(Complex::new(-0.1, 0.0), 1e-4, 0.01), None);
+    }
+
+    #[test]
+    fn minibrots_are_found_and_framed() {
+        let minibrot = minibrot::Minibrot::near(Complex::new(-1.754, 0.001)).unwrap();
+        assert_eq!(minibrot.period, 3);
+        assert!((minibrot.nucleus - Complex::new(-1.7548776662466927, 0.0)).norm() < 1e-12);
+        assert!((minibrot.size.norm() - 0.019035).abs() < 1e-5, "{}", minibrot.size);
+        // Inside a bulb, the period of its cycle rather than of a multiple
+        assert_eq!(minibrot::Minibrot::near(Complex::new(-1.0, 0.01)).map(|minibrot| (minibrot.period, minibrot.nucleus)), Some((2, Complex::new(-1.0, 0.0))));
+
+        // The framed whole set spans 40% of the view height
+        let whole = minibrot::Minibrot::near(Complex::new(0.3, 0.02)).unwrap();
+        let framed = whole.framed(&Mandelbrot::new(), minibrot::FRAME);
+        let (top, bottom) = (framed.complex_to_pixel(Complex::new(-0.1, 1.1226)), framed.complex_to_pixel(Complex::new(-0.1, -1.1226)));
+        assert!(((top.1 - bottom.1).abs() / HEIGHT as f64 - minibrot::FRAME).abs() < 1e-3);
+    }
+
+    #[test]
+    fn interior_distance_estimates_boundary() {
+        // The centers of the main cardioid and of the period 2 disk are a
+        // quarter from their boundaries
+        for c in [Complex::new(0.0, 0.0), Complex::new(-1.0, 0.0)] {
+            let distance = coloring::interior_distance(c, Mandelbrot::new().iterate_point(Complex::new(0.0, 0.0), c).1).unwrap();
+            assert!((0.25 / 4.0..=0.25 * 4.0).contains(&distance), "{c}: {distance}");
+        }
+        // Outside the set there is no attracting cycle
+        assert_eq!(coloring::interior_distance(Complex::new(0.5, 0.0), Complex::new(100.0, 0.0)), None);
+
+        // Only the boundary is drawn for the boundary coloring
+        let mut mandelbrot = Mandelbrot::new();
+        mandelbrot.coloring = coloring::Coloring::Boundary;
+        assert_eq!(mandelbrot.shade_at(Complex::new(-0.1, 0.0)).1, [255, 255, 255, 255]);
+        assert!(mandelbrot.shade_at(Complex::new(0.2505, 0.0)).1[0] < 64);
+    }
+
+    #[test]
+    fn mipmap_shrinks_the_set() {
+        let mut mandelbrot = Mandelbrot::new();
+        (mandelbrot.center_x, mandelbrot.center_y, mandelbrot.zoom) = (0.0, 0.0, 0.25);
+        let tables = mandelbrot.coordinate_tables(None);
+        assert!(tables.mipmap.is_some());
+        // Over the main cardioid, and far out in the plane
+        let mut span = [0; 8];
+        let mut counts = [0; 2];
+        let (x, _) = mandelbrot.complex_to_pixel(Complex::new(-0.2, 0.0));
+        mandelbrot.draw_span(&tables, x as usize, HEIGHT as usize / 2, &mut span[..4], &mut counts[..1]);
+        mandelbrot.draw_span(&tables, 0, 0, &mut span[4..], &mut counts[1..]);
+        assert_eq!((counts, &span[..4]), ([MAX_ITER, 1], &mandelbrot.color(MAX_ITER)[..]));
+        assert_eq!(span[4..], mandelbrot.color(1));
+
+        // Zoomed back in, through the reused tables
+        mandelbrot.zoom = 1.0;
+        assert!(mandelbrot.coordinate_tables(Some(tables)).mipmap.is_none());
+    }
+
+    #[test]
+    fn angle_coloring_spans_the_palette_over_a_turn() {
+        let mut view = view_from_args(&["--coloring=angle".to_owned()]).unwrap();
+        assert_eq!(view.coloring.name(), "angle");
+        assert_eq!(view.shade(5, Complex::new(3.0, 0.0)), view.color(0));
+        assert_eq!(view.shade(5, Complex::new(-3.0, 0.0)), view.color(50));
+        assert_eq!(view.shade(MAX_ITER, Complex::new(-0.1, 0.0)), view.color(MAX_ITER));
+        // Mixed away, and averaged along the orbit
+        view.angle_blend = 0.0;
+        assert_eq!(view.shade(5, Complex::new(-3.0, 0.0)), view.color(5));
+        view.coloring = coloring::Coloring::Angle { average: true };
+        let (m, rgba) = view.shade_at(Complex::new(0.5, 0.5));
+        assert!(m < MAX_ITER);
+        assert_eq!(rgba, view.color(m));
+    }
+
+    #[test]
+    fn session_options_restore_view() {
+        let mut mandelbrot = Mandelbrot::new();
+        (mandelbrot.center_x, mandelbrot.center_y, mandelbrot.zoom) = (-0.743643887037151, 0.13182590420533, 1234.5);
+        mandelbrot.rotation = 0.3;
+        mandelbrot.skew = skew::normalize(&[2.0, 0.5, 0.0, 1.0]).unwrap();
+        mandelbrot.julia = Some(Complex::new(-0.8, 0.156));
+        mandelbrot.bailout = Bailout::Manhattan;
+        mandelbrot.coloring = coloring::Coloring::Binary { level_sets: true };
+        (mandelbrot.palette, mandelbrot.palette_reversed) = (palette::Palette::Magma, true);
+        mandelbrot.contours = true;
+        mandelbrot.contour_interval = 7;
+
+        let mut restored = view_from_args(&session::view_options(&mandelbrot)).unwrap();
+        // The rotation goes through degrees and the skew is normalized again,
+        // the rest is exact
+        assert!((restored.rotation - mandelbrot.rotation).abs() < 1e-15);
+        assert!(restored.skew.iter().zip(&mandelbrot.skew).all(|(a, b)| (a - b).abs() < 1e-15));
+        (restored.rotation, restored.skew) = (mandelbrot.rotation, mandelbrot.skew);
+        assert_eq!(format!("{restored:?}"), format!("{mandelbrot:?}"));
+    }
+
+    #[test]
+    fn estimates_sample_the_render() {
+        let encoding = |format| export::Encoding { format, bit_depth: 8, quality: 90, dpi: None };
+        let view = Mandelbrot::new();
+        let estimate = export::estimate(&view, 4000, 3000, encoding(export::Format::Png));
+        assert!(estimate.time.as_secs_f64() > 0.0);
+        assert!(estimate.inside > 0.0 && estimate.inside < 1.0);
+        assert!(estimate.mean_iterations > 1.0 && estimate.mean_iterations < MAX_ITER as f64);
+        assert_eq!(estimate.disk_bytes, 4000 * 3000 * 4);
+        // Strips on their way to the encoder, or the whole image for JPEG
+        assert!(estimate.memory_bytes < estimate.disk_bytes / 10);
+        let contoured = Mandelbrot { contours: true, ..view.clone() };
+        let estimate = export::estimate(&contoured, 4000, 3000, encoding(export::Format::Jpeg));
+        assert_eq!((estimate.disk_bytes, estimate.memory_bytes), (2 * 4000 * 3000 * 4, 4000 * 3000 * 4));
+    }
+
+    #[test]
+    fn queued_jobs_run_in_order_after_each_other() {
+        let dir = std::env::temp_dir().join("mandelbrot-queue-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let (path, log) = (dir.join("queue.json"), dir.join("queue.log"));
+        let job = |out: &str| ["render", &format!("--out={out}"), "--size=16x12"].map(str::to_owned).to_vec();
+        let options = |options: &[&str]| options.iter().map(|option| option.to_string()).collect::<Vec<_>>();
+        let image = dir.join("image.png").display().to_string();
+        let later = queue::add(&path, &options(&["--at=+0s"]), job(&image)).unwrap();
+        let first = queue::add(&path, &[], job(&image)).unwrap();
+        let failing = queue::add(&path, &options(&["--after=1"]), job(&dir.join("missing").join("image.png").display().to_string())).unwrap();
+        queue::add(&path, &options(&[&format!("--after={failing}")]), job(&image)).unwrap();
+        assert!(queue::add(&path, &options(&["--after=9"]), job(&image)).is_err());
+        assert!(queue::add(&path, &[], vec!["queue".to_owned(), "run".to_owned()]).is_err());
+
+        assert!(queue::run(&path, &log).is_err());
+        let statuses: Vec<_> = queue::load(&path).unwrap().iter().map(|job| job.status).collect();
+        assert_eq!(statuses, [queue::Status::Done, queue::Status::Done, queue::Status::Failed, queue::Status::Skipped]);
+        assert_eq!((later, first), (1, 2));
+        let log = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(log.lines().filter(|line| line.contains("started")).count(), 3);
+        assert!(std::fs::metadata(&image).is_ok());
+
+        // Another run leaves the jobs of this one alone
+        let lock = dir.join("queue.json.lock");
+        assert!(std::fs::metadata(&lock).is_err());
+        let pending = queue::add(&path, &[], job(&image)).unwrap();
+        std::fs::write(&lock, "").unwrap();
+        assert!(queue::run(&path, &dir.join("queue.log")).is_err());
+        assert!(queue::load(&path).unwrap().iter().any(|job| job.id == pending && job.status == queue::Status::Pending));
+        std::fs::remove_file(&lock).unwrap();
+        queue::remove(&path, pending).unwrap();
+
+        // Jobs after a removed one don't wait for it
+        let waiting = queue::add(&path, &options(&[&format!("--after={later}")]), job(&image)).unwrap();
+        queue::remove(&path, later).unwrap();
+        assert!(queue::remove(&path, later).is_err());
+        assert!(queue::load(&path).unwrap().iter().any(|job| job.id == waiting && job.after.is_none()));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(queue::parse_time("2026-10-15T01:30Z", 0), Some(1_792_027_800));
+        assert_eq!(queue::format_time(1_792_027_800), "2026-10-15T01:30:00Z");
+        assert_eq!(queue::format_time(queue::parse_time("2024-02-29T23:59:59Z", 0).unwrap()), "2024-02-29T23:59:59Z");
+        assert_eq!(queue::parse_time("+1.5h", 100), Some(5500));
+        assert_eq!(queue::parse_time("2026-13-01T00:00Z", 0), None);
+        assert_eq!(queue::parse_time("2026-02-31T00:00Z", 0), None);
+        assert_eq!(queue::parse_time("2026-04-31T00:00Z", 0), None);
+        assert_eq!(queue::parse_time("2100-02-29T00:00Z", 0), None);
+        assert_eq!(queue::parse_time("+1e30s", 100), None);
+        assert_eq!(queue::parse_time("9999-12-31T23:59:59Z", 0), Some(253_402_300_799));
+        assert_eq!(queue::parse_time("99999999999999999-01-01T00:00Z", 0), None);
+    }
+
+    #[test]
+    fn presets_make_views_and_user_presets_replace_built_in_ones() {
+        let built_in = presets::merge(Vec::new());
+        for preset in &built_in {
+            assert!(view_from_args(&preset.options).is_ok(), "{}", preset.name);
+        }
+
+        let dir = std::env::temp_dir().join("mandelbrot-presets-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.zoom, view.palette) = (-1.25, 8.0, palette::Palette::Turbo);
+        presets::save_to(&dir, "mine", &view).unwrap();
+        presets::save_to(&dir, "seahorse-valley", &Mandelbrot::new()).unwrap();
+        std::fs::write(dir.join(".hidden"), "--zoom=3\n").unwrap();
+        let presets = presets::merge(presets::load_dir(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(presets.len(), built_in.len() + 1);
+        assert!(presets[1].user && presets[1].name == "seahorse-valley");
+        assert!(presets.last().is_some_and(|preset| preset.user && preset.name == "mine"));
+
+        // The options given beside the preset take precedence
+        let args = presets::expand_from(vec!["--preset=mine".to_owned(), "--zoom=5".to_owned()], presets.clone()).unwrap();
+        let expanded = view_from_args(&args).unwrap();
+        assert_eq!((expanded.center_x, expanded.zoom, expanded.palette), (-1.25, 5.0, palette::Palette::Turbo));
+        assert!(presets::expand_from(vec!["--preset=nowhere".to_owned()], presets).is_err());
+    }
+
+    #[test]
+    fn data_export_matches_the_render() {
+        let mandelbrot = Mandelbrot { julia: Some(Complex::new(-0.8, 0.156)), ..Mandelbrot::new() };
+        let (width, height) = (16, 12);
+        let counts: Vec<u32> = (0..height).flat_map(|y| export::render_row(&mandelbrot, None, width, height, y).1).collect();
+        let [csv, npy] = ["csv", "npy"].map(|extension| std::env::temp_dir().join(format!("mandelbrot-data-test.{extension}")));
+        for out in [&csv, &npy] {
+            let args = ["data".to_owned(), format!("--out={}", out.display()), format!("--size={width}x{height}")];
+            data::data_from_args(&mandelbrot, &args, progress::Mode::Quiet).unwrap();
+        }
+
+        let text = std::fs::read_to_string(&csv).unwrap();
+        let lines: Vec<&str> = text.lines().skip(1).collect();
+        assert_eq!(lines.len(), counts.len());
+        for (line, &count) in lines.iter().zip(&counts) {
+            let fields: Vec<&str> = line.split(',').collect();
+            assert_eq!((fields.len(), fields[4].parse(), fields[5].is_empty()), (6, Ok(count), count == MAX_ITER), "{line}");
+        }
+
+        // Records of 28 bytes after a header padded to 64
+        let bytes = std::fs::read(&npy).unwrap();
+        let header = data::npy_header(width, height);
+        assert_eq!((header.len() % 64, bytes.len()), (0, header.len() + 28 * counts.len()));
+        let iterations = |i: usize| u32::from_le_bytes(bytes[header.len() + 28 * i + 16..][..4].try_into().unwrap());
+        assert!((0..counts.len()).all(|i| iterations(i) == counts[i]));
+        std::fs::remove_file(csv).unwrap();
+        std::fs::remove_file(npy).unwrap();
+    }
+
+    #[test]
+    fn divergence_counts_the_pixels_that_differ() {
+        let d = verify::Divergence::between(&[1, 5, MAX_ITER, 7], &[1, 6, 3, 20]);
+        assert_eq!((d.pixels, d.differing, d.beyond_one, d.max, d.escape_mismatches), (4, 3, 2, MAX_ITER - 3, 1));
+        assert!((d.mean - (1 + MAX_ITER - 3 + 13) as f64 / 3.0).abs() < 1e-12);
+        assert_eq!(verify::Divergence::between(&[2, 3], &[2, 3]), verify::Divergence { pixels: 2, ..Default::default() });
+    }
+
+    #[test]
+    fn animation_passes_through_keyframes() {
+        let path = std::env::temp_dir().join("mandelbrot-animation-test.toml");
+        let script = "[[segment]]\nduration = 2\ncenter = [-0.75, 0.1]\nzoom = 100\n\n[[segment]]\nduration = 1\njulia = [-0.8, 0.156]\nrotation = 90\n";
+        std::fs::write(&path, script).unwrap();
+        let start = Mandelbrot::new();
+        let animation = animation::Animation::load(&path, &start).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(animation.duration(), 3.0);
+        assert_eq!(format!("{:?}", animation.view_at(0.0)), format!("{start:?}"));
+        let middle = animation.view_at(2.0);
+        assert_eq!((middle.center_x, middle.center_y, middle.zoom, middle.julia), (-0.75, 0.1, 100.0, Some(Complex::new(-0.8, 0.156))));
+        let end = animation.view_at(10.0);
+        assert!((end.rotation - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+        // Zooming the center closes in monotonically
+        let distances: Vec<f64> = (0..=8).map(|i| animation.view_at(i as f64 / 4.0)).map(|v| (v.center_x + 0.75).abs()).collect();
+        assert!(distances.windows(2).all(|pair| pair[1] <= pair[0]));
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn gpu_precision_follows_the_zoom() {
+        use gpu::Precision;
+        let at = |zoom: f64| gpu::precision(&seahorse_valley(zoom));
+        assert_eq!(at(100.0), Some(Precision::Single));
+        assert_eq!(at(1e6), Some(Precision::DoubleSingle));
+        assert_eq!(at(1e8), Some(Precision::DoubleSingle));
+        assert_eq!(at(1e11), None);
+        assert_eq!(gpu::precision(&Mandelbrot { zoom: 1e6, exponent: 3.0, ..Mandelbrot::new() }), None);
+
+        // The two parts hold what a single f32 drops
+        let x = -0.743643887037151;
+        let [high, low] = gpu::split(x);
+        assert_eq!(high, x as f32);
+        assert!((high as f64 + low as f64 - x).abs() < x.abs() * 1e-14);
+    }
+
+    #[test]
+    fn bifurcation_strip_doubles_the_period_along_the_axis() {
+        let rows = |c: f64| bifurcation::column(c, 2.0).iter().filter(|&&hits| hits > 0).count();
+        // The fixed point of the main cardioid, the period 2 and 4 bulbs,
+        // chaos near the tip, and nothing outside the set
+        assert_eq!(rows(-0.5), 1);
+        assert_eq!(rows(-1.0), 2);
+        assert_eq!(rows(-1.3), 4);
+        assert!(rows(-1.9) > 20);
+        assert_eq!(rows(0.5), 0);
+        // Period 3 in the window of the minibrot at -1.75
+        assert_eq!(rows(-1.755), 3);
+
+        let view = Mandelbrot::new();
+        let parameters = bifurcation::parameters(&view);
+        assert_eq!(parameters.len(), WIDTH as usize);
+        assert!(parameters.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((parameters[WIDTH as usize / 2] - view.center_x).abs() < 0.01);
+    }
+
+    #[cfg(feature = "opencl")]
+    #[test]
+    fn opencl_renders_what_double_precision_resolves() {
+        let at = |zoom: f64| opencl::suitable(&seahorse_valley(zoom));
+        // Up to where perturbation takes over
+        assert!(at(100.0) && at(1e6) && at(1e9));
+        assert!(!at(1e10));
+        assert!(!opencl::suitable(&Mandelbrot { exponent: 3.0, ..Mandelbrot::new() }));
+        assert!(!opencl::suitable(&Mandelbrot { exponential_map: true, ..Mandelbrot::new() }));
+        assert_eq!(telemetry::Backend::OpenCl.name(), "opencl");
+    }
+
+    #[test]
+    fn multibrot_exponents_render_and_sweep() {
+        // Whole powers by squaring agree with the polar form
+        let z = Complex::new(0.3, -1.2);
+        assert!((multibrot::power(z, 5.0) - z.powf(5.0)).norm() < 1e-12);
+        assert_eq!(multibrot::power(z, 2.0), z * z);
+
+        // The cubic set is symmetric under c -> -c, unlike the Mandelbrot set
+        let cubic = view_from_args(&["--exponent=3".to_owned()]).unwrap();
+        let quadratic = Mandelbrot::new();
+        let points = [Complex::new(0.5, 0.1), Complex::new(0.1, 0.95), Complex::new(0.38, 0.62), Complex::new(-0.2, 0.7)];
+        assert!(points.iter().all(|&c| cubic.iterate_at(c).0 == cubic.iterate_at(-c).0));
+        assert!(points.iter().any(|&c| quadratic.iterate_at(c).0 != quadratic.iterate_at(-c).0));
+        let (mut counts, mut colors) = (vec![0; points.len()], vec![0; points.len() * 4]);
+        cubic.shade_batch(&points, &mut counts, &mut colors);
+        assert!(points.iter().zip(&counts).all(|(&c, &m)| cubic.iterate_at(c).0 == m));
+        assert!(!cubic.deep() && !cubic.mipmapped());
+        assert!(view_from_args(&["--exponent=1".to_owned()]).is_err());
+
+        // There and back, eased at both ends
+        let args = ["--sweep=2,6", "--sweep-duration=10"].map(str::to_owned);
+        let (sweep, given) = animation::Sweep::from_args(&args).unwrap();
+        assert!(given);
+        let animation = sweep.animation(&quadratic);
+        assert_eq!(animation.duration(), 10.0);
+        assert_eq!([0.0, 2.5, 5.0, 10.0].map(|time| animation.view_at(time).exponent), [2.0, 4.0, 6.0, 2.0]);
+        assert!(animation.view_at(0.5).exponent < 2.4);
+        assert!(animation::Sweep::from_args(&["--sweep=2".to_owned()]).is_err());
+
+        // And animated in scripts
+        let path = std::env::temp_dir().join("mandelbrot-exponent-test.toml");
+        std::fs::write(&path, "[[segment]]\nduration = 2\nexponent = 4\n").unwrap();
+        let animation = animation::Animation::load(&path, &quadratic).unwrap();
+        assert_eq!(animation.view_at(1.0).exponent, 3.0);
+        std::fs::write(&path, "[[segment]]\nduration = 2\nexponent = 0.5\n").unwrap();
+        assert!(animation::Animation::load(&path, &quadratic).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn orbits_turn_around_the_center() {
+        let args = ["--orbit=-2", "--orbit-zoom=100", "--orbit-duration=4"].map(str::to_owned);
+        let (orbit, given) = animation::Orbit::from_args(&args).unwrap();
+        assert!(given);
+        let start = Mandelbrot { center_x: -0.75, rotation: 0.5, ..Mandelbrot::new() };
+        let spiral = orbit.animation(&start);
+        assert_eq!(spiral.duration(), 4.0);
+        // Steadily, holding the center while zooming in
+        let middle = spiral.view_at(1.0);
+        assert!((middle.rotation - (0.5 - std::f64::consts::PI)).abs() < 1e-12);
+        assert!((middle.zoom - 100f64.powf(0.25)).abs() < 1e-12);
+        assert_eq!((middle.center_x, middle.center_y), (start.center_x, start.center_y));
+        assert!(animation::Orbit::from_args(&["--orbit=0".to_owned()]).is_err());
+
+        // Or turned in an animation script
+        let path = std::env::temp_dir().join("mandelbrot-orbit-test.toml");
+        std::fs::write(&path, "[[segment]]\nduration = 2\nturns = 0.25\n").unwrap();
+        let turned = animation::Animation::load(&path, &start).unwrap().view_at(2.0);
+        std::fs::write(&path, "[[segment]]\nduration = 2\nturns = 1\nrotation = 90\n").unwrap();
+        assert!(animation::Animation::load(&path, &start).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!((turned.rotation - (0.5 + std::f64::consts::FRAC_PI_2)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn tweens_zoom_steadily_and_blend_palettes() {
+        let from = Mandelbrot::new();
+        let preset = presets::merge(Vec::new()).into_iter().find(|preset| preset.name == "seahorse-valley").unwrap();
+        let to = preset.view().unwrap();
+        let tween = animation::Animation::tween(&from, to.clone(), 4.0, animation::Easing::default());
+
+        let start = tween.view_at(0.0);
+        assert_eq!((start.center_x, start.center_y, start.zoom, start.color(40)), (from.center_x, from.center_y, from.zoom, from.color(40)));
+        assert_eq!(format!("{:?}", tween.view_at(4.0)), format!("{to:?}"));
+        // Halfway through the easing the zoom is halfway on a log scale
+        let middle = tween.view_at(2.0);
+        assert!((middle.zoom - (from.zoom * to.zoom).sqrt()).abs() < 1e-9 * middle.zoom);
+        assert_eq!(middle.palette_blend, Some((palette::Palette::Classic, 0.5)));
+        let blended = middle.color(40);
+        let (a, b) = (Mandelbrot { palette: palette::Palette::Classic, ..to.clone() }.color(40), to.color(40));
+        assert!((0..3).all(|i| blended[i].abs_diff(a[i]).max(blended[i].abs_diff(b[i])) <= a[i].abs_diff(b[i]) + 1));
+    }
+
+    #[test]
+    fn easings_run_from_start_to_end() {
+        use animation::Easing;
+        // Progress read off the zoom of a tween a thousandfold deep
+        let to = Mandelbrot { zoom: 1000.0, ..Mandelbrot::new() };
+        let progress = |easing: Easing, t: f64| animation::Animation::tween(&Mandelbrot::new(), to.clone(), 1.0, easing).view_at(t).zoom.log(1000.0);
+        let easings = ["linear", "ease-in", "ease-out", "smoothstep", "exponential", "cubic-bezier(0.25, 0.1, 0.25, 1)"].map(|name| Easing::from_name(name).unwrap());
+        for easing in easings {
+            assert_eq!(Easing::from_name(&easing.name()), Some(easing));
+            let samples: Vec<f64> = (0..=20).map(|i| progress(easing, i as f64 / 20.0 - 1e-12)).collect();
+            assert!(samples[0].abs() < 1e-9 && (samples[20] - 1.0).abs() < 1e-6, "{}", easing.name());
+            assert!(samples.windows(2).all(|pair| pair[1] >= pair[0] - 1e-12), "{}", easing.name());
+        }
+        assert_eq!(Easing::from_name("smoothstep"), Some(Easing::EaseInOut));
+        assert!((progress(Easing::Exponential, 0.5) - 0.5).abs() < 1e-9);
+        assert!(progress(Easing::Exponential, 0.1) < progress(Easing::EaseInOut, 0.1) / 10.0);
+        // Control points on the diagonal are linear
+        assert!((progress(Easing::Bezier([0.2, 0.2, 0.6, 0.6]), 0.3) - 0.3).abs() < 1e-9);
+        assert_eq!(Easing::from_name("cubic-bezier(1.5, 0, 0.5, 1)"), None);
+        assert_eq!(Easing::from_name("cubic-bezier(0.5, 0, 0.5)"), None);
+    }
+
+    #[test]
+    fn recorded_sessions_replay_their_views() {
+        let path = std::env::temp_dir().join("mandelbrot-recording-test.jsonl");
+        let start = Mandelbrot { coloring: coloring::Coloring::Curvature, ..Mandelbrot::new() };
+        let mut recorder = recording::Recorder::create(&path).unwrap();
+        recorder.record(&start).unwrap();
+        recorder.record(&start).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let middle = Mandelbrot { center_x: -0.75, zoom: 40.0, rotation: 0.3, ..start.clone() };
+        recorder.record(&middle).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let end = Mandelbrot { julia: Some(Complex::new(-0.8, 0.156)), palette: palette::Palette::Magma, ..middle.clone() };
+        recorder.record(&end).unwrap();
+        drop(recorder);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let replay = recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The header and a line per frame that navigated
+        assert_eq!(text.lines().count(), 4);
+        assert_eq!(format!("{:?}", replay.view_at(0.0)), format!("{start:?}"));
+        assert_eq!(format!("{:?}", replay.view_at(replay.duration())), format!("{end:?}"));
+        assert!(replay.duration() >= 0.01);
+    }
+
+    #[cfg(feature = "editor")]
+    #[test]
+    fn locations_parse_in_every_format() {
+        let at = |text: &str| location::parse(text, 1.0).map(|location| (location.center, location.zoom));
+        let center = Complex::new(-0.75, 0.1);
+        assert_eq!(at("-0.75, 0.1"), Ok((center, None)));
+        assert_eq!(at(" -7.5e-1 1E-1 4e2 "), Ok((center, Some(400.0))));
+        assert_eq!(at("-0.75+0.1i, 40"), Ok((center, Some(40.0))));
+        assert_eq!(at("-0.75-1e-1i"), Ok((center.conj(), None)));
+        assert_eq!(at("--center=-0.75,0.1 --zoom=40"), Ok((center, Some(40.0))));
+        assert_eq!(at("Re: -0.75\r\nIm: 0.1\r\nZoom: 4E2\r\nIterations: 200\r\n"), Ok((center, Some(250.0))));
+        assert_eq!(at("fractal=mandel center-mag=-0.75/0.1/8 maxiter=500"), Ok((center, Some(10.0))));
+
+        for wrong in ["", "-0.75", "-0.75, x", "1, 2, 3, 4", "-0.75, 0.1, -5", "Re: -0.75", "center-mag=-0.75"] {
+            assert!(location::parse(wrong, 1.0).is_err(), "{wrong}");
+        }
+        // Digits beyond double precision only matter deep enough
+        let precise = "-1.7490581015496773806514394235, 0";
+        assert!(location::parse(precise, 1e6).unwrap().note.is_none());
+        assert!(location::parse(precise, 1e20).unwrap().note.is_some());
+    }
+
+    #[cfg(feature = "editor")]
+    #[test]
+    fn edited_animation_saves_and_loads() {
+        let start = Mandelbrot::new();
+        let mut end = Mandelbrot { center_x: -0.75, center_y: 0.1, zoom: 100.0, julia: Some(Complex::new(-0.8, 0.156)), ..Mandelbrot::new() };
+        end.palette = palette::Palette::Magma;
+        let keyframes = [
+            animation::Keyframe { time: 0.0, view: start.clone(), easing: animation::Easing::default() },
+            animation::Keyframe { time: 1.5, view: Mandelbrot { rotation: 1.0, ..start.clone() }, easing: animation::Easing::Linear },
+            animation::Keyframe { time: 4.0, view: end, easing: animation::Easing::EaseOut },
+        ];
+        let edited = animation::Animation::from_keyframes(&keyframes);
+
+        let path = std::env::temp_dir().join("mandelbrot-timeline-test.toml");
+        std::fs::write(&path, edited.to_toml()).unwrap();
+        // The saved start takes the place of the view it is loaded for
+        let elsewhere = Mandelbrot { center_x: 1.0, zoom: 7.0, ..Mandelbrot::new() };
+        let loaded = animation::Animation::load(&path, &elsewhere).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.duration(), 4.0);
+        for time in [0.0, 0.7, 1.5, 3.0, 4.0] {
+            let (a, b) = (edited.view_at(time), loaded.view_at(time));
+            assert!((a.rotation - b.rotation).abs() < 1e-12);
+            assert_eq!(format!("{:?}", Mandelbrot { rotation: 0.0, ..a }), format!("{:?}", Mandelbrot { rotation: 0.0, ..b }));
+        }
+    }
+
+    #[test]
+    fn clip_writes_animated_webp() {
+        let path = std::env::temp_dir().join("mandelbrot-clip-test.webp");
+        let args: Vec<String> = ["clip", "--kind=zoom-loop", "--size=16x12", "--frames=3", "--loops=2", "--quiet"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain([format!("--out={}", path.display())])
+            .collect();
+        clip::clip_from_args(&Mandelbrot::new(), &args).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let decoder = image_webp::WebPDecoder::new(std::io::Cursor::new(data)).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!((decoder.num_frames(), decoder.dimensions()), (3, (16, 12)));
+        assert_eq!(decoder.loop_count(), image_webp::LoopCount::Times(std::num::NonZeroU16::new(2).unwrap()));
+    }
+
+    #[test]
+    fn stream_repeats_the_latest_frame() {
+        let path = std::env::temp_dir().join("mandelbrot-stream-test.y4m");
+        let args = [format!("--stream={}", path.display()), "--stream-fps=100".to_owned()];
+        let stream = stream::Stream::from_args(&args).unwrap().unwrap();
+        stream.publish(&[255; (WIDTH * HEIGHT * 4) as usize]);
+        let header = format!("YUV4MPEG2 W{WIDTH} H{HEIGHT} F100000:1000 Ip A1:1 C444\n");
+        let frame = 6 + 3 * (WIDTH * HEIGHT) as usize;
+        // Converting frames takes a while in debug builds, so wait for two
+        // and then for the writer to finish the one it is on
+        let size = || std::fs::metadata(&path).map_or(0, |metadata| metadata.len() as usize);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while size() < header.len() + 2 * frame && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        drop(stream);
+        while !size().saturating_sub(header.len()).is_multiple_of(frame) && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(data.starts_with(header.as_bytes()));
+        let frames = &data[header.len()..];
+        assert!(frames.len() >= 2 * frame && frames.len().is_multiple_of(frame));
+        // White is full luma and no chroma
+        assert!(frames.starts_with(b"FRAME\n"));
+        assert_eq!((frames[6], frames[6 + (WIDTH * HEIGHT) as usize]), (235, 128));
+    }
+
+    #[test]
+    fn control_commands_parse() {
+        let parse = |line: &str| serde_json::from_str::<control::Command>(line);
+        assert!(matches!(parse(r#"{"command":"set-center","re":-0.743,"im":0.131}"#), Ok(control::Command::SetCenter { re: Some(re), .. }) if re == -0.743));
+        assert!(matches!(parse(r#"{"command":"set-julia","im":0.5}"#), Ok(control::Command::SetJulia { re: None, im: Some(_) })));
+        assert!(matches!(parse(r#"{"command":"screenshot","out":"shot.png"}"#), Ok(control::Command::Screenshot { size: None, .. })));
+        assert!(matches!(parse(r#"{"command":"get-state"}"#), Ok(control::Command::GetState)));
+        assert!(parse(r#"{"command":"set-zoom"}"#).is_err());
+        assert!(parse(r#"{"command":"explode"}"#).is_err());
+        assert_eq!(control::ok(serde_json::json!({ "zoom": 2.0 })).to_string(), r#"{"ok":true,"zoom":2.0}"#);
+    }
+
+    #[test]
+    fn telemetry_logs_frames_as_csv() {
+        let path = std::env::temp_dir().join(format!("mandelbrot-telemetry-{}.csv", std::process::id()));
+        let args = vec![format!("--telemetry={}", path.display())];
+        let mut log = telemetry::Telemetry::from_args(&args).unwrap().unwrap();
+        let view = Mandelbrot::new();
+        log.record(telemetry::Backend::Cpu, std::time::Duration::from_millis(12), &view, Some(&[10, 20, MAX_ITER, 30]), false).unwrap();
+        log.record(telemetry::Backend::Terrain, std::time::Duration::from_millis(3), &view, None, true).unwrap();
+        drop(log);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<Vec<&str>> = text.lines().map(|line| line.split(',').collect()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0][3], "backend");
+        assert_eq!((lines[1][0], lines[1][2], lines[1][3], lines[1][4]), ("0", "12.000", "cpu", "false"));
+        assert_eq!((lines[1][6], lines[1][7], lines[1][8]), ("20.000", "30", "0.250000"));
+        assert_eq!((lines[2][3], lines[2][6]), ("terrain", ""));
+        assert!(telemetry::Telemetry::from_args(&["--telemetry=frames.txt".to_owned()]).is_err());
+    }
+
+    #[cfg(feature = "osc")]
+    #[test]
+    fn osc_messages_map_to_commands() {
+        let message = |addr: &str, args: Vec<rosc::OscType>| rosc::OscMessage { addr: addr.to_owned(), args };
+        let center = message("/mandelbrot/center", vec![rosc::OscType::Float(-0.5), rosc::OscType::Double(0.25)]);
+        assert!(matches!(osc::command(&center), Some(control::Command::SetCenter { re: Some(re), im: Some(im) }) if re == -0.5 && im == 0.25));
+        let zoom = message("/mandelbrot/zoom", vec![rosc::OscType::Int(100)]);
+        assert!(matches!(osc::command(&zoom), Some(control::Command::SetZoom { zoom }) if zoom == 100.0));
+        let palette = message("/mandelbrot/palette", vec![rosc::OscType::String("magma".to_owned())]);
+        assert!(matches!(osc::command(&palette), Some(control::Command::SetPalette { .. })));
+        assert!(osc::command(&message("/mandelbrot/zoom", vec![])).is_none());
+        assert!(osc::command(&message("/mandelbrot/zoom", vec![rosc::OscType::String("far".to_owned())])).is_none());
+        assert!(osc::command(&message("/other/zoom", vec![rosc::OscType::Float(2.0)])).is_none());
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn remote_control_refuses_other_pages() {
+        // Commands from scripts, and from the page the server serves, pass
+        assert!(remote::same_origin(None, Some("localhost:7878")));
+        assert!(remote::same_origin(Some("http://localhost:7878"), Some("localhost:7878")));
+        assert!(!remote::same_origin(Some("https://example.com"), Some("localhost:7878")));
+        assert!(!remote::same_origin(Some("null"), Some("localhost:7878")));
+        assert!(remote::json(Some("application/json; charset=utf-8")));
+        assert!(!remote::json(Some("text/plain")) && !remote::json(None));
+        assert_eq!(control::network_address("8080", "127.0.0.1"), "127.0.0.1:8080");
+        assert_eq!(control::network_address("0.0.0.0:8080", "127.0.0.1"), "0.0.0.0:8080");
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn bass_beats_stand_out_of_the_running_average() {
+        let tone = |hz: f32, amplitude: f32| -> Vec<f32> {
+            (0..1024).map(|i| amplitude * (2.0 * std::f32::consts::PI * hz * i as f32 / 48000.0).sin()).collect()
+        };
+        let mut history = Vec::new();
+        let first = audio::analyse(&tone(60.0, 0.2), 48000.0, &mut history);
+        assert!((first.energy - 1.0).abs() < 1e-6 && !first.beat);
+        let steady = audio::analyse(&tone(60.0, 0.2), 48000.0, &mut history);
+        assert!((steady.energy - 1.0).abs() < 1e-3 && !steady.beat);
+        // Three times the amplitude is nine times the energy, capped at 4
+        let kick = audio::analyse(&tone(60.0, 0.6), 48000.0, &mut history);
+        assert!(kick.energy == 4.0 && kick.beat);
+        // Treble doesn't count, nor does silence
+        let treble = audio::analyse(&tone(1000.0, 0.6), 48000.0, &mut history);
+        assert!(treble.energy < 0.01 && !treble.beat);
+        let silence = audio::analyse(&[], 48000.0, &mut Vec::new());
+        assert!(silence.energy == 0.0 && !silence.beat);
+
+        assert_eq!(audio::AudioTarget::from_name("palette"), Some(audio::AudioTarget::PaletteOffset));
+        assert_eq!(audio::AudioTarget::from_name("volume"), None);
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn audio_levels_modulate_the_view() {
+        let mut view = Mandelbrot::new();
+        let average = audio::AudioLevels { energy: 1.0, beat: false };
+        let beat = audio::AudioLevels { energy: 2.0, beat: true };
+        assert!((view.apply_audio(audio::AudioTarget::ZoomSpeed, average) - ZOOM_SPEED * ZOOM_SPEED).abs() < 1e-12);
+        assert_eq!(view.palette_offset, 0.0);
+
+        // Beats kick the palette offset, which then decays
+        assert_eq!(view.apply_audio(audio::AudioTarget::PaletteOffset, beat), ZOOM_SPEED);
+        assert_eq!(view.palette_offset, 40.0);
+        view.apply_audio(audio::AudioTarget::PaletteOffset, average);
+        assert_eq!(view.palette_offset, 36.0);
+    }
+
+    #[test]
+    fn auto_range_spans_the_escape_counts() {
+        let counts = [MAX_ITER, 10, 20, 30, 40, 999, 50, 60, 70, 80, 90];
+        assert_eq!(coloring::count_range(&counts, 0.0), Some((10.0, 999.0)));
+        assert_eq!(coloring::count_range(&counts, 10.0), Some((20.0, 90.0)));
+        assert_eq!(coloring::count_range(&[MAX_ITER; 4], 0.0), None);
+
+        let mut view = view_from_args(&["--auto-range=10".to_owned()]).unwrap();
+        assert!(view.normalize_colors(&counts, 0));
+        assert!(!view.normalize_colors(&counts, 0));
+        assert_eq!(view.palette_position(20), 0.0);
+        assert_eq!(view.palette_position(90), palette::PERIOD);
+        assert!(view_from_args(&["--auto-range=50".to_owned()]).is_err());
+
+        // Smoothed ranges move part of the way, and average over neighbours
+        assert!(view.normalize_colors(&[120, 130], 3));
+        assert_eq!(view.color_range, Some((45.0, 100.0)));
+        let ranges = [Some((0.0, 30.0)), None, Some((30.0, 60.0)), Some((60.0, 90.0))];
+        assert_eq!(coloring::smooth_ranges(&ranges, 1, false), [Some((0.0, 30.0)), Some((15.0, 45.0)), Some((45.0, 75.0)), Some((45.0, 75.0))]);
+        assert_eq!(coloring::smooth_ranges(&ranges, 1, true)[0], Some((30.0, 60.0)));
+    }
+
+    #[test]
+    fn effects_run_in_order() {
+        let stages = effects::from_arg("vignette:1,bloom").unwrap();
+        assert_eq!(stages, [effects::Stage { effect: effects::Effect::Vignette, strength: 1.0 }, effects::Stage { effect: effects::Effect::Bloom, strength: 0.6 }]);
+        assert_eq!(effects::from_arg(&effects::to_arg(&stages)["--effects=".len()..]), Some(stages.clone()));
+        assert!(effects::from_arg("blur").is_none());
+        assert!(effects::from_arg("grain:-1").is_none());
+
+        // A full vignette darkens the corners most and leaves the center
+        let mut frame = vec![200; 9 * 9 * 4];
+        effects::apply(&stages[..1], &mut frame, 9, 9);
+        assert!(frame[0] < 50 && frame[(4 * 9 + 4) * 4] == 200);
+    }
+
+    #[test]
+    fn luts_grade_colors() {
+        // Inverts the colors, given as a 2x2x2 table red first
+        let path = std::env::temp_dir().join("mandelbrot-lut-test.cube");
+        let entries: String = (0..8).map(|i| format!("{} {} {}\n", 1 - (i & 1), 1 - (i >> 1 & 1), 1 - (i >> 2 & 1))).collect();
+        std::fs::write(&path, format!("# inverts\nTITLE \"invert\"\nLUT_3D_SIZE 2\n\n{entries}")).unwrap();
+        let lut = lut::Lut::load(&path).unwrap();
+        let mut frame = [0, 64, 255, 255, 128, 128, 128, 7];
+        lut.apply(&mut frame);
+        assert_eq!(frame, [255, 191, 0, 255, 127, 127, 127, 7]);
+
+        std::fs::write(&path, "LUT_3D_SIZE 2\n0 0 0\n").unwrap();
+        assert!(lut::Lut::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn half_floats_round_to_nearest() {
+        assert_eq!(surface::half(0.0), 0);
+        assert_eq!(surface::half(1.0), 0x3c00);
+        assert_eq!(surface::half(-2.5), 0xc100);
+        assert_eq!(surface::half(1.0 + 1.0 / 1024.0), 0x3c01);
+        assert_eq!(surface::half(2f32.powi(-24)), 1);
+        assert_eq!(surface::half(1e6), 0x7c00);
+    }
+
+    #[test]
+    fn new_palettes_recolor_the_rendered_fractal() {
+        let view = Mandelbrot::new();
+        let recolored = Mandelbrot { palette: view.palette.next(), palette_offset: 40.0, ..view.clone() };
+        assert_eq!(viewer::fractal_key(&view), viewer::fractal_key(&recolored));
+        assert_ne!(viewer::colors_key(&view), viewer::colors_key(&recolored));
+        assert_ne!(viewer::fractal_key(&view), viewer::fractal_key(&Mandelbrot { zoom: 2.0, ..view.clone() }));
+
+        // The colorings that follow the orbit render again
+        let stalks = Mandelbrot { coloring: coloring::Coloring::Stalks, ..view };
+        assert_ne!(viewer::fractal_key(&stalks), viewer::fractal_key(&Mandelbrot { palette_offset: 40.0, ..stalks.clone() }));
+    }
+
+    #[test]
+    fn ctrl_click_opens_the_julia_set_of_the_point() {
+        let mut view = Mandelbrot { center_x: -0.75, center_y: 0.1, zoom: 4.0, ..Mandelbrot::new() };
+        view.julia_path = vec![Complex::new(0.0, 0.0), Complex::new(0.25, 0.0)];
+        let mut julia = view.julia_window(WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0);
+        assert_eq!(julia.julia, Some(Complex::new(-0.75, 0.1)));
+        assert_eq!((julia.center_x, julia.center_y, julia.zoom), (0.0, 0.0, 1.0));
+        assert!(julia.julia_path.is_empty() && view.julia.is_none());
+
+        // The corner of the window is the corner of the view
+        let corner = view.julia_window(0.0, 0.0).julia.unwrap();
+        assert!((corner - view.pixel_to_complex(0.0, 0.0)).norm() < 1e-12);
+        julia.toggle_julia(Complex::new(0.0, 0.0));
+        assert_eq!((julia.center_x, julia.center_y, julia.zoom), (-0.75, 0.1, 4.0));
+    }
+
+    #[test]
+    fn interlaced_scan_shows_the_whole_view_first() {
+        use progressive::{Progressive, Scan};
+        let view = Mandelbrot { zoom: 0.25, ..Mandelbrot::new() };
+        let mut tiled = Progressive::new();
+        tiled.render(&view, (0.0, 0.0), std::time::Duration::MAX, false, Scan::Tiles);
+
+        // The first sub-pass covers the view long before the pass completes
+        let mut interlaced = Progressive::new();
+        let covered = |progressive: &Progressive| progressive.image().chunks_exact(4).all(|rgba| rgba[3] != 0);
+        while !covered(&interlaced) {
+            interlaced.render(&view, (0.0, 0.0), std::time::Duration::ZERO, false, Scan::Interlaced);
+        }
+        assert!(!interlaced.idle());
+        assert_ne!(interlaced.counts(), tiled.counts());
+        interlaced.render(&view, (0.0, 0.0), std::time::Duration::MAX, false, Scan::Interlaced);
+        assert!(interlaced.idle() && !interlaced.coarse());
+        assert_eq!(interlaced.counts(), tiled.counts());
+        assert!(Scan::from_name("spiral").is_none());
+    }
+
+    #[test]
+    fn eco_mode_renders_coarsely_and_reads_the_power_supply() {
+        let view = Mandelbrot::new();
+        let mut progressive = progressive::Progressive::new();
+        progressive.render(&view, (0.0, 0.0), std::time::Duration::MAX, true, progressive::Scan::Tiles);
+        assert!(progressive.idle() && progressive.coarse());
+        let coarse = progressive.counts().to_vec();
+        let at = |counts: &[u32], x: usize, y: usize| counts[y * WIDTH as usize + x];
+        let pixels = || (0..HEIGHT as usize).flat_map(|y| (0..WIDTH as usize).map(move |x| (x, y)));
+        assert!(pixels().all(|(x, y)| at(&coarse, x, y) == at(&coarse, x & !1, y & !1)));
+        progressive.render(&view, (0.0, 0.0), std::time::Duration::MAX, false, progressive::Scan::Tiles);
+        assert!(!progressive.coarse());
+        assert!(pixels().all(|(x, y)| x % 2 == 1 || y % 2 == 1 || at(progressive.counts(), x, y) == at(&coarse, x, y)));
+        assert_ne!(progressive.counts(), coarse);
+
+        let earlier = eco::CpuTimes::parse("cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 50 0 50 350 50 0 0 0 0 0").unwrap();
+        assert_eq!(earlier, eco::CpuTimes { busy: 200, total: 1000 });
+        let later = eco::CpuTimes { busy: 290, total: 1100 };
+        assert_eq!(later.load_since(&earlier), Some(90.0));
+        assert_eq!(earlier.load_since(&earlier), None);
+
+        let dir = std::env::temp_dir().join("mandelbrot-eco-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            for (file, value) in files {
+                std::fs::write(dir.join(name).join(file), format!("{value}\n")).unwrap();
+            }
+        };
+        supply("AC", &[("type", "Mains"), ("online", "1")]);
+        supply("BAT0", &[("type", "Battery"), ("status", "Charging")]);
+        assert!(!eco::on_battery(&dir));
+        supply("BAT0", &[("status", "Discharging")]);
+        assert!(eco::on_battery(&dir));
+        assert!(!eco::on_battery(&dir.join("missing")));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(eco::Eco::from_args(&args(&["--eco"])).unwrap().active());
+        assert!(!eco::Eco::from_args(&args(&["--eco=auto"])).unwrap().active());
+        assert!(eco::Eco::from_args(&args(&["--eco=sometimes"])).is_err());
+        assert!(eco::Eco::from_args(&args(&["--eco-load=120"])).is_err());
+    }
+
+    #[test]
+    fn depth_indicator_reads_the_zoom_and_the_cursor() {
+        assert_eq!(depth::magnification(2.5), "2.5×");
+        assert_eq!(depth::magnification(40.0), "40×");
+        assert_eq!(depth::magnification(2.3e12), "10^12×");
+        let mut view = Mandelbrot::new();
+        view.zoom = 4.0;
+        assert_eq!(depth::width(&view), "width = 8.3e-1");
+        assert!(!view.deep());
+        view.zoom = view.deep_zoom() * 2.0;
+        assert!(view.deep());
+
+        let mut frame = vec![200; (WIDTH * HEIGHT * 4) as usize];
+        depth::draw(&mut frame, &view, None);
+        let at = |frame: &[u8], x: u32, y: u32| frame[((y * WIDTH + x) * 4) as usize..][..4].to_vec();
+        assert_eq!(at(&frame, WIDTH - 1, 0), [200; 4]);
+        assert!((0..HEIGHT).any(|y| at(&frame, 9, y) == [100, 100, 100, 200]));
+        assert!((0..HEIGHT).any(|y| (0..WIDTH).any(|x| at(&frame, x, y) == [255, 160, 0, 255])));
+
+        // The point under the cursor goes over the rest, to every digit
+        let c = num::Complex::new(-0.743643887037151, 1.3182590420533e-7);
+        assert_eq!(depth::coordinates(c), ("-0.743643887037151".to_owned(), "1.3182590420533e-7".to_owned()));
+        let mut with_cursor = vec![200; (WIDTH * HEIGHT * 4) as usize];
+        depth::draw(&mut with_cursor, &view, Some(c));
+        let panel_top = |frame: &[u8]| (0..HEIGHT).position(|y| at(frame, 9, y) != [200; 4]);
+        assert!(panel_top(&with_cursor) < panel_top(&frame));
+        #[cfg(feature = "editor")]
+        {
+            let (re, im) = depth::coordinates(c);
+            assert_eq!(location::parse(&format!("{re}, {im}"), 1.0).unwrap().center, c);
+        }
+    }
+
+    #[test]
+    fn minimap_outlines_the_view_and_moves_it() {
+        let map = minimap::Minimap::new();
+        let drawn = |view: &Mandelbrot| {
+            let mut frame = vec![0; (WIDTH * HEIGHT * 4) as usize];
+            map.draw(&mut frame, view);
+            let mut clean = vec![0; (WIDTH * HEIGHT * 4) as usize];
+            map.draw(&mut clean, &Mandelbrot { center_x: 10.0, ..view.clone() });
+            frame.chunks_exact(4).zip(clean.chunks_exact(4)).filter(|(a, b)| a != b).count()
+        };
+        // Zoomed in twice on the middle of the map, the view outlines a
+        // quarter of it, and deep down an arrow points to it
+        let mut view = Mandelbrot { center_x: -0.75, zoom: 2.0, ..Mandelbrot::new() };
+        let outlined = drawn(&view);
+        assert!(outlined > 2 * 70 + 2 * 50, "{outlined} pixels outlined");
+        view.zoom = 1e6;
+        let arrow = drawn(&view);
+        assert!(arrow > 10 && arrow < 60, "{arrow} pixels point to the view");
+
+        let mut map = map;
+        let (x, y) = (WIDTH as usize - 8 - 80, 8 + 60);
+        assert!(minimap::Minimap::contains(x, y) && !minimap::Minimap::contains(x, 8 + 120));
+        let press = accessibility::Buttons { left_pressed: true, left_held: true, right_pressed: false, right_released: false, middle_pressed: false };
+        let c = map.update(&press, Some((x, y))).unwrap();
+        assert!((c - num::Complex::new(-0.75, 0.0)).norm() < 0.05, "{c}");
+        assert!(map.dragging());
+        let release = accessibility::Buttons { left_pressed: false, left_held: false, ..press };
+        assert_eq!(map.update(&release, Some((x, y))), None);
+        assert!(map.update(&press, Some((0, 0))).is_none() && !map.dragging());
+    }
+
+    #[test]
+    fn adaptive_iterations_follow_the_boundary() {
+        let fixed = Mandelbrot { center_x: -0.743643887, center_y: 0.131825904, zoom: 2e4, ..Mandelbrot::new() };
+        let adaptive = Mandelbrot { adaptive_iterations: Some(16 * MAX_ITER), ..fixed.clone() };
+        let (width, height) = (64, 48);
+        let points: Vec<_> = (0..width * height).map(|i| fixed.pixel_to_complex((i % width) as f64 * 12.5, (i / width) as f64 * 12.5)).collect();
+        let shade = |view: &Mandelbrot| {
+            let (mut counts, mut colors) = (vec![0; points.len()], vec![0; points.len() * 4]);
+            view.shade_block(&points, width, &mut counts, &mut colors);
+            counts
+        };
+        let (fixed, adaptive) = (shade(&fixed), shade(&adaptive));
+        let escaped = fixed.iter().filter(|&&m| m < MAX_ITER).count();
+        let same = fixed.iter().zip(&adaptive).filter(|&(&a, &b)| a < MAX_ITER && a == b).count();
+        let deeper = fixed.iter().zip(&adaptive).filter(|&(&a, &b)| a == MAX_ITER && b > MAX_ITER).count();
+        assert!(same as f64 >= 0.99 * escaped as f64, "{same} of {escaped} escape counts kept");
+        assert!(deeper > 0, "no escapes past {MAX_ITER}");
+        assert!(view_from_args(&["--adaptive-iterations=0".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn high_contrast_outlines_overlays() {
+        let mut frame = vec![100; (WIDTH * HEIGHT * 4) as usize];
+        let under = frame.clone();
+        overlay::draw_crosshair(&mut frame, (10, 10), 2, [255, 255, 255, 255]);
+        accessibility::outline(&mut frame, &under);
+        let at = |x: u32, y: u32| &frame[((y * WIDTH + x) * 4) as usize..][..4];
+        assert_eq!(at(10, 10), [255, 255, 255, 255]);
+        assert_eq!(at(11, 11), [0, 0, 0, 255]);
+        assert_eq!(at(13, 10), [0, 0, 0, 255]);
+        assert_eq!(at(14, 10), [100; 4]);
+
+        assert_eq!(palette::Palette::from_name("high-contrast"), Some(palette::Palette::HighContrast));
+        assert_eq!(palette::Palette::HighContrast.next().previous(), palette::Palette::HighContrast);
+    }
+
+    /// `bytes` with a few random bytes changed, dropped, added or cut off,
+    /// the way files get damaged.
+    fn damage(rng: &mut impl Rng, bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        for _ in 0..rng.gen_range(1..8) {
+            let at = rng.gen_range(0..=bytes.len());
+            match rng.gen_range(0..4) {
+                0 if at < bytes.len() => bytes[at] = rng.gen(),
+                1 if at < bytes.len() => drop(bytes.remove(at)),
+                2 => bytes.insert(at, *b" -.,:#e0123456789[]=\"\n".get(rng.gen_range(0..23)).unwrap_or(&b'x')),
+                _ => bytes.truncate(at),
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn damaged_inputs_fail_without_panicking() {
+        let path = std::env::temp_dir().join("mandelbrot-damaged-test");
+        let view = Mandelbrot::new();
+        let lut: String = "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\n".to_owned() + &(0..8).map(|i| format!("{} {} {}\n", i & 1, i >> 1 & 1, i >> 2 & 1)).collect::<String>();
+        let animation = "[start]\nzoom = 2\n[[segment]]\nduration = 1\ncenter = [-0.7, 0.1]\npalette = \"magma\"\njulia = [0.3, 0.5]\n";
+        let mut png = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png, 4, 4);
+            encoder.set_color(png::ColorType::Rgba);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&(0..64).map(|i| i as u8 * 4).collect::<Vec<_>>()).unwrap();
+        }
+
+        for_random_cases(|rng| {
+            let text = |rng: &mut rand::rngs::StdRng, valid: &str| String::from_utf8_lossy(&damage(rng, valid.as_bytes())).into_owned();
+            palette::Palette::from_name(&text(rng, "#1a0f3c,#4d3a8a,#8a6fc0,#c4b0e0,#fff0ff"));
+            effects::from_arg(&text(rng, "bloom:0.5,vignette,grain:0.1"));
+            skew::from_arg(&text(rng, "1.5,0.2,-0.1,0.8"));
+            projection::Projection::from_mobius_arg(&text(rng, "1,0,1,2i"));
+            rays::Angle::from_arg(&text(rng, "1/3"));
+            let _ = view_from_args(&[text(rng, "--center=-0.75,0.1"), text(rng, "--zoom=1e3"), text(rng, "--coloring=stalks")]);
+
+            std::fs::write(&path, text(rng, &lut)).unwrap();
+            let _ = lut::Lut::load(&path);
+            std::fs::write(&path, text(rng, animation)).unwrap();
+            let _ = animation::Animation::load(&path, &view);
+            std::fs::write(&path, damage(rng, &png)).unwrap();
+            let _ = palette::Palette::from_image(&path);
+            let _ = trap::TrapImage::load(&path);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
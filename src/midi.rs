@@ -0,0 +1,152 @@
+//! MIDI controller mapping of the viewer parameters.
+//!
+//! `--midi=<mapping.toml>` listens to a MIDI input with midir, the first one
+//! or the first whose name contains `--midi-port=<name>`, and sets viewer
+//! parameters from its control changes as knobs and faders move. The mapping
+//! file lists which controller sets which parameter over which range:
+//!
+//! ```toml
+//! # Knob 1 from zooming out slowly to zooming in fast
+//! [[control]]
+//! cc = 1
+//! target = "zoom-speed"
+//! min = 0.98
+//! max = 1.05
+//!
+//! # Faders 16 and 17 on channel 10 move the Julia parameter
+//! [[control]]
+//! channel = 10
+//! cc = 16
+//! target = "julia-re"
+//! min = -2
+//! max = 1
+//! ```
+//!
+//! Targets are `zoom-speed`, the factor the view zooms by per frame,
+//! `palette-offset`, and `julia-re` and `julia-im`, the parts of the Julia
+//! parameter, which apply while showing a Julia set. Controllers on any
+//! channel match without a `channel`. The iteration count is fixed when the
+//! viewer is built, so no controller can set it.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use midir::{MidiInput, MidiInputConnection};
+use serde::Deserialize;
+use winit::event_loop::EventLoopProxy;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Status byte of control changes, with the channel in the low bits
+const CONTROL_CHANGE: u8 = 0xb0;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Mapping {
+    #[serde(rename = "control", default)]
+    controls: Vec<Control>,
+}
+
+/// A controller and the parameter it sets
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Control {
+    /// Channel from 1 to 16, any channel when `None`
+    channel: Option<u8>,
+    cc: u8,
+    target: MidiTarget,
+    min: f64,
+    max: f64,
+}
+
+/// Viewer parameter set by a controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MidiTarget {
+    ZoomSpeed,
+    PaletteOffset,
+    JuliaRe,
+    JuliaIm,
+}
+
+/// Parameters set by the controllers, `None` where they haven't moved since
+/// the last frame. The zoom speed stays set once it has been.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MidiLevels {
+    pub zoom_speed: Option<f64>,
+    pub palette_offset: Option<f64>,
+    pub julia_re: Option<f64>,
+    pub julia_im: Option<f64>,
+}
+
+/// Listens to a MIDI input and collects the parameters it sets.
+pub struct MidiControls {
+    // The connection stops listening when dropped
+    _connection: MidiInputConnection<()>,
+    levels: Arc<Mutex<MidiLevels>>,
+}
+
+impl MidiControls {
+    /// Connect to the input named like `port`, or the first one, with the
+    /// mapping file at `path`. Every control change wakes the event loop
+    /// through `proxy`.
+    pub fn connect(path: &Path, port: Option<&str>, proxy: EventLoopProxy<()>) -> Result<Self, BoxError> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let mapping: Mapping = toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        if let Some(control) = mapping.controls.iter().find(|control| control.channel.is_some_and(|channel| !(1..=16).contains(&channel))) {
+            return Err(format!("{}: no MIDI channel {}", path.display(), control.channel.unwrap_or_default()).into());
+        }
+
+        let input = MidiInput::new("mandelbrot")?;
+        let ports = input.ports();
+        let chosen = ports.iter().find(|candidate| {
+            let name = input.port_name(candidate).unwrap_or_default();
+            port.is_none_or(|port| name.contains(port))
+        });
+        let chosen = chosen.ok_or_else(|| match port {
+            Some(port) => format!("no MIDI input named like `{port}`"),
+            None => "no MIDI input available".to_owned(),
+        })?;
+        eprintln!("listening to MIDI input {}", input.port_name(chosen).unwrap_or_default());
+
+        let levels = Arc::new(Mutex::new(MidiLevels::default()));
+        let shared = levels.clone();
+        let connection = input
+            .connect(
+                chosen,
+                "mandelbrot-controls",
+                move |_, message, _| {
+                    let &[status, cc, value] = message else { return };
+                    if status & 0xf0 != CONTROL_CHANGE {
+                        return;
+                    }
+                    let channel = (status & 0x0f) + 1;
+                    let Ok(mut levels) = shared.lock() else { return };
+                    let t = value as f64 / 127.0;
+                    for control in &mapping.controls {
+                        if control.cc == cc && control.channel.is_none_or(|c| c == channel) {
+                            let value = Some(control.min + (control.max - control.min) * t);
+                            match control.target {
+                                MidiTarget::ZoomSpeed => levels.zoom_speed = value,
+                                MidiTarget::PaletteOffset => levels.palette_offset = value,
+                                MidiTarget::JuliaRe => levels.julia_re = value,
+                                MidiTarget::JuliaIm => levels.julia_im = value,
+                            }
+                        }
+                    }
+                    let _ = proxy.send_event(());
+                },
+                (),
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(Self { _connection: connection, levels })
+    }
+
+    /// The parameters set since the last frame.
+    pub fn levels(&self) -> MidiLevels {
+        let Ok(mut levels) = self.levels.lock() else { return MidiLevels::default() };
+        let current = *levels;
+        *levels = MidiLevels { zoom_speed: current.zoom_speed, ..MidiLevels::default() };
+        current
+    }
+}
@@ -0,0 +1,147 @@
+//! Minibrots: finding them and framing them.
+//!
+//! The view center lies in the atom domain of a minibrot, the one whose
+//! period is the last iteration at which its orbit came closer to zero than
+//! ever before, or inside the set, the period of the cycle it settles on. Newton's
+//! method on that iterate finds the nucleus, where the orbit returns to zero
+//! exactly, and the derivatives along the orbit of the nucleus give the size
+//! and the angle of the minibrot as a copy of the whole set, after
+//! Claude Heiland-Allen's estimate.
+//!
+//! Shift+Z flies to frame the minibrot of the view center, upright and
+//! spanning 40% of the view, and `mandelbrot minibrot [--frame=<percent>]`
+//! prints the minibrot of the initial view with the options that frame it.
+//! The nucleus is found in `f64` like the view center, so minibrots smaller
+//! than about 1e-13 come out blurred or not at all.
+
+use num::Complex;
+use pixels::Error;
+
+use crate::{arg_value, invalid_arg, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Part of the view the framed minibrot spans by default
+pub const FRAME: f64 = 0.4;
+/// Bounding box of the whole set, which minibrots are scaled and rotated
+/// copies of, by its center and size
+const SET_CENTER: f64 = -0.7642;
+const SET_WIDTH: f64 = 2.4717;
+const SET_HEIGHT: f64 = 2.2452;
+const NEWTON_STEPS: usize = 64;
+/// How close an orbit returns to where it was to count as a cycle, or to
+/// zero to pass through the nucleus
+const CYCLE_TOLERANCE: f64 = 1e-9;
+
+/// A minibrot of the Mandelbrot set
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Minibrot {
+    pub period: u32,
+    pub nucleus: Complex<f64>,
+    /// Scale and rotation of the minibrot as a copy of the whole set
+    pub size: Complex<f64>,
+}
+
+impl Minibrot {
+    /// The minibrot whose atom domain `c` is in, if Newton's method finds
+    /// its nucleus.
+    pub fn near(c: Complex<f64>) -> Option<Self> {
+        let nucleus = nucleus(c, atom_period(c))?;
+        // Newton's method may land on a nucleus of a period dividing the one
+        // it looked for
+        let mut z = Complex::new(0.0, 0.0);
+        let period = (1..MAX_ITER).find(|_| {
+            z = z * z + nucleus;
+            z.norm() < CYCLE_TOLERANCE
+        })?;
+        // The derivatives by z and by c, of the orbit through the nucleus
+        let (mut z, mut dz, mut b) = (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), Complex::new(1.0, 0.0));
+        for _ in 1..period {
+            z = z * z + nucleus;
+            dz = 2.0 * z * dz;
+            b += 1.0 / dz;
+        }
+        let size = 1.0 / (b * dz * dz);
+        size.is_finite().then_some(Self { period, nucleus, size })
+    }
+
+    /// `view` moved to show the minibrot upright, spanning `fraction` of the
+    /// view along whichever of its sides is the tighter fit.
+    pub fn framed(&self, view: &Mandelbrot, fraction: f64) -> Mandelbrot {
+        let center = self.nucleus + self.size * SET_CENTER;
+        let scale = self.size.norm();
+        let span = (SET_HEIGHT * scale).max(SET_WIDTH * scale * HEIGHT as f64 / WIDTH as f64);
+        Mandelbrot {
+            center_x: center.re,
+            center_y: center.im,
+            zoom: 2.5 * fraction / span,
+            rotation: self.size.arg(),
+            julia: None,
+            ..view.clone()
+        }
+    }
+}
+
+/// Period of the atom domain `c` is in: the last iteration at which its
+/// orbit came closer to zero than ever before, or for points that don't
+/// escape, the period of the cycle the orbit settles on.
+fn atom_period(c: Complex<f64>) -> u32 {
+    let (mut z, mut closest, mut period) = (c, f64::INFINITY, 1);
+    for n in 1..MAX_ITER {
+        if z.norm_sqr() > 4.0 {
+            return period;
+        }
+        if z.norm_sqr() < closest {
+            (closest, period) = (z.norm_sqr(), n);
+        }
+        z = z * z + c;
+    }
+    let settled = z;
+    for n in 1..MAX_ITER {
+        z = z * z + c;
+        if (z - settled).norm() < CYCLE_TOLERANCE {
+            return n;
+        }
+    }
+    period
+}
+
+/// The nucleus of period `period` that Newton's method reaches from `c`.
+fn nucleus(mut c: Complex<f64>, period: u32) -> Option<Complex<f64>> {
+    for _ in 0..NEWTON_STEPS {
+        let (mut z, mut dc) = (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0));
+        for _ in 0..period {
+            dc = 2.0 * z * dc + 1.0;
+            z = z * z + c;
+        }
+        let step = z / dc;
+        if !step.is_finite() {
+            return None;
+        }
+        c -= step;
+        if step.norm() <= c.norm() * f64::EPSILON {
+            return Some(c);
+        }
+    }
+    // Converged as far as rounding lets it, or not at all
+    let mut z = Complex::new(0.0, 0.0);
+    for _ in 0..period {
+        z = z * z + c;
+    }
+    (z.norm() < CYCLE_TOLERANCE).then_some(c)
+}
+
+/// Run the `minibrot` command.
+pub fn minibrot_from_args(mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    let fraction = match arg_value(args, "frame") {
+        Some(value) => value.parse::<f64>().ok().filter(|percent| *percent > 0.0 && *percent <= 100.0).ok_or_else(|| invalid_arg("frame", &value))? / 100.0,
+        None => FRAME,
+    };
+    if mandelbrot.julia.is_some() {
+        return Err(Error::UserDefined("minibrots are in the Mandelbrot set, not in Julia sets".into()));
+    }
+    let center = Complex::new(mandelbrot.center_x, mandelbrot.center_y);
+    let minibrot = Minibrot::near(center).ok_or_else(|| Error::UserDefined(format!("no minibrot nucleus found near {center}").into()))?;
+    println!("period {} nucleus {} size {:e} angle {:.2}°", minibrot.period, minibrot.nucleus, minibrot.size.norm(), minibrot.size.arg().to_degrees());
+    let framed = minibrot.framed(mandelbrot, fraction);
+    println!("--center={},{} --zoom={} --rotation={}", framed.center_x, framed.center_y, framed.zoom, framed.rotation.to_degrees());
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! Minimap of the whole set.
+//!
+//! U, or `--minimap` from the start, shows a map of the whole Mandelbrot set
+//! in the top right corner of the window, outlining where the view is on
+//! it: its edges as the projections and the exponential map bend them, or
+//! once the view is too small to outline, an arrow pointing at it. Clicking
+//! the map, or dragging on it, moves the view center there at the same zoom.
+//! Julia sets have the inset map of their parameter in its place.
+
+use num::Complex;
+
+use crate::accessibility::Buttons;
+use crate::overlay;
+use crate::{Mandelbrot, HEIGHT, WIDTH};
+
+/// Size of the map, which has the aspect ratio of the window
+const MINIMAP_WIDTH: usize = 160;
+const MINIMAP_HEIGHT: usize = 120;
+/// Distance of the map from the top right corner of the window
+const MINIMAP_MARGIN: usize = 8;
+/// Points along each edge of the view its outline passes through
+const EDGE_POINTS: usize = 16;
+/// Map pixels across below which the view is pointed at rather than outlined
+const MIN_OUTLINE: f64 = 4.0;
+/// Length of the arrow in map pixels, and of its head
+const ARROW_LENGTH: f64 = 16.0;
+const ARROW_HEAD: f64 = 5.0;
+const OUTLINE_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+pub struct Minimap {
+    /// The whole set as the map shows it
+    view: Mandelbrot,
+    image: Vec<[u8; 4]>,
+    dragging: bool,
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        let mut view = Mandelbrot::new();
+        (view.center_x, view.center_y, view.zoom) = (-0.75, 0.0, 1.0);
+
+        // The map never changes, so render it once at its own resolution
+        let image = (0..MINIMAP_WIDTH * MINIMAP_HEIGHT)
+            .map(|i| {
+                let (x, y) = to_window((i % MINIMAP_WIDTH) as f64, (i / MINIMAP_WIDTH) as f64);
+                view.color(view.iterate(x, y).0)
+            })
+            .collect();
+
+        Self { view, image, dragging: false }
+    }
+
+    /// Whether frame buffer pixel `x`, `y` is on the map.
+    pub fn contains(x: usize, y: usize) -> bool {
+        (left()..left() + MINIMAP_WIDTH).contains(&x) && (MINIMAP_MARGIN..MINIMAP_MARGIN + MINIMAP_HEIGHT).contains(&y)
+    }
+
+    /// Whether a drag started on the map is going on.
+    pub fn dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Track clicks and drags on the map, returning the point of the plane
+    /// under the cursor while the left button is held after going down on
+    /// the map. `cursor` is in frame buffer pixels.
+    pub fn update(&mut self, buttons: &Buttons, cursor: Option<(usize, usize)>) -> Option<Complex<f64>> {
+        if buttons.left_pressed {
+            self.dragging = cursor.is_some_and(|(x, y)| Self::contains(x, y));
+        }
+        if !buttons.left_held {
+            self.dragging = false;
+        }
+        if !self.dragging {
+            return None;
+        }
+
+        // Keep following the cursor when it leaves the map mid-drag
+        let (x, y) = cursor?;
+        let mx = (x as f64 - left() as f64).clamp(0.0, MINIMAP_WIDTH as f64 - 1.0);
+        let my = (y as f64 - MINIMAP_MARGIN as f64).clamp(0.0, MINIMAP_HEIGHT as f64 - 1.0);
+        let (px, py) = to_window(mx, my);
+        Some(self.view.pixel_to_complex(px, py))
+    }
+
+    /// Draw the map with where `view` is on it to the frame buffer.
+    pub fn draw(&self, frame: &mut [u8], view: &Mandelbrot) {
+        let width = WIDTH as usize;
+        for (row, line) in self.image.chunks_exact(MINIMAP_WIDTH).enumerate() {
+            let start = ((MINIMAP_MARGIN + row) * width + left()) * 4;
+            for (pixel, rgba) in frame[start..start + MINIMAP_WIDTH * 4].chunks_exact_mut(4).zip(line) {
+                pixel.copy_from_slice(rgba);
+            }
+        }
+
+        // The edges of the view, clockwise from the top left corner
+        let (w, h) = (WIDTH as f64, HEIGHT as f64);
+        let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h), (0.0, 0.0)];
+        let edges = corners.windows(2).flat_map(|edge| {
+            let ((x0, y0), (x1, y1)) = (edge[0], edge[1]);
+            (0..EDGE_POINTS).map(move |i| {
+                let t = i as f64 / EDGE_POINTS as f64;
+                (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)
+            })
+        });
+        // Points the projections send to infinity are left out
+        let mut outline: Vec<(f64, f64)> = edges
+            .map(|(x, y)| self.to_frame(view.projection.apply(view.pixel_to_complex(x, y))))
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+            .collect();
+        let Some(&first) = outline.first() else {
+            return;
+        };
+        outline.push(first);
+        let extent = |coordinate: fn(&(f64, f64)) -> f64| {
+            let (min, max) = outline.iter().map(coordinate).fold((f64::MAX, f64::MIN), |(min, max), value| (min.min(value), max.max(value)));
+            max - min
+        };
+        if extent(|point| point.0).max(extent(|point| point.1)) >= MIN_OUTLINE {
+            return overlay::draw_polyline(frame, &clip(&outline), OUTLINE_COLOR);
+        }
+
+        // An arrow from the middle of the map, or from its right where the
+        // view is in the middle, to the view
+        let tip = self.to_frame(view.projection.apply(view.pixel_to_complex(w / 2.0, h / 2.0)));
+        let middle = (left() as f64 + MINIMAP_WIDTH as f64 / 2.0, MINIMAP_MARGIN as f64 + MINIMAP_HEIGHT as f64 / 2.0);
+        let (dx, dy) = (middle.0 - tip.0, middle.1 - tip.1);
+        let length = dx.hypot(dy);
+        let (dx, dy) = if length < 1.0 { (1.0, 0.0) } else { (dx / length, dy / length) };
+        let at = |along: f64, aside: f64| (tip.0 + dx * along - dy * aside, tip.1 + dy * along + dx * aside);
+        let arrow = [at(ARROW_LENGTH, 0.0), tip, at(ARROW_HEAD, ARROW_HEAD), tip, at(ARROW_HEAD, -ARROW_HEAD)];
+        overlay::draw_polyline(frame, &clip(&arrow), OUTLINE_COLOR);
+    }
+
+    /// The frame buffer pixel of the map showing point `c` of the plane.
+    fn to_frame(&self, c: Complex<f64>) -> (f64, f64) {
+        let (x, y) = self.view.complex_to_pixel(c);
+        (left() as f64 + x * MINIMAP_WIDTH as f64 / WIDTH as f64, MINIMAP_MARGIN as f64 + y * MINIMAP_HEIGHT as f64 / HEIGHT as f64)
+    }
+}
+
+fn left() -> usize {
+    WIDTH as usize - MINIMAP_WIDTH - MINIMAP_MARGIN
+}
+
+/// Scale map pixel coordinates up to the full window the map's view maps.
+fn to_window(x: f64, y: f64) -> (f64, f64) {
+    (x * WIDTH as f64 / MINIMAP_WIDTH as f64, y * HEIGHT as f64 / MINIMAP_HEIGHT as f64)
+}
+
+/// `points` clamped onto the map, so lines leaving it run along its edge.
+fn clip(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let (x0, y0) = (left() as f64, MINIMAP_MARGIN as f64);
+    let (x1, y1) = (x0 + MINIMAP_WIDTH as f64 - 1.0, y0 + MINIMAP_HEIGHT as f64 - 1.0);
+    points.iter().map(|&(x, y)| (x.clamp(x0, x1), y.clamp(y0, y1))).collect()
+}
@@ -0,0 +1,176 @@
+//! Zooming out past the whole set.
+//!
+//! Below a zoom of 1/2 the set covers fewer pixels than show its detail, and
+//! points sampled at the pixel centers only alias. Such views take colors
+//! from a mipmap instead: the square around the origin that holds the set,
+//! rendered once at the detail of a zoom of 1/2 and averaged down level by
+//! level to a single texel. Each pixel blends the two levels nearest to its
+//! size, so the set shrinks smoothly into a dot, and the plane beyond the
+//! square, where every point escapes at once, is iterated directly. Views
+//! stop zooming out at `MIN_ZOOM`, where the set is far below a pixel.
+//!
+//! This covers Mandelbrot and Julia sets on the plain plane in the viewer.
+//! The mipmap is kept for later passes while the view only moves, zooms and
+//! turns.
+
+use num::Complex;
+
+use crate::projection::Projection;
+use crate::{Mandelbrot, HEIGHT};
+
+/// Zoom below which views are mipmapped
+const MIPMAP_ZOOM: f64 = 0.5;
+/// Smallest zoom of a view
+pub const MIN_ZOOM: f64 = 1e-6;
+/// Texels along a side of the largest level
+const BASE_SIZE: usize = 512;
+
+/// Escape counts and colors of one level, row by row
+struct Level {
+    size: usize,
+    colors: Vec<[u8; 4]>,
+    counts: Vec<u32>,
+}
+
+/// The square around the origin holding the set, at every level of detail
+pub struct Mipmap {
+    /// The view the mipmap is for, but for where it looks
+    key: String,
+    /// Half the side of the square
+    radius: f64,
+    /// The largest level first, each level half the size of the one before
+    levels: Vec<Level>,
+}
+
+impl Mipmap {
+    /// The mipmap of `view` if it is mipmapped, reusing `previous`, the
+    /// mipmap of an earlier frame, where it still serves.
+    pub fn reuse(view: &Mandelbrot, previous: Option<Self>) -> Option<Self> {
+        if !view.mipmapped() {
+            return None;
+        }
+        let key = key(view);
+        match previous {
+            Some(previous) if previous.key == key => Some(previous),
+            _ => Some(Self::new(view, key)),
+        }
+    }
+
+    fn new(view: &Mandelbrot, key: String) -> Self {
+        // Orbits starting farther out escape at once, and the Julia sets of
+        // parameters up to the radius lie inside it
+        let radius = view.bailout_radius.max(2.0).max(view.julia.map_or(0.0, |c| c.norm()));
+        let texel = 2.0 * radius / BASE_SIZE as f64;
+        // Pixels the size of the texels, for the colorings that draw lines a
+        // pixel wide
+        let base_view = Mandelbrot { zoom: 2.5 / (HEIGHT as f64 * texel), ..view.clone() };
+
+        let points: Vec<_> = (0..BASE_SIZE * BASE_SIZE)
+            .map(|i| Complex::new(((i % BASE_SIZE) as f64 + 0.5) * texel - radius, ((i / BASE_SIZE) as f64 + 0.5) * texel - radius))
+            .collect();
+        let mut counts = vec![0; points.len()];
+        let mut colors = vec![0; points.len() * 4];
+        base_view.shade_batch(&points, &mut counts, &mut colors);
+        let colors = colors.chunks_exact(4).map(|rgba| [rgba[0], rgba[1], rgba[2], rgba[3]]).collect();
+
+        let mut levels = vec![Level { size: BASE_SIZE, colors, counts }];
+        while let Some(level) = levels.last().filter(|level| level.size > 1) {
+            levels.push(level.halved());
+        }
+        Self { key, radius, levels }
+    }
+
+    /// Color and escape count of a pixel of `footprint` across around the
+    /// point of the plane at `point`, `None` outside the square.
+    fn sample(&self, point: Complex<f64>, footprint: f64) -> Option<([u8; 4], u32)> {
+        // Texel coordinates of the largest level
+        let u = (point.re + self.radius) / (2.0 * self.radius);
+        let v = (point.im + self.radius) / (2.0 * self.radius);
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return None;
+        }
+        let depth = (footprint * BASE_SIZE as f64 / (2.0 * self.radius)).log2().clamp(0.0, (self.levels.len() - 1) as f64);
+        let below = depth.floor() as usize;
+        let above = (below + 1).min(self.levels.len() - 1);
+        let rgba = mix(self.levels[below].bilinear(u, v), self.levels[above].bilinear(u, v), depth - below as f64);
+
+        let base = &self.levels[0];
+        let count = base.counts[(v * base.size as f64) as usize * base.size + (u * base.size as f64) as usize];
+        Some((rgba.map(|channel| channel.round() as u8), count))
+    }
+}
+
+impl Level {
+    /// The level of half the size, each texel the average of four, and the
+    /// escape count of the first of them.
+    fn halved(&self) -> Level {
+        let size = self.size / 2;
+        let at = |x: usize, y: usize| y * self.size + x;
+        let mut colors = Vec::with_capacity(size * size);
+        let mut counts = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                let texels = [at(2 * x, 2 * y), at(2 * x + 1, 2 * y), at(2 * x, 2 * y + 1), at(2 * x + 1, 2 * y + 1)];
+                colors.push(std::array::from_fn(|i| (texels.iter().map(|&t| self.colors[t][i] as u32).sum::<u32>() / 4) as u8));
+                counts.push(self.counts[texels[0]]);
+            }
+        }
+        Level { size, colors, counts }
+    }
+
+    /// Color at texture coordinates `u` and `v` between 0 and 1, interpolated
+    /// between the nearest texel centers.
+    fn bilinear(&self, u: f64, v: f64) -> [f64; 4] {
+        let last = (self.size - 1) as f64;
+        let (x, y) = ((u * self.size as f64 - 0.5).clamp(0.0, last), (v * self.size as f64 - 0.5).clamp(0.0, last));
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+        let texel = |x: usize, y: usize| self.colors[y * self.size + x].map(|channel| channel as f64);
+        let top = mix(texel(x0, y0), texel(x1, y0), x - x0 as f64);
+        let bottom = mix(texel(x0, y1), texel(x1, y1), x - x0 as f64);
+        mix(top, bottom, y - y0 as f64)
+    }
+}
+
+fn mix(a: [f64; 4], b: [f64; 4], t: f64) -> [f64; 4] {
+    std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// What a mipmap depends on: the view but for its center, zoom, rotation and
+/// skew, and what is drawn over it.
+fn key(view: &Mandelbrot) -> String {
+    let view = Mandelbrot {
+        center_x: 0.0,
+        center_y: 0.0,
+        zoom: 1.0,
+        rotation: 0.0,
+        skew: crate::skew::IDENTITY,
+        contours: false,
+        morph: None,
+        julia_path: Vec::new(),
+        ..view.clone()
+    };
+    format!("{view:?}")
+}
+
+impl Mandelbrot {
+    /// Whether the view is zoomed out far enough to take its colors from a
+    /// `Mipmap`.
+    pub fn mipmapped(&self) -> bool {
+        self.zoom < MIPMAP_ZOOM && !self.exponential_map && self.projection == Projection::Plane && !self.multibrot()
+    }
+
+    /// Escape counts and colors of the points of the view plane at `points`
+    /// from `mipmap`, like `shade_batch`.
+    pub fn shade_mipmapped(&self, mipmap: &Mipmap, points: &[Complex<f64>], counts: &mut [u32], colors: &mut [u8]) {
+        let footprint = 2.5 / self.zoom / HEIGHT as f64;
+        for ((&point, count), pixel) in points.iter().zip(counts).zip(colors.chunks_exact_mut(4)) {
+            let (rgba, m) = mipmap.sample(point, footprint).unwrap_or_else(|| {
+                let (m, rgba) = self.shade_at(point);
+                (rgba, m)
+            });
+            *count = m;
+            pixel.copy_from_slice(&rgba);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Multibrot sets.
+//!
+//! `--exponent=<d>` iterates `z -> z^d + c` instead of `z -> z^2 + c`,
+//! showing the Multibrot set of degree `d`, with `d - 1` fold symmetry, or
+//! with `--julia` its Julia sets. The exponent needn't be whole: in between
+//! the sets grow their extra lobes out of a cut along the negative real axis,
+//! where the powers of the arguments wrap around. Whole exponents up to 64
+//! are powers by repeated squaring, others go through polar form.
+//!
+//! The exponent animates like the other values of a view: animation files
+//! and the timeline set it with `exponent = <d>`, which is interpolated
+//! linearly, and `--sweep=<from>,<to>` sets up a sweep from one exponent to
+//! the other and back, which Shift+Y plays in the viewer and `zoom-video
+//! --sweep` renders, see [`crate::animation`].
+//!
+//! Multibrot views are rendered on the CPU in double precision, pixel by
+//! pixel: neither the GPU, the SIMD kernels, perturbation nor the mipmap of
+//! the zoomed out views knows other exponents. The external rays, minibrots,
+//! boundary tracking, the minimap and the inset map of Julia mode stay those
+//! of the Mandelbrot set.
+
+use num::Complex;
+
+use mandelbrot_core::Bailout;
+
+use crate::Mandelbrot;
+
+/// The exponent of the Mandelbrot set
+pub const QUADRATIC: f64 = 2.0;
+/// Largest whole exponent raised to by repeated squaring
+const MAX_WHOLE: f64 = 64.0;
+
+impl Mandelbrot {
+    /// Whether the view iterates another exponent than 2.
+    pub fn multibrot(&self) -> bool {
+        self.exponent != QUADRATIC
+    }
+}
+
+/// `z^d`, on the principal branch for exponents that aren't whole.
+#[inline]
+pub fn power(z: Complex<f64>, d: f64) -> Complex<f64> {
+    match d {
+        QUADRATIC => z * z,
+        d if d.fract() == 0.0 && (0.0..=MAX_WHOLE).contains(&d) => z.powu(d as u32),
+        d => z.powf(d),
+    }
+}
+
+/// Iterate `z -> z^d + c` from `z0` like `mandelbrot_core::iterate`.
+pub fn iterate(z0: Complex<f64>, c: Complex<f64>, d: f64, bailout: Bailout, radius: f64, max_iter: u32) -> (u32, Complex<f64>) {
+    let mut z = z0;
+    for n in 0..max_iter {
+        if bailout.escaped(z.re, z.im, z.norm_sqr(), radius) {
+            return (n, z);
+        }
+        z = power(z, d) + c;
+    }
+    (max_iter, z)
+}
+
+/// Continuous escape count of a point that escaped after `n` iterations at
+/// `z`, like `mandelbrot_core::smooth_count` for exponent `d`.
+pub fn smooth_count(n: u32, z: Complex<f64>, d: f64) -> f64 {
+    (n as f64 + 1.0 - (z.norm_sqr().ln() / 2.0).ln() / d.ln()).max(0.0)
+}
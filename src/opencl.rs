@@ -0,0 +1,337 @@
+//! Escape counts computed with OpenCL.
+//!
+//! `--opencl` renders views on an OpenCL device rather than with the wgpu
+//! compute shaders of [`crate::gpu`], for drivers whose OpenCL supports
+//! double precision far better than their Vulkan does. The kernel iterates in
+//! `double`, so it renders every view of the plane the CPU renders in double
+//! precision, down to the zoom where perturbation takes over, and the counts
+//! are read back and colored by the CPU like those of the other backends.
+//! The projections, the exponential map, Multibrot views, adaptive iteration
+//! limits and the colorings other than escape time stay on the CPU.
+//!
+//! The OpenCL library is loaded when a window opens rather than linked, so
+//! builds need no OpenCL SDK and run where there is none. The device is the
+//! first GPU with `cl_khr_fp64` of the platforms installed, or any other
+//! device with it when no GPU has it. Without one, or once rendering fails,
+//! the viewer renders as it would without `--opencl`.
+
+use std::ffi::{c_char, c_void};
+use std::ptr;
+
+use libloading::Library;
+use pixels::Error;
+
+use crate::projection::Projection;
+use crate::{Bailout, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Names the OpenCL library goes by
+#[cfg(target_os = "windows")]
+const LIBRARIES: &[&str] = &["OpenCL.dll"];
+#[cfg(target_os = "macos")]
+const LIBRARIES: &[&str] = &["/System/Library/Frameworks/OpenCL.framework/OpenCL"];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBRARIES: &[&str] = &["libOpenCL.so.1", "libOpenCL.so"];
+
+const CL_SUCCESS: i32 = 0;
+const CL_DEVICE_TYPE_GPU: u64 = 1 << 2;
+const CL_DEVICE_TYPE_ALL: u64 = 0xFFFF_FFFF;
+const CL_DEVICE_NAME: u32 = 0x102B;
+const CL_DEVICE_EXTENSIONS: u32 = 0x1030;
+const CL_PROGRAM_BUILD_LOG: u32 = 0x1183;
+const CL_MEM_WRITE_ONLY: u64 = 1 << 1;
+const CL_TRUE: u32 = 1;
+
+const KERNEL: &str = r#"
+#pragma OPENCL EXTENSION cl_khr_fp64 : enable
+
+bool escaped(double2 z, double norm_sqr, uint bailout, double radius) {
+    switch (bailout) {
+        case 1: return fabs(z.x) + fabs(z.y) > radius;
+        case 2: return fmax(fabs(z.x), fabs(z.y)) > radius;
+        default: return norm_sqr > radius * radius;
+    }
+}
+
+// column and row are the offsets of neighbouring pixel columns and rows in
+// the complex plane
+__kernel void mandelbrot(
+    double2 center, double2 column, double2 row, double2 julia,
+    uint width, uint height, uint max_iter, uint is_julia, uint bailout, double radius,
+    __global uint* counts
+) {
+    uint x = get_global_id(0);
+    uint y = get_global_id(1);
+    if (x >= width || y >= height) {
+        return;
+    }
+    double2 point = center + ((double)x - width / 2.0) * column + ((double)y - height / 2.0) * row;
+
+    double2 z = (double2)(0.0, 0.0);
+    double2 c = point;
+    if (is_julia != 0) {
+        z = point;
+        c = julia;
+    }
+
+    uint n = 0;
+    for (; n < max_iter; n++) {
+        double2 sqr = z * z;
+        if (escaped(z, sqr.x + sqr.y, bailout, radius)) {
+            break;
+        }
+        z = (double2)(sqr.x - sqr.y + c.x, 2.0 * z.x * z.y + c.y);
+    }
+    counts[y * width + x] = n;
+}
+"#;
+
+/// Handles of OpenCL objects: platforms, devices, contexts, queues,
+/// programs, kernels and buffers
+type Handle = *mut c_void;
+type Info = unsafe extern "system" fn(Handle, u32, usize, *mut c_void, *mut usize) -> i32;
+type BuildInfo = unsafe extern "system" fn(Handle, Handle, u32, usize, *mut c_void, *mut usize) -> i32;
+type Enqueue = unsafe extern "system" fn(Handle, Handle, u32, *const usize, *const usize, *const usize, u32, *const Handle, *mut Handle) -> i32;
+type Read = unsafe extern "system" fn(Handle, Handle, u32, usize, usize, *mut c_void, u32, *const Handle, *mut Handle) -> i32;
+type Release = unsafe extern "system" fn(Handle) -> i32;
+
+/// The functions of the OpenCL library used, which stay valid while it is
+/// loaded
+struct Api {
+    get_platform_ids: unsafe extern "system" fn(u32, *mut Handle, *mut u32) -> i32,
+    get_device_ids: unsafe extern "system" fn(Handle, u64, u32, *mut Handle, *mut u32) -> i32,
+    get_device_info: Info,
+    create_context: unsafe extern "system" fn(*const isize, u32, *const Handle, *const c_void, *mut c_void, *mut i32) -> Handle,
+    create_command_queue: unsafe extern "system" fn(Handle, Handle, u64, *mut i32) -> Handle,
+    create_program_with_source: unsafe extern "system" fn(Handle, u32, *const *const c_char, *const usize, *mut i32) -> Handle,
+    build_program: unsafe extern "system" fn(Handle, u32, *const Handle, *const c_char, *const c_void, *mut c_void) -> i32,
+    get_program_build_info: BuildInfo,
+    create_kernel: unsafe extern "system" fn(Handle, *const c_char, *mut i32) -> Handle,
+    create_buffer: unsafe extern "system" fn(Handle, u64, usize, *mut c_void, *mut i32) -> Handle,
+    set_kernel_arg: unsafe extern "system" fn(Handle, u32, usize, *const c_void) -> i32,
+    enqueue_nd_range_kernel: Enqueue,
+    enqueue_read_buffer: Read,
+    release_mem_object: Release,
+    release_kernel: Release,
+    release_program: Release,
+    release_command_queue: Release,
+    release_context: Release,
+}
+
+impl Api {
+    /// Look the functions up in `library`.
+    fn new(library: &Library) -> Result<Self, Error> {
+        // SAFETY: the types are those of the OpenCL 1.2 headers
+        unsafe {
+            Ok(Self {
+                get_platform_ids: symbol(library, "clGetPlatformIDs")?,
+                get_device_ids: symbol(library, "clGetDeviceIDs")?,
+                get_device_info: symbol(library, "clGetDeviceInfo")?,
+                create_context: symbol(library, "clCreateContext")?,
+                create_command_queue: symbol(library, "clCreateCommandQueue")?,
+                create_program_with_source: symbol(library, "clCreateProgramWithSource")?,
+                build_program: symbol(library, "clBuildProgram")?,
+                get_program_build_info: symbol(library, "clGetProgramBuildInfo")?,
+                create_kernel: symbol(library, "clCreateKernel")?,
+                create_buffer: symbol(library, "clCreateBuffer")?,
+                set_kernel_arg: symbol(library, "clSetKernelArg")?,
+                enqueue_nd_range_kernel: symbol(library, "clEnqueueNDRangeKernel")?,
+                enqueue_read_buffer: symbol(library, "clEnqueueReadBuffer")?,
+                release_mem_object: symbol(library, "clReleaseMemObject")?,
+                release_kernel: symbol(library, "clReleaseKernel")?,
+                release_program: symbol(library, "clReleaseProgram")?,
+                release_command_queue: symbol(library, "clReleaseCommandQueue")?,
+                release_context: symbol(library, "clReleaseContext")?,
+            })
+        }
+    }
+
+    /// A string the device reports of itself, like its name.
+    fn device_info(&self, device: Handle, param: u32) -> Result<String, Error> {
+        let mut size = 0;
+        check(unsafe { (self.get_device_info)(device, param, 0, ptr::null_mut(), &mut size) }, "clGetDeviceInfo")?;
+        let mut bytes = vec![0u8; size];
+        check(unsafe { (self.get_device_info)(device, param, size, bytes.as_mut_ptr().cast(), ptr::null_mut()) }, "clGetDeviceInfo")?;
+        Ok(String::from_utf8_lossy(&bytes).trim_end_matches('\0').to_string())
+    }
+
+    /// The first GPU supporting double precision of the platforms, or any
+    /// other device supporting it.
+    fn device(&self) -> Result<Handle, Error> {
+        let mut count = 0;
+        check(unsafe { (self.get_platform_ids)(0, ptr::null_mut(), &mut count) }, "clGetPlatformIDs")?;
+        let mut platforms = vec![ptr::null_mut(); count as usize];
+        check(unsafe { (self.get_platform_ids)(count, platforms.as_mut_ptr(), ptr::null_mut()) }, "clGetPlatformIDs")?;
+
+        for device_type in [CL_DEVICE_TYPE_GPU, CL_DEVICE_TYPE_ALL] {
+            for &platform in &platforms {
+                // Platforms without devices of the type fail
+                let mut count = 0;
+                if unsafe { (self.get_device_ids)(platform, device_type, 0, ptr::null_mut(), &mut count) } != CL_SUCCESS {
+                    continue;
+                }
+                let mut devices = vec![ptr::null_mut(); count as usize];
+                check(unsafe { (self.get_device_ids)(platform, device_type, count, devices.as_mut_ptr(), ptr::null_mut()) }, "clGetDeviceIDs")?;
+                let doubles = |&device: &Handle| self.device_info(device, CL_DEVICE_EXTENSIONS).is_ok_and(|extensions| extensions.split_whitespace().any(|e| e == "cl_khr_fp64"));
+                if let Some(device) = devices.into_iter().find(doubles) {
+                    return Ok(device);
+                }
+            }
+        }
+        Err(Error::UserDefined("no OpenCL device supports double precision".into()))
+    }
+}
+
+/// The function `name` of `library`, of type `T`.
+///
+/// # Safety
+///
+/// `T` has to be the type of the function.
+unsafe fn symbol<T: Copy>(library: &Library, name: &str) -> Result<T, Error> {
+    match library.get::<T>(name.as_bytes()) {
+        Ok(symbol) => Ok(*symbol),
+        Err(e) => Err(Error::UserDefined(format!("the OpenCL library lacks {name}: {e}").into())),
+    }
+}
+
+/// An error for the status of an OpenCL call other than success.
+fn check(status: i32, call: &str) -> Result<(), Error> {
+    match status {
+        CL_SUCCESS => Ok(()),
+        status => Err(Error::UserDefined(format!("{call} failed with OpenCL error {status}").into())),
+    }
+}
+
+/// Whether OpenCL renders the view: a view of the plane that double
+/// precision resolves, colored by its escape counts.
+pub fn suitable(mandelbrot: &Mandelbrot) -> bool {
+    !mandelbrot.exponential_map
+        && mandelbrot.projection == Projection::Plane
+        && mandelbrot.coloring.counts_only()
+        && !mandelbrot.mipmapped()
+        && mandelbrot.adaptive_iterations.is_none()
+        && !mandelbrot.multibrot()
+        && !mandelbrot.deep()
+}
+
+/// The kernel built for a device and the buffer it writes the counts to
+pub struct OpenCl {
+    api: Api,
+    context: Handle,
+    queue: Handle,
+    program: Handle,
+    kernel: Handle,
+    counts: Handle,
+    /// Name of the device, for the messages
+    pub device_name: String,
+    /// Loaded while the functions of `api` are used, so dropped last
+    _library: Library,
+}
+
+impl OpenCl {
+    /// Load the OpenCL library and build the kernel for a device supporting
+    /// double precision.
+    pub fn new() -> Result<Self, Error> {
+        // SAFETY: loading the library runs its initialization, which is
+        // that of the OpenCL drivers
+        let library = LIBRARIES
+            .iter()
+            .find_map(|name| unsafe { Library::new(name) }.ok())
+            .ok_or_else(|| Error::UserDefined("no OpenCL library found".into()))?;
+        let api = Api::new(&library)?;
+        let device = api.device()?;
+        let device_name = api.device_info(device, CL_DEVICE_NAME)?;
+
+        // Each object is released as soon as something after it fails
+        let mut status = CL_SUCCESS;
+        let context = unsafe { (api.create_context)(ptr::null(), 1, &device, ptr::null(), ptr::null_mut(), &mut status) };
+        check(status, "clCreateContext")?;
+        let queue = unsafe { (api.create_command_queue)(context, device, 0, &mut status) };
+        let mut opencl = Self { api, context, queue, program: ptr::null_mut(), kernel: ptr::null_mut(), counts: ptr::null_mut(), device_name, _library: library };
+        check(status, "clCreateCommandQueue")?;
+
+        let source = KERNEL.as_ptr().cast::<c_char>();
+        let api = &opencl.api;
+        opencl.program = unsafe { (api.create_program_with_source)(context, 1, &source, &KERNEL.len(), &mut status) };
+        check(status, "clCreateProgramWithSource")?;
+        if unsafe { (api.build_program)(opencl.program, 1, &device, ptr::null(), ptr::null(), ptr::null_mut()) } != CL_SUCCESS {
+            return Err(Error::UserDefined(format!("the OpenCL kernel failed to build: {}", opencl.build_log(device)).into()));
+        }
+        opencl.kernel = unsafe { (api.create_kernel)(opencl.program, c"mandelbrot".as_ptr(), &mut status) };
+        check(status, "clCreateKernel")?;
+        let size = WIDTH as usize * HEIGHT as usize * 4;
+        opencl.counts = unsafe { (api.create_buffer)(context, CL_MEM_WRITE_ONLY, size, ptr::null_mut(), &mut status) };
+        check(status, "clCreateBuffer")?;
+        Ok(opencl)
+    }
+
+    /// What the compiler reported building the kernel for `device`.
+    fn build_log(&self, device: Handle) -> String {
+        let mut size = 0;
+        let info = self.api.get_program_build_info;
+        if unsafe { info(self.program, device, CL_PROGRAM_BUILD_LOG, 0, ptr::null_mut(), &mut size) } != CL_SUCCESS {
+            return "no build log".to_string();
+        }
+        let mut bytes = vec![0u8; size];
+        unsafe { info(self.program, device, CL_PROGRAM_BUILD_LOG, size, bytes.as_mut_ptr().cast(), ptr::null_mut()) };
+        String::from_utf8_lossy(&bytes).trim_end_matches('\0').trim().to_string()
+    }
+
+    /// Set argument `index` of the kernel to `value`.
+    fn arg<T>(&self, index: u32, value: &T) -> Result<(), Error> {
+        let value: *const T = value;
+        check(unsafe { (self.api.set_kernel_arg)(self.kernel, index, size_of::<T>(), value.cast()) }, "clSetKernelArg")
+    }
+
+    /// Escape counts of every pixel of the view, row by row.
+    pub fn render(&self, mandelbrot: &Mandelbrot) -> Result<Vec<u32>, Error> {
+        let (x, y) = (WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0);
+        let center = mandelbrot.pixel_to_complex(x, y);
+        let column = mandelbrot.pixel_to_complex(x + 1.0, y) - center;
+        let row = mandelbrot.pixel_to_complex(x, y + 1.0) - center;
+        let julia = mandelbrot.julia.unwrap_or_default();
+        let bailout: u32 = match mandelbrot.bailout {
+            Bailout::Modulus => 0,
+            Bailout::Manhattan => 1,
+            Bailout::MaxComponent => 2,
+        };
+
+        for (index, c) in [center, column, row, julia].into_iter().enumerate() {
+            self.arg(index as u32, &[c.re, c.im])?;
+        }
+        for (index, value) in [WIDTH, HEIGHT, MAX_ITER, mandelbrot.julia.is_some() as u32, bailout].into_iter().enumerate() {
+            self.arg(4 + index as u32, &value)?;
+        }
+        self.arg(9, &mandelbrot.bailout_radius)?;
+        self.arg(10, &self.counts)?;
+
+        let global = [WIDTH as usize, HEIGHT as usize];
+        let mut counts = vec![0u32; WIDTH as usize * HEIGHT as usize];
+        let api = &self.api;
+        check(
+            unsafe { (api.enqueue_nd_range_kernel)(self.queue, self.kernel, 2, ptr::null(), global.as_ptr(), ptr::null(), 0, ptr::null(), ptr::null_mut()) },
+            "clEnqueueNDRangeKernel",
+        )?;
+        // The queue runs in order, so the blocking read waits for the kernel
+        check(
+            unsafe { (api.enqueue_read_buffer)(self.queue, self.counts, CL_TRUE, 0, counts.len() * 4, counts.as_mut_ptr().cast(), 0, ptr::null(), ptr::null_mut()) },
+            "clEnqueueReadBuffer",
+        )?;
+        Ok(counts)
+    }
+}
+
+impl Drop for OpenCl {
+    fn drop(&mut self) {
+        let api = &self.api;
+        let objects = [
+            (self.counts, api.release_mem_object),
+            (self.kernel, api.release_kernel),
+            (self.program, api.release_program),
+            (self.queue, api.release_command_queue),
+            (self.context, api.release_context),
+        ];
+        for (object, release) in objects.into_iter().filter(|(object, _)| !object.is_null()) {
+            unsafe { release(object) };
+        }
+    }
+}
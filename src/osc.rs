@@ -0,0 +1,116 @@
+//! Open Sound Control input of the viewer parameters.
+//!
+//! `--osc=<port>` listens for OSC messages on a UDP port, on all interfaces,
+//! or `--osc=<address:port>` on one of them, so VJ software and controller
+//! apps such as TouchOSC can drive the first window. Messages, alone or in
+//! bundles, take integer or float arguments, and the palette its name:
+//!
+//! ```text
+//! /mandelbrot/center re im
+//! /mandelbrot/center/re re
+//! /mandelbrot/center/im im
+//! /mandelbrot/zoom zoom
+//! /mandelbrot/rotation degrees
+//! /mandelbrot/palette-offset offset
+//! /mandelbrot/julia [re im]
+//! /mandelbrot/julia/re re
+//! /mandelbrot/julia/im im
+//! /mandelbrot/mandelbrot
+//! /mandelbrot/palette name
+//! ```
+//!
+//! They act as the commands of the same name on the control socket, see
+//! [`crate::control`], with nothing sent back: `/mandelbrot/julia` without
+//! arguments shows the Julia set as it is, and `/mandelbrot/mandelbrot` goes
+//! back to the Mandelbrot set. Messages to other addresses or with the wrong
+//! arguments are left alone.
+
+use std::net::UdpSocket;
+use std::sync::mpsc::{self, Sender};
+
+use pixels::Error;
+use rosc::{OscMessage, OscPacket, OscType};
+use winit::event_loop::EventLoopProxy;
+
+use crate::arg_value;
+use crate::control::{network_address, Command, Request};
+
+/// Largest datagram accepted
+const MAX_PACKET: usize = 65536;
+
+/// Listen on the port `--osc` asks for, if any, sending the commands of the
+/// messages to `requests` and waking the event loop through `proxy`.
+pub fn listen(args: &[String], requests: Sender<Request>, proxy: EventLoopProxy<()>) -> Result<(), Error> {
+    let Some(value) = arg_value(args, "osc") else {
+        return Ok(());
+    };
+    let socket = UdpSocket::bind(network_address(&value, "0.0.0.0")).map_err(|e| Error::UserDefined(format!("--osc={value}: {e}").into()))?;
+
+    std::thread::Builder::new()
+        .name("osc".to_owned())
+        .spawn(move || {
+            let mut buffer = vec![0; MAX_PACKET];
+            while let Ok(size) = socket.recv(&mut buffer) {
+                let Ok((_, packet)) = rosc::decoder::decode_udp(&buffer[..size]) else { continue };
+                let mut commands = Vec::new();
+                collect(packet, &mut commands);
+                for command in commands {
+                    // Nobody waits for the answer
+                    let (reply, _) = mpsc::channel();
+                    if requests.send(Request { command, reply }).is_err() || proxy.send_event(()).is_err() {
+                        return;
+                    }
+                }
+            }
+        })
+        .map_err(|e| Error::UserDefined(Box::new(e)))?;
+    Ok(())
+}
+
+/// Add the commands of the messages in `packet` to `commands`, in order.
+fn collect(packet: OscPacket, commands: &mut Vec<Command>) {
+    match packet {
+        OscPacket::Message(message) => commands.extend(command(&message)),
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                collect(packet, commands);
+            }
+        }
+    }
+}
+
+/// The command a message stands for, if any.
+pub fn command(message: &OscMessage) -> Option<Command> {
+    if message.addr == "/mandelbrot/palette" {
+        return match message.args.as_slice() {
+            [OscType::String(palette)] => Some(Command::SetPalette { palette: palette.clone() }),
+            _ => None,
+        };
+    }
+    let numbers = message.args.iter().map(number).collect::<Option<Vec<f64>>>()?;
+    let command = match (message.addr.as_str(), numbers.as_slice()) {
+        ("/mandelbrot/center", &[re, im]) => Command::SetCenter { re: Some(re), im: Some(im) },
+        ("/mandelbrot/center/re", &[re]) => Command::SetCenter { re: Some(re), im: None },
+        ("/mandelbrot/center/im", &[im]) => Command::SetCenter { re: None, im: Some(im) },
+        ("/mandelbrot/zoom", &[zoom]) => Command::SetZoom { zoom },
+        ("/mandelbrot/rotation", &[degrees]) => Command::SetRotation { degrees },
+        ("/mandelbrot/palette-offset", &[offset]) => Command::SetPaletteOffset { offset },
+        ("/mandelbrot/julia", &[re, im]) => Command::SetJulia { re: Some(re), im: Some(im) },
+        ("/mandelbrot/julia", &[]) => Command::SetJulia { re: None, im: None },
+        ("/mandelbrot/julia/re", &[re]) => Command::SetJulia { re: Some(re), im: None },
+        ("/mandelbrot/julia/im", &[im]) => Command::SetJulia { re: None, im: Some(im) },
+        ("/mandelbrot/mandelbrot", &[]) => Command::SetMandelbrot,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn number(arg: &OscType) -> Option<f64> {
+    match *arg {
+        OscType::Int(value) => Some(value as f64),
+        OscType::Long(value) => Some(value as f64),
+        OscType::Float(value) => Some(value as f64),
+        OscType::Double(value) => Some(value),
+        _ => None,
+    }
+}
@@ -0,0 +1,104 @@
+//! Drawing helpers for overlays on top of the rendered frame.
+
+use crate::{HEIGHT, WIDTH};
+
+/// Color of the iso-iteration contour lines
+pub const CONTOUR_COLOR: [u8; 4] = [255, 255, 255, 255];
+/// Color of external rays
+pub const RAY_COLOR: [u8; 4] = [255, 220, 0, 255];
+
+/// Outline the rectangle spanned by the corners `a` and `b`, in frame buffer
+/// pixels, clipped to the frame.
+pub fn draw_rect(frame: &mut [u8], a: (f64, f64), b: (f64, f64), rgba: [u8; 4]) {
+    let clamp_x = |x: f64| x.clamp(0.0, WIDTH as f64 - 1.0) as usize;
+    let clamp_y = |y: f64| y.clamp(0.0, HEIGHT as f64 - 1.0) as usize;
+    let (left, right) = (clamp_x(a.0.min(b.0)), clamp_x(a.0.max(b.0)));
+    let (top, bottom) = (clamp_y(a.1.min(b.1)), clamp_y(a.1.max(b.1)));
+
+    let mut put = |x: usize, y: usize| {
+        let i = (y * WIDTH as usize + x) * 4;
+        frame[i..i + 4].copy_from_slice(&rgba);
+    };
+    for x in left..=right {
+        put(x, top);
+        put(x, bottom);
+    }
+    for y in top..=bottom {
+        put(left, y);
+        put(right, y);
+    }
+}
+
+/// Draw a crosshair `radius` pixels across either way around `center`,
+/// clipped to the frame.
+pub fn draw_crosshair(frame: &mut [u8], center: (usize, usize), radius: usize, rgba: [u8; 4]) {
+    let (x, y) = (center.0 as f64 + 0.5, center.1 as f64 + 0.5);
+    let r = radius as f64;
+    draw_polyline(frame, &[(x - r, y), (x + r, y)], rgba);
+    draw_polyline(frame, &[(x, y - r), (x, y + r)], rgba);
+}
+
+/// Draw the line through `points`, in frame buffer pixels, clipped to the
+/// frame.
+pub fn draw_polyline(frame: &mut [u8], points: &[(f64, f64)], rgba: [u8; 4]) {
+    for segment in points.windows(2) {
+        let Some((a, b)) = clip(segment[0], segment[1]) else {
+            continue;
+        };
+        let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil().max(1.0);
+        for step in 0..=steps as usize {
+            let t = step as f64 / steps;
+            let (x, y) = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            let i = (y as usize * WIDTH as usize + x as usize) * 4;
+            frame[i..i + 4].copy_from_slice(&rgba);
+        }
+    }
+}
+
+/// The part of the segment from `a` to `b` inside the frame, clipped with
+/// the Liang-Barsky algorithm.
+fn clip(a: (f64, f64), b: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    // Stay half a pixel inside so the points round down into the frame
+    let (right, bottom) = (WIDTH as f64 - 0.5, HEIGHT as f64 - 0.5);
+    let (mut start, mut end) = (0.0, 1.0);
+    for (p, q) in [(-dx, a.0), (dx, right - a.0), (-dy, a.1), (dy, bottom - a.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            start = f64::max(start, q / p);
+        } else {
+            end = f64::min(end, q / p);
+        }
+    }
+    if start > end || !(start.is_finite() && end.is_finite()) {
+        return None;
+    }
+    Some(((a.0 + start * dx, a.1 + start * dy), (a.0 + end * dx, a.1 + end * dy)))
+}
+
+/// Draw iso-iteration contour lines on a row of pixels, on every pixel whose
+/// band of `interval` escape counts differs from its left neighbour or the
+/// one `above`. `counts` holds the escape counts of the row.
+pub fn draw_contour_row(row: &mut [u8], counts: &[u32], above: Option<&[u32]>, interval: u32, rgba: [u8; 4]) {
+    let band = |n: u32| n / interval;
+    for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+        let left = x.checked_sub(1).map(|x| counts[x]);
+        let up = above.map(|above| above[x]);
+        if [left, up].into_iter().flatten().any(|n| band(n) != band(counts[x])) {
+            pixel.copy_from_slice(&rgba);
+        }
+    }
+}
+
+/// Draw iso-iteration contour lines over the whole frame, see
+/// `draw_contour_row`.
+pub fn draw_contours(frame: &mut [u8], counts: &[u32], interval: u32, rgba: [u8; 4]) {
+    let width = WIDTH as usize;
+    for (y, row) in frame.chunks_exact_mut(width * 4).enumerate() {
+        let above = y.checked_sub(1).map(|y| &counts[y * width..(y + 1) * width]);
+        draw_contour_row(row, &counts[y * width..(y + 1) * width], above, interval, rgba);
+    }
+}
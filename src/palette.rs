@@ -0,0 +1,240 @@
+//! Palettes of the escape time colors.
+//!
+//! The classic palette ramps from red to yellow and saturates after about a
+//! hundred escape counts, which leaves the detail of deep views all yellow.
+//! The others are the perceptually uniform colormaps of matplotlib, viridis,
+//! magma and inferno, Google's turbo, cividis, which reads the same to
+//! those with color vision deficiencies, `high-contrast`, from white to
+//! black, and palettes of a few colors, made up at random or taken from an
+//! image. They run from their first color to their last over `PERIOD` escape
+//! counts and back again, so the colors stay continuous however many counts
+//! the view spans, and can be reversed to run from their last color.
+//!
+//! Random palettes are colors evenly spaced in lightness, from dark to light,
+//! with their hue drifting in one direction and moderate saturation, picked
+//! in the Oklab color space so the steps between them look even.
+//! `--palette-image=<file.png>` takes the dominant colors of an image instead,
+//! the centers of its pixels clustered with k-means in Oklab, from dark to
+//! light, to match renders to existing artwork. Either is named by its five
+//! colors, e.g. `--palette=#1a0f3c,#4d3a8a,...`, so it can be given again.
+
+use std::f64::consts::TAU;
+
+use std::path::Path;
+
+use colorous::Gradient;
+use pixels::Error;
+use rand::Rng;
+
+use crate::export;
+
+/// Escape counts from one end of a colormap to the other
+pub const PERIOD: f64 = mandelbrot_core::PERIOD;
+/// Escape counts after which the colors of a colormap repeat
+pub const CYCLE: f64 = 2.0 * PERIOD;
+/// Colors of a random or extracted palette
+const STOPS: usize = 5;
+/// Pixels of an image clustered for its palette, and the k-means iterations
+const KMEANS_SAMPLES: usize = 4096;
+const KMEANS_ROUNDS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    Classic,
+    Viridis,
+    Magma,
+    Inferno,
+    Turbo,
+    Cividis,
+    /// White to black, for the most contrast between the bands
+    HighContrast,
+    /// Colors spaced evenly over the palette, interpolated in between
+    Stops([[u8; 3]; STOPS]),
+}
+
+impl Palette {
+    /// Parse the value of the `--palette` argument.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Palette::Classic),
+            "viridis" => Some(Palette::Viridis),
+            "magma" => Some(Palette::Magma),
+            "inferno" => Some(Palette::Inferno),
+            "turbo" => Some(Palette::Turbo),
+            "cividis" => Some(Palette::Cividis),
+            "high-contrast" => Some(Palette::HighContrast),
+            _ => {
+                let stops: Vec<[u8; 3]> = name.split(',').map(parse_hex).collect::<Option<_>>()?;
+                Some(Palette::Stops(stops.try_into().ok()?))
+            }
+        }
+    }
+
+    /// Name of the palette for the `--palette` argument.
+    pub fn name(self) -> String {
+        match self {
+            Palette::Classic => "classic".to_owned(),
+            Palette::Viridis => "viridis".to_owned(),
+            Palette::Magma => "magma".to_owned(),
+            Palette::Inferno => "inferno".to_owned(),
+            Palette::Turbo => "turbo".to_owned(),
+            Palette::Cividis => "cividis".to_owned(),
+            Palette::HighContrast => "high-contrast".to_owned(),
+            Palette::Stops(stops) => {
+                let hex: Vec<String> = stops.iter().map(|[r, g, b]| format!("#{r:02x}{g:02x}{b:02x}")).collect();
+                hex.join(",")
+            }
+        }
+    }
+
+    /// The built-in palette after this one, for cycling through them in the
+    /// viewer. Palettes of colors continue with the first.
+    pub fn next(self) -> Self {
+        match self {
+            Palette::Classic => Palette::Viridis,
+            Palette::Viridis => Palette::Magma,
+            Palette::Magma => Palette::Inferno,
+            Palette::Inferno => Palette::Turbo,
+            Palette::Turbo => Palette::Cividis,
+            Palette::Cividis => Palette::HighContrast,
+            Palette::HighContrast | Palette::Stops(_) => Palette::Classic,
+        }
+    }
+
+    /// The built-in palette before this one. Palettes of colors go back to
+    /// the last.
+    pub fn previous(self) -> Self {
+        match self {
+            Palette::Classic | Palette::Stops(_) => Palette::HighContrast,
+            Palette::Viridis => Palette::Classic,
+            Palette::Magma => Palette::Viridis,
+            Palette::Inferno => Palette::Magma,
+            Palette::Turbo => Palette::Inferno,
+            Palette::Cividis => Palette::Turbo,
+            Palette::HighContrast => Palette::Cividis,
+        }
+    }
+
+    /// A new random palette.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let mut hue = rng.gen_range(0.0..TAU);
+        let drift = rng.gen_range(0.4..1.2) * if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+        Palette::Stops(std::array::from_fn(|i| {
+            let lightness = 0.2 + 0.75 * i as f64 / (STOPS - 1) as f64;
+            let chroma = rng.gen_range(0.06..0.16);
+            hue += drift;
+            oklab_to_srgb(lightness, chroma * hue.cos(), chroma * hue.sin())
+        }))
+    }
+
+    /// The dominant colors of the image at `path`, from dark to light.
+    pub fn from_image(path: &Path) -> Result<Self, Error> {
+        let (width, height, pixels) = export::read_png(path)?;
+        // Cluster a few thousand pixels evenly spread over the image, leaving
+        // out transparent ones
+        let step = (width * height).div_ceil(KMEANS_SAMPLES).max(1);
+        let mut samples: Vec<[f64; 3]> =
+            pixels.iter().step_by(step).filter(|pixel| pixel[3] >= 128).map(|&[r, g, b, _]| srgb_to_oklab([r, g, b])).collect();
+        if samples.len() < STOPS {
+            return Err(Error::UserDefined(format!("{}: too few opaque pixels for a palette", path.display()).into()));
+        }
+
+        // Start from the median lightness, then from the samples farthest from
+        // the centers so far, so the clusters are spread out and the result
+        // doesn't depend on chance
+        let distance = |a: &[f64; 3], b: &[f64; 3]| (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f64>();
+        samples.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        let mut centers = [samples[samples.len() / 2]; STOPS];
+        for i in 1..STOPS {
+            let farthest = samples.iter().max_by(|a, b| {
+                let nearest = |sample: &[f64; 3]| centers[..i].iter().map(|center| distance(sample, center)).fold(f64::INFINITY, f64::min);
+                nearest(a).total_cmp(&nearest(b))
+            });
+            centers[i] = *farthest.unwrap_or(&centers[0]);
+        }
+        for _ in 0..KMEANS_ROUNDS {
+            let mut sums = [[0.0; 3]; STOPS];
+            let mut counts = [0usize; STOPS];
+            for sample in &samples {
+                let nearest = (0..STOPS).min_by(|&a, &b| distance(sample, &centers[a]).total_cmp(&distance(sample, &centers[b])));
+                let nearest = nearest.unwrap_or(0);
+                (0..3).for_each(|i| sums[nearest][i] += sample[i]);
+                counts[nearest] += 1;
+            }
+            for ((center, sum), count) in centers.iter_mut().zip(&sums).zip(counts) {
+                if count > 0 {
+                    *center = sum.map(|sum| sum / count as f64);
+                }
+            }
+        }
+
+        centers.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        Ok(Palette::Stops(centers.map(|[lightness, a, b]| oklab_to_srgb(lightness, a, b))))
+    }
+
+    fn gradient(self) -> Option<Gradient> {
+        match self {
+            Palette::Classic | Palette::Stops(_) => None,
+            Palette::Viridis => Some(colorous::VIRIDIS),
+            Palette::Magma => Some(colorous::MAGMA),
+            Palette::Inferno => Some(colorous::INFERNO),
+            Palette::Turbo => Some(colorous::TURBO),
+            Palette::Cividis => Some(colorous::CIVIDIS),
+            Palette::HighContrast => Some(colorous::GREYS),
+        }
+    }
+
+    /// Color `m` escape counts into the colormap, offset included, `None`
+    /// for the classic palette.
+    pub fn color(self, m: f64, reversed: bool) -> Option<[u8; 4]> {
+        // Up the colormap and back down
+        let t = mandelbrot_core::fold(m, PERIOD, reversed);
+        if let Palette::Stops(stops) = self {
+            return Some(mandelbrot_core::stops_color(&stops, t));
+        }
+        Some(mandelbrot_core::gradient_color(self.gradient()?, t))
+    }
+}
+
+/// Parse a `#rrggbb` color.
+fn parse_hex(value: &str) -> Option<[u8; 3]> {
+    let hex = value.trim().strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Convert an sRGB color to Oklab lightness and its a and b axes.
+fn srgb_to_oklab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    });
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+/// Convert an Oklab color to sRGB, clamping it into the gamut.
+fn oklab_to_srgb(lightness: f64, a: f64, b: f64) -> [u8; 3] {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    let linear = [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ];
+    linear.map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        let encoded = if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (encoded * 255.0).round() as u8
+    })
+}
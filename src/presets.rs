@@ -0,0 +1,154 @@
+//! Named views to start from or switch to.
+//!
+//! A preset is a name for a view: the fractal, its coordinates and zoom and
+//! its palette, as the options that make it. `--preset=<name>` starts from
+//! one, with the options given beside it taking precedence, and the keys 1
+//! to 9 of the viewer switch to the first nine, or with Shift fly there.
+//! Besides the built-in presets there are those of the user, files of one
+//! option per line like the session, in `$XDG_CONFIG_HOME/mandelbrot/presets`
+//! (`~/.config` by default) or `%APPDATA%\mandelbrot\presets` on Windows, each
+//! named as its file. A user preset of the same name takes the place of a
+//! built-in one.
+//!
+//! `mandelbrot presets` lists them, and `mandelbrot presets --save=<name>`
+//! saves the view of the other options given as a user preset.
+
+use std::path::{Path, PathBuf};
+
+use pixels::Error;
+
+use crate::{arg_value, invalid_arg, session, view_from_args, Mandelbrot};
+
+/// The built-in presets, by name and options
+const BUILT_IN: [(&str, &[&str]); 9] = [
+    ("whole", &["--center=-0.75,0", "--zoom=1"]),
+    ("seahorse-valley", &["--center=-0.743643887037151,0.13182590420533", "--zoom=2000", "--palette=viridis"]),
+    ("elephant-valley", &["--center=0.2925,0.0149", "--zoom=150", "--palette=magma"]),
+    ("triple-spiral", &["--center=-0.0883,0.6542", "--zoom=250", "--palette=inferno"]),
+    ("minibrot", &["--center=-1.7548776662,0", "--zoom=600", "--palette=turbo"]),
+    ("douady-rabbit", &["--julia=-0.123,0.745", "--center=0,0", "--zoom=1"]),
+    ("dendrite", &["--julia=0,1", "--center=0,0", "--zoom=1", "--palette=cividis"]),
+    ("san-marco", &["--julia=-0.75,0", "--center=0,0", "--zoom=1", "--palette=magma"]),
+    ("siegel-disk", &["--julia=-0.390541,-0.586788", "--center=0,0", "--zoom=1", "--palette=viridis"]),
+];
+
+/// A named view
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub options: Vec<String>,
+    /// Whether it is one of the user's, rather than built in
+    pub user: bool,
+}
+
+impl Preset {
+    /// The view the options of the preset make.
+    pub fn view(&self) -> Result<Mandelbrot, Error> {
+        view_from_args(&self.options)
+    }
+}
+
+fn dir() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let config = if cfg!(windows) {
+        var("APPDATA")?
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| Some(var("HOME")?.join(".config")))?
+    };
+    Some(config.join("mandelbrot").join("presets"))
+}
+
+/// The built-in presets and then the rest of the user's, in order of their
+/// names.
+pub fn all() -> Vec<Preset> {
+    merge(dir().map(|dir| load_dir(&dir)).unwrap_or_default())
+}
+
+/// The built-in presets, those of `user` in their place where named the
+/// same, followed by the rest of `user`.
+pub fn merge(mut user: Vec<Preset>) -> Vec<Preset> {
+    let mut presets: Vec<Preset> = BUILT_IN
+        .iter()
+        .map(|(name, options)| match user.iter().position(|preset| preset.name == *name) {
+            Some(i) => user.remove(i),
+            None => Preset { name: name.to_string(), options: options.iter().map(|option| option.to_string()).collect(), user: false },
+        })
+        .collect();
+    presets.extend(user);
+    presets
+}
+
+/// The user presets in `dir`, in order of their names, leaving out hidden
+/// files and those that can't be read.
+pub fn load_dir(dir: &Path) -> Vec<Preset> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut presets: Vec<Preset> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str().filter(|name| !name.starts_with('.'))?.to_owned();
+            let text = std::fs::read_to_string(&path).ok()?;
+            let options = text.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_owned).collect();
+            Some(Preset { name, options, user: true })
+        })
+        .collect();
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    presets
+}
+
+/// The preset `name`, if there is one.
+pub fn find(name: &str) -> Option<Preset> {
+    all().into_iter().find(|preset| preset.name == name)
+}
+
+/// The command line `args` followed by the options of the preset that
+/// `--preset` names, if any.
+pub fn expand(args: Vec<String>) -> Result<Vec<String>, Error> {
+    expand_from(args, all())
+}
+
+/// `expand` with the `presets` to choose from.
+pub fn expand_from(args: Vec<String>, presets: Vec<Preset>) -> Result<Vec<String>, Error> {
+    let Some(name) = arg_value(&args, "preset") else {
+        return Ok(args);
+    };
+    let preset = presets.into_iter().find(|preset| preset.name == name).ok_or_else(|| invalid_arg("preset", &name))?;
+    // A preset naming another preset would chain without end
+    let options = preset.options.into_iter().filter(|option| !option.starts_with("--preset="));
+    Ok(args.into_iter().chain(options).collect())
+}
+
+/// Whether `name` can be the file name of a preset.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\', ':'])
+}
+
+/// Save the options of `mandelbrot` as the user preset `name` in `dir`.
+pub fn save_to(dir: &Path, name: &str, mandelbrot: &Mandelbrot) -> Result<PathBuf, Error> {
+    let io = |e: std::io::Error| Error::UserDefined(format!("{}: {e}", dir.display()).into());
+    std::fs::create_dir_all(dir).map_err(io)?;
+    let path = dir.join(name);
+    std::fs::write(&path, session::view_options(mandelbrot).join("\n") + "\n").map_err(io)?;
+    Ok(path)
+}
+
+/// Run the `presets` command, listing the presets or saving the view of
+/// `mandelbrot` as one.
+pub fn presets_from_args(mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    if let Some(name) = arg_value(args, "save") {
+        if !valid_name(&name) {
+            return Err(invalid_arg("save", &name));
+        }
+        let dir = dir().ok_or_else(|| Error::UserDefined("no directory to save presets in".into()))?;
+        let path = save_to(&dir, &name, mandelbrot)?;
+        println!("saved {}", path.display());
+        return Ok(());
+    }
+    for (i, preset) in all().iter().enumerate() {
+        let key = if i < 9 { format!("{}", i + 1) } else { " ".to_owned() };
+        let user = if preset.user { " (user)" } else { "" };
+        println!("{key} {:<20} {}{user}", preset.name, preset.options.join(" "));
+    }
+    Ok(())
+}
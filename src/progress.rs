@@ -0,0 +1,129 @@
+//! Progress reports of the offline renders.
+//!
+//! The `render`, `data`, `buddhabrot`, `zoom-video`, `clip` and `batch` commands show a
+//! progress bar on the terminal, with the throughput measured so far and the
+//! time left at that throughput. `--quiet` leaves it out, and
+//! `--json-progress` prints every update as a line of JSON to the standard
+//! output instead, for scripts:
+//!
+//! ```text
+//! {"task":"render","unit":"rows","done":512,"total":2160,"elapsed":3.218,"rate":159.1,"pixel_rate":611000000,"eta":10.36}
+//! ```
+//!
+//! `rate` counts units per second, `pixel_rate` is there for the tasks that
+//! render pixels, and `eta` is `null` until there is a throughput to go by.
+
+use std::time::{Duration, Instant};
+
+use indicatif::{HumanDuration, ProgressBar, ProgressStyle};
+
+/// How progress is reported
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Bar,
+    Quiet,
+    Json,
+}
+
+impl Mode {
+    pub fn from_args(args: &[String]) -> Self {
+        if args.iter().any(|a| a == "--json-progress") {
+            Mode::Json
+        } else if args.iter().any(|a| a == "--quiet") {
+            Mode::Quiet
+        } else {
+            Mode::Bar
+        }
+    }
+}
+
+/// Progress through `total` units of a task
+pub struct Progress {
+    mode: Mode,
+    task: &'static str,
+    unit: &'static str,
+    total: u64,
+    /// Pixels rendered per unit, if the task renders pixels
+    pixels_per_unit: Option<u64>,
+    start: Instant,
+    /// Units done before the start, which don't count towards the throughput
+    first: u64,
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    pub fn new(mode: Mode, task: &'static str, unit: &'static str, total: u64, pixels_per_unit: Option<u64>) -> Self {
+        let bar = (mode == Mode::Bar).then(|| {
+            let bar = ProgressBar::new(total);
+            let style = ProgressStyle::with_template("{prefix} [{wide_bar}] {pos}/{len} {msg}").unwrap();
+            bar.set_style(style.progress_chars("=> "));
+            bar.set_prefix(task);
+            bar
+        });
+        Self { mode, task, unit, total, pixels_per_unit, start: Instant::now(), first: 0, bar }
+    }
+
+    /// Continue from `done` units finished earlier.
+    pub fn resume(&mut self, done: u64) {
+        self.first = done;
+        self.start = Instant::now();
+        if let Some(bar) = &self.bar {
+            bar.set_position(done);
+        }
+    }
+
+    /// Print a message about the task, unless quiet.
+    pub fn note(&self, message: &str) {
+        match (&self.bar, self.mode) {
+            (_, Mode::Quiet) => {}
+            (Some(bar), _) => bar.println(message),
+            (None, _) => eprintln!("{message}"),
+        }
+    }
+
+    /// Report `done` units finished.
+    pub fn set(&self, done: u64) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = (elapsed > 0.0 && done > self.first).then(|| (done - self.first) as f64 / elapsed);
+        let eta = rate.map(|rate| (self.total - done.min(self.total)) as f64 / rate);
+
+        match self.mode {
+            Mode::Bar => {
+                let Some(bar) = &self.bar else { return };
+                bar.set_position(done);
+                let mut message = match (rate, self.pixels_per_unit) {
+                    (Some(rate), Some(pixels)) => format!("{:.1} Mpx/s", rate * pixels as f64 / 1e6),
+                    (Some(rate), None) => format!("{rate:.0} {}/s", self.unit),
+                    (None, _) => String::new(),
+                };
+                if let Some(eta) = eta.filter(|_| done < self.total) {
+                    message += &format!(", {} left", HumanDuration(Duration::from_secs_f64(eta)));
+                }
+                bar.set_message(message);
+            }
+            Mode::Quiet => {}
+            Mode::Json => {
+                let number = |value: Option<f64>| value.map_or("null".to_owned(), |value| format!("{value:.3}"));
+                let pixel_rate = match self.pixels_per_unit {
+                    Some(pixels) => format!(",\"pixel_rate\":{}", number(rate.map(|rate| rate * pixels as f64))),
+                    None => String::new(),
+                };
+                println!(
+                    "{{\"task\":\"{}\",\"unit\":\"{}\",\"done\":{done},\"total\":{},\"elapsed\":{elapsed:.3},\"rate\":{}{pixel_rate},\"eta\":{}}}",
+                    self.task,
+                    self.unit,
+                    self.total,
+                    number(rate),
+                    number(eta),
+                );
+            }
+        }
+    }
+
+    /// Leave the bar as it is once the task is done.
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish();
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Progressive rendering in tiles.
+//!
+//! Views that take longer than a frame to render, like deep zooms on the CPU,
+//! are rendered in passes spread over several frames while the viewer stays
+//! responsive. Each pass renders a snapshot of the view tile by tile, nearest
+//! to the focus first, so the part of the image being looked at resolves first
+//! and the rest keeps showing the previous pass until its tiles come up. The
+//! next pass starts on the then current view once a pass is complete.
+//!
+//! Coarse passes render every other pixel of every other row and fill the
+//! 2×2 block of each with it, in a quarter of the time, for views on the move
+//! where the detail would be gone by the time it shows.
+//!
+//! `--scan=interlaced` renders each pass in the seven sub-passes of Adam7
+//! interlacing instead, from one pixel in every 8×8 block to all of them,
+//! each pixel filling the block no sub-pass so far has rendered more of. So
+//! the whole view shows at once, blocky, and sharpens over the sub-passes,
+//! each going through the tiles nearest to the focus first. Coarse passes
+//! stop after the fifth, at one pixel in every 2×2 block.
+
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::{CoordinateTables, Mandelbrot, HEIGHT, WIDTH};
+
+/// Width and height of a tile in pixels, a multiple of the 8×8 blocks of
+/// Adam7
+const TILE_SIZE: usize = 32;
+
+/// An Adam7 sub-pass: the offset and the step of the pixels it renders in a
+/// tile, and the block each of those fills, in x then y
+struct Subpass {
+    offset: (usize, usize),
+    step: (usize, usize),
+    block: (usize, usize),
+}
+
+const ADAM7: [Subpass; 7] = [
+    Subpass { offset: (0, 0), step: (8, 8), block: (8, 8) },
+    Subpass { offset: (4, 0), step: (8, 8), block: (4, 8) },
+    Subpass { offset: (0, 4), step: (4, 8), block: (4, 4) },
+    Subpass { offset: (2, 0), step: (4, 4), block: (2, 4) },
+    Subpass { offset: (0, 2), step: (2, 4), block: (2, 2) },
+    Subpass { offset: (1, 0), step: (2, 2), block: (1, 2) },
+    Subpass { offset: (0, 1), step: (1, 2), block: (1, 1) },
+];
+/// Adam7 sub-passes of a coarse pass
+const COARSE_SUBPASSES: usize = 5;
+
+/// The order passes render the pixels of the view in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scan {
+    /// Tile by tile
+    Tiles,
+    /// In the Adam7 sub-passes over all tiles
+    Interlaced,
+}
+
+impl Scan {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tiles" => Some(Self::Tiles),
+            "interlaced" => Some(Self::Interlaced),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    /// The Adam7 sub-pass to render of the tile, all of it where `None`
+    subpass: Option<usize>,
+}
+
+/// What a tile rendered: the whole tile, or pixels of a sub-pass at their
+/// coordinates in the tile
+enum Rendered {
+    Tile(Vec<u8>, Vec<u32>),
+    Pixels(Vec<((usize, usize), [u8; 4], u32)>),
+}
+
+/// A pass over the view in progress
+struct Pass {
+    view: Mandelbrot,
+    tables: CoordinateTables,
+    /// Tiles still to render, the next one last
+    tiles: Vec<Tile>,
+    coarse: bool,
+}
+
+/// Renders views progressively into an image kept across frames.
+pub struct Progressive {
+    image: Vec<u8>,
+    /// Escape count of every pixel of `image`
+    counts: Vec<u32>,
+    pass: Option<Pass>,
+    /// Coordinate tables of the last pass, for the next one to reuse what it
+    /// can of
+    previous: Option<CoordinateTables>,
+    /// Whether the last complete pass was coarse
+    coarse: bool,
+}
+
+impl Progressive {
+    pub fn new() -> Self {
+        let pixels = (WIDTH * HEIGHT) as usize;
+        Self { image: vec![0; pixels * 4], counts: vec![0; pixels], pass: None, previous: None, coarse: false }
+    }
+
+    /// Whether the last pass is complete.
+    pub fn idle(&self) -> bool {
+        self.pass.is_none()
+    }
+
+    /// Whether the image is of a coarse pass, to be followed by a full one.
+    pub fn coarse(&self) -> bool {
+        self.coarse
+    }
+
+    /// The rendered image, in the layout of the frame buffer.
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// Escape counts of the rendered image, one per pixel row by row.
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// Show the whole view from escape counts rendered elsewhere, which is
+    /// only done while `idle`.
+    #[cfg(any(feature = "gpu", feature = "opencl"))]
+    pub fn show(&mut self, mandelbrot: &Mandelbrot, counts: Vec<u32>) {
+        mandelbrot.draw_counts(&mut self.image, &counts);
+        self.counts = counts;
+        self.coarse = false;
+    }
+
+    /// Switch to the palette of `mandelbrot`, recoloring what is rendered so
+    /// far from its escape counts where the coloring allows.
+    pub fn recolor(&mut self, mandelbrot: &Mandelbrot) {
+        if mandelbrot.coloring.counts_only() {
+            mandelbrot.draw_counts(&mut self.image, &self.counts);
+        }
+        if let Some(pass) = &mut self.pass {
+            (pass.view.palette, pass.view.palette_offset, pass.view.palette_reversed) = (mandelbrot.palette, mandelbrot.palette_offset, mandelbrot.palette_reversed);
+        }
+    }
+
+    /// Continue the current pass, or start one on `mandelbrot` when idle, and
+    /// render tiles until `budget` runs out. A pass starts with the tiles
+    /// nearest to `focus` in pixel coordinates. At least one batch of tiles is
+    /// rendered per call, so every pass completes eventually. A new pass is
+    /// `coarse` or not and follows `scan`, one under way continues as it
+    /// started.
+    pub fn render(&mut self, mandelbrot: &Mandelbrot, focus: (f64, f64), budget: Duration, coarse: bool, scan: Scan) {
+        let start = Instant::now();
+        let previous = &mut self.previous;
+        let pass = self.pass.get_or_insert_with(|| Pass {
+            view: mandelbrot.clone(),
+            tables: mandelbrot.coordinate_tables(previous.take()),
+            tiles: match scan {
+                Scan::Tiles => tiles(focus),
+                // The first sub-pass last, as the tiles do within each
+                Scan::Interlaced => {
+                    let subpasses = if coarse { COARSE_SUBPASSES } else { ADAM7.len() };
+                    (0..subpasses).rev().flat_map(|subpass| tiles(focus).into_iter().map(move |tile| Tile { subpass: Some(subpass), ..tile })).collect()
+                }
+            },
+            coarse,
+        });
+
+        // One tile per thread of the shared pool at a time
+        let batch_size = rayon::current_num_threads().max(1);
+        while !pass.tiles.is_empty() {
+            let batch = pass.tiles.split_off(pass.tiles.len().saturating_sub(batch_size));
+            let rendered: Vec<Rendered> = batch
+                .par_iter()
+                .map(|tile| {
+                    if let Some(subpass) = tile.subpass {
+                        return Rendered::Pixels(draw_subpass(&pass.view, &pass.tables, tile, subpass));
+                    }
+                    let mut pixels = vec![0; tile.width * tile.height * 4];
+                    let mut counts = vec![0; tile.width * tile.height];
+                    match pass.coarse {
+                        true => draw_coarse(&pass.view, &pass.tables, tile, &mut pixels, &mut counts),
+                        false => pass.view.draw_block(&pass.tables, tile.x, tile.y, tile.width, &mut pixels, &mut counts),
+                    }
+                    Rendered::Tile(pixels, counts)
+                })
+                .collect();
+
+            for (tile, rendered) in batch.iter().zip(&rendered) {
+                match rendered {
+                    Rendered::Tile(pixels, counts) => {
+                        let rows = pixels.chunks_exact(tile.width * 4).zip(counts.chunks_exact(tile.width));
+                        for (row, (span, counts)) in rows.enumerate() {
+                            let i = (tile.y + row) * WIDTH as usize + tile.x;
+                            self.image[i * 4..(i + tile.width) * 4].copy_from_slice(span);
+                            self.counts[i..i + tile.width].copy_from_slice(counts);
+                        }
+                    }
+                    // Each pixel fills its block, up to the edges of the tile
+                    Rendered::Pixels(pixels) => {
+                        let (width, height) = ADAM7[tile.subpass.unwrap_or_default()].block;
+                        for &((x, y), pixel, count) in pixels {
+                            for row in tile.y + y..tile.y + (y + height).min(tile.height) {
+                                let i = row * WIDTH as usize + tile.x + x;
+                                let columns = (x + width).min(tile.width) - x;
+                                self.image[i * 4..(i + columns) * 4].chunks_exact_mut(4).for_each(|rgba| rgba.copy_from_slice(&pixel));
+                                self.counts[i..i + columns].fill(count);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+
+        if pass.tiles.is_empty() {
+            self.coarse = pass.coarse;
+            self.previous = self.pass.take().map(|pass| pass.tables);
+        }
+    }
+}
+
+/// Draw every other pixel of every other row of `tile` into its `pixels`
+/// and `counts`, filling the 2×2 block of each.
+fn draw_coarse(view: &Mandelbrot, tables: &CoordinateTables, tile: &Tile, pixels: &mut [u8], counts: &mut [u32]) {
+    for y in (0..tile.height).step_by(2) {
+        for x in (0..tile.width).step_by(2) {
+            let (mut pixel, mut count) = ([0; 4], [0]);
+            view.draw_span(tables, tile.x + x, tile.y + y, &mut pixel, &mut count);
+            for y in y..(y + 2).min(tile.height) {
+                for x in x..(x + 2).min(tile.width) {
+                    let i = y * tile.width + x;
+                    pixels[i * 4..i * 4 + 4].copy_from_slice(&pixel);
+                    counts[i] = count[0];
+                }
+            }
+        }
+    }
+}
+
+/// The pixels of Adam7 sub-pass `subpass` of `tile`, with their coordinates
+/// in the tile.
+fn draw_subpass(view: &Mandelbrot, tables: &CoordinateTables, tile: &Tile, subpass: usize) -> Vec<((usize, usize), [u8; 4], u32)> {
+    let Subpass { offset: (x0, y0), step: (dx, dy), .. } = ADAM7[subpass];
+    let at: Vec<_> = (y0..tile.height).step_by(dy).flat_map(|y| (x0..tile.width).step_by(dx).map(move |x| (x, y))).collect();
+    let in_view: Vec<_> = at.iter().map(|&(x, y)| (tile.x + x, tile.y + y)).collect();
+    let (mut pixels, mut counts) = (vec![0; at.len() * 4], vec![0; at.len()]);
+    view.draw_pixels(tables, &in_view, &mut pixels, &mut counts);
+    at.into_iter().zip(pixels.chunks_exact(4).zip(counts)).map(|(at, (pixel, count))| (at, pixel.try_into().unwrap(), count)).collect()
+}
+
+/// The tiles covering the view, the one nearest to `focus` last.
+fn tiles(focus: (f64, f64)) -> Vec<Tile> {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let mut tiles: Vec<Tile> = (0..height)
+        .step_by(TILE_SIZE)
+        .flat_map(|y| {
+            (0..width).step_by(TILE_SIZE).map(move |x| Tile {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+                subpass: None,
+            })
+        })
+        .collect();
+
+    let distance = |tile: &Tile| {
+        let dx = tile.x as f64 + tile.width as f64 / 2.0 - focus.0;
+        let dy = tile.y as f64 + tile.height as f64 / 2.0 - focus.1;
+        dx * dx + dy * dy
+    };
+    tiles.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+    tiles
+}
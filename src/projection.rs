@@ -0,0 +1,65 @@
+//! Alternative projections of the complex plane.
+//!
+//! The viewport maps pixels to a point `w` of the view plane, which the
+//! projection then maps to the point that is iterated. Besides the plain plane
+//! this allows looking at the set under `c -> 1/c`, which turns the
+//! neighbourhood of infinity inside out, or a general Möbius transform.
+
+use num::Complex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// The view plane is the complex plane
+    Plane,
+    /// `c = 1 / w`
+    Inverse,
+    /// `c = (a w + b) / (c w + d)` for the coefficients `[a, b, c, d]`
+    Mobius([Complex<f64>; 4]),
+}
+
+impl Projection {
+    /// Parse the value of the `--mobius=<a,b,c,d>` argument, with complex
+    /// coefficients such as `1`, `0.5-2i` or `3i`.
+    pub fn from_mobius_arg(value: &str) -> Option<Self> {
+        let coefficients: Vec<Complex<f64>> = value
+            .split(',')
+            .map(|v| v.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        let [a, b, c, d]: [Complex<f64>; 4] = coefficients.try_into().ok()?;
+        // A zero determinant collapses the plane to a single point
+        if (a * d - b * c).norm_sqr() == 0.0 {
+            return None;
+        }
+        Some(Projection::Mobius([a, b, c, d]))
+    }
+
+    /// Map a point of the view plane to the point to iterate.
+    pub fn apply(&self, w: Complex<f64>) -> Complex<f64> {
+        match self {
+            Projection::Plane => w,
+            Projection::Inverse => w.inv(),
+            Projection::Mobius([a, b, c, d]) => (a * w + b) / (c * w + d),
+        }
+    }
+
+    /// Derivative of `apply` at `w`.
+    pub fn derivative(&self, w: Complex<f64>) -> Complex<f64> {
+        match self {
+            Projection::Plane => Complex::new(1.0, 0.0),
+            Projection::Inverse => -(w * w).inv(),
+            Projection::Mobius([a, b, c, d]) => {
+                let denominator = c * w + d;
+                (a * d - b * c) / (denominator * denominator)
+            }
+        }
+    }
+
+    /// Map an iterated point back to the view plane, the inverse of `apply`.
+    pub fn invert(&self, p: Complex<f64>) -> Complex<f64> {
+        match self {
+            Projection::Plane => p,
+            Projection::Inverse => p.inv(),
+            Projection::Mobius([a, b, c, d]) => (d * p - b) / (a - c * p),
+        }
+    }
+}
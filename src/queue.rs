@@ -0,0 +1,341 @@
+//! A queue of render jobs run one after another, e.g. overnight on a shared
+//! machine.
+//!
+//! `mandelbrot queue add [--at=<time>] [--after=<job>] <command> <options>`
+//! queues a headless command, like `render` or `zoom-video`, with its
+//! options, and prints the number of the job. It starts no earlier than
+//! `--at`, a UTC time like `2026-10-15T01:30Z` or a delay from now like
+//! `+6h`, `+90m` or `+30s`, and only once the job `--after` has finished;
+//! should that job fail, it is skipped. Presets are expanded as the job is
+//! queued.
+//!
+//! `mandelbrot queue run` runs the queued jobs in order, waiting for their
+//! start times, until none is left to run, and logs when each started and how
+//! it ended to `--log=<file>`, `queue.log` beside the queue by default, as
+//! well as to the terminal. Jobs queued while it runs are picked up too.
+//! `mandelbrot queue list` shows the jobs and how they stand.
+//! `mandelbrot queue remove <job>` takes one off the queue, and the jobs after
+//! it no longer wait for it.
+//!
+//! The queue is kept in `--queue=<file>`, by default `queue.json` in the
+//! state directory of the session, see [`crate::session`], and written again
+//! after every change, so a run that is stopped carries on with the job it
+//! was running when started again. One run at a time takes jobs from a
+//! queue: it holds a lock file beside it, `queue.json.lock` for `queue.json`,
+//! and another run fails while the lock is there. A run that was killed
+//! leaves it behind, to be deleted by hand.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use pixels::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::{arg_value, error_message, invalid_arg, presets, run_command, session, view_from_args};
+
+/// Longest wait for a start time before the queue is read again, for jobs
+/// queued meanwhile
+const MAX_WAIT: Duration = Duration::from_secs(60);
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Seconds since the Unix epoch of 10000-01-01, which start times come before
+const LATEST: u64 = 253_402_300_800;
+
+/// How a job stands
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    /// Not run since the job it comes after failed
+    Skipped,
+}
+
+/// A queued command
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub id: u32,
+    /// The command and its options
+    pub args: Vec<String>,
+    /// Seconds since the Unix epoch it starts at the earliest
+    pub at: Option<u64>,
+    /// The job it runs after
+    pub after: Option<u32>,
+    pub status: Status,
+    /// Why it failed or was skipped
+    pub message: Option<String>,
+}
+
+/// Run the `queue` command.
+pub fn queue_from_args(args: &[String]) -> Result<(), Error> {
+    let subcommand = args.get(1).map(String::as_str).filter(|arg| !arg.starts_with("--"));
+    // The options of the queue come before the command of a job
+    let split = args.iter().skip(2).position(|arg| !arg.starts_with("--")).map_or(args.len(), |i| i + 2);
+    let (options, rest) = args.split_at(split);
+    let path = match arg_value(options, "queue") {
+        Some(path) => PathBuf::from(path),
+        None => session::state_path("queue.json").ok_or_else(|| Error::UserDefined("no directory to keep the queue in".into()))?,
+    };
+    match subcommand {
+        Some("add") => {
+            let id = add(&path, options, rest.to_vec())?;
+            println!("queued job {id}");
+            Ok(())
+        }
+        Some("run") => {
+            let log = arg_value(options, "log").map_or_else(|| path.with_file_name("queue.log"), PathBuf::from);
+            run(&path, &log)
+        }
+        Some("list") => {
+            for job in load(&path)? {
+                let at = job.at.map(|at| format!(" at {}", format_time(at))).unwrap_or_default();
+                let after = job.after.map(|after| format!(" after {after}")).unwrap_or_default();
+                let message = job.message.map(|message| format!(": {message}")).unwrap_or_default();
+                println!("{} {:?}{at}{after}{message}: {}", job.id, job.status, job.args.join(" "));
+            }
+            Ok(())
+        }
+        Some("remove") => {
+            let id = rest.first().ok_or_else(|| invalid_arg("remove", ""))?;
+            let id: u32 = id.parse().map_err(|_| invalid_arg("remove", id))?;
+            remove(&path, id)
+        }
+        _ => Err(Error::UserDefined("expected queue add, run, list or remove".into())),
+    }
+}
+
+/// Take the job `id` off the queue, and out of the dependencies of those
+/// after it, which would wait for it forever otherwise.
+pub fn remove(path: &Path, id: u32) -> Result<(), Error> {
+    let mut jobs = load(path)?;
+    let before = jobs.len();
+    jobs.retain(|job| job.id != id);
+    if jobs.len() == before {
+        return Err(Error::UserDefined(format!("there is no job {id}").into()));
+    }
+    for job in jobs.iter_mut().filter(|job| job.after == Some(id)) {
+        job.after = None;
+    }
+    save(path, &jobs)
+}
+
+/// Queue the command line `job` with the queue `options`, returning its
+/// number.
+pub fn add(path: &Path, options: &[String], job: Vec<String>) -> Result<u32, Error> {
+    match job.first().map(String::as_str) {
+        None => return Err(Error::UserDefined("no command to queue".into())),
+        Some("queue") => return Err(Error::UserDefined("the queue can't queue itself".into())),
+        Some(_) => {}
+    }
+    // Checked now rather than hours later
+    view_from_args(&job)?;
+    let at = match arg_value(options, "at") {
+        Some(value) => Some(parse_time(&value, now()).ok_or_else(|| invalid_arg("at", &value))?),
+        None => None,
+    };
+    let mut jobs = load(path)?;
+    let after = match arg_value(options, "after") {
+        Some(value) => Some(value.parse().ok().filter(|after| jobs.iter().any(|job| job.id == *after)).ok_or_else(|| invalid_arg("after", &value))?),
+        None => None,
+    };
+    let id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+    let args = job.into_iter().filter(|arg| !arg.starts_with("--preset=")).collect();
+    jobs.push(Job { id, args, at, after, status: Status::Pending, message: None });
+    save(path, &jobs)?;
+    Ok(id)
+}
+
+/// Run the jobs of the queue at `path` until none is left to run, logging to
+/// `log`.
+pub fn run(path: &Path, log: &Path) -> Result<(), Error> {
+    let _lock = Lock::take(path)?;
+    let mut log = OpenOptions::new().create(true).append(true).open(log).map_err(|e| Error::UserDefined(format!("{}: {e}", log.display()).into()))?;
+    let mut note = |line: String| {
+        eprintln!("{line}");
+        // The jobs matter more than their log
+        let _ = writeln!(log, "{} {line}", format_time(now()));
+    };
+    let mut failed = 0;
+    loop {
+        let mut jobs = load(path)?;
+        // A job of a run that stopped is started over
+        for job in jobs.iter_mut().filter(|job| job.status == Status::Running) {
+            job.status = Status::Pending;
+        }
+        // Skip jobs after failed ones, and after those in turn
+        while let Some(index) = jobs.iter().position(|job| job.status == Status::Pending && job.after.is_some_and(|after| jobs.iter().any(|other| other.id == after && matches!(other.status, Status::Failed | Status::Skipped)))) {
+            let job = &mut jobs[index];
+            (job.status, job.message) = (Status::Skipped, Some(format!("job {} didn't finish", job.after.unwrap_or_default())));
+            note(format!("job {} skipped", job.id));
+        }
+        save(path, &jobs)?;
+
+        let ready = |job: &&Job| job.status == Status::Pending && job.after.is_none_or(|after| jobs.iter().any(|other| other.id == after && other.status == Status::Done));
+        let Some(next) = jobs.iter().filter(ready).min_by_key(|job| (job.at.unwrap_or(0), job.id)) else {
+            break;
+        };
+        let now = now();
+        if let Some(at) = next.at.filter(|&at| at > now) {
+            std::thread::sleep(Duration::from_secs(at - now).min(MAX_WAIT));
+            continue;
+        }
+
+        let (id, args) = (next.id, next.args.clone());
+        set_status(path, id, Status::Running, None)?;
+        note(format!("job {id} started: {}", args.join(" ")));
+        let start = Instant::now();
+        let result = presets::expand(args).and_then(|args| run_command(&args[0], &view_from_args(&args)?, &args));
+        match result {
+            Ok(()) => {
+                note(format!("job {id} done in {:.0?}", start.elapsed()));
+                set_status(path, id, Status::Done, None)?;
+            }
+            Err(e) => {
+                let message = error_message(&e);
+                note(format!("job {id} failed after {:.0?}: {message}", start.elapsed()));
+                set_status(path, id, Status::Failed, Some(message))?;
+                failed += 1;
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(Error::UserDefined(format!("{failed} jobs failed").into())),
+    }
+}
+
+/// The lock of a run on a queue, released when dropped.
+struct Lock(PathBuf);
+
+impl Lock {
+    fn take(path: &Path) -> Result<Self, Error> {
+        let lock = path.with_extension("json.lock");
+        let io = |e: std::io::Error| Error::UserDefined(format!("{}: {e}", lock.display()).into());
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io)?;
+        }
+        match OpenOptions::new().write(true).create_new(true).open(&lock) {
+            Ok(_) => Ok(Self(lock)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(Error::UserDefined(
+                format!("another run holds the queue, or delete {} if it was killed", lock.display()).into(),
+            )),
+            Err(e) => Err(io(e)),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// The jobs of the queue at `path`, none if there is no queue yet.
+pub fn load(path: &Path) -> Result<Vec<Job>, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| Error::UserDefined(format!("{}: {e}", path.display()).into())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(Error::UserDefined(format!("{}: {e}", path.display()).into())),
+    }
+}
+
+fn save(path: &Path, jobs: &[Job]) -> Result<(), Error> {
+    let io = |e: std::io::Error| Error::UserDefined(format!("{}: {e}", path.display()).into());
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(io)?;
+    }
+    let text = serde_json::to_string_pretty(jobs).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    // Replaced whole, so a run stopped while writing leaves the queue intact
+    let partial = path.with_extension("json.partial");
+    std::fs::write(&partial, text + "\n").map_err(io)?;
+    std::fs::rename(&partial, path).map_err(io)
+}
+
+/// Mark the job `id` of the queue at `path`, read again for jobs queued or
+/// removed meanwhile.
+fn set_status(path: &Path, id: u32, status: Status, message: Option<String>) -> Result<(), Error> {
+    let mut jobs = load(path)?;
+    if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+        (job.status, job.message) = (status, message);
+    }
+    save(path, &jobs)
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+/// Seconds since the Unix epoch of `value`, a UTC time
+/// `YYYY-MM-DDTHH:MM[:SS]Z` or a delay from `now` like `+6h`.
+pub fn parse_time(value: &str, now: u64) -> Option<u64> {
+    if let Some(delay) = value.strip_prefix('+') {
+        let unit = match delay.chars().last()? {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => SECONDS_PER_DAY,
+            _ => return None,
+        };
+        let amount: f64 = delay[..delay.len() - 1].parse().ok().filter(|amount: &f64| *amount >= 0.0 && amount.is_finite())?;
+        // The cast saturates, so delays beyond the range fail either check
+        return now.checked_add((amount * unit as f64).round() as u64).filter(|&at| at < LATEST);
+    }
+    let (date, time) = value.strip_suffix(['Z', 'z'])?.split_once(['T', 't', ' '])?;
+    let numbers = |text: &str, separator: char| text.split(separator).map(|part| part.parse::<u64>().ok()).collect::<Option<Vec<u64>>>();
+    let (date, time) = (numbers(date, '-')?, numbers(time, ':')?);
+    let (&[year, month, day], &[hour, minute, ref second @ ..]) = (date.as_slice(), time.as_slice()) else {
+        return None;
+    };
+    let second = match second {
+        [] => 0,
+        [second] => *second,
+        _ => return None,
+    };
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) || hour > 23 || minute > 59 || second > 59 || !(1970..=9999).contains(&year) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second)
+}
+
+/// Days of `month` in `year` of the Gregorian calendar.
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// `seconds` since the Unix epoch as a UTC time `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_time(seconds: u64) -> String {
+    let (days, time) = (seconds / SECONDS_PER_DAY, seconds % SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Days since the Unix epoch of a date, after Howard Hinnant's algorithm over
+/// eras of 400 years starting in March.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let (era, year_of_era) = (year / 400, year % 400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date of `days` since the Unix epoch, inverting `days_from_civil`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let (era, day_of_era) = (days / 146_097, days % 146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    (era * 400 + year_of_era + (month <= 2) as u64, month, day)
+}
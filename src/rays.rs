@@ -0,0 +1,171 @@
+//! External rays of the Mandelbrot set.
+//!
+//! The external ray of angle θ, in turns, is the field line of parameters
+//! outside the set whose orbits escape in the direction of 2^(n-1) θ turns at
+//! iteration n. Rays are traced inwards from far outside the set with
+//! Newton's method, a few points per halving of the escape potential, and the
+//! ray through a point outside the set is traced outwards the same way. Going
+//! out, each level of potential crossed yields a binary digit of the angle
+//! from the direction the orbit escapes in, so the angle is exact to as many
+//! digits as the point takes iterations to escape.
+
+use std::f64::consts::TAU;
+use std::fmt;
+
+use num::Complex;
+
+use crate::MAX_ITER;
+
+/// Orbits this far out escape in the direction of their external angle
+const ESCAPE_RADIUS: f64 = 65536.0;
+/// Points traced per halving of the escape potential
+const SHARPNESS: u32 = 8;
+/// Halvings of the potential traced inwards at most
+const RAY_DEPTH: u32 = 256;
+const NEWTON_STEPS: u32 = 16;
+/// Binary digits of an angle shown
+const SHOWN_DIGITS: usize = 32;
+
+/// A rational external angle `numerator / denominator` in turns, which can be
+/// doubled exactly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Angle {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl Angle {
+    /// Parse a `<p>/<q>` argument such as `1/3`.
+    pub fn from_arg(value: &str) -> Option<Self> {
+        let (numerator, denominator) = value.split_once('/')?;
+        let (numerator, denominator): (u64, u64) = (numerator.trim().parse().ok()?, denominator.trim().parse().ok()?);
+        (denominator > 0).then_some(Self { numerator: numerator % denominator, denominator })
+    }
+
+    fn turns(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// The angle of the ray the next iteration maps this one to.
+    fn doubled(self) -> Self {
+        let numerator = (2 * self.numerator as u128 % self.denominator as u128) as u64;
+        Self { numerator, denominator: self.denominator }
+    }
+}
+
+/// Points of the external ray of `angle` from far outside the set towards
+/// where it lands.
+pub fn trace(angle: Angle) -> Vec<Complex<f64>> {
+    let mut c = Complex::from_polar(ESCAPE_RADIUS, TAU * angle.turns());
+    let mut points = vec![c];
+    let mut angle = angle;
+    for depth in 0..RAY_DEPTH {
+        // Down from the escape radius at iteration depth + 1 to its square
+        // root, where the next iteration is back at the escape radius
+        for step in 1..=SHARPNESS {
+            let radius = ESCAPE_RADIUS.powf(0.5f64.powf(step as f64 / SHARPNESS as f64));
+            let target = Complex::from_polar(radius, TAU * angle.turns());
+            let Some(next) = newton(c, depth + 1, target) else {
+                return points;
+            };
+            let landed = (next - c).norm() <= f64::EPSILON * next.norm().max(1.0);
+            c = next;
+            points.push(c);
+            if landed {
+                return points;
+            }
+        }
+        angle = angle.doubled();
+    }
+    points
+}
+
+/// The external ray through a point outside the set, traced outwards
+#[derive(Clone, Debug)]
+pub struct OutwardRay {
+    /// Exact leading binary digits of the external angle
+    pub digits: Vec<bool>,
+    /// The external angle in turns
+    pub turns: f64,
+    pub points: Vec<Complex<f64>>,
+}
+
+impl fmt::Display for OutwardRay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits: String = self.digits.iter().take(SHOWN_DIGITS).map(|&digit| if digit { '1' } else { '0' }).collect();
+        let more = if self.digits.len() > SHOWN_DIGITS { "…" } else { "" };
+        write!(f, "{:.8} turns (0.{digits}{more})", self.turns)
+    }
+}
+
+/// Trace the external ray through `c` outwards and read off its angle,
+/// `None` when `c` doesn't escape.
+pub fn trace_out(c: Complex<f64>) -> Option<OutwardRay> {
+    let mut n = 0;
+    let mut z = Complex::new(0.0, 0.0);
+    while z.norm() <= ESCAPE_RADIUS {
+        if n == MAX_ITER {
+            return None;
+        }
+        z = z * z + c;
+        n += 1;
+    }
+    // The direction of the orbit this far out is the angle doubled n - 1 times
+    let mut turns = (z.arg() / TAU).rem_euclid(1.0);
+
+    let mut c = c;
+    let mut points = vec![c];
+    let mut digits = Vec::new();
+    while n > 1 {
+        // Up from the escape radius at iteration n to its square, where the
+        // previous iteration is at the escape radius
+        for step in 1..=SHARPNESS {
+            let radius = ESCAPE_RADIUS.powf(2f64.powf(step as f64 / SHARPNESS as f64));
+            let z = orbit(c, n).0;
+            if z.norm() >= radius {
+                continue;
+            }
+            c = newton(c, n, z / z.norm() * radius)?;
+            points.push(c);
+        }
+        // Halving the angle doubled n - 1 times leaves two choices for the
+        // previous one, binary digit n is the one in the direction of the
+        // orbit at iteration n
+        n -= 1;
+        let direction = (orbit(c, n).0.arg() / TAU).rem_euclid(1.0);
+        let distance = |turns: f64| (turns - direction).abs().min(1.0 - (turns - direction).abs());
+        let digit = distance((turns + 1.0) / 2.0) < distance(turns / 2.0);
+        turns = (turns + digit as u8 as f64) / 2.0;
+        digits.push(digit);
+    }
+    digits.reverse();
+    Some(OutwardRay { digits, turns, points })
+}
+
+/// The iteration `n` of the orbit of 0 under `z -> z^2 + c` and its
+/// derivative with respect to `c`.
+fn orbit(c: Complex<f64>, n: u32) -> (Complex<f64>, Complex<f64>) {
+    let (mut z, mut dc) = (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0));
+    for _ in 0..n {
+        dc = 2.0 * z * dc + 1.0;
+        z = z * z + c;
+    }
+    (z, dc)
+}
+
+/// Solve `orbit(c, n).0 == target` for `c` with Newton's method starting from
+/// `c`, `None` when it diverges.
+fn newton(mut c: Complex<f64>, n: u32, target: Complex<f64>) -> Option<Complex<f64>> {
+    for _ in 0..NEWTON_STEPS {
+        let (z, dc) = orbit(c, n);
+        let step = (z - target) / dc;
+        c -= step;
+        if !c.is_finite() {
+            return None;
+        }
+        if step.norm() <= f64::EPSILON * c.norm().max(1.0) {
+            break;
+        }
+    }
+    Some(c)
+}
@@ -0,0 +1,149 @@
+//! Recording of navigation sessions, to replay them later.
+//!
+//! `--record=<file.jsonl>` logs the navigation of the first window: the view
+//! it starts from, and whenever the center, zoom, rotation, Julia parameter,
+//! palette or palette offset change, their new values and the seconds since
+//! the recording started. These are the views the keys and the mouse lead
+//! to rather than the input itself, so replaying them doesn't depend on the
+//! frame rate of either run. The file has one JSON object per line, the
+//! first with the options of the starting view:
+//!
+//! ```text
+//! {"view":["--center=0,1","--zoom=1",...]}
+//! {"time":0.0,"center":[0.0,1.0],"zoom":1.0,"rotation":0.0,"julia":null,"palette":"classic","palette-offset":0.0}
+//! ```
+//!
+//! with the rotation in radians. `--replay=<file.jsonl>` plays a recording
+//! in the viewer, and `zoom-video --replay=<file.jsonl>` renders it frame by
+//! frame like an animation, at any frame rate and with motion blur, so a live
+//! exploration becomes a smooth video. Between the recorded frames the view
+//! moves as in animations, see [`crate::animation`].
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use num::Complex;
+use pixels::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{Animation, Easing, Keyframe};
+use crate::palette::Palette;
+use crate::{session, view_from_args, Mandelbrot};
+
+/// The first line of a recording
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    view: Vec<String>,
+}
+
+/// The navigation of a recorded frame
+#[derive(Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Frame {
+    time: f64,
+    center: [f64; 2],
+    zoom: f64,
+    rotation: f64,
+    julia: Option<[f64; 2]>,
+    palette: String,
+    palette_offset: f64,
+}
+
+impl Frame {
+    fn of(view: &Mandelbrot, time: f64) -> Self {
+        Frame {
+            time,
+            center: [view.center_x, view.center_y],
+            zoom: view.zoom,
+            rotation: view.rotation,
+            julia: view.julia.map(|c| [c.re, c.im]),
+            palette: view.palette.name(),
+            palette_offset: view.palette_offset,
+        }
+    }
+
+    /// `start` navigated as the frame was, or what is invalid about it.
+    fn apply(&self, start: &Mandelbrot) -> Result<Mandelbrot, &'static str> {
+        if !(self.time.is_finite() && self.zoom > 0.0 && self.zoom.is_finite()) {
+            return Err("invalid time or zoom");
+        }
+        Ok(Mandelbrot {
+            center_x: self.center[0],
+            center_y: self.center[1],
+            zoom: self.zoom,
+            rotation: self.rotation,
+            julia: self.julia.map(|[re, im]| Complex::new(re, im)),
+            palette: Palette::from_name(&self.palette).ok_or("invalid palette")?,
+            palette_offset: self.palette_offset,
+            ..start.clone()
+        })
+    }
+}
+
+/// A recording being written
+pub struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+    /// The last frame written, to leave out those that don't navigate
+    last: Option<Frame>,
+}
+
+impl Recorder {
+    /// Start recording to `path`.
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|e| Error::UserDefined(format!("{}: {e}", path.display()).into()))?;
+        Ok(Self { out: BufWriter::new(file), start: Instant::now(), last: None })
+    }
+
+    /// Log the view of a frame if it navigated anywhere since the last one,
+    /// starting with the options of the first.
+    pub fn record(&mut self, view: &Mandelbrot) -> std::io::Result<()> {
+        match &self.last {
+            Some(last) if Frame::of(view, last.time) == *last => return Ok(()),
+            Some(_) => {}
+            None => {
+                serde_json::to_writer(&mut self.out, &Header { view: session::view_options(view) })?;
+                writeln!(self.out)?;
+            }
+        }
+        let frame = Frame::of(view, self.start.elapsed().as_secs_f64());
+        serde_json::to_writer(&mut self.out, &frame)?;
+        writeln!(self.out)?;
+        self.last = Some(frame);
+        // Keep the recording whole if the viewer goes down
+        self.out.flush()
+    }
+}
+
+/// Load the recording at `path` as an animation through its frames.
+pub fn load(path: &Path) -> Result<Animation, Error> {
+    let invalid = |message: String| Error::UserDefined(format!("{}: {message}", path.display()).into());
+    let text = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let header: Header = match lines.next() {
+        Some((index, line)) => serde_json::from_str(line).map_err(|e| invalid(format!("line {}: {e}", index + 1)))?,
+        None => return Err(invalid("empty recording".to_owned())),
+    };
+    let start = view_from_args(&header.view)?;
+
+    let mut keyframes: Vec<Keyframe> = Vec::new();
+    let mut first_time = None;
+    for (index, line) in lines {
+        let wrong = |message: &str| invalid(format!("line {}: {message}", index + 1));
+        let frame: Frame = serde_json::from_str(line).map_err(|e| wrong(&e.to_string()))?;
+        let view = frame.apply(&start).map_err(wrong)?;
+        // The replay starts with the first frame
+        let time = frame.time - *first_time.get_or_insert(frame.time);
+        if keyframes.last().is_some_and(|last| time <= last.time) {
+            return Err(wrong("time not after the previous frame's"));
+        }
+        keyframes.push(Keyframe { time, view, easing: Easing::Linear });
+    }
+    if keyframes.len() < 2 {
+        return Err(invalid("no navigation recorded".to_owned()));
+    }
+    Ok(Animation::from_keyframes(&keyframes))
+}
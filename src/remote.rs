@@ -0,0 +1,240 @@
+//! Remote control of the viewer over HTTP and WebSocket.
+//!
+//! `--serve-control` serves an API on port 7878 of this machine only, or
+//! `--serve-control=<port>` on another port, and
+//! `--serve-control=<address:port>` on the address given, such as
+//! `0.0.0.0:7878` for all interfaces, so a phone or a second screen can
+//! drive the first window of an installation. It takes the commands of the
+//! control socket, see [`crate::control`]:
+//!
+//! - `GET /` is a remote control page for a browser, over the WebSocket
+//! - `GET /state` answers like `get-state`
+//! - `POST /command` with a JSON command of type `application/json` answers
+//!   it, e.g. `curl -H 'Content-Type: application/json'
+//!   -d '{"command":"set-zoom","zoom":200}' localhost:7878/command`
+//! - `GET /ws` opens a WebSocket, every text message a JSON command answered
+//!   by a text message
+//!
+//! Anyone who can reach the port can drive the viewer, so `screenshot` and
+//! `start-animation`, which name files on this machine, are refused. Web
+//! pages other than the remote control page can't: browsers send their
+//! `Origin`, and WebSockets and commands from an origin other than the
+//! server's are refused, as are commands of other types than JSON, which
+//! pages could post without asking the server first. Request heads are
+//! limited to 16 KiB, and clients that stall for 10 seconds are dropped,
+//! apart from open WebSockets, which the page keeps open while idle.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use pixels::Error;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+use winit::event_loop::EventLoopProxy;
+
+use crate::control::{self, network_address, Command, Request};
+
+const DEFAULT_PORT: u16 = 7878;
+/// Largest command body accepted, and request line and headers
+const MAX_BODY: usize = 65536;
+const MAX_HEAD: usize = 16384;
+/// How long a client may stall reading or writing before it is dropped
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width">
+<title>Mandelbrot remote</title>
+<style>body{font-family:sans-serif;max-width:30em;margin:auto;padding:1em}button{font-size:1.4em;min-width:3em;margin:.2em}input{width:100%}</style>
+</head><body>
+<h1>Mandelbrot remote</h1>
+<p><button data-pan="0,-1">&uarr;</button><button data-pan="-1,0">&larr;</button><button data-pan="1,0">&rarr;</button><button data-pan="0,1">&darr;</button></p>
+<p><button data-zoom="2">+</button><button data-zoom="0.5">&minus;</button></p>
+<p>Rotation <input id="rotation" type="range" min="-180" max="180"></p>
+<p>Palette offset <input id="offset" type="range" min="0" max="1000"></p>
+<pre id="state"></pre>
+<script>
+let state = null;
+const socket = new WebSocket(`ws://${location.host}/ws`);
+const send = command => socket.send(JSON.stringify(command));
+const refresh = () => send({command: "get-state"});
+socket.onopen = refresh;
+socket.onmessage = event => {
+  const reply = JSON.parse(event.data);
+  if (reply.state) {
+    state = reply.state;
+    document.getElementById("state").textContent = JSON.stringify(state, null, 1);
+    document.getElementById("rotation").value = state.rotation;
+    document.getElementById("offset").value = state.palette_offset;
+  } else {
+    refresh();
+  }
+};
+for (const button of document.querySelectorAll("[data-zoom]")) {
+  button.onclick = () => state && send({command: "set-zoom", zoom: state.zoom * button.dataset.zoom});
+}
+for (const button of document.querySelectorAll("[data-pan]")) {
+  // A quarter of the view height each step
+  button.onclick = () => {
+    if (!state) return;
+    const [dx, dy] = button.dataset.pan.split(",").map(Number);
+    const step = 0.25 * 2.5 / state.zoom;
+    send({command: "set-center", re: state.center[0] + dx * step, im: state.center[1] + dy * step});
+  };
+}
+document.getElementById("rotation").oninput = event => send({command: "set-rotation", degrees: Number(event.target.value)});
+document.getElementById("offset").oninput = event => send({command: "set-palette-offset", offset: Number(event.target.value)});
+</script>
+</body></html>
+"#;
+
+/// Serve the API `--serve-control` asks for, if any, sending the commands to
+/// `requests` and waking the event loop through `proxy` for each.
+pub fn listen(args: &[String], requests: Sender<Request>, proxy: EventLoopProxy<()>) -> Result<(), Error> {
+    let Some(arg) = args.iter().find(|arg| *arg == "--serve-control" || arg.starts_with("--serve-control=")) else {
+        return Ok(());
+    };
+    let address = match arg.strip_prefix("--serve-control=") {
+        Some(value) => network_address(value, "127.0.0.1"),
+        None => format!("127.0.0.1:{DEFAULT_PORT}"),
+    };
+    let listener = TcpListener::bind(&address).map_err(|e| Error::UserDefined(format!("--serve-control: {address}: {e}").into()))?;
+    eprintln!("serving remote control on http://{address}/");
+
+    std::thread::Builder::new()
+        .name("remote".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (requests, proxy) = (requests.clone(), proxy.clone());
+                std::thread::spawn(move || serve(stream, requests, proxy));
+            }
+        })
+        .map_err(|e| Error::UserDefined(Box::new(e)))?;
+    Ok(())
+}
+
+/// Answer one HTTP request, or the messages of a WebSocket opened by one.
+fn serve(stream: TcpStream, requests: Sender<Request>, proxy: EventLoopProxy<()>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    let mut head = MAX_HEAD;
+    read_head_line(&mut reader, &mut line, &mut head)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default().to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if read_head_line(&mut reader, &mut line, &mut head)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_owned()));
+        }
+    }
+    let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+    let mut stream = stream;
+    if !same_origin(header("origin"), header("host")) && path != "/" {
+        return respond(&mut stream, "403 Forbidden", "application/json", &control::failed("cross-origin requests are refused").to_string());
+    }
+    match (method.as_str(), path.as_str()) {
+        ("GET", "/") => respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+        ("GET", "/state") => answer(&mut stream, Ok(Command::GetState), &requests, &proxy),
+        ("POST", "/command") => {
+            if !json(header("content-type")) {
+                return respond(&mut stream, "415 Unsupported Media Type", "application/json", &control::failed("expected application/json").to_string());
+            }
+            let length = header("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+            if length > MAX_BODY {
+                return respond(&mut stream, "413 Payload Too Large", "application/json", &control::failed("command too long").to_string());
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            answer(&mut stream, serde_json::from_slice(&body), &requests, &proxy)
+        }
+        ("GET", "/ws") => match header("sec-websocket-key") {
+            Some(key) if header("upgrade").is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket")) => {
+                let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+                write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n")?;
+                stream.flush()?;
+                // The remote control page keeps its socket open while idle
+                stream.set_read_timeout(None)?;
+                relay(WebSocket::from_raw_socket(stream, Role::Server, None), &requests, &proxy);
+                Ok(())
+            }
+            _ => respond(&mut stream, "400 Bad Request", "application/json", &control::failed("expected a WebSocket upgrade").to_string()),
+        },
+        _ => respond(&mut stream, "404 Not Found", "application/json", &control::failed(format!("no {method} {path}")).to_string()),
+    }
+}
+
+/// Read a line of the request head into `line`, failing once the head
+/// grows past the `left` bytes it has left.
+fn read_head_line(reader: &mut BufReader<TcpStream>, line: &mut String, left: &mut usize) -> io::Result<usize> {
+    let read = reader.by_ref().take(*left as u64).read_line(line)?;
+    *left -= read;
+    match *left {
+        0 => Err(io::Error::new(io::ErrorKind::InvalidData, "request head too long")),
+        _ => Ok(read),
+    }
+}
+
+/// Whether a request from `origin`, as browsers send it, comes from the
+/// server at `host` itself, or from no web page at all.
+pub fn same_origin(origin: Option<&str>, host: Option<&str>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let origin = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://"));
+    origin.is_some() && origin == host
+}
+
+/// Whether a `content-type` is JSON, with or without parameters.
+pub fn json(content_type: Option<&str>) -> bool {
+    content_type.and_then(|value| value.split(';').next()).is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Answer the commands of a WebSocket until it closes or the viewer quits.
+fn relay(mut socket: WebSocket<TcpStream>, requests: &Sender<Request>, proxy: &EventLoopProxy<()>) {
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) | Err(_) => return,
+            Ok(_) => continue,
+        };
+        let Some(reply) = reply(serde_json::from_str(&text), requests, proxy) else {
+            let _ = socket.close(None);
+            return;
+        };
+        if socket.send(Message::Text(reply.to_string())).is_err() {
+            return;
+        }
+    }
+}
+
+/// The answer to a command parsed from a request, `None` once the viewer has quit.
+fn reply(command: serde_json::Result<Command>, requests: &Sender<Request>, proxy: &EventLoopProxy<()>) -> Option<serde_json::Value> {
+    match command {
+        Ok(Command::Screenshot { .. } | Command::StartAnimation { .. }) => Some(control::failed("not available remotely")),
+        Ok(command) => control::submit(command, requests, proxy),
+        Err(e) => Some(control::failed(e)),
+    }
+}
+
+fn answer(stream: &mut TcpStream, command: serde_json::Result<Command>, requests: &Sender<Request>, proxy: &EventLoopProxy<()>) -> std::io::Result<()> {
+    match reply(command, requests, proxy) {
+        Some(reply) => respond(stream, "200 OK", "application/json", &reply.to_string()),
+        None => respond(stream, "503 Service Unavailable", "application/json", &control::failed("the viewer has quit").to_string()),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> std::io::Result<()> {
+    write!(stream, "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())?;
+    stream.flush()
+}
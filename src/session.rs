@@ -0,0 +1,123 @@
+//! Sessions continued across launches.
+//!
+//! On exit the viewer saves the view of the last window closed, with its
+//! size and position, as the command line options that recreate it. The next
+//! launch of the viewer starts from them, with the options it is given taking
+//! precedence, unless `--fresh` is given. The session lives in
+//! `$XDG_STATE_HOME/mandelbrot/session` (`~/.local/state` by default), or
+//! `%LOCALAPPDATA%\mandelbrot\session` on Windows, one option per line.
+
+use std::path::PathBuf;
+
+use pixels::Error;
+
+use crate::viewer::Geometry;
+use crate::{effects, error_message, skew, view_from_args, Mandelbrot};
+
+/// The file `name` in the state directory of the program, where the session
+/// and the render queue live.
+pub fn state_path(name: &str) -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let state = if cfg!(windows) {
+        var("LOCALAPPDATA")?
+    } else {
+        var("XDG_STATE_HOME").or_else(|| Some(var("HOME")?.join(".local").join("state")))?
+    };
+    Some(state.join("mandelbrot").join(name))
+}
+
+/// The command line `args` followed by the options of the saved session,
+/// unless there is none or it no longer makes a valid view.
+pub fn resume(args: Vec<String>) -> Vec<String> {
+    let Some(saved) = state_path("session").and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return args;
+    };
+    let resumed: Vec<String> = args.iter().cloned().chain(saved.lines().map(str::to_owned)).collect();
+    match view_from_args(&resumed) {
+        Ok(_) => resumed,
+        Err(e) => {
+            eprintln!("not resuming the last session: {}", error_message(&e));
+            args
+        }
+    }
+}
+
+/// Save the view and window geometry as the session to resume.
+pub fn save(mandelbrot: &Mandelbrot, geometry: Geometry) -> Result<(), Error> {
+    let io = |e: std::io::Error| Error::UserDefined(Box::new(e));
+    let path = state_path("session").ok_or_else(|| Error::UserDefined("no directory to save the session in".into()))?;
+    let mut options = view_options(mandelbrot);
+    // A full screen window is saved by its monitor, the size and position
+    // being the monitor's
+    if geometry.fullscreen {
+        options.push("--fullscreen".to_owned());
+        options.extend(geometry.monitor.map(|n| format!("--monitor={n}")));
+    } else {
+        if let Some((width, height)) = geometry.size {
+            options.push(format!("--window-size={width}x{height}"));
+        }
+        if let Some((x, y)) = geometry.position {
+            options.push(format!("--window-position={x},{y}"));
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(io)?;
+    }
+    std::fs::write(&path, options.join("\n") + "\n").map_err(io)
+}
+
+/// The options `view_from_args` turns back into the view.
+pub fn view_options(mandelbrot: &Mandelbrot) -> Vec<String> {
+    let m = mandelbrot;
+    let mut options = vec![
+        format!("--center={},{}", m.center_x, m.center_y),
+        format!("--zoom={}", m.zoom),
+        format!("--rotation={}", m.rotation.to_degrees()),
+        format!("--exponent={}", m.exponent),
+        format!("--bailout={}", m.bailout_radius),
+        format!("--bailout-norm={}", m.bailout.name()),
+        format!("--palette-offset={}", m.palette_offset),
+        format!("--palette={}", m.palette.name()),
+        format!("--coloring={}", m.coloring.name()),
+        format!("--stalk-width={}", m.stalk_width),
+        format!("--stalk-blend={}", m.stalk_blend),
+        format!("--trap-blend={}", m.trap_blend),
+        format!("--angle-blend={}", m.angle_blend),
+    ];
+    if m.skew != skew::IDENTITY {
+        let [a, b, c, d] = m.skew;
+        options.push(format!("--skew={a},{b},{c},{d}"));
+    }
+    if let Some(julia) = m.julia {
+        options.push(format!("--julia={},{}", julia.re, julia.im));
+    }
+    if m.palette_reversed {
+        options.push("--palette-reversed".to_owned());
+    }
+    if let Some(percent) = m.auto_range {
+        options.push(format!("--auto-range={percent}"));
+        options.push(format!("--range-smoothing={}", m.range_smoothing));
+    }
+    if m.contours {
+        options.push(format!("--contours={}", m.contour_interval));
+    }
+    if let Some(limit) = m.adaptive_iterations {
+        options.push(format!("--adaptive-iterations={limit}"));
+    }
+    if !m.effects.is_empty() {
+        options.push(effects::to_arg(&m.effects));
+    }
+    if let Some(lut) = &m.lut {
+        let path = std::fs::canonicalize(lut.path()).unwrap_or_else(|_| lut.path().to_owned());
+        options.push(format!("--lut={}", path.display()));
+    }
+    if let Some(image) = &m.trap_image {
+        // Absolute, as the next launch may start elsewhere
+        let path = std::fs::canonicalize(image.path()).unwrap_or_else(|_| image.path().to_owned());
+        options.push(format!("--trap-image={}", path.display()));
+        options.push(format!("--trap-center={},{}", image.center.re, image.center.im));
+        options.push(format!("--trap-size={}", image.size));
+    }
+    options
+}
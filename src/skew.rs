@@ -0,0 +1,114 @@
+//! Skew and stretch of the viewport.
+//!
+//! Features near minibrot "needles" can be extremely anisotropic. The view
+//! applies a 2x2 matrix with unit determinant to pixel offsets (before the view
+//! rotation) so such features can be straightened out, and `estimate` derives a
+//! matrix that makes the current view locally isotropic.
+
+use crate::{Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Row-major 2x2 matrix `[a, b, c, d]` for `[[a, b], [c, d]]`
+pub type Matrix = [f64; 4];
+
+pub const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0];
+
+/// Number of samples across the view used by `estimate`
+const SAMPLES_X: usize = 40;
+const SAMPLES_Y: usize = 30;
+/// Largest stretch factor `estimate` will produce
+const MAX_STRETCH: f64 = 1e4;
+
+pub fn apply(m: &Matrix, x: f64, y: f64) -> (f64, f64) {
+    (m[0] * x + m[1] * y, m[2] * x + m[3] * y)
+}
+
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+    ]
+}
+
+pub fn inverse(m: &Matrix) -> Matrix {
+    let det = m[0] * m[3] - m[1] * m[2];
+    [m[3] / det, -m[1] / det, -m[2] / det, m[0] / det]
+}
+
+/// Scale `m` to unit determinant, so it skews and stretches without zooming.
+pub fn normalize(m: &Matrix) -> Option<Matrix> {
+    let det = m[0] * m[3] - m[1] * m[2];
+    if !det.is_finite() || det <= 0.0 {
+        return None;
+    }
+    let s = det.sqrt();
+    Some([m[0] / s, m[1] / s, m[2] / s, m[3] / s])
+}
+
+/// Parse the value of the `--skew=<a,b,c,d>` argument.
+pub fn from_arg(value: &str) -> Option<Matrix> {
+    let values: Vec<f64> = value.split(',').map(|v| v.trim().parse().ok()).collect::<Option<_>>()?;
+    normalize(&values.try_into().ok()?)
+}
+
+/// Estimate the skew that makes the features of the current view isotropic.
+///
+/// The gradient of the smooth escape count follows from the derivative of the
+/// orbit: with `z` the final orbit value and `dz` its derivative with respect
+/// to the pixel's point, the gradient points along `-conj(dz / z)`. Stretched
+/// features have gradients that mostly point one way, so the structure tensor
+/// of the normalised gradients is anisotropic; the skew is its inverse square
+/// root, expressed relative to the view rotation. Returns `None` when too few
+/// sampled points escape to tell.
+pub fn estimate(mandelbrot: &Mandelbrot) -> Option<Matrix> {
+    // Structure tensor [[xx, xy], [xy, yy]] in complex plane coordinates
+    let (mut xx, mut xy, mut yy) = (0.0, 0.0, 0.0);
+    let mut count = 0;
+    for j in 0..SAMPLES_Y {
+        for i in 0..SAMPLES_X {
+            let x = (i as f64 + 0.5) * WIDTH as f64 / SAMPLES_X as f64;
+            let y = (j as f64 + 0.5) * HEIGHT as f64 / SAMPLES_Y as f64;
+            let (n, z, dz) = mandelbrot.iterate_derivative(mandelbrot.pixel_to_complex(x, y));
+            if n == MAX_ITER {
+                continue;
+            }
+            let gradient = -(dz / z).conj();
+            let length = gradient.norm();
+            if !length.is_finite() || length == 0.0 {
+                continue;
+            }
+            let (gx, gy) = (gradient.re / length, gradient.im / length);
+            xx += gx * gx;
+            xy += gx * gy;
+            yy += gy * gy;
+            count += 1;
+        }
+    }
+    if count < 8 {
+        return None;
+    }
+
+    // Inverse square root of the symmetric positive definite tensor
+    let det = xx * yy - xy * xy;
+    if det <= 0.0 {
+        return None;
+    }
+    let s = det.sqrt();
+    let t = (xx + yy + 2.0 * s).sqrt();
+    let root = [(xx + s) / t, xy / t, xy / t, (yy + s) / t];
+    let skew = normalize(&inverse(&root))?;
+
+    // Limit the stretch, the larger singular value of a unit matrix
+    let [a, b, c, d] = skew;
+    let sum = a * a + b * b + c * c + d * d;
+    let stretch = (sum / 2.0 + ((sum / 2.0).powi(2) - 1.0).max(0.0).sqrt()).sqrt();
+    if !stretch.is_finite() || stretch > MAX_STRETCH {
+        return None;
+    }
+
+    // The view applies the skew before the rotation
+    let (sin, cos) = mandelbrot.rotation.sin_cos();
+    let unrotate = [cos, sin, -sin, cos];
+    normalize(&multiply(&multiply(&unrotate, &skew), &[cos, -sin, sin, cos]))
+}
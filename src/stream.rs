@@ -0,0 +1,144 @@
+//! Live output of the viewer to a pipe.
+//!
+//! `--stream=<path>` writes the view of the first window, overlays included,
+//! to a named pipe or file continuously, or to the standard output with
+//! `--stream=-`, so e.g. ffmpeg or OBS can take it in as a live source. A
+//! frame goes out every `1/--stream-fps` seconds (default 30), the latest one
+//! drawn again while the view stays still, so the stream keeps a constant
+//! frame rate. Frames are written from a thread of their own, and a reader
+//! falling behind slows down the stream rather than the viewer.
+//!
+//! `--stream-format` picks the format of the frames, all 800x600:
+//!
+//! - `y4m` (the default): a YUV4MPEG2 stream, 4:4:4 BT.601 in limited range,
+//!   which describes its size and frame rate itself:
+//!   `mandelbrot --stream=- | ffmpeg -i - zoom.mp4`
+//! - `rgba`: raw 8-bit RGBA, row by row from the top left, with nothing
+//!   between frames, for
+//!   `ffmpeg -f rawvideo -pixel_format rgba -video_size 800x600 -framerate 30 -i <path>`
+//! - `png`: every frame as a complete PNG image, one after the other, for
+//!   `ffmpeg -f image2pipe -c:v png -framerate 30 -i <path>`
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use pixels::Error;
+
+use crate::{arg_value, invalid_arg, parse_positive, HEIGHT, WIDTH};
+
+const DEFAULT_FPS: f64 = 30.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Y4m,
+    Rgba,
+    Png,
+}
+
+/// Frames on their way to the stream
+pub struct Stream {
+    /// The frame last drawn, which the writing thread stops without
+    latest: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl Stream {
+    /// Start the stream `--stream` asks for, if any.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, Error> {
+        let Some(target) = arg_value(args, "stream") else {
+            return Ok(None);
+        };
+        let format = match arg_value(args, "stream-format").as_deref() {
+            None | Some("y4m") => Format::Y4m,
+            Some("rgba") => Format::Rgba,
+            Some("png") => Format::Png,
+            Some(value) => return Err(invalid_arg("stream-format", value)),
+        };
+        let fps = match arg_value(args, "stream-fps") {
+            Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("stream-fps", &value))?,
+            None => DEFAULT_FPS,
+        };
+
+        let latest = Arc::new(Mutex::new(None));
+        let frames = Arc::downgrade(&latest);
+        std::thread::Builder::new()
+            .name("stream".to_owned())
+            .spawn(move || {
+                if let Err(e) = write_frames(&target, format, fps, frames) {
+                    eprintln!("stopping the stream to {target}: {e}");
+                }
+            })
+            .map_err(|e| Error::UserDefined(Box::new(e)))?;
+        Ok(Some(Self { latest }))
+    }
+
+    /// Send `frame`, an RGBA frame buffer, from now on.
+    pub fn publish(&self, frame: &[u8]) {
+        if let Ok(mut latest) = self.latest.lock() {
+            match latest.as_mut() {
+                Some(latest) => latest.copy_from_slice(frame),
+                None => *latest = Some(frame.to_vec()),
+            }
+        }
+    }
+}
+
+/// Write the latest of `frames` to `target` at `fps` frames per second, once
+/// there is one, until the stream is dropped.
+fn write_frames(target: &str, format: Format, fps: f64, frames: Weak<Mutex<Option<Vec<u8>>>>) -> std::io::Result<()> {
+    // Opening a named pipe waits for a reader
+    let mut out: Box<dyn Write> = match target {
+        "-" => Box::new(std::io::stdout().lock()),
+        path => Box::new(OpenOptions::new().write(true).create(true).truncate(true).open(path)?),
+    };
+    if format == Format::Y4m {
+        // Frame rates as a fraction to the millihertz
+        let rate = (fps * 1000.0).round() as u64;
+        writeln!(out, "YUV4MPEG2 W{WIDTH} H{HEIGHT} F{rate}:1000 Ip A1:1 C444")?;
+    }
+
+    let interval = Duration::from_secs_f64(1.0 / fps);
+    let mut next = Instant::now();
+    while let Some(frames) = frames.upgrade() {
+        let frame = frames.lock().ok().and_then(|latest| latest.clone());
+        drop(frames);
+        if let Some(frame) = frame {
+            match format {
+                Format::Y4m => {
+                    out.write_all(b"FRAME\n")?;
+                    out.write_all(&to_yuv444(&frame))?;
+                }
+                Format::Rgba => out.write_all(&frame)?,
+                Format::Png => {
+                    let mut encoder = png::Encoder::new(&mut out, WIDTH, HEIGHT);
+                    encoder.set_color(png::ColorType::Rgba);
+                    encoder.set_depth(png::BitDepth::Eight);
+                    encoder.write_header()?.write_image_data(&frame)?;
+                }
+            }
+            out.flush()?;
+        }
+
+        // Keep to the frame rate, starting over after falling behind
+        next += interval;
+        match next.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            None => next = Instant::now(),
+        }
+    }
+    Ok(())
+}
+
+/// The planes of an RGBA frame in limited range BT.601 Y'CbCr, luma first.
+fn to_yuv444(frame: &[u8]) -> Vec<u8> {
+    let pixels = frame.len() / 4;
+    let mut planes = vec![0; 3 * pixels];
+    for (i, pixel) in frame.chunks_exact(4).enumerate() {
+        let [r, g, b] = [0, 1, 2].map(|c| pixel[c] as f64);
+        planes[i] = (16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0).round() as u8;
+        planes[pixels + i] = (128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0).round() as u8;
+        planes[2 * pixels + i] = (128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0).round() as u8;
+    }
+    planes
+}
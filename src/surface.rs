@@ -0,0 +1,150 @@
+//! Window surfaces with more than 8 bits per channel.
+//!
+//! `--surface=10-bit` presents the windows on a 10-bit surface and
+//! `--surface=hdr` on a half float one in the extended linear range of HDR
+//! displays, where the adapter and the window system offer them, falling
+//! back to 8 bits elsewhere. The frames are still composed with 8 bits per
+//! channel; on the deeper surfaces they are dithered into the finer steps,
+//! so smooth gradients don't band. HDR surfaces take 1 as 80 nits, and the
+//! white of the frame is placed at `--hdr-white=<nits>`, 203 by default as
+//! for HDR video.
+
+use std::sync::Arc;
+
+use pixels::{wgpu, Error, Pixels, PixelsBuilder, SurfaceTexture};
+use rayon::prelude::*;
+use winit::window::Window;
+
+use crate::{arg_value, invalid_arg, parse_positive, HEIGHT, WIDTH};
+
+/// Brightness of 1 on the extended linear surfaces, in nits
+const LINEAR_WHITE_NITS: f32 = 80.0;
+/// Default brightness of the white of frames on HDR surfaces, in nits
+const DEFAULT_HDR_WHITE: f32 = 203.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Surface {
+    Standard,
+    TenBit,
+    /// Extended linear sRGB, with frame white at `white` nits
+    Hdr { white: f32 },
+}
+
+impl Surface {
+    /// The surface `--surface` asks for, 8 bits without it.
+    pub fn from_args(args: &[String]) -> Result<Self, Error> {
+        let white = match arg_value(args, "hdr-white") {
+            Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("hdr-white", &value))? as f32,
+            None => DEFAULT_HDR_WHITE,
+        };
+        match arg_value(args, "surface").as_deref() {
+            None | Some("8-bit") => Ok(Surface::Standard),
+            Some("10-bit") => Ok(Surface::TenBit),
+            Some("hdr") => Ok(Surface::Hdr { white }),
+            Some(value) => Err(invalid_arg("surface", value)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Surface::Standard => "8-bit",
+            Surface::TenBit => "10-bit",
+            Surface::Hdr { .. } => "hdr",
+        }
+    }
+
+    /// Format of the window surface, `None` for the one the surface prefers
+    fn format(self) -> Option<wgpu::TextureFormat> {
+        match self {
+            Surface::Standard => None,
+            Surface::TenBit => Some(wgpu::TextureFormat::Rgb10a2Unorm),
+            Surface::Hdr { .. } => Some(wgpu::TextureFormat::Rgba16Float),
+        }
+    }
+}
+
+/// The pixel buffer of the view with a surface on `window`, as deep as
+/// `surface` where the window supports it, and the surface it got.
+pub fn new_pixels(window: &Arc<Window>, surface: Surface) -> Result<(Pixels<'static>, Surface), Error> {
+    let surface = match surface.format() {
+        Some(format) if !supported(window, format) => {
+            eprintln!("the window doesn't support {} colors, showing 8 bits per channel", surface.name());
+            Surface::Standard
+        }
+        _ => surface,
+    };
+    let window_size = window.inner_size();
+    let surface_texture = SurfaceTexture::new(window_size.width*3, window_size.height*4, window.clone());
+    let builder = PixelsBuilder::new(WIDTH, HEIGHT, surface_texture);
+    let pixels = match surface.format() {
+        Some(format) => builder.texture_format(wgpu::TextureFormat::Rgba16Float).surface_texture_format(format).build()?,
+        None => builder.build()?,
+    };
+    Ok((pixels, surface))
+}
+
+/// Whether surfaces on `window` can have `format`, asked of a surface of
+/// its own as `pixels` doesn't share its surface before it is configured.
+fn supported(window: &Arc<Window>, format: wgpu::TextureFormat) -> bool {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let Ok(surface) = instance.create_surface(window.clone()) else {
+        return false;
+    };
+    let options = wgpu::RequestAdapterOptions { compatible_surface: Some(&surface), ..Default::default() };
+    pollster::block_on(instance.request_adapter(&options)).is_some_and(|adapter| surface.get_capabilities(&adapter).formats.contains(&format))
+}
+
+/// Copy the RGBA `frame` into the texture of `pixels` for `surface`.
+pub fn upload(surface: Surface, frame: &[u8], pixels: &mut Pixels) {
+    let scale = match surface {
+        Surface::Standard => return pixels.frame_mut().copy_from_slice(frame),
+        Surface::TenBit => None,
+        Surface::Hdr { white } => Some(white / LINEAR_WHITE_NITS),
+    };
+    pixels.frame_mut().par_chunks_exact_mut(WIDTH as usize * 8).zip(frame.par_chunks_exact(WIDTH as usize * 4)).enumerate().for_each(|(y, (texels, row))| {
+        for (x, (texel, pixel)) in texels.chunks_exact_mut(8).zip(row.chunks_exact(4)).enumerate() {
+            let offset = dither(x, y) / 255.0;
+            for (i, &channel) in pixel.iter().enumerate() {
+                let encoded = channel as f32 / 255.0;
+                let value = match (i, scale) {
+                    (3, _) => encoded,
+                    (_, None) => (encoded + offset).clamp(0.0, 1.0),
+                    (_, Some(scale)) => linear((encoded + offset).clamp(0.0, 1.0)) * scale,
+                };
+                texel[2 * i..2 * i + 2].copy_from_slice(&half(value).to_le_bytes());
+            }
+        }
+    });
+}
+
+/// Offset of pixel `x`, `y` between -1/2 and 1/2 in interleaved gradient
+/// noise, which breaks up bands without a visible pattern.
+fn dither(x: usize, y: usize) -> f32 {
+    (52.982_918 * (0.067_110_56 * x as f32 + 0.005_837_15 * y as f32).fract()).fract() - 0.5
+}
+
+/// Linear light of an sRGB encoded channel between 0 and 1.
+fn linear(encoded: f32) -> f32 {
+    if encoded <= 0.04045 { encoded / 12.92 } else { ((encoded + 0.055) / 1.055).powf(2.4) }
+}
+
+/// The nearest half float to `value`, for values within the half float range
+/// of about 65504.
+pub fn half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = (bits >> 16 & 0x8000) as u16;
+    let exponent = (bits >> 23 & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    match exponent {
+        // Too small even for a subnormal half
+        ..=-11 => sign,
+        // Subnormal
+        -10..=0 => {
+            let shift = 14 - exponent;
+            sign | (((mantissa | 0x80_0000) + (1 << (shift - 1))) >> shift) as u16
+        }
+        31.. => sign | 0x7c00,
+        // Rounding may carry into the exponent, which is still right
+        _ => sign | (((exponent as u32) << 10) + ((mantissa + 0x1000) >> 13)) as u16,
+    }
+}
@@ -0,0 +1,151 @@
+//! SVG export of the set boundary and iso-iteration contours.
+//!
+//! `mandelbrot svg --out=<file.svg> [--size=<width>x<height>] [--levels=<n,...>]`
+//! samples the escape counts of the initial view on a grid of the given size,
+//! like `render`, and traces the boundary between the points reaching each
+//! level and the rest with marching squares. The boundary of the set itself is
+//! always traced, `--levels` adds the contours of lower escape counts. Each
+//! level becomes one SVG path of polylines joined from the cell segments.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use pixels::Error;
+use rayon::prelude::*;
+
+use crate::deep::Reference;
+use crate::{arg_value, export, invalid_arg, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// A crossing on a grid edge, the edge from sample `(x, y)` to the right or
+/// the one downwards
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Crossing {
+    x: u32,
+    y: u32,
+    down: bool,
+}
+
+impl Crossing {
+    /// Position at the middle of the edge.
+    fn position(self) -> (f64, f64) {
+        match self.down {
+            false => (self.x as f64 + 0.5, self.y as f64),
+            true => (self.x as f64, self.y as f64 + 0.5),
+        }
+    }
+}
+
+/// Run the `svg` command.
+pub fn svg_from_args(mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    let out = PathBuf::from(arg_value(args, "out").ok_or_else(|| invalid_arg("out", ""))?);
+    let (width, height) = match arg_value(args, "size") {
+        Some(value) => export::parse_size(&value).ok_or_else(|| invalid_arg("size", &value))?,
+        None => (WIDTH, HEIGHT),
+    };
+    let mut levels = vec![MAX_ITER];
+    if let Some(value) = arg_value(args, "levels") {
+        let parsed: Option<Vec<u32>> = value.split(',').map(|level| level.trim().parse().ok()).collect();
+        let parsed = parsed.filter(|levels| levels.iter().all(|&level| (1..MAX_ITER).contains(&level)));
+        levels.extend(parsed.ok_or_else(|| invalid_arg("levels", &value))?);
+    }
+
+    let reference = Reference::for_view(mandelbrot);
+    let counts: Vec<Vec<u32>> = (0..height).into_par_iter().map(|y| export::render_row(mandelbrot, reference.as_ref(), width, height, y).1).collect();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    for &level in &levels {
+        let inside = |x: u32, y: u32| counts[y as usize][x as usize] >= level;
+        let mut path = String::new();
+        for line in trace(width, height, inside) {
+            for (i, crossing) in line.iter().enumerate() {
+                let (x, y) = crossing.position();
+                let _ = write!(path, "{}{x} {y} ", if i == 0 { "M" } else { "L" });
+            }
+            if line.first() == line.last() {
+                path.push('Z');
+            }
+        }
+        // The set boundary in black, the other contours in grey
+        let stroke = if level == MAX_ITER { "black" } else { "grey" };
+        let _ = writeln!(svg, "<path d=\"{}\" fill=\"none\" stroke=\"{stroke}\" stroke-width=\"1\"/>", path.trim_end());
+    }
+    svg.push_str("</svg>\n");
+
+    std::fs::write(&out, svg).map_err(|e| Error::UserDefined(Box::new(e)))
+}
+
+/// Trace the boundaries of the region of samples where `inside` holds with
+/// marching squares, as polylines of edge crossings. Closed lines end where
+/// they start.
+fn trace(width: u32, height: u32, inside: impl Fn(u32, u32) -> bool) -> Vec<Vec<Crossing>> {
+    // The segments through every crossing, at most two
+    let mut links: HashMap<Crossing, Vec<Crossing>> = HashMap::new();
+    let mut link = |a: Crossing, b: Crossing| {
+        links.entry(a).or_default().push(b);
+        links.entry(b).or_default().push(a);
+    };
+
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width.saturating_sub(1) {
+            let top = Crossing { x, y, down: false };
+            let bottom = Crossing { x, y: y + 1, down: false };
+            let left = Crossing { x, y, down: true };
+            let right = Crossing { x: x + 1, y, down: true };
+            let corners = [inside(x, y), inside(x + 1, y), inside(x + 1, y + 1), inside(x, y + 1)];
+            let case = corners.iter().enumerate().fold(0, |case, (i, &corner)| case | (corner as u8) << i);
+            match case {
+                0 | 15 => {}
+                1 | 14 => link(left, top),
+                2 | 13 => link(top, right),
+                3 | 12 => link(left, right),
+                4 | 11 => link(right, bottom),
+                6 | 9 => link(top, bottom),
+                7 | 8 => link(left, bottom),
+                // Saddles, resolved so that diagonal inside corners connect
+                5 => {
+                    link(left, bottom);
+                    link(top, right);
+                }
+                10 => {
+                    link(left, top);
+                    link(right, bottom);
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Walk the chains, starting from the open ends so those come out whole
+    let mut starts: Vec<Crossing> = links.iter().filter(|(_, next)| next.len() == 1).map(|(&c, _)| c).collect();
+    starts.extend(links.keys().copied());
+    let mut lines = Vec::new();
+    for start in starts {
+        if !links.contains_key(&start) {
+            continue;
+        }
+        let mut line = vec![start];
+        let mut current = start;
+        while let Some(next) = links.get_mut(&current).and_then(|next| next.pop()) {
+            // Drop the link back from the other end
+            if let Some(back) = links.get_mut(&next) {
+                if let Some(i) = back.iter().position(|&c| c == current) {
+                    back.swap_remove(i);
+                }
+            }
+            line.push(next);
+            current = next;
+        }
+        for crossing in &line {
+            if links.get(crossing).is_some_and(|next| next.is_empty()) {
+                links.remove(crossing);
+            }
+        }
+        if line.len() > 1 {
+            lines.push(line);
+        }
+    }
+    lines
+}
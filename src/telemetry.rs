@@ -0,0 +1,169 @@
+//! Per-frame performance telemetry.
+//!
+//! `--telemetry=<path>` logs a record of every frame the first window draws,
+//! or every frame `zoom-video --animation` renders, so performance can be
+//! analyzed offline and compared between builds. Paths ending in `.csv` get
+//! CSV with a header line, paths ending in `.json` or `.jsonl` one JSON
+//! object per line. Each record has:
+//!
+//! - `frame`: the frame number, from 0
+//! - `time`: seconds since the log started
+//! - `frame_ms`: milliseconds spent rendering and drawing the frame
+//! - `backend`: what rendered it, `gpu`, `opencl`, `cpu`, `cached` when the
+//!   finished view was drawn again, or `terrain` for the height field
+//! - `complete`: whether the fractal is fully rendered, not still resolving
+//!   over the following frames
+//! - `zoom`
+//! - `mean_iterations` and `max_iterations`: over the pixels that escaped
+//! - `interior`: the fraction of the pixels that did not escape
+//!
+//! The iteration statistics are left empty, or `null`, for the height field.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use pixels::Error;
+use serde::Serialize;
+
+use crate::{arg_value, invalid_arg, Mandelbrot, MAX_ITER};
+
+/// What rendered a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    Gpu,
+    #[cfg_attr(not(feature = "opencl"), allow(dead_code))]
+    OpenCl,
+    Cpu,
+    Cached,
+    Terrain,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Gpu => "gpu",
+            Backend::OpenCl => "opencl",
+            Backend::Cpu => "cpu",
+            Backend::Cached => "cached",
+            Backend::Terrain => "terrain",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+/// One line of the log
+#[derive(Serialize)]
+struct Record {
+    frame: u64,
+    time: f64,
+    frame_ms: f64,
+    backend: Backend,
+    complete: bool,
+    zoom: f64,
+    mean_iterations: Option<f64>,
+    max_iterations: Option<u32>,
+    interior: Option<f64>,
+}
+
+/// A log of frames being written
+pub struct Telemetry {
+    out: BufWriter<File>,
+    format: Format,
+    start: Instant,
+    frames: u64,
+}
+
+impl Telemetry {
+    /// Start the log `--telemetry` asks for, if any.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, Error> {
+        let Some(value) = arg_value(args, "telemetry") else {
+            return Ok(None);
+        };
+        let path = Path::new(&value);
+        let format = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Format::Csv,
+            Some("json" | "jsonl") => Format::JsonLines,
+            _ => return Err(invalid_arg("telemetry", &value)),
+        };
+        let file = File::create(path).map_err(|e| Error::UserDefined(format!("{value}: {e}").into()))?;
+        let mut out = BufWriter::new(file);
+        if format == Format::Csv {
+            writeln!(out, "frame,time,frame_ms,backend,complete,zoom,mean_iterations,max_iterations,interior").map_err(|e| Error::UserDefined(Box::new(e)))?;
+        }
+        Ok(Some(Self { out, format, start: Instant::now(), frames: 0 }))
+    }
+
+    /// Log a frame of `view` that took `elapsed`, with the escape counts of
+    /// its pixels where there are any.
+    pub fn record(&mut self, backend: Backend, elapsed: Duration, view: &Mandelbrot, counts: Option<&[u32]>, complete: bool) -> std::io::Result<()> {
+        let (mean_iterations, max_iterations, interior) = match counts.map(statistics) {
+            Some((mean, max, interior)) => (mean, max, Some(interior)),
+            None => (None, None, None),
+        };
+        let record = Record {
+            frame: self.frames,
+            time: self.start.elapsed().as_secs_f64(),
+            frame_ms: elapsed.as_secs_f64() * 1000.0,
+            backend,
+            complete,
+            zoom: view.zoom,
+            mean_iterations,
+            max_iterations,
+            interior,
+        };
+        self.frames += 1;
+
+        match self.format {
+            Format::Csv => {
+                let optional = |value: Option<String>| value.unwrap_or_default();
+                writeln!(
+                    self.out,
+                    "{},{:.6},{:.3},{},{},{},{},{},{}",
+                    record.frame,
+                    record.time,
+                    record.frame_ms,
+                    record.backend.name(),
+                    record.complete,
+                    record.zoom,
+                    optional(record.mean_iterations.map(|mean| format!("{mean:.3}"))),
+                    optional(record.max_iterations.map(|max| max.to_string())),
+                    optional(record.interior.map(|interior| format!("{interior:.6}"))),
+                )?;
+            }
+            Format::JsonLines => {
+                serde_json::to_writer(&mut self.out, &record)?;
+                writeln!(self.out)?;
+            }
+        }
+        // Keep the log whole if the viewer goes down
+        self.out.flush()
+    }
+}
+
+/// The mean and largest escape count of the pixels that escaped, if any did,
+/// and the fraction that didn't.
+fn statistics(counts: &[u32]) -> (Option<f64>, Option<u32>, f64) {
+    let (mut sum, mut escaped, mut max) = (0u64, 0u64, None);
+    for &count in counts {
+        if count < MAX_ITER {
+            sum += count as u64;
+            escaped += 1;
+            max = max.max(Some(count));
+        }
+    }
+    let mean = (escaped > 0).then(|| sum as f64 / escaped as f64);
+    let interior = match counts.len() {
+        0 => 0.0,
+        pixels => (pixels as u64 - escaped) as f64 / pixels as f64,
+    };
+    (mean, max, interior)
+}
@@ -0,0 +1,279 @@
+//! Height-field terrain view of the fractal.
+//!
+//! The smooth escape count of the current view is sampled into a square height
+//! map which is rendered as a lit landscape with a voxel-space raycaster: every
+//! screen column marches a ray front to back over the map and draws the spans
+//! that rise above everything drawn before them.
+
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+use crate::{Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Number of height map cells along each side
+const MAP_SIZE: usize = 512;
+/// World height of the tallest peak, in map cells
+const HEIGHT_SCALE: f32 = 120.0;
+const MAX_DISTANCE: f32 = 800.0;
+const FIELD_OF_VIEW: f32 = std::f32::consts::FRAC_PI_2;
+const MOVE_SPEED: f32 = 3.0;
+const TURN_SPEED: f32 = 0.03;
+const CLIMB_SPEED: f32 = 3.0;
+const SKY: [u8; 4] = [110, 150, 200, 255];
+/// Distance between the two eyes of the stereo views, in map cells
+const EYE_SEPARATION: f32 = 4.0;
+
+/// Viewpoint of the terrain renderer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// Position in map cells
+    pub x: f32,
+    pub y: f32,
+    pub altitude: f32,
+    /// Heading in radians
+    pub yaw: f32,
+    /// Screen row of the horizon, tilts the view up and down
+    pub horizon: f32,
+}
+
+/// How the terrain is presented on screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stereo {
+    /// A single view from the camera
+    Mono,
+    /// Red-cyan anaglyph of the left and right eye views
+    Anaglyph,
+    /// Left and right eye views next to each other
+    SideBySide,
+}
+
+impl Stereo {
+    /// The next output mode, cycling back to `Mono`.
+    pub fn next(self) -> Self {
+        match self {
+            Stereo::Mono => Stereo::Anaglyph,
+            Stereo::Anaglyph => Stereo::SideBySide,
+            Stereo::SideBySide => Stereo::Mono,
+        }
+    }
+}
+
+/// A height field sampled from the fractal with precomputed lit colors
+pub struct Terrain {
+    heights: Vec<f32>,
+    colors: Vec<[u8; 3]>,
+    pub camera: Camera,
+    pub stereo: Stereo,
+}
+
+impl Terrain {
+    /// Sample the current view of `mandelbrot` into a new terrain.
+    pub fn new(mandelbrot: &Mandelbrot) -> Self {
+        let max_height = (1.0 + MAX_ITER as f64).ln();
+        let mut heights = Vec::with_capacity(MAP_SIZE * MAP_SIZE);
+        let mut base_colors = Vec::with_capacity(MAP_SIZE * MAP_SIZE);
+
+        for j in 0..MAP_SIZE {
+            for i in 0..MAP_SIZE {
+                // Sample the square in the middle of the window
+                let x = WIDTH as f64 / 2.0 + (i as f64 / MAP_SIZE as f64 - 0.5) * HEIGHT as f64;
+                let y = j as f64 / MAP_SIZE as f64 * HEIGHT as f64;
+
+                // Points in the set form a flat lake at the bottom
+                let (h, m) = match mandelbrot.smooth(x, y) {
+                    Some(s) => (((1.0 + s).ln() / max_height) as f32, s as u32),
+                    None => (0.0, MAX_ITER),
+                };
+                heights.push(h);
+                base_colors.push(mandelbrot.color(m));
+            }
+        }
+
+        // Iteration counts jump sharply along the filaments; a light box blur
+        // turns single-cell spikes into ridges
+        let heights: Vec<f32> = (0..MAP_SIZE * MAP_SIZE)
+            .map(|idx| {
+                let (i, j) = (idx % MAP_SIZE, idx / MAP_SIZE);
+                let mut sum = 0.0;
+                let mut count = 0.0;
+                for y in j.saturating_sub(1)..(j + 2).min(MAP_SIZE) {
+                    for x in i.saturating_sub(1)..(i + 2).min(MAP_SIZE) {
+                        sum += heights[y * MAP_SIZE + x];
+                        count += 1.0;
+                    }
+                }
+                sum / count
+            })
+            .collect();
+
+        // Lambert shading from the local slope
+        let light = normalize([-1.0, -1.0, 1.5]);
+        let colors = (0..MAP_SIZE * MAP_SIZE)
+            .map(|idx| {
+                let (i, j) = (idx % MAP_SIZE, idx / MAP_SIZE);
+                let h = |i: usize, j: usize| heights[j.min(MAP_SIZE - 1) * MAP_SIZE + i.min(MAP_SIZE - 1)];
+                let dx = (h(i + 1, j) - h(i.saturating_sub(1), j)) * HEIGHT_SCALE / 2.0;
+                let dy = (h(i, j + 1) - h(i, j.saturating_sub(1))) * HEIGHT_SCALE / 2.0;
+                let normal = normalize([-dx, -dy, 1.0]);
+                let lambert = (normal[0] * light[0] + normal[1] * light[1] + normal[2] * light[2]).max(0.0);
+                let shade = 0.25 + 0.75 * lambert;
+
+                let [r, g, b, _] = base_colors[idx];
+                [
+                    (r as f32 * shade) as u8,
+                    (g as f32 * shade) as u8,
+                    (b as f32 * shade) as u8,
+                ]
+            })
+            .collect();
+
+        Self {
+            heights,
+            colors,
+            camera: Camera {
+                x: MAP_SIZE as f32 / 2.0,
+                y: MAP_SIZE as f32 * 1.1,
+                altitude: HEIGHT_SCALE * 1.5,
+                yaw: -std::f32::consts::FRAC_PI_2,
+                horizon: HEIGHT as f32 / 3.0,
+            },
+            stereo: Stereo::Mono,
+        }
+    }
+
+    /// Move the camera from the keyboard, returning whether the view changed.
+    ///
+    /// W/S move forward and back, A/D turn, R/F climb and descend and the
+    /// up/down arrows tilt the view. V cycles the stereo output mode.
+    pub fn update(&mut self, input: &WinitInputHelper) -> bool {
+        let before = (self.camera, self.stereo);
+        if input.key_pressed(KeyCode::KeyV) {
+            self.stereo = self.stereo.next();
+        }
+        let camera = &mut self.camera;
+        if input.key_held(KeyCode::KeyA) {
+            camera.yaw -= TURN_SPEED;
+        }
+        if input.key_held(KeyCode::KeyD) {
+            camera.yaw += TURN_SPEED;
+        }
+        let mut step = 0.0;
+        if input.key_held(KeyCode::KeyW) {
+            step += MOVE_SPEED;
+        }
+        if input.key_held(KeyCode::KeyS) {
+            step -= MOVE_SPEED;
+        }
+        camera.x += camera.yaw.cos() * step;
+        camera.y += camera.yaw.sin() * step;
+        if input.key_held(KeyCode::KeyR) {
+            camera.altitude += CLIMB_SPEED;
+        }
+        if input.key_held(KeyCode::KeyF) {
+            camera.altitude = (camera.altitude - CLIMB_SPEED).max(1.0);
+        }
+        if input.key_held(KeyCode::ArrowUp) {
+            camera.horizon += CLIMB_SPEED;
+        }
+        if input.key_held(KeyCode::ArrowDown) {
+            camera.horizon -= CLIMB_SPEED;
+        }
+        (self.camera, self.stereo) != before
+    }
+
+    /// Draw the terrain as seen from `camera` to a frame buffer `width` pixels wide.
+    fn draw_from(&self, camera: &Camera, frame: &mut [u8], width: usize) {
+        for pixel in frame.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&SKY);
+        }
+
+        // Distance to the projection plane for the field of view
+        let projection = width as f32 / 2.0 / (FIELD_OF_VIEW / 2.0).tan();
+
+        for column in 0..width {
+            let angle = camera.yaw + (column as f32 / width as f32 - 0.5) * FIELD_OF_VIEW;
+            let (dir_y, dir_x) = angle.sin_cos();
+            // Nothing may be drawn below the lowest row already covered
+            let mut lowest_row = HEIGHT as f32;
+
+            let mut distance = 1.0;
+            let mut step = 0.5;
+            while distance < MAX_DISTANCE && lowest_row > 0.0 {
+                let map_x = camera.x + dir_x * distance;
+                let map_y = camera.y + dir_y * distance;
+                if let Some(cell) = self.cell(map_x, map_y) {
+                    let h = self.heights[cell] * HEIGHT_SCALE;
+                    let row = ((camera.altitude - h) / distance * projection + camera.horizon).max(0.0);
+                    if row < lowest_row {
+                        let [r, g, b] = self.colors[cell];
+                        for y in row as usize..lowest_row as usize {
+                            let i = (y * width + column) * 4;
+                            frame[i..i + 4].copy_from_slice(&[r, g, b, 255]);
+                        }
+                        lowest_row = row;
+                    }
+                }
+                distance += step;
+                step *= 1.005;
+            }
+        }
+    }
+
+    /// Draw the terrain from the current camera in the current output mode.
+    pub fn draw(&self, frame: &mut [u8]) {
+        let width = WIDTH as usize;
+        match self.stereo {
+            Stereo::Mono => self.draw_from(&self.camera, frame, width),
+            Stereo::Anaglyph => {
+                let (left, right) = self.eyes();
+                let mut right_frame = vec![0; frame.len()];
+                self.draw_from(&left, frame, width);
+                self.draw_from(&right, &mut right_frame, width);
+
+                // Left eye luminance through the red filter, right eye color through cyan
+                for (l, r) in frame.chunks_exact_mut(4).zip(right_frame.chunks_exact(4)) {
+                    let luma = 0.299 * l[0] as f32 + 0.587 * l[1] as f32 + 0.114 * l[2] as f32;
+                    l.copy_from_slice(&[luma as u8, r[1], r[2], 255]);
+                }
+            }
+            Stereo::SideBySide => {
+                let half = width / 2;
+                let (left, right) = self.eyes();
+                let mut eye_frame = vec![0; half * HEIGHT as usize * 4];
+                for (camera, offset) in [(left, 0), (right, half)] {
+                    self.draw_from(&camera, &mut eye_frame, half);
+                    for (y, row) in eye_frame.chunks_exact(half * 4).enumerate() {
+                        let start = (y * width + offset) * 4;
+                        frame[start..start + half * 4].copy_from_slice(row);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Cameras for the left and right eye, offset either side of the camera.
+    fn eyes(&self) -> (Camera, Camera) {
+        // Perpendicular to the heading, pointing to the right
+        let (side_x, side_y) = (-self.camera.yaw.sin(), self.camera.yaw.cos());
+        let offset = EYE_SEPARATION / 2.0;
+        let mut left = self.camera;
+        left.x -= side_x * offset;
+        left.y -= side_y * offset;
+        let mut right = self.camera;
+        right.x += side_x * offset;
+        right.y += side_y * offset;
+        (left, right)
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Option<usize> {
+        if x < 0.0 || y < 0.0 || x >= MAP_SIZE as f32 || y >= MAP_SIZE as f32 {
+            return None;
+        }
+        Some(y as usize * MAP_SIZE + x as usize)
+    }
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    [v[0] / len, v[1] / len, v[2] / len]
+}
@@ -0,0 +1,202 @@
+//! Timeline editor of animations.
+//!
+//! L shows a panel along the bottom of the viewer with the keyframes of an
+//! animation on a time axis, starting from the animation playing or the
+//! view when it was first shown. "Add keyframe" puts the current view at the
+//! playhead, replacing a keyframe already there. Dragging a keyframe retimes
+//! it, and clicking or dragging along the track scrubs through the animation,
+//! previewed in the window. "Play" plays it from the playhead, and "Save"
+//! writes it as an animation file, for the viewer and `zoom-video` to play
+//! with `--animation`.
+
+use egui::{Color32, Context, Pos2, Rect, Sense, Shape, Stroke};
+
+use crate::animation::{Animation, Easing, Keyframe};
+use crate::Mandelbrot;
+
+/// Seconds kept between keyframes, and keyframes added closer to one replace it
+const MIN_GAP: f64 = 0.1;
+/// Seconds the track shows at least, and past the last keyframe
+const MIN_LENGTH: f64 = 10.0;
+const TAIL: f64 = 2.0;
+const TRACK_HEIGHT: f32 = 36.0;
+const MARKER_SIZE: f32 = 8.0;
+const DEFAULT_PATH: &str = "animation.toml";
+
+/// What the panel asks of the viewer
+pub enum Request {
+    /// Show this view of the animation
+    Preview(Box<Mandelbrot>),
+    /// Play the animation from this many seconds in
+    Play(Animation, f64),
+    Stop,
+}
+
+pub struct Timeline {
+    keyframes: Vec<Keyframe>,
+    selected: Option<usize>,
+    playhead: f64,
+    /// Where "Save" writes the animation to
+    path: String,
+    /// Outcome of the last save
+    status: String,
+}
+
+impl Timeline {
+    /// A timeline with the keyframes of `animation`, or only `view` at the start.
+    pub fn new(view: &Mandelbrot, animation: Option<&Animation>) -> Self {
+        let keyframes = match animation {
+            Some(animation) => animation.keyframes(),
+            None => vec![Keyframe { time: 0.0, view: view.clone(), easing: Easing::default() }],
+        };
+        Self { keyframes, selected: None, playhead: 0.0, path: DEFAULT_PATH.to_owned(), status: String::new() }
+    }
+
+    /// The animation through the keyframes, once there are two of them.
+    fn animation(&self) -> Option<Animation> {
+        (self.keyframes.len() > 1).then(|| Animation::from_keyframes(&self.keyframes))
+    }
+
+    /// Put `view` at the playhead.
+    fn add(&mut self, view: &Mandelbrot) {
+        let index = match self.keyframes.iter().position(|keyframe| (keyframe.time - self.playhead).abs() < MIN_GAP) {
+            Some(index) => {
+                self.keyframes[index].view = view.clone();
+                index
+            }
+            None => {
+                let index = self.keyframes.partition_point(|keyframe| keyframe.time < self.playhead);
+                let keyframe = Keyframe { time: self.playhead, view: view.clone(), easing: Easing::default() };
+                self.keyframes.insert(index, keyframe);
+                index
+            }
+        };
+        self.selected = Some(index);
+    }
+
+    fn save(&self) -> String {
+        let Some(animation) = self.animation() else {
+            return "add a keyframe to save".to_owned();
+        };
+        match std::fs::write(&self.path, animation.to_toml()) {
+            Ok(()) => format!("saved {:.1} s", animation.duration()),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    /// Lay out the panel for the current `view`, with the time into the
+    /// animation if one is `playing`.
+    pub fn ui(&mut self, context: &Context, view: &Mandelbrot, playing: Option<f64>) -> Option<Request> {
+        let mut request = None;
+        if let Some(time) = playing {
+            self.playhead = time;
+        }
+
+        egui::TopBottomPanel::bottom("timeline").show(context, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Add keyframe").clicked() {
+                    self.add(view);
+                }
+                let removable = self.selected.filter(|&index| index > 0);
+                if ui.add_enabled(removable.is_some(), egui::Button::new("Delete")).clicked() {
+                    if let Some(index) = removable {
+                        self.keyframes.remove(index);
+                        self.selected = None;
+                    }
+                }
+                if playing.is_some() {
+                    if ui.button("Stop").clicked() {
+                        request = Some(Request::Stop);
+                    }
+                } else if ui.add_enabled(self.keyframes.len() > 1, egui::Button::new("Play")).clicked() {
+                    if let Some(animation) = self.animation() {
+                        // From the start again once the playhead is at the end
+                        let from = if self.playhead < animation.duration() { self.playhead } else { 0.0 };
+                        request = Some(Request::Play(animation, from));
+                    }
+                }
+
+                // Easing into the selected keyframe
+                if let Some(index) = self.selected.filter(|&index| index > 0) {
+                    let easing = &mut self.keyframes[index].easing;
+                    // Bézier curves by their control points below
+                    let label = |easing: Easing| match easing {
+                        Easing::Bezier(_) => "cubic-bezier".to_owned(),
+                        _ => easing.name(),
+                    };
+                    egui::ComboBox::from_id_source("easing").selected_text(label(*easing)).show_ui(ui, |ui| {
+                        for choice in Easing::ALL {
+                            if ui.selectable_label(std::mem::discriminant(easing) == std::mem::discriminant(&choice), label(choice)).clicked() {
+                                *easing = choice;
+                            }
+                        }
+                    });
+                    // The control points of a Bézier curve, the times kept
+                    // between 0 and 1
+                    if let Easing::Bezier(points) = easing {
+                        for (i, point) in points.iter_mut().enumerate() {
+                            let range = if i % 2 == 0 { 0.0..=1.0 } else { -1.0..=2.0 };
+                            ui.add(egui::DragValue::new(point).speed(0.01).clamp_range(range));
+                        }
+                    }
+                }
+                ui.label(format!("{:.2} s", self.playhead));
+
+                ui.separator();
+                ui.text_edit_singleline(&mut self.path);
+                if ui.button("Save").clicked() {
+                    self.status = self.save();
+                }
+                ui.label(&self.status);
+            });
+
+            let (track, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), TRACK_HEIGHT), Sense::click_and_drag());
+            let end = self.keyframes.last().map_or(0.0, |keyframe| keyframe.time);
+            let length = (end + TAIL).max(MIN_LENGTH);
+            let to_x = |time: f64| track.left() + (time / length) as f32 * track.width();
+            let painter = ui.painter_at(track);
+            painter.rect_filled(track, 2.0, Color32::from_gray(32));
+            for second in 0..=length as usize {
+                let tick = if second % 5 == 0 { 0.5 } else { 0.25 };
+                let x = to_x(second as f64);
+                painter.vline(x, track.bottom() - track.height() * tick..=track.bottom(), Stroke::new(1.0, Color32::GRAY));
+            }
+
+            // Keyframes, selected and retimed by dragging, the first staying at the start
+            for index in 0..self.keyframes.len() {
+                let center = Pos2::new(to_x(self.keyframes[index].time), track.center().y);
+                let marker = Rect::from_center_size(center, egui::vec2(2.0 * MARKER_SIZE, 2.0 * MARKER_SIZE));
+                let handle = ui.interact(marker, ui.id().with(("keyframe", index)), Sense::click_and_drag());
+                if handle.clicked() || handle.drag_started() {
+                    self.selected = Some(index);
+                }
+                if index > 0 && handle.dragged() {
+                    let previous = self.keyframes[index - 1].time + MIN_GAP;
+                    let next = self.keyframes.get(index + 1).map_or(f64::INFINITY, |keyframe| keyframe.time - MIN_GAP);
+                    let time = self.keyframes[index].time + (handle.drag_delta().x / track.width()) as f64 * length;
+                    self.keyframes[index].time = time.clamp(previous, next);
+                }
+
+                let color = if self.selected == Some(index) { Color32::YELLOW } else { Color32::LIGHT_GRAY };
+                let diamond = [(0.0, -1.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0)];
+                let points = diamond.iter().map(|&(dx, dy)| center + egui::vec2(dx, dy) * MARKER_SIZE).collect();
+                painter.add(Shape::convex_polygon(points, color, Stroke::new(1.0, Color32::BLACK)));
+            }
+
+            // Scrub along the rest of the track
+            if response.clicked() || response.dragged() {
+                if let Some(position) = response.interact_pointer_pos() {
+                    self.playhead = ((position.x - track.left()) / track.width()) as f64 * length;
+                    self.playhead = self.playhead.clamp(0.0, length);
+                    let preview = match self.animation() {
+                        Some(animation) => animation.view_at(self.playhead),
+                        None => self.keyframes[0].view.clone(),
+                    };
+                    request = Some(Request::Preview(Box::new(preview)));
+                }
+            }
+            painter.vline(to_x(self.playhead), track.y_range(), Stroke::new(2.0, Color32::RED));
+        });
+        request
+    }
+}
@@ -0,0 +1,71 @@
+//! Images used as orbit traps.
+//!
+//! The image is placed on a square of the complex plane and every orbit
+//! takes the color of the first point of the image it lands on, so the
+//! picture shows up again wherever orbits pass through its square, distorted
+//! and repeated by the iteration.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use num::Complex;
+use pixels::Error;
+
+use crate::export;
+
+/// A decoded image placed on the complex plane
+#[derive(Clone)]
+pub struct TrapImage {
+    path: PathBuf,
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 4]>,
+    /// The center of the image and the length of its longer side
+    pub center: Complex<f64>,
+    pub size: f64,
+}
+
+// The pixels would swamp the view's debug output, which the render
+// checkpoints use to recognize their view
+impl fmt::Debug for TrapImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TrapImage({} {}x{} at {} size {})", self.path.display(), self.width, self.height, self.center, self.size)
+    }
+}
+
+impl TrapImage {
+    /// Load a PNG image, centered at the origin with its longer side 1 long.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let (width, height, pixels) = export::read_png(path)?;
+        Ok(Self { path: path.to_owned(), width, height, pixels, center: Complex::new(0.0, 0.0), size: 1.0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The color of the image at `z`, interpolated between its pixels, or
+    /// `None` outside the image. Rows run along the imaginary axis like the
+    /// rows of the view, so the image appears upright.
+    pub fn sample(&self, z: Complex<f64>) -> Option<[u8; 4]> {
+        let scale = self.width.max(self.height) as f64 / self.size;
+        let offset = (z - self.center) * scale;
+        let x = offset.re + self.width as f64 / 2.0 - 0.5;
+        let y = offset.im + self.height as f64 / 2.0 - 0.5;
+        if !(x >= -0.5 && y >= -0.5 && x < self.width as f64 - 0.5 && y < self.height as f64 - 0.5) {
+            return None;
+        }
+
+        // Bilinear interpolation, clamped at the edges
+        let (x, y) = (x.max(0.0), y.max(0.0));
+        let (left, top) = (x as usize, y as usize);
+        let (right, bottom) = ((left + 1).min(self.width - 1), (top + 1).min(self.height - 1));
+        let (tx, ty) = (x - left as f64, y - top as f64);
+        let pixel = |x: usize, y: usize| self.pixels[y * self.width + x];
+        Some(std::array::from_fn(|i| {
+            let upper = pixel(left, top)[i] as f64 * (1.0 - tx) + pixel(right, top)[i] as f64 * tx;
+            let lower = pixel(left, bottom)[i] as f64 * (1.0 - tx) + pixel(right, bottom)[i] as f64 * tx;
+            (upper * (1.0 - ty) + lower * ty).round() as u8
+        }))
+    }
+}
@@ -0,0 +1,233 @@
+//! Cross-validation of the backends that compute escape counts.
+//!
+//! `mandelbrot verify [--backends=<backend>,...] [--diff=<file.png>]`
+//! computes the escape counts of the initial view at the window size of
+//! 800x600 with each backend, and compares those of the others with the
+//! first, reporting how many pixels differ, by how many iterations on
+//! average and at most, and at how many one backend has the point escape and
+//! the other not. The backends are:
+//!
+//! - `scalar`: the scalar kernel in double precision
+//! - `simd`: the SIMD kernel of [`crate::simd`], on all threads
+//! - `perturbation`: perturbation from a reference orbit at the view center,
+//!   as deep views are rendered, see [`crate::deep`]
+//! - `gpu`: the compute shaders of [`crate::gpu`], in single precision or in
+//!   double-single for deeper views, on the first adapter found, warning
+//!   when the view is too deep for either, or the device lacks the second
+//! - `opencl`: the kernel of [`crate::opencl`] in double precision, on the
+//!   device the viewer would pick
+//!
+//! all but `gpu` and `opencl` by default. `--diff` draws where the first and
+//! the last backends differ: black where they agree, brighter yellow the
+//! more they differ by, and red where one has the point escape and the other
+//! not. The command fails when more than `--tolerance=<percent>` of the
+//! pixels (0 by default) differ by more than an iteration, so scripts can
+//! catch a new backend going wrong while allowing for the rounding of single
+//! precision.
+
+use std::path::Path;
+
+#[cfg(feature = "gpu")]
+use pixels::wgpu;
+use pixels::Error;
+use rayon::prelude::*;
+
+#[cfg(feature = "gpu")]
+use crate::gpu;
+#[cfg(feature = "opencl")]
+use crate::opencl;
+use crate::{arg_value, deep, invalid_arg, video, Mandelbrot, HEIGHT, MAX_ITER, WIDTH};
+
+/// Points handed to a thread at a time by the SIMD backend
+const CHUNK: usize = 256;
+const DEFAULT_BACKENDS: [Backend; 3] = [Backend::Scalar, Backend::Simd, Backend::Perturbation];
+/// Color of the pixels escaping in one backend only in the difference image
+const ESCAPE_COLOR: [u8; 4] = [255, 0, 0, 255];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Scalar,
+    Simd,
+    Perturbation,
+    Gpu,
+    OpenCl,
+}
+
+impl Backend {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "scalar" => Some(Backend::Scalar),
+            "simd" => Some(Backend::Simd),
+            "perturbation" => Some(Backend::Perturbation),
+            "gpu" => Some(Backend::Gpu),
+            "opencl" => Some(Backend::OpenCl),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Scalar => "scalar",
+            Backend::Simd => "simd",
+            Backend::Perturbation => "perturbation",
+            Backend::Gpu => "gpu",
+            Backend::OpenCl => "opencl",
+        }
+    }
+
+    /// Escape counts of every pixel of `view`, row by row.
+    pub fn counts(self, view: &Mandelbrot) -> Result<Vec<u32>, Error> {
+        let pixels = (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| (x as f64, y as f64)));
+        match self {
+            Backend::Scalar => {
+                let points: Vec<_> = pixels.map(|(x, y)| view.pixel_to_complex(x, y)).collect();
+                Ok(points.par_iter().map(|&point| view.iterate_at(point).0).collect())
+            }
+            Backend::Simd => {
+                let points: Vec<_> = pixels.map(|(x, y)| view.pixel_to_complex(x, y)).collect();
+                let mut results = vec![(0, num::Complex::new(0.0, 0.0)); points.len()];
+                points.par_chunks(CHUNK).zip(results.par_chunks_mut(CHUNK)).for_each(|(points, results)| view.iterate_batch(points, results));
+                Ok(results.into_iter().map(|(n, _)| n).collect())
+            }
+            Backend::Perturbation => {
+                let reference = deep::Reference::new(view);
+                let offsets: Vec<_> = pixels.map(|(x, y)| view.view_offset(x, y)).collect();
+                Ok(offsets.par_iter().map(|&offset| reference.iterate(view, offset).0).collect())
+            }
+            #[cfg(feature = "gpu")]
+            Backend::Gpu => gpu_counts(view),
+            #[cfg(not(feature = "gpu"))]
+            Backend::Gpu => Err(Error::UserDefined("built without the gpu feature".into())),
+            #[cfg(feature = "opencl")]
+            Backend::OpenCl => opencl_counts(view),
+            #[cfg(not(feature = "opencl"))]
+            Backend::OpenCl => Err(Error::UserDefined("built without the opencl feature".into())),
+        }
+    }
+}
+
+/// Escape counts of `view` on the GPU, set up without a window.
+#[cfg(feature = "gpu")]
+fn gpu_counts(view: &Mandelbrot) -> Result<Vec<u32>, Error> {
+    if gpu::precision(view).is_none() {
+        eprintln!("warning: the view is too deep, or its projection or coloring too involved, for the GPU renderer");
+    }
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()));
+    let adapter = adapter.ok_or_else(|| Error::UserDefined("no GPU adapter found".into()))?;
+    let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    let gpu = gpu::Gpu::new(&adapter, &device).ok_or_else(|| Error::UserDefined("the GPU doesn't support compute shaders".into()))?;
+    if gpu::precision(view) == Some(gpu::Precision::DoubleSingle) && !gpu.suitable(view) {
+        eprintln!("warning: the GPU renders in single precision only, which doesn't resolve the view");
+    }
+    gpu.render(&device, &queue, view)
+}
+
+/// Escape counts of `view` with OpenCL.
+#[cfg(feature = "opencl")]
+fn opencl_counts(view: &Mandelbrot) -> Result<Vec<u32>, Error> {
+    if !opencl::suitable(view) {
+        eprintln!("warning: the view is too deep, or its projection or coloring too involved, for the OpenCL renderer");
+    }
+    opencl::OpenCl::new()?.render(view)
+}
+
+/// How far the escape counts of two backends diverge
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Divergence {
+    pub pixels: usize,
+    pub differing: usize,
+    /// Of those that differ, by more than one iteration
+    pub beyond_one: usize,
+    /// Average difference of the pixels that differ
+    pub mean: f64,
+    pub max: u32,
+    /// Pixels that escape in one backend and not in the other
+    pub escape_mismatches: usize,
+}
+
+impl Divergence {
+    pub fn between(a: &[u32], b: &[u32]) -> Self {
+        let mut divergence = Divergence { pixels: a.len(), ..Default::default() };
+        let mut total = 0u64;
+        for (&a, &b) in a.iter().zip(b) {
+            let difference = a.abs_diff(b);
+            if difference == 0 {
+                continue;
+            }
+            divergence.differing += 1;
+            divergence.beyond_one += (difference > 1) as usize;
+            divergence.escape_mismatches += ((a == MAX_ITER) != (b == MAX_ITER)) as usize;
+            divergence.max = divergence.max.max(difference);
+            total += difference as u64;
+        }
+        if divergence.differing > 0 {
+            divergence.mean = total as f64 / divergence.differing as f64;
+        }
+        divergence
+    }
+}
+
+/// The difference image of the counts `a` and `b`.
+fn difference_image(a: &[u32], b: &[u32]) -> Vec<u8> {
+    let scale = (MAX_ITER as f64 + 1.0).ln();
+    a.iter()
+        .zip(b)
+        .flat_map(|(&a, &b)| {
+            if (a == MAX_ITER) != (b == MAX_ITER) {
+                return ESCAPE_COLOR;
+            }
+            // On a logarithmic scale, so differences of one still show
+            let level = ((a.abs_diff(b) as f64 + 1.0).ln() / scale * 255.0).round() as u8;
+            [level, level, 0, 255]
+        })
+        .collect()
+}
+
+/// Run the `verify` command.
+pub fn verify_from_args(mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    let backends = match arg_value(args, "backends") {
+        Some(value) => {
+            let backends: Option<Vec<_>> = value.split(',').map(|name| Backend::from_name(name.trim())).collect();
+            backends.filter(|backends| backends.len() >= 2).ok_or_else(|| invalid_arg("backends", &value))?
+        }
+        None => DEFAULT_BACKENDS.to_vec(),
+    };
+    let tolerance = match arg_value(args, "tolerance") {
+        Some(value) => value.parse().ok().filter(|percent| (0.0..=100.0).contains(percent)).ok_or_else(|| invalid_arg("tolerance", &value))?,
+        None => 0.0,
+    };
+
+    let first = backends[0].counts(mandelbrot)?;
+    let mut last = None;
+    let mut diverged = false;
+    for &backend in &backends[1..] {
+        let counts = backend.counts(mandelbrot)?;
+        let d = Divergence::between(&first, &counts);
+        let versus = format!("{} vs {}", backends[0].name(), backend.name());
+        if d.differing == 0 {
+            println!("{versus}: all {} pixels agree", d.pixels);
+        } else {
+            println!(
+                "{versus}: {} of {} pixels differ ({:.3}%), {} by more than an iteration, by {:.2} on average and {} at most; {} escape in one only",
+                d.differing,
+                d.pixels,
+                d.differing as f64 / d.pixels as f64 * 100.0,
+                d.beyond_one,
+                d.mean,
+                d.max,
+                d.escape_mismatches
+            );
+        }
+        diverged |= d.beyond_one as f64 > d.pixels as f64 * tolerance / 100.0;
+        last = Some(counts);
+    }
+
+    if let (Some(path), Some(counts)) = (arg_value(args, "diff"), last) {
+        video::write_png(Path::new(&path), &difference_image(&first, &counts))?;
+    }
+    match diverged {
+        true => Err(Error::UserDefined("the backends diverge".into())),
+        false => Ok(()),
+    }
+}
@@ -0,0 +1,335 @@
+//! Zoom video assembly from exponential map strips.
+//!
+//! Rendering every frame of a zoom video from scratch repeats almost all of the
+//! work, since each frame mostly shows the previous one enlarged. Instead the
+//! zoom path is rendered once as an exponential map around the zoom target, in
+//! strips one octave of zoom wide, and every video frame is reprojected from
+//! the strips it covers. Strips are rendered as the video reaches them and
+//! dropped once it has zoomed past them.
+//!
+//! `mandelbrot zoom-video --out=<dir> --frames=<n> --final-zoom=<zoom>` writes
+//! the frames as numbered PNG files, ready for e.g.
+//! `ffmpeg -i <dir>/frame_%05d.png zoom.mp4`.
+//!
+//! With `--animation=<file.toml> [--fps=<n>]` the frames follow an animation
+//! script instead, at 30 frames per second by default. They turn and change
+//! in ways the exponential map can't follow, so each is rendered in full,
+//! which makes it a benchmark too: `--telemetry=<path>` logs how long every
+//! frame took, see [`crate::telemetry`]. `--tween=<preset>
+//! [--duration=<seconds>] [--tween-easing=<easing>]` renders the tween from
+//! the view to a preset, 4 seconds long by default, the same way,
+//! `--replay=<file.jsonl>` a recorded session, see [`crate::recording`], and
+//! `--orbit=<turns>` an orbit around the view center and `--sweep=<from>,<to>`
+//! a sweep of the Multibrot exponent, see [`crate::animation`].
+//!
+//! `--motion-blur=<n>` renders either kind of video as `n` sub-frames per
+//! frame, spread evenly over the time to the next frame, and writes their
+//! average, blurring the motion between frames like the shutter of a camera
+//! instead of strobing from one sharp frame to the next.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use num::Complex;
+use pixels::Error;
+use rayon::prelude::*;
+
+use crate::animation::{self, Animation};
+use crate::deep::Reference;
+use crate::presets;
+use crate::progress::{self, Progress};
+use crate::recording;
+use crate::telemetry::{Backend, Telemetry};
+use crate::{arg_value, coloring, export, invalid_arg, parse_positive, Mandelbrot, HEIGHT, WIDTH};
+
+/// Samples around the zoom target in each strip. The outer edge of a frame
+/// has the most pixels per turn, half of them are enough with interpolation.
+const STRIP_ROWS: usize = 1600;
+/// Frame rate of animation videos
+const DEFAULT_FPS: f64 = 30.0;
+
+/// One octave of the exponential map, starting `index` octaves below the outer
+/// edge of the first frame
+struct Strip {
+    index: usize,
+    /// Column-major colors, `STRIP_ROWS` per column
+    colors: Vec<[u8; 4]>,
+}
+
+/// Exponential map around the zoom target, sampled lazily in strips.
+struct ExponentialMap<'a> {
+    mandelbrot: &'a Mandelbrot,
+    /// Distance of the corner of the first frame from the zoom target
+    outer_radius: f64,
+    /// Columns per strip
+    columns: usize,
+    /// Change of log radius per column
+    step: f64,
+    strips: VecDeque<Strip>,
+}
+
+impl<'a> ExponentialMap<'a> {
+    fn new(mandelbrot: &'a Mandelbrot) -> Self {
+        let columns = (std::f64::consts::LN_2 / (2.0 * std::f64::consts::PI / STRIP_ROWS as f64)).round() as usize;
+        Self {
+            mandelbrot,
+            outer_radius: corner_radius(mandelbrot.zoom),
+            columns,
+            step: std::f64::consts::LN_2 / columns as f64,
+            strips: VecDeque::new(),
+        }
+    }
+
+    /// Fractional column showing distance `radius` from the zoom target.
+    fn column(&self, radius: f64) -> f64 {
+        (self.outer_radius / radius).ln() / self.step
+    }
+
+    /// Make sure the strips covering columns `first..=last` are rendered,
+    /// dropping the ones before.
+    fn prepare(&mut self, first: f64, last: f64) {
+        let first = (first.max(0.0) / self.columns as f64) as usize;
+        let last = (last.max(0.0) / self.columns as f64) as usize + 1;
+        while self.strips.front().is_some_and(|strip| strip.index < first) {
+            self.strips.pop_front();
+        }
+        let mut next = self.strips.back().map_or(first, |strip| strip.index + 1);
+        while next <= last {
+            let strip = self.render_strip(next);
+            self.strips.push_back(strip);
+            next += 1;
+        }
+    }
+
+    fn render_strip(&self, index: usize) -> Strip {
+        let center = Complex::new(self.mandelbrot.center_x, self.mandelbrot.center_y);
+        let mut colors = Vec::with_capacity(self.columns * STRIP_ROWS);
+        for column in 0..self.columns {
+            let global = (index * self.columns + column) as f64;
+            let radius = self.outer_radius * (-global * self.step).exp();
+            for row in 0..STRIP_ROWS {
+                let angle = 2.0 * std::f64::consts::PI * row as f64 / STRIP_ROWS as f64;
+                colors.push(self.mandelbrot.shade_at(center + Complex::from_polar(radius, angle)).1);
+            }
+        }
+        Strip { index, colors }
+    }
+
+    /// Color at a column and row, clamped to the prepared strips.
+    fn sample_at(&self, column: usize, row: usize) -> [u8; 4] {
+        let first = self.strips.front().map_or(0, |strip| strip.index);
+        let last = self.strips.back().map_or(0, |strip| strip.index);
+        let column = column.clamp(first * self.columns, (last + 1) * self.columns - 1);
+        let strip = &self.strips[column / self.columns - first];
+        strip.colors[(column % self.columns) * STRIP_ROWS + row % STRIP_ROWS]
+    }
+
+    /// Bilinearly interpolated color at distance `radius` and `angle` from the
+    /// zoom target.
+    fn sample(&self, radius: f64, angle: f64) -> [u8; 4] {
+        let x = self.column(radius).max(0.0);
+        let y = angle.rem_euclid(2.0 * std::f64::consts::PI) / (2.0 * std::f64::consts::PI) * STRIP_ROWS as f64;
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (fx, fy) = (x.fract(), y.fract());
+
+        let corners = [
+            (self.sample_at(x0, y0), (1.0 - fx) * (1.0 - fy)),
+            (self.sample_at(x0 + 1, y0), fx * (1.0 - fy)),
+            (self.sample_at(x0, y0 + 1), (1.0 - fx) * fy),
+            (self.sample_at(x0 + 1, y0 + 1), fx * fy),
+        ];
+        let mut rgba = [0.0; 4];
+        for (color, weight) in corners {
+            for (channel, &value) in rgba.iter_mut().zip(&color) {
+                *channel += value as f64 * weight;
+            }
+        }
+        rgba.map(|channel| channel.round() as u8)
+    }
+}
+
+/// Write the frames of `animation` at `fps` frames per second into the
+/// directory `out`, each blended from `sub_frames` renders, reporting the
+/// frames written and logging them to `telemetry`.
+pub fn animation_video(animation: &Animation, out: &Path, fps: f64, sub_frames: u32, mode: progress::Mode, mut telemetry: Option<Telemetry>) -> Result<(), Error> {
+    std::fs::create_dir_all(out).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    let frames = (animation.duration() * fps).ceil() as usize + 1;
+    let progress = Progress::new(mode, "zoom-video", "frames", frames as u64, Some((WIDTH * HEIGHT) as u64));
+    // The color ranges of all frames first, to smooth them over the frames
+    let mut views: Vec<_> = (0..frames).map(|index| animation.view_at(index as f64 / fps)).collect();
+    let ranges: Vec<_> = views.iter().map(export::sample_color_range).collect();
+    for (view, range) in views.iter_mut().zip(coloring::smooth_ranges(&ranges, animation.view_at(0.0).range_smoothing, false)) {
+        view.color_range = range;
+    }
+    let mut sums = vec![0; (WIDTH * HEIGHT * 4) as usize];
+    for (index, view) in views.into_iter().enumerate() {
+        let started = Instant::now();
+        sums.fill(0);
+        let mut middle_counts = Vec::new();
+        for sub_frame in 0..sub_frames {
+            let time = (index as f64 + sub_frame_offset(sub_frame, sub_frames)) / fps;
+            let sub_view = match sub_frames {
+                1 => view.clone(),
+                _ => Mandelbrot { color_range: view.color_range, ..animation.view_at(time.clamp(0.0, animation.duration())) },
+            };
+            let reference = Reference::for_view(&sub_view);
+            let (rows, counts): (Vec<_>, Vec<_>) = (0..HEIGHT).into_par_iter().map(|y| export::render_row(&sub_view, reference.as_ref(), WIDTH, HEIGHT, y)).unzip();
+            accumulate(&mut sums, &rows.concat());
+            if sub_frame == sub_frames / 2 {
+                middle_counts = counts.concat();
+            }
+        }
+        let elapsed = started.elapsed();
+        if let Some(telemetry) = &mut telemetry {
+            telemetry.record(Backend::Cpu, elapsed, &view, Some(&middle_counts), true).map_err(|e| Error::UserDefined(Box::new(e)))?;
+        }
+        let mut frame = average(&sums, sub_frames);
+        view.post_process(&mut frame, WIDTH, HEIGHT);
+        write_png(&out.join(format!("frame_{index:05}.png")), &frame)?;
+        progress.set(index as u64 + 1);
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Offset from its frame, in frames, of sub-frame `index` of `sub_frames`
+/// spread evenly over the time to the next frame, zero for a single one.
+fn sub_frame_offset(index: u32, sub_frames: u32) -> f64 {
+    (index as f64 + 0.5) / sub_frames as f64 - 0.5
+}
+
+/// Add the channels of an RGBA frame to `sums`.
+fn accumulate(sums: &mut [u32], frame: &[u8]) {
+    for (sum, &channel) in sums.iter_mut().zip(frame) {
+        *sum += channel as u32;
+    }
+}
+
+/// The frame of the averages of `sums` over `count` frames.
+fn average(sums: &[u32], count: u32) -> Vec<u8> {
+    sums.iter().map(|&sum| ((sum + count / 2) / count) as u8).collect()
+}
+
+/// Distance of the window corner from the view center at `zoom`.
+fn corner_radius(zoom: f64) -> f64 {
+    let zoom_width = 2.5 / zoom;
+    let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
+    zoom_width / 2.0 * (aspect_ratio * aspect_ratio + 1.0).sqrt()
+}
+
+/// Run the `zoom-video` command.
+pub fn zoom_video_from_args(mandelbrot: &Mandelbrot, args: &[String]) -> Result<(), Error> {
+    let out = PathBuf::from(arg_value(args, "out").ok_or_else(|| invalid_arg("out", ""))?);
+    let mode = progress::Mode::from_args(args);
+    let sub_frames = match arg_value(args, "motion-blur") {
+        Some(value) => value.parse().ok().filter(|&n: &u32| n >= 1).ok_or_else(|| invalid_arg("motion-blur", &value))?,
+        None => 1,
+    };
+    let (orbit, orbiting) = animation::Orbit::from_args(args)?;
+    let (sweep, sweeping) = animation::Sweep::from_args(args)?;
+    let animation = match (arg_value(args, "animation"), arg_value(args, "tween"), arg_value(args, "replay"), orbiting, sweeping) {
+        (Some(path), None, None, false, false) => Some(Animation::load(Path::new(&path), mandelbrot)?),
+        (None, Some(name), None, false, false) => {
+            let preset = presets::find(&name).ok_or_else(|| invalid_arg("tween", &name))?;
+            let duration = match arg_value(args, "duration") {
+                Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("duration", &value))?,
+                None => animation::TWEEN_DURATION,
+            };
+            Some(Animation::tween(mandelbrot, preset.view()?, duration, animation::tween_easing(args)?))
+        }
+        (None, None, Some(path), false, false) => Some(recording::load(Path::new(&path))?),
+        (None, None, None, true, false) => Some(orbit.animation(mandelbrot)),
+        (None, None, None, false, true) => Some(sweep.animation(mandelbrot)),
+        (None, None, None, false, false) => None,
+        _ => return Err(Error::UserDefined("only one of --animation, --tween, --replay, --orbit and --sweep can be given".into())),
+    };
+    if let Some(animation) = animation {
+        let fps = match arg_value(args, "fps") {
+            Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("fps", &value))?,
+            None => DEFAULT_FPS,
+        };
+        return animation_video(&animation, &out, fps, sub_frames, mode, Telemetry::from_args(args)?);
+    }
+    let frames = match arg_value(args, "frames") {
+        Some(value) => value.parse().ok().filter(|&n: &usize| n >= 2).ok_or_else(|| invalid_arg("frames", &value))?,
+        None => 300,
+    };
+    let final_zoom = match arg_value(args, "final-zoom") {
+        Some(value) => parse_positive(&value).ok_or_else(|| invalid_arg("final-zoom", &value))?,
+        None => mandelbrot.zoom * 1e6,
+    };
+    zoom_video(mandelbrot, &out, frames, final_zoom, sub_frames, mode)
+}
+
+/// Write `frames` frames zooming from the current view to `final_zoom` into
+/// the directory `out`, each blended from `sub_frames` reprojections,
+/// reporting the frames written.
+pub fn zoom_video(
+    mandelbrot: &Mandelbrot,
+    out: &Path,
+    frames: usize,
+    final_zoom: f64,
+    sub_frames: u32,
+    mode: progress::Mode,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(out).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    let mut map = ExponentialMap::new(mandelbrot);
+    let mut frame = vec![0; (WIDTH * HEIGHT * 4) as usize];
+    let mut sums = vec![0; frame.len()];
+
+    // Same viewport, but centered on zero so it maps pixels to their offset
+    // from the zoom target
+    let mut view = Mandelbrot::new();
+    (view.center_x, view.center_y) = (0.0, 0.0);
+    view.rotation = mandelbrot.rotation;
+    view.skew = mandelbrot.skew;
+
+    let progress = Progress::new(mode, "zoom-video", "frames", frames as u64, Some((WIDTH * HEIGHT) as u64));
+    for index in 0..frames {
+        let zooms: Vec<f64> = (0..sub_frames)
+            .map(|sub_frame| {
+                let t = ((index as f64 + sub_frame_offset(sub_frame, sub_frames)) / (frames - 1) as f64).clamp(0.0, 1.0);
+                mandelbrot.zoom * (final_zoom / mandelbrot.zoom).powf(t)
+            })
+            .collect();
+
+        // The sub-frames reach from the corner of the widest down to half a
+        // pixel of the deepest
+        let widest = zooms.iter().copied().fold(f64::INFINITY, f64::min);
+        let deepest = zooms.iter().copied().fold(0.0, f64::max);
+        map.prepare(map.column(corner_radius(widest)), map.column(2.5 / deepest / HEIGHT as f64 / 2.0));
+
+        sums.fill(0);
+        for &zoom in &zooms {
+            view.zoom = zoom;
+            let pixel_size = 2.5 / view.zoom / HEIGHT as f64;
+            for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
+                let x = (i % WIDTH as usize) as f64 + 0.5;
+                let y = (i / WIDTH as usize) as f64 + 0.5;
+                let (radius, angle) = view.pixel_to_complex(x, y).to_polar();
+                pixel.copy_from_slice(&map.sample(radius.max(pixel_size / 2.0), angle));
+            }
+            accumulate(&mut sums, &frame);
+        }
+
+        let mut frame = average(&sums, sub_frames);
+        mandelbrot.post_process(&mut frame, WIDTH, HEIGHT);
+        write_png(&out.join(format!("frame_{index:05}.png")), &frame)?;
+        progress.set(index as u64 + 1);
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Write an RGBA frame buffer as a PNG file.
+pub fn write_png(path: &Path, frame: &[u8]) -> Result<(), Error> {
+    let file = File::create(path).map_err(|e| Error::UserDefined(Box::new(e)))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| Error::UserDefined(Box::new(e)))?;
+    writer.write_image_data(frame).map_err(|e| Error::UserDefined(Box::new(e)))
+}
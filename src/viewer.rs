@@ -0,0 +1,1162 @@
+//! Viewer windows.
+//!
+//! Each window has its own view of the fractal and interaction state, so
+//! several regions can be explored and compared side by side.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use pixels::{wgpu, Error, Pixels};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::EventLoopWindowTarget;
+use winit::keyboard::KeyCode;
+use winit::window::{Fullscreen, Window, WindowBuilder};
+use winit_input_helper::WinitInputHelper;
+
+#[cfg(feature = "audio")]
+use crate::audio;
+#[cfg(feature = "gpu")]
+use crate::gpu;
+#[cfg(feature = "editor")]
+use crate::{goto, gui, timeline};
+#[cfg(feature = "midi")]
+use crate::midi;
+#[cfg(feature = "opencl")]
+use crate::opencl;
+use crate::animation::{self, Animation};
+use crate::palette::Palette;
+use crate::projection::Projection;
+use crate::{
+    accessibility, bifurcation, clipboard, control, depth, eco, error_message, export, julia, minibrot, minimap, mipmap, overlay, presets, progress, progressive, rays, recording, session, skew, stream, surface, telemetry, terrain, Mandelbrot, HEIGHT, MAX_ITER, PAN_SPEED, ROTATE_SPEED, WIDTH,
+    ZOOM_SPEED, ZOOM_STEP,
+};
+
+/// Time the CPU may spend rendering a frame before the rest of the view is
+/// left to the following frames
+const FRAME_BUDGET: Duration = Duration::from_millis(30);
+/// Frames in a row that may fail to render before the window closes
+const MAX_RENDER_FAILURES: u32 = 10;
+/// Reach of the crosshair of the keyboard cursor either way, in pixels
+const KEY_CURSOR_RADIUS: usize = 6;
+
+/// State shared by every window for the current frame
+pub struct FrameContext {
+    /// Möbius projection from the command line, cycled through with I
+    pub mobius: Option<Projection>,
+    /// External rays from the command line, drawn over the Mandelbrot set
+    pub rays: Vec<Vec<num::Complex<f64>>>,
+    /// This frame's audio levels in the audio-reactive mode
+    #[cfg(feature = "audio")]
+    pub audio: Option<(audio::AudioTarget, audio::AudioLevels)>,
+    /// Parameters the MIDI controllers set since the last frame
+    #[cfg(feature = "midi")]
+    pub midi: midi::MidiLevels,
+    /// Depth of the window surfaces asked for
+    pub surface: surface::Surface,
+    pub accessibility: accessibility::Accessibility,
+    /// Easing of the tweens to presets and minibrots
+    pub tween_easing: animation::Easing,
+    /// The orbit Y plays, and the sweep of the exponent Shift+Y plays
+    pub orbit: animation::Orbit,
+    pub sweep: animation::Sweep,
+    pub eco: eco::Eco,
+    /// Order the CPU renders the pixels of views in
+    pub scan: progressive::Scan,
+    /// Whether windows open showing the zoom depth indicator
+    pub depth: bool,
+    /// Whether windows open showing the minimap
+    pub minimap: bool,
+    /// Whether windows open showing the bifurcation strip
+    pub bifurcation: bool,
+    /// Whether windows render on an OpenCL device where they can
+    #[cfg(feature = "opencl")]
+    pub opencl: bool,
+}
+
+/// Size and position of a window in physical pixels, the system's choice
+/// where `None`, and whether it fills a monitor
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Geometry {
+    pub size: Option<(u32, u32)>,
+    pub position: Option<(i32, i32)>,
+    pub fullscreen: bool,
+    /// Monitor to open on, counted from 1 in the order the system lists them
+    pub monitor: Option<usize>,
+}
+
+/// What the event loop should do with a window after an event
+pub enum Action {
+    Continue,
+    Close,
+    /// Open another window starting from this view
+    Open(Box<Mandelbrot>),
+}
+
+/// A window viewing the fractal
+pub struct Viewer {
+    pub window: Arc<Window>,
+    pixels: Pixels<'static>,
+    /// Depth of the surface the window got
+    surface: surface::Surface,
+    /// The frame as drawn, with 8 bits per channel whatever the surface
+    frame: Vec<u8>,
+    input: WinitInputHelper,
+    mandelbrot: Mandelbrot,
+    /// GPU renderer for shallow views, `None` once it is unsupported or failed
+    #[cfg(feature = "gpu")]
+    gpu: Option<gpu::Gpu>,
+    /// OpenCL renderer asked for with `--opencl`, `None` once it failed
+    #[cfg(feature = "opencl")]
+    opencl: Option<opencl::OpenCl>,
+    progressive: progressive::Progressive,
+    /// Height-field view of the fractal, toggled with T
+    terrain: Option<terrain::Terrain>,
+    seed_map: julia::SeedMap,
+    /// Map of the whole set, while shown, toggled with U
+    minimap: Option<minimap::Minimap>,
+    bifurcation: Option<bifurcation::Bifurcation>,
+    /// Corner where the right mouse button went down for zoom-to-rectangle
+    selection_start: Option<(f64, f64)>,
+    cursor: Option<(usize, usize)>,
+    /// Whether the cursor was last moved from the keyboard, and is drawn
+    key_cursor: bool,
+    /// Enter holds the left mouse button down
+    left_latched: bool,
+    /// Report the external angle of the point under the cursor, toggled with R
+    show_angle: bool,
+    /// The external ray through the point under the cursor
+    outward_ray: Option<rays::OutwardRay>,
+    /// Show the zoom depth indicator, toggled with H
+    show_depth: bool,
+    /// The title last set, which follows the view
+    title: String,
+    /// Frames in a row that failed to reach the window
+    render_failures: u32,
+    /// When the last frame was drawn, and whether the view changed since in
+    /// a way yet to be drawn, which eco mode holds back to its frame rate
+    drawn: Instant,
+    redraw_due: bool,
+    /// Hold the automatic zoom, toggled with Space
+    paused: bool,
+    /// Keep the view center on the boundary of the set, toggled with B
+    follow_boundary: bool,
+    /// `fractal_key` of the view in `progressive`, complete once it is idle
+    fractal: Option<String>,
+    /// `colors_key` of the palette `progressive` is colored with
+    colors: String,
+    /// Animation playing and when it started
+    animation: Option<(Animation, Instant)>,
+    /// Timeline editor, kept while hidden, shown with L
+    #[cfg(feature = "editor")]
+    timeline: Option<timeline::Timeline>,
+    show_timeline: bool,
+    /// The go-to dialog, while open
+    #[cfg(feature = "editor")]
+    goto: Option<goto::GoTo>,
+    /// egui state of the timeline, made the first time it is shown and again
+    /// for new surfaces
+    #[cfg(feature = "editor")]
+    gui: Option<gui::Gui>,
+    /// Live output of the frames drawn
+    stream: Option<stream::Stream>,
+    /// Log of the frames drawn, dropped once it can't be written
+    telemetry: Option<telemetry::Telemetry>,
+    /// Recording of the navigation, dropped once it can't be written
+    recorder: Option<recording::Recorder>,
+}
+
+impl Viewer {
+    /// Open a new window showing `mandelbrot`, on a surface as deep as the
+    /// window allows of the one `context` asks for.
+    pub fn open(elwt: &EventLoopWindowTarget<()>, mandelbrot: Mandelbrot, geometry: Geometry, context: &FrameContext) -> Result<Self, Error> {
+        let window = {
+            let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
+            let mut builder = WindowBuilder::new().with_title("Mandelbrot").with_inner_size(size).with_min_inner_size(size);
+            if let Some((width, height)) = geometry.size {
+                builder = builder.with_inner_size(PhysicalSize::new(width, height));
+            }
+            let monitor = geometry.monitor.and_then(|n| {
+                let monitor = elwt.available_monitors().nth(n - 1);
+                if monitor.is_none() {
+                    eprintln!("there is no monitor {n}, only {}", elwt.available_monitors().count());
+                }
+                monitor
+            });
+            match (geometry.position, &monitor) {
+                (Some((x, y)), _) => builder = builder.with_position(PhysicalPosition::new(x, y)),
+                // Centered on the monitor
+                (None, Some(monitor)) => {
+                    let (width, height) = geometry.size.unwrap_or_else(|| size.to_physical::<u32>(monitor.scale_factor()).into());
+                    let (position, area) = (monitor.position(), monitor.size());
+                    let x = position.x + (area.width as i32 - width as i32) / 2;
+                    let y = position.y + (area.height as i32 - height as i32) / 2;
+                    builder = builder.with_position(PhysicalPosition::new(x, y));
+                }
+                (None, None) => {}
+            }
+            if geometry.fullscreen {
+                builder = builder.with_fullscreen(Some(Fullscreen::Borderless(monitor)));
+            }
+            builder.build(elwt).map_err(|e| Error::UserDefined(Box::new(e)))?
+        };
+        let window = Arc::new(window);
+
+        let (pixels, surface) = surface::new_pixels(&window, context.surface)?;
+        #[cfg(feature = "gpu")]
+        let gpu = gpu::Gpu::new(pixels.adapter(), pixels.device());
+        #[cfg(feature = "opencl")]
+        let opencl = match context.opencl {
+            true => opencl::OpenCl::new().inspect_err(|e| eprintln!("rendering without OpenCL: {}", error_message(e))).ok(),
+            false => None,
+        };
+
+        let colors = colors_key(&mandelbrot);
+        Ok(Self {
+            window,
+            pixels,
+            surface,
+            frame: vec![0; (WIDTH * HEIGHT * 4) as usize],
+            input: WinitInputHelper::new(),
+            mandelbrot,
+            #[cfg(feature = "gpu")]
+            gpu,
+            #[cfg(feature = "opencl")]
+            opencl,
+            progressive: progressive::Progressive::new(),
+            terrain: None,
+            seed_map: julia::SeedMap::new(),
+            minimap: context.minimap.then(minimap::Minimap::new),
+            bifurcation: context.bifurcation.then(bifurcation::Bifurcation::new),
+            selection_start: None,
+            cursor: None,
+            key_cursor: false,
+            left_latched: false,
+            show_angle: false,
+            outward_ray: None,
+            show_depth: context.depth,
+            title: String::new(),
+            render_failures: 0,
+            drawn: Instant::now(),
+            redraw_due: false,
+            paused: context.accessibility.reduced_motion,
+            follow_boundary: false,
+            fractal: None,
+            colors,
+            animation: None,
+            #[cfg(feature = "editor")]
+            timeline: None,
+            show_timeline: false,
+            #[cfg(feature = "editor")]
+            goto: None,
+            #[cfg(feature = "editor")]
+            gui: None,
+            stream: None,
+            telemetry: None,
+            recorder: None,
+        })
+    }
+
+    /// When eco mode allows the next frame held back for it, to wake up for.
+    pub fn redraw_due(&self, eco: &eco::Eco) -> Option<Instant> {
+        (self.redraw_due && eco.active()).then(|| self.drawn + eco.frame_interval)
+    }
+
+    /// Play `animation` from now on, in place of the automatic zoom.
+    pub fn play(&mut self, animation: Animation) {
+        self.animation = Some((animation, Instant::now()));
+    }
+
+    /// Send the frames drawn from now on to `stream`.
+    pub fn stream_to(&mut self, stream: stream::Stream) {
+        self.stream = Some(stream);
+    }
+
+    /// Log the frames drawn from now on to `telemetry`.
+    pub fn log_to(&mut self, telemetry: telemetry::Telemetry) {
+        self.telemetry = Some(telemetry);
+    }
+
+    /// Record the navigation from now on to `recorder`.
+    pub fn record_to(&mut self, recorder: recording::Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Carry out a command of the control socket and answer it.
+    pub fn control(&mut self, request: control::Request) {
+        let control::Request { command, reply } = request;
+        let answer = match command {
+            control::Command::SetCenter { re, im } => {
+                self.animation = None;
+                self.mandelbrot.center_x = re.unwrap_or(self.mandelbrot.center_x);
+                self.mandelbrot.center_y = im.unwrap_or(self.mandelbrot.center_y);
+                control::ok(serde_json::json!({}))
+            }
+            control::Command::SetZoom { zoom } if zoom > 0.0 && zoom.is_finite() => {
+                self.animation = None;
+                self.mandelbrot.zoom = zoom.max(mipmap::MIN_ZOOM);
+                control::ok(serde_json::json!({}))
+            }
+            control::Command::SetZoom { zoom } => control::failed(format!("invalid zoom {zoom}")),
+            control::Command::SetRotation { degrees } => {
+                self.animation = None;
+                self.mandelbrot.rotation = degrees.to_radians();
+                control::ok(serde_json::json!({}))
+            }
+            control::Command::SetPalette { palette } => match Palette::from_name(&palette) {
+                Some(palette) => {
+                    self.mandelbrot.palette = palette;
+                    control::ok(serde_json::json!({}))
+                }
+                None => control::failed(format!("unknown palette `{palette}`")),
+            },
+            control::Command::SetPaletteOffset { offset } if offset >= 0.0 && offset.is_finite() => {
+                self.mandelbrot.palette_offset = offset;
+                control::ok(serde_json::json!({}))
+            }
+            control::Command::SetPaletteOffset { offset } => control::failed(format!("invalid palette offset {offset}")),
+            control::Command::SetJulia { re, im } => {
+                self.animation = None;
+                self.mandelbrot.morph = None;
+                let center = num::Complex::new(self.mandelbrot.center_x, self.mandelbrot.center_y);
+                let current = self.mandelbrot.julia.unwrap_or(center);
+                let c = num::Complex::new(re.unwrap_or(current.re), im.unwrap_or(current.im));
+                match self.mandelbrot.julia {
+                    Some(_) => self.mandelbrot.julia = Some(c),
+                    None => self.mandelbrot.toggle_julia(c),
+                }
+                control::ok(serde_json::json!({}))
+            }
+            control::Command::SetMandelbrot => {
+                self.animation = None;
+                self.mandelbrot.morph = None;
+                if let Some(c) = self.mandelbrot.julia {
+                    self.mandelbrot.toggle_julia(c);
+                }
+                control::ok(serde_json::json!({}))
+            }
+            control::Command::Screenshot { out, size } => {
+                // Rendered aside, so the window goes on meanwhile
+                let view = self.mandelbrot.clone();
+                let mut args = vec!["render".to_owned(), format!("--out={out}")];
+                args.extend(size.map(|size| format!("--size={size}")));
+                std::thread::spawn(move || {
+                    let answer = match export::render_from_args(&view, &args, progress::Mode::Quiet) {
+                        Ok(()) => control::ok(serde_json::json!({ "out": out })),
+                        Err(e) => control::failed(error_message(&e)),
+                    };
+                    let _ = reply.send(answer);
+                });
+                return;
+            }
+            control::Command::StartAnimation { path } => match Animation::load(Path::new(&path), &self.mandelbrot) {
+                Ok(animation) => {
+                    let duration = animation.duration();
+                    self.play(animation);
+                    control::ok(serde_json::json!({ "duration": duration }))
+                }
+                Err(e) => control::failed(error_message(&e)),
+            },
+            control::Command::StopAnimation => {
+                self.animation = None;
+                control::ok(serde_json::json!({}))
+            }
+            control::Command::GetState => {
+                let m = &self.mandelbrot;
+                control::ok(serde_json::json!({ "state": {
+                    "center": [m.center_x, m.center_y],
+                    "zoom": m.zoom,
+                    "rotation": m.rotation.to_degrees(),
+                    "palette": m.palette.name(),
+                    "palette_offset": m.palette_offset,
+                    "julia": m.julia.map(|c| [c.re, c.im]),
+                    "animating": self.animation.is_some(),
+                    "options": session::view_options(m),
+                }}))
+            }
+        };
+        self.window.request_redraw();
+        let _ = reply.send(answer);
+    }
+
+    pub fn mandelbrot(&self) -> &Mandelbrot {
+        &self.mandelbrot
+    }
+
+    /// The window's current geometry.
+    pub fn geometry(&self) -> Geometry {
+        let size = self.window.inner_size();
+        let position = self.window.outer_position().ok().map(|position| (position.x, position.y));
+        let fullscreen = self.window.fullscreen().is_some();
+        let current = self.window.current_monitor();
+        let monitor = self.window.available_monitors().position(|monitor| Some(monitor) == current).map(|index| index + 1);
+        Geometry { size: Some((size.width, size.height)), position, fullscreen, monitor }
+    }
+
+    /// Recover from a frame that failed to render. A frame timing out is
+    /// skipped, otherwise the surface is lost beyond reconfiguring, as when
+    /// the laptop wakes from sleep or the GPU changes, and the surface and
+    /// pixel buffer are recreated. The GPU renderer starts over on the new
+    /// device, also when it stopped after losing the old one. Gives up on
+    /// failures that keep coming back.
+    fn recover(&mut self, error: Error) -> Result<(), Error> {
+        self.render_failures += 1;
+        if self.render_failures > MAX_RENDER_FAILURES {
+            return Err(error);
+        }
+        if let Error::Surface(wgpu::SurfaceError::Timeout) = error {
+            return Ok(());
+        }
+
+        eprintln!("recreating the window surface: {}", error_message(&error));
+        let (mut pixels, surface) = surface::new_pixels(&self.window, self.surface)?;
+        surface::upload(surface, &self.frame, &mut pixels);
+        self.surface = surface;
+        #[cfg(feature = "gpu")]
+        {
+            self.gpu = gpu::Gpu::new(pixels.adapter(), pixels.device());
+        }
+        self.pixels = pixels;
+        #[cfg(feature = "editor")]
+        {
+            self.gui = None;
+        }
+        Ok(())
+    }
+
+    /// Handle an event of the event loop, ignoring events for other windows.
+    pub fn handle(&mut self, event: &Event<()>, context: &FrameContext) -> Action {
+        if let Event::WindowEvent { window_id, event: window_event } = event {
+            if *window_id != self.window.id() {
+                return Action::Continue;
+            }
+
+            // The timeline and the go-to dialog take the events meant for them
+            #[cfg(feature = "editor")]
+            if let (Some(gui), true) = (&mut self.gui, self.show_timeline || self.goto.is_some()) {
+                let response = gui.handle_event(&self.window, window_event);
+                if response.repaint {
+                    self.window.request_redraw();
+                }
+                if response.consumed {
+                    return Action::Continue;
+                }
+            }
+
+            // Draw the current frame
+            if let WindowEvent::RedrawRequested = window_event {
+                #[cfg(feature = "editor")]
+                if self.show_timeline || self.goto.is_some() {
+                    self.gui_ui(context.accessibility.high_contrast);
+                }
+                let started = Instant::now();
+                self.drawn = started;
+                let backend = self.draw(context);
+                if let Some(log) = &mut self.telemetry {
+                    let counts = (backend != telemetry::Backend::Terrain).then(|| self.progressive.counts());
+                    if let Err(e) = log.record(backend, started.elapsed(), &self.mandelbrot, counts, self.progressive.idle()) {
+                        eprintln!("stopping the telemetry log: {e}");
+                        self.telemetry = None;
+                    }
+                }
+                if let Some(recorder) = &mut self.recorder {
+                    if let Err(e) = recorder.record(&self.mandelbrot) {
+                        eprintln!("stopping the recording: {e}");
+                        self.recorder = None;
+                    }
+                }
+                if let Some(stream) = &self.stream {
+                    stream.publish(&self.frame);
+                }
+                surface::upload(self.surface, &self.frame, &mut self.pixels);
+                #[cfg(feature = "editor")]
+                let rendered = match (&mut self.gui, self.show_timeline || self.goto.is_some()) {
+                    (Some(gui), true) => self.pixels.render_with(|encoder, target, pixels| {
+                        pixels.scaling_renderer.render(encoder, target);
+                        gui.render(encoder, target, pixels);
+                        Ok(())
+                    }),
+                    _ => self.pixels.render(),
+                };
+                #[cfg(not(feature = "editor"))]
+                let rendered = self.pixels.render();
+                match rendered {
+                    Ok(()) => self.render_failures = 0,
+                    Err(e) => {
+                        if let Err(e) = self.recover(e) {
+                            eprintln!("closing the window, rendering failed: {}", error_message(&e));
+                            return Action::Close;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Handle input events
+        if self.input.update(event) {
+            return self.update(context);
+        }
+        Action::Continue
+    }
+
+    /// Lay out the timeline panel and the go-to dialog, those shown, in
+    /// `high_contrast` or not, and do what they ask.
+    #[cfg(feature = "editor")]
+    fn gui_ui(&mut self, high_contrast: bool) {
+        let gui = self.gui.get_or_insert_with(|| gui::Gui::new(&self.window, &self.pixels, high_contrast));
+        let (view, animation) = (&self.mandelbrot, &self.animation);
+        let mut timeline = self
+            .show_timeline
+            .then(|| self.timeline.get_or_insert_with(|| timeline::Timeline::new(view, animation.as_ref().map(|(animation, _)| animation))));
+        let playing = self.animation.as_ref().map(|(_, start)| start.elapsed().as_secs_f64());
+        let (mut request, mut goto_request) = (None, None);
+        let goto = &mut self.goto;
+        let again = gui.prepare(&self.window, |context| {
+            if let Some(timeline) = timeline.as_mut() {
+                request = timeline.ui(context, view, playing);
+            }
+            if let Some(goto) = goto.as_mut() {
+                goto_request = goto.ui(context, view);
+            }
+        });
+
+        match request {
+            Some(timeline::Request::Preview(view)) => {
+                self.animation = None;
+                self.mandelbrot = *view;
+                self.window.request_redraw();
+            }
+            Some(timeline::Request::Play(animation, from)) => {
+                let now = Instant::now();
+                self.animation = Some((animation, now.checked_sub(Duration::from_secs_f64(from)).unwrap_or(now)));
+                self.window.request_redraw();
+            }
+            Some(timeline::Request::Stop) => self.animation = None,
+            None => {}
+        }
+        match goto_request {
+            Some(goto::Request::Go(location)) => {
+                self.animation = None;
+                (self.mandelbrot.center_x, self.mandelbrot.center_y) = (location.center.re, location.center.im);
+                self.mandelbrot.zoom = location.zoom.unwrap_or(self.mandelbrot.zoom);
+                // Stay open to show what was left out
+                if location.note.is_none() {
+                    self.goto = None;
+                }
+                self.window.request_redraw();
+            }
+            Some(goto::Request::Close) => {
+                self.goto = None;
+                self.window.request_redraw();
+            }
+            None => {}
+        }
+        if again {
+            self.window.request_redraw();
+        }
+    }
+
+    /// Draw the frame, telling what rendered it.
+    fn draw(&mut self, context: &FrameContext) -> telemetry::Backend {
+        if let Some(terrain) = &self.terrain {
+            terrain.draw(&mut self.frame);
+            return telemetry::Backend::Terrain;
+        }
+
+        // The frame is composed of the fractal, kept in `progressive` while
+        // the view stays the same, and the overlays drawn over it every frame.
+        // New palettes, from the keys, animations or the remote, recolor it
+        let colors = colors_key(&self.mandelbrot);
+        if colors != self.colors {
+            self.progressive.recolor(&self.mandelbrot);
+            self.colors = colors;
+        }
+        let key = fractal_key(&self.mandelbrot);
+        let backend = match !self.progressive.idle() || self.fractal.as_ref() != Some(&key) || self.progressive.coarse() {
+            true => self.render_fractal(key, context),
+            false => telemetry::Backend::Cached,
+        };
+        // Finished frames move the palette towards their escape counts, over
+        // a few frames where recoloring the counts suffices and motion isn't
+        // reduced, rendering again for the other colorings
+        let eased = self.mandelbrot.coloring.counts_only() && !context.accessibility.reduced_motion;
+        let smoothing = if eased { self.mandelbrot.range_smoothing } else { 0 };
+        if self.progressive.idle() && self.mandelbrot.normalize_colors(self.progressive.counts(), smoothing) {
+            match self.mandelbrot.coloring.counts_only() {
+                true => self.progressive.recolor(&self.mandelbrot),
+                false => self.fractal = None,
+            }
+        }
+
+        // In high contrast the overlays are outlined against the frame under
+        // them, the contours too rather than going through the effects
+        let high_contrast = context.accessibility.high_contrast;
+        let frame = &mut self.frame;
+        frame.copy_from_slice(self.progressive.image());
+        if self.mandelbrot.contours && !high_contrast {
+            overlay::draw_contours(frame, self.progressive.counts(), self.mandelbrot.contour_interval, overlay::CONTOUR_COLOR);
+        }
+        self.mandelbrot.post_process(frame, WIDTH, HEIGHT);
+        let under = high_contrast.then(|| frame.to_vec());
+        if self.mandelbrot.contours && high_contrast {
+            overlay::draw_contours(frame, self.progressive.counts(), self.mandelbrot.contour_interval, overlay::CONTOUR_COLOR);
+        }
+        self.mandelbrot.draw_path(frame);
+        if self.mandelbrot.julia.is_none() {
+            let outward = self.outward_ray.iter().map(|ray| &ray.points);
+            for ray in context.rays.iter().chain(outward) {
+                let points: Vec<_> = ray.iter().map(|&c| self.mandelbrot.complex_to_pixel(self.mandelbrot.projection.invert(c))).collect();
+                overlay::draw_polyline(frame, &points, overlay::RAY_COLOR);
+            }
+        }
+        if let (None, Some(strip)) = (self.mandelbrot.julia, &mut self.bifurcation) {
+            strip.draw(frame, &self.mandelbrot);
+        }
+        match (self.mandelbrot.julia, &self.minimap) {
+            (Some(c), _) => self.seed_map.draw(frame, c),
+            (None, Some(minimap)) => minimap.draw(frame, &self.mandelbrot),
+            (None, None) => {}
+        }
+        if let (Some(start), Some((x, y))) = (self.selection_start, self.cursor) {
+            overlay::draw_rect(frame, start, (x as f64, y as f64), [255, 255, 255, 255]);
+        }
+        if let (true, Some(cursor)) = (self.key_cursor, self.cursor) {
+            overlay::draw_crosshair(frame, cursor, KEY_CURSOR_RADIUS, [255, 255, 255, 255]);
+        }
+        if self.show_depth {
+            let point = self.cursor.map(|(x, y)| self.mandelbrot.projection.apply(self.mandelbrot.pixel_to_complex(x as f64, y as f64)));
+            depth::draw(frame, &self.mandelbrot, point);
+        }
+        if let Some(under) = under {
+            accessibility::outline(frame, &under);
+        }
+        backend
+    }
+
+    /// Render the fractal layer, or continue rendering it, for the view
+    /// identified by `key`, on the backend returned, in eco mode coarsely
+    /// while the view changes and on fewer threads.
+    fn render_fractal(&mut self, key: String, context: &FrameContext) -> telemetry::Backend {
+        // OpenCL, where asked for, renders every view double precision
+        // resolves, before the GPU would in its lower precision
+        #[cfg(feature = "opencl")]
+        if let (Some(opencl), true) = (&self.opencl, self.progressive.idle()) {
+            if opencl::suitable(&self.mandelbrot) {
+                match opencl.render(&self.mandelbrot) {
+                    Ok(counts) => {
+                        self.progressive.show(&self.mandelbrot, counts);
+                        self.fractal = Some(key);
+                        return telemetry::Backend::OpenCl;
+                    }
+                    Err(e) => {
+                        eprintln!("OpenCL rendering failed on {}, rendering without it from now on: {}", opencl.device_name, error_message(&e));
+                        self.opencl = None;
+                    }
+                }
+            }
+        }
+        // Render on the GPU while its precision suffices, falling back to the
+        // CPU for deep zooms and for good once the GPU fails
+        #[cfg(feature = "gpu")]
+        if let (Some(gpu), true) = (&self.gpu, self.progressive.idle()) {
+            if gpu.suitable(&self.mandelbrot) {
+                match gpu.render(self.pixels.device(), self.pixels.queue(), &self.mandelbrot) {
+                    Ok(counts) => {
+                        self.progressive.show(&self.mandelbrot, counts);
+                        self.fractal = Some(key);
+                        return telemetry::Backend::Gpu;
+                    }
+                    Err(e) => {
+                        eprintln!("GPU rendering failed, rendering on the CPU from now on: {}", error_message(&e));
+                        self.gpu = None;
+                    }
+                }
+            }
+        }
+        // The CPU renders progressively, starting under the cursor, and a new
+        // pass renders the view as it is now
+        let (eco, scan) = (&context.eco, context.scan);
+        let coarse = eco.active() && self.fractal.as_ref() != Some(&key);
+        if self.progressive.idle() {
+            self.fractal = Some(key);
+        }
+        let focus = self.cursor.map_or((WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0), |(x, y)| (x as f64, y as f64));
+        let (progressive, mandelbrot) = (&mut self.progressive, &self.mandelbrot);
+        match (&eco.pool, eco.active()) {
+            (Some(pool), true) => pool.install(|| progressive.render(mandelbrot, focus, FRAME_BUDGET, coarse, scan)),
+            _ => progressive.render(mandelbrot, focus, FRAME_BUDGET, coarse, scan),
+        }
+        telemetry::Backend::Cpu
+    }
+
+    /// Apply the input of the current frame and step the animations.
+    fn update(&mut self, context: &FrameContext) -> Action {
+        let Viewer {
+            window,
+            pixels,
+            input,
+            mandelbrot,
+            progressive,
+            terrain,
+            seed_map,
+            minimap,
+            bifurcation,
+            selection_start,
+            cursor,
+            key_cursor,
+            left_latched,
+            show_angle,
+            outward_ray,
+            show_depth,
+            title,
+            drawn,
+            redraw_due,
+            paused,
+            follow_boundary,
+            animation,
+            show_timeline,
+            #[cfg(feature = "editor")]
+            goto,
+            #[cfg(feature = "editor")]
+            gui,
+            ..
+        } = self;
+        // The view as it was, to tell whether this frame changes it
+        let view = format!("{mandelbrot:?}");
+        let previous_selection = *selection_start;
+
+        // Close events
+        if input.key_pressed(KeyCode::Escape) || input.close_requested() {
+            return Action::Close;
+        }
+
+        // J toggles Julia mode for the view center, P starts or stops morphing
+        if input.key_pressed(KeyCode::KeyJ) {
+            mandelbrot.morph = None;
+            let center = num::Complex::new(mandelbrot.center_x, mandelbrot.center_y);
+            mandelbrot.toggle_julia(center);
+        }
+        if input.key_pressed(KeyCode::KeyP) {
+            mandelbrot.toggle_morph();
+        }
+
+        // The cursor follows the mouse, or Shift and the arrow keys until the
+        // mouse moves again
+        let (previous_cursor, previous_key_cursor) = (*cursor, *key_cursor);
+        if input.cursor_diff() != (0.0, 0.0) || !*key_cursor {
+            *key_cursor = false;
+            *cursor = input
+                .cursor()
+                .and_then(|pos| pixels.window_pos_to_pixel(pos).ok());
+        }
+        if let Some(moved) = accessibility::move_cursor(input, *cursor).filter(|_| terrain.is_none()) {
+            *cursor = Some(moved);
+            *key_cursor = true;
+        }
+        let buttons = accessibility::Buttons::read(input, left_latched, selection_start.is_some());
+
+        // R reports the external angle of the point under the cursor in the
+        // title and draws its ray
+        let toggled = terrain.is_none() && input.key_pressed(KeyCode::KeyR);
+        if toggled {
+            *show_angle = !*show_angle;
+        }
+        let point = cursor.filter(|_| *show_angle && mandelbrot.julia.is_none());
+
+        // H shows or hides how deep the view is
+        let depth_toggled = input.key_pressed(KeyCode::KeyH);
+        if depth_toggled {
+            *show_depth = !*show_depth;
+        }
+
+        // The middle button or Ctrl+C copies the point under the cursor, to
+        // paste into the go-to dialog
+        if let (true, Some((x, y))) = (buttons.middle_pressed, *cursor) {
+            let (re, im) = depth::coordinates(mandelbrot.projection.apply(mandelbrot.pixel_to_complex(x as f64, y as f64)));
+            if let Err(e) = clipboard::copy(&format!("{re}, {im}")) {
+                eprintln!("{e}");
+            }
+        }
+        if toggled || (*show_angle && *cursor != previous_cursor) {
+            *outward_ray = point.and_then(|(x, y)| {
+                let c = mandelbrot.projection.apply(mandelbrot.pixel_to_complex(x as f64, y as f64));
+                rays::trace_out(c)
+            });
+        }
+
+        // Drag with the right mouse button to zoom into a rectangle
+        if terrain.is_none() {
+            if buttons.right_pressed {
+                *selection_start = cursor.map(|(x, y)| (x as f64, y as f64));
+            }
+            if buttons.right_released {
+                if let (Some(start), Some((x, y))) = (selection_start.take(), *cursor) {
+                    mandelbrot.zoom_to_rect(start, (x as f64, y as f64));
+                }
+            }
+        }
+
+        // Drag the marker on the inset map to change the Julia parameter
+        if mandelbrot.julia.is_some() && terrain.is_none() {
+            if let Some(c) = seed_map.update(&buttons, *cursor) {
+                mandelbrot.morph = None;
+                mandelbrot.julia = Some(c);
+            }
+        }
+
+        // U shows or hides the minimap, on which the left mouse button moves
+        // the view center
+        let minimap_toggled = input.key_pressed(KeyCode::KeyU);
+        if minimap_toggled {
+            *minimap = match minimap.take() {
+                Some(_) => None,
+                None => Some(minimap::Minimap::new()),
+            };
+        }
+        let on_minimap = match minimap.as_mut().filter(|_| mandelbrot.julia.is_none() && terrain.is_none()) {
+            Some(minimap) => {
+                if let Some(c) = minimap.update(&buttons, *cursor) {
+                    let center = mandelbrot.projection.invert(c);
+                    (mandelbrot.center_x, mandelbrot.center_y) = (center.re, center.im);
+                }
+                minimap.dragging()
+            }
+            None => false,
+        };
+
+        // Ctrl-click on the Mandelbrot view opens a window on the Julia set
+        // for the point clicked
+        let julia_window = match (buttons.left_pressed && input.held_control(), *cursor) {
+            (true, Some((x, y))) if mandelbrot.julia.is_none() && terrain.is_none() && !on_minimap => Some(mandelbrot.julia_window(x as f64, y as f64)),
+            _ => None,
+        };
+
+        // Hold the left mouse button on the Mandelbrot view to draw a morph path
+        if mandelbrot.julia.is_none() && terrain.is_none() && !on_minimap && !input.held_control() {
+            if buttons.left_pressed {
+                mandelbrot.julia_path.clear();
+            }
+            if let (true, Some((x, y))) = (buttons.left_held, *cursor) {
+                let w = mandelbrot.pixel_to_complex(x as f64, y as f64);
+                let c = mandelbrot.projection.apply(w);
+                if mandelbrot.julia_path.last() != Some(&c) {
+                    mandelbrot.julia_path.push(c);
+                }
+            }
+        }
+
+        // Arrow keys or WASD pan, Q/E rotate, PageUp/PageDown zoom in steps.
+        // The arrow keys move the cursor instead with Shift
+        if terrain.is_none() {
+            let held = |[arrow, letter]: [KeyCode; 2]| (input.key_held(arrow) && !input.held_shift()) || input.key_held(letter);
+            let mut pan = (0.0, 0.0);
+            if held([KeyCode::ArrowLeft, KeyCode::KeyA]) {
+                pan.0 -= PAN_SPEED;
+            }
+            if held([KeyCode::ArrowRight, KeyCode::KeyD]) {
+                pan.0 += PAN_SPEED;
+            }
+            if held([KeyCode::ArrowUp, KeyCode::KeyW]) {
+                pan.1 -= PAN_SPEED;
+            }
+            if held([KeyCode::ArrowDown, KeyCode::KeyS]) {
+                pan.1 += PAN_SPEED;
+            }
+            mandelbrot.pan(pan.0, pan.1);
+
+            if input.key_held(KeyCode::KeyQ) {
+                mandelbrot.rotation += ROTATE_SPEED;
+            }
+            if input.key_held(KeyCode::KeyE) {
+                mandelbrot.rotation -= ROTATE_SPEED;
+            }
+
+            // K straightens out the features of the view, or resets the skew
+            if input.key_pressed(KeyCode::KeyK) {
+                mandelbrot.skew = match mandelbrot.skew {
+                    skew::IDENTITY => skew::estimate(mandelbrot).unwrap_or(skew::IDENTITY),
+                    _ => skew::IDENTITY,
+                };
+            }
+
+            if input.key_pressed(KeyCode::PageUp) {
+                mandelbrot.zoom *= ZOOM_STEP;
+            }
+            if input.key_pressed(KeyCode::PageDown) {
+                mandelbrot.zoom /= ZOOM_STEP;
+            }
+        }
+
+        if input.key_pressed(KeyCode::KeyI) {
+            mandelbrot.projection = match mandelbrot.projection {
+                Projection::Plane => Projection::Inverse,
+                Projection::Inverse => context.mobius.unwrap_or(Projection::Plane),
+                Projection::Mobius(_) => Projection::Plane,
+            };
+        }
+
+        // O cycles the coloring algorithms
+        if input.key_pressed(KeyCode::KeyO) {
+            mandelbrot.next_coloring();
+        }
+
+        // [ and ] cycle the palettes, Shift+G makes up a random one
+        let palette = if input.key_pressed(KeyCode::BracketRight) {
+            Some(mandelbrot.palette.next())
+        } else if input.key_pressed(KeyCode::BracketLeft) {
+            Some(mandelbrot.palette.previous())
+        } else if input.key_pressed(KeyCode::KeyG) && input.held_shift() {
+            Some(Palette::random(&mut rand::thread_rng()))
+        } else {
+            None
+        };
+        if let Some(palette) = palette {
+            mandelbrot.palette = palette;
+        }
+
+        // The digit keys switch to the first nine presets, with Shift tweening
+        // there from the view
+        let digits = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9];
+        if let Some(i) = digits.iter().position(|&key| input.key_pressed(key)) {
+            if let Some(preset) = presets::all().into_iter().nth(i) {
+                match preset.view() {
+                    Ok(view) if input.held_shift() => *animation = Some((Animation::tween(mandelbrot, view, animation::TWEEN_DURATION, context.tween_easing), Instant::now())),
+                    Ok(view) => *mandelbrot = view,
+                    Err(e) => eprintln!("invalid preset {}: {}", preset.name, error_message(&e)),
+                }
+            }
+        }
+
+        // C toggles iso-iteration contour lines
+        if input.key_pressed(KeyCode::KeyC) && !input.held_control() {
+            mandelbrot.contours = !mandelbrot.contours;
+        }
+
+        if input.key_pressed(KeyCode::KeyX) {
+            mandelbrot.exponential_map = !mandelbrot.exponential_map;
+        }
+
+        if input.key_pressed(KeyCode::Space) {
+            *paused = !*paused;
+        }
+        // Y orbits around the view center, Shift+Y sweeps the exponent
+        if input.key_pressed(KeyCode::KeyY) {
+            let played = if input.held_shift() { context.sweep.animation(mandelbrot) } else { context.orbit.animation(mandelbrot) };
+            *animation = Some((played, Instant::now()));
+        }
+        // Shift+Z flies to frame the minibrot of the view center, Z alone
+        // selects rectangles
+        if input.key_pressed(KeyCode::KeyZ) && input.held_shift() && mandelbrot.julia.is_none() {
+            match minibrot::Minibrot::near(num::Complex::new(mandelbrot.center_x, mandelbrot.center_y)) {
+                Some(minibrot) => {
+                    let framed = minibrot.framed(mandelbrot, minibrot::FRAME);
+                    *animation = Some((Animation::tween(mandelbrot, framed, animation::TWEEN_DURATION, context.tween_easing), Instant::now()));
+                }
+                None => eprintln!("no minibrot nucleus found near the view center"),
+            }
+        }
+        // B keeps the zoom on the boundary, Shift+B shows or hides the
+        // bifurcation strip
+        let bifurcation_toggled = input.key_pressed(KeyCode::KeyB) && input.held_shift();
+        if bifurcation_toggled {
+            *bifurcation = match bifurcation.take() {
+                Some(_) => None,
+                None => Some(bifurcation::Bifurcation::new()),
+            };
+        } else if input.key_pressed(KeyCode::KeyB) {
+            *follow_boundary = !*follow_boundary;
+        }
+
+        // L shows or hides the timeline editor, G opens the go-to dialog
+        let timeline_toggled = cfg!(feature = "editor") && input.key_pressed(KeyCode::KeyL);
+        if timeline_toggled {
+            *show_timeline = !*show_timeline;
+        }
+        let goto_opened = cfg!(feature = "editor") && input.key_pressed(KeyCode::KeyG) && !input.held_shift();
+        #[cfg(feature = "editor")]
+        if goto_opened {
+            goto.get_or_insert_with(goto::GoTo::default);
+        }
+        #[cfg(feature = "editor")]
+        let editing = *show_timeline || goto.is_some();
+        #[cfg(not(feature = "editor"))]
+        let editing = *show_timeline;
+
+        let terrain_toggled = input.key_pressed(KeyCode::KeyT);
+        if terrain_toggled {
+            *terrain = match terrain {
+                Some(_) => None,
+                None => Some(terrain::Terrain::new(mandelbrot)),
+            };
+        }
+
+        // Automatically zoom in by 1% each frame, unless paused or editing the
+        // timeline
+        #[cfg(feature = "audio")]
+        let zoom_speed = match context.audio {
+            Some((target, levels)) => mandelbrot.apply_audio(target, levels),
+            None => ZOOM_SPEED,
+        };
+        #[cfg(not(feature = "audio"))]
+        let zoom_speed = ZOOM_SPEED;
+        #[cfg(feature = "midi")]
+        let zoom_speed = mandelbrot.apply_midi(context.midi, zoom_speed);
+        // The terrain is a snapshot, so stop animating while flying over it
+        let terrain_moved = match terrain.as_mut() {
+            Some(terrain) => terrain.update(input),
+            None => {
+                // A playing animation takes the place of the automatic zoom
+                match animation {
+                    Some((script, start)) => {
+                        let time = start.elapsed().as_secs_f64();
+                        *mandelbrot = script.view_at(time);
+                        if time >= script.duration() {
+                            *animation = None;
+                        }
+                    }
+                    None => {
+                        // A tween stopped part of the way leaves its palettes blended
+                        mandelbrot.palette_blend = None;
+                        if *follow_boundary {
+                            mandelbrot.nudge_to_boundary();
+                        }
+                        mandelbrot.step(if *paused || editing { 1.0 } else { zoom_speed })
+                    }
+                }
+                mandelbrot.zoom = mandelbrot.zoom.max(mipmap::MIN_ZOOM);
+                false
+            }
+        };
+
+        // Resize the window
+        if let Some(size) = input.window_resized() {
+            if pixels.resize_surface(size.width, size.height).is_err() {
+                return Action::Close;
+            }
+            #[cfg(feature = "editor")]
+            if let Some(gui) = gui {
+                gui.resize(size.width, size.height);
+            }
+        }
+
+        // The title shows where the view is, after this frame's changes
+        let location = location(mandelbrot);
+        let new_title = match (&outward_ray, point) {
+            (Some(ray), _) => format!("{location} - external angle {ray}"),
+            (None, Some(_)) => format!("{location} - inside the set"),
+            (None, None) => location,
+        };
+        if new_title != *title {
+            window.set_title(&new_title);
+            *title = new_title;
+        }
+
+        // Redraw only when something on screen changes, or to continue a
+        // progressive pass, so a paused view leaves the CPU and GPU idle, and
+        // in eco mode no sooner than its frame rate allows
+        let dirty = format!("{mandelbrot:?}") != view
+            || terrain_toggled
+            || timeline_toggled
+            || goto_opened
+            || terrain_moved
+            || toggled
+            || depth_toggled
+            || minimap_toggled
+            || bifurcation_toggled
+            || !progressive.idle()
+            || progressive.coarse()
+            || *selection_start != previous_selection
+            || *key_cursor != previous_key_cursor
+            || (*cursor != previous_cursor && (*show_angle || *show_depth || selection_start.is_some() || *key_cursor));
+        *redraw_due |= dirty;
+        if *redraw_due && !(context.eco.active() && drawn.elapsed() < context.eco.frame_interval) {
+            window.request_redraw();
+            *redraw_due = false;
+        }
+
+        // F11 toggles filling the monitor, M moves to the next monitor
+        if input.key_pressed(KeyCode::F11) {
+            window.set_fullscreen(match window.fullscreen() {
+                Some(_) => None,
+                None => Some(Fullscreen::Borderless(None)),
+            });
+        }
+        if input.key_pressed(KeyCode::KeyM) {
+            move_to_next_monitor(window);
+        }
+
+        // N opens another window on the current view
+        if input.key_pressed(KeyCode::KeyN) {
+            return Action::Open(Box::new(mandelbrot.clone()));
+        }
+        if let Some(view) = julia_window {
+            return Action::Open(Box::new(view));
+        }
+        Action::Continue
+    }
+}
+
+/// Move `window` to the monitor after the one it is on, or back to the
+/// first, filling it if the window filled the last one and otherwise at the
+/// same place relative to its corner.
+fn move_to_next_monitor(window: &Window) {
+    let monitors: Vec<_> = window.available_monitors().collect();
+    let current = window.current_monitor();
+    let index = monitors.iter().position(|monitor| Some(monitor) == current.as_ref());
+    let Some(next) = monitors.get(index.map_or(0, |index| (index + 1) % monitors.len())).cloned() else {
+        return;
+    };
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(Some(Fullscreen::Borderless(Some(next))));
+        return;
+    }
+    let offset = match (window.outer_position(), &current) {
+        (Ok(position), Some(current)) => (position.x - current.position().x, position.y - current.position().y),
+        _ => (0, 0),
+    };
+    // Kept on the new monitor where it is smaller
+    let (size, area) = (window.outer_size(), next.size());
+    let x = offset.0.clamp(0, (area.width as i32 - size.width as i32).max(0));
+    let y = offset.1.clamp(0, (area.height as i32 - size.height as i32).max(0));
+    window.set_outer_position(PhysicalPosition::new(next.position().x + x, next.position().y + y));
+}
+
+/// Identifies what the fractal under the overlays shows, leaving out the
+/// state only the overlays and animations depend on, and for colorings by
+/// the escape counts alone the palette, which recoloring the counts follows.
+pub fn fractal_key(mandelbrot: &Mandelbrot) -> String {
+    let mut view = Mandelbrot { contours: false, morph: None, julia_path: Vec::new(), color_range: None, ..mandelbrot.clone() };
+    if view.coloring.counts_only() {
+        let plain = Mandelbrot::new();
+        (view.palette, view.palette_offset, view.palette_reversed) = (plain.palette, plain.palette_offset, plain.palette_reversed);
+    }
+    format!("{view:?}")
+}
+
+/// Identifies the palette of the view, as set, offset and reversed.
+pub fn colors_key(mandelbrot: &Mandelbrot) -> String {
+    format!("{:?} {} {}", mandelbrot.palette, mandelbrot.palette_offset, mandelbrot.palette_reversed)
+}
+
+/// The fractal, center, magnification and iteration count of the view.
+fn location(mandelbrot: &Mandelbrot) -> String {
+    // Enough digits to tell apart views a window width apart
+    let digits = (mandelbrot.zoom.log10().ceil().max(0.0) as usize + 3).min(17);
+    let center = format!("{:.digits$}, {:.digits$}i", mandelbrot.center_x, mandelbrot.center_y);
+    let fractal = match mandelbrot.julia {
+        Some(c) => format!("Julia set of {:.6}{:+.6}i", c.re, c.im),
+        None if mandelbrot.multibrot() => "Multibrot".to_owned(),
+        None => "Mandelbrot".to_owned(),
+    };
+    let fractal = match mandelbrot.multibrot() {
+        true => format!("{fractal}, z^{:.2} + c", mandelbrot.exponent),
+        false => fractal,
+    };
+    let zoom = if mandelbrot.zoom < 1e5 { format!("{:.1}", mandelbrot.zoom) } else { format!("{:.2e}", mandelbrot.zoom) };
+    let iterations = match mandelbrot.adaptive_iterations {
+        Some(limit) => format!("up to {limit} iterations"),
+        None => format!("{MAX_ITER} iterations"),
+    };
+    format!("{fractal} - {center} - zoom {zoom}x - {iterations}")
+}
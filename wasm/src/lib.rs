@@ -0,0 +1,127 @@
+//! WebAssembly bindings of the render core, packaged for npm.
+//!
+//! `wasm-pack build wasm --target web` builds the package into `wasm/pkg`,
+//! whose `renderTile(centerRe, centerIm, zoom, width, height, options)`
+//! renders a tile of a view into a `Uint8ClampedArray` of RGBA pixels,
+//! ready for `new ImageData(pixels, width, height)`. The views are mapped as
+//! in the viewer, so tiles that share a zoom and have centers `width` and
+//! `height` pixels apart fit together. The options are a `TileOptions`, whose
+//! `palette`, `maxIter`, `bailout`, `bailoutNorm` and Julia parameter follow
+//! the viewer's options of the same names, `new TileOptions()` for their
+//! defaults. They are only borrowed, so one `TileOptions` serves all the
+//! tiles of a view.
+
+use mandelbrot_core::{Bailout, Complex, Palette, View};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+
+/// Options of a tile, the viewer's defaults unless set
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct TileOptions {
+    palette: String,
+    max_iter: u32,
+    bailout_norm: String,
+    bailout: f64,
+    julia: Option<(f64, f64)>,
+}
+
+impl Default for TileOptions {
+    fn default() -> Self {
+        Self { palette: "classic".to_owned(), max_iter: 1000, bailout_norm: "modulus".to_owned(), bailout: 2.0, julia: None }
+    }
+}
+
+#[wasm_bindgen]
+impl TileOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_palette(&mut self, palette: String) {
+        self.palette = palette;
+    }
+
+    #[wasm_bindgen(setter = maxIter)]
+    pub fn set_max_iter(&mut self, max_iter: u32) {
+        self.max_iter = max_iter;
+    }
+
+    #[wasm_bindgen(setter = bailoutNorm)]
+    pub fn set_bailout_norm(&mut self, norm: String) {
+        self.bailout_norm = norm;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_bailout(&mut self, radius: f64) {
+        self.bailout = radius;
+    }
+
+    /// Render the Julia set of `re + im*i` instead of the Mandelbrot set.
+    #[wasm_bindgen(js_name = setJulia)]
+    pub fn set_julia(&mut self, re: f64, im: f64) {
+        self.julia = Some((re, im));
+    }
+
+    /// Render the Mandelbrot set again.
+    #[wasm_bindgen(js_name = clearJulia)]
+    pub fn clear_julia(&mut self) {
+        self.julia = None;
+    }
+}
+
+/// The view of a tile, or why there is none.
+fn view(center: Complex<f64>, zoom: f64, options: &TileOptions) -> Result<(View, Palette), String> {
+    if !(zoom > 0.0 && zoom.is_finite()) {
+        return Err(format!("invalid zoom: {zoom}"));
+    }
+    if options.max_iter == 0 || !(options.bailout > 0.0 && options.bailout.is_finite()) {
+        return Err("invalid maxIter or bailout".to_owned());
+    }
+    let bailout = Bailout::from_name(&options.bailout_norm).ok_or_else(|| format!("invalid bailoutNorm: {}", options.bailout_norm))?;
+    let palette = Palette::from_name(&options.palette).ok_or_else(|| format!("invalid palette: {}", options.palette))?;
+    let view = View {
+        julia: options.julia.map(|(re, im)| Complex::new(re, im)),
+        bailout,
+        bailout_radius: options.bailout,
+        max_iter: options.max_iter,
+        ..View::new(center, zoom)
+    };
+    Ok((view, palette))
+}
+
+/// Render the tile of `width` by `height` pixels centered on
+/// `centerRe + centerIm*i` at `zoom` into RGBA pixels.
+#[wasm_bindgen(js_name = renderTile)]
+pub fn render_tile(center_re: f64, center_im: f64, zoom: f64, width: usize, height: usize, options: &TileOptions) -> Result<Clamped<Vec<u8>>, JsError> {
+    let (view, palette) = view(Complex::new(center_re, center_im), zoom, options).map_err(|e| JsError::new(&e))?;
+    Ok(Clamped(view.render(width, height, palette)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_render_with_their_options() {
+        let mut options = TileOptions::new();
+        options.set_palette("turbo".to_owned());
+        options.set_julia(-0.8, 0.156);
+        let Ok(Clamped(pixels)) = render_tile(0.0, 0.0, 2.0, 32, 24, &options) else {
+            panic!("no tile");
+        };
+        let (tile_view, palette) = view(Complex::new(0.0, 0.0), 2.0, &options).unwrap();
+        assert_eq!(pixels, tile_view.render(32, 24, palette));
+
+        // The same options render the next tile, and back to the Mandelbrot set
+        assert!(render_tile(0.25, 0.0, 2.0, 32, 24, &options).is_ok());
+        options.clear_julia();
+        assert_eq!(view(Complex::new(0.0, 0.0), 2.0, &options).unwrap().0.julia, None);
+
+        options.set_palette("sepia".to_owned());
+        assert_eq!(view(Complex::new(0.0, 0.0), 2.0, &options).unwrap_err(), "invalid palette: sepia");
+        assert!(view(Complex::new(0.0, 0.0), -1.0, &TileOptions::new()).is_err());
+    }
+}